@@ -4,9 +4,9 @@ use dora_node_api::{
     self,
     arrow::array::{AsArray, UInt8Array},
     merged::{MergeExternal, MergedEvent},
-    Event, EventStream,
+    Event, EventStream, MetadataParameters, Parameter,
 };
-use eyre::bail;
+use eyre::{bail, eyre};
 
 #[cfg(feature = "ros2-bridge")]
 use dora_ros2_bridge::{_core, ros2_client};
@@ -32,6 +32,7 @@ mod ffi {
     struct DoraInput {
         id: String,
         data: Vec<u8>,
+        metadata: Box<Metadata>,
     }
 
     struct DoraResult {
@@ -52,6 +53,7 @@ mod ffi {
         type DoraEvent;
         type MergedEvents;
         type MergedDoraEvent;
+        type Metadata;
 
         fn init_dora_node() -> Result<DoraNode>;
 
@@ -66,6 +68,24 @@ mod ffi {
             id: String,
             data: &[u8],
         ) -> DoraResult;
+        fn send_output_with_metadata(
+            output_sender: &mut Box<OutputSender>,
+            id: String,
+            data: &[u8],
+            metadata: &Metadata,
+        ) -> DoraResult;
+
+        fn new_metadata() -> Box<Metadata>;
+        fn set_bool(self: &mut Metadata, key: &str, value: bool);
+        fn set_int(self: &mut Metadata, key: &str, value: i64);
+        fn set_float(self: &mut Metadata, key: &str, value: f64);
+        fn set_string(self: &mut Metadata, key: &str, value: &str);
+        fn set_bytes(self: &mut Metadata, key: &str, value: &[u8]);
+        fn get_bool(self: &Metadata, key: &str) -> Result<bool>;
+        fn get_int(self: &Metadata, key: &str) -> Result<i64>;
+        fn get_float(self: &Metadata, key: &str) -> Result<f64>;
+        fn get_string(self: &Metadata, key: &str) -> Result<String>;
+        fn get_bytes(self: &Metadata, key: &str) -> Result<Vec<u8>>;
 
         fn next(self: &mut CombinedEvents) -> CombinedEvent;
 
@@ -158,15 +178,34 @@ fn event_as_input(event: Box<DoraEvent>) -> eyre::Result<ffi::DoraInput> {
     Ok(ffi::DoraInput {
         id: id.into(),
         data,
+        metadata: Box::new(Metadata(metadata.parameters)),
     })
 }
 
 pub struct OutputSender(dora_node_api::DoraNode);
 
 fn send_output(sender: &mut Box<OutputSender>, id: String, data: &[u8]) -> ffi::DoraResult {
+    send_output_with_parameters(sender, id, data, Default::default())
+}
+
+fn send_output_with_metadata(
+    sender: &mut Box<OutputSender>,
+    id: String,
+    data: &[u8],
+    metadata: &Metadata,
+) -> ffi::DoraResult {
+    send_output_with_parameters(sender, id, data, metadata.0.clone())
+}
+
+fn send_output_with_parameters(
+    sender: &mut Box<OutputSender>,
+    id: String,
+    data: &[u8],
+    parameters: MetadataParameters,
+) -> ffi::DoraResult {
     let result = sender
         .0
-        .send_output_raw(id.into(), Default::default(), data.len(), |out| {
+        .send_output_raw(id.into(), parameters, data.len(), |out| {
             out.copy_from_slice(data)
         });
     let error = match result {
@@ -176,6 +215,81 @@ fn send_output(sender: &mut Box<OutputSender>, id: String, data: &[u8]) -> ffi::
     ffi::DoraResult { error }
 }
 
+/// Metadata of an input or output, used to access its user parameters.
+pub struct Metadata(MetadataParameters);
+
+fn new_metadata() -> Box<Metadata> {
+    Box::new(Metadata(Default::default()))
+}
+
+impl Metadata {
+    fn set(&mut self, key: &str, value: Parameter) {
+        self.0.user.insert(key.to_owned(), value);
+    }
+
+    fn get(&self, key: &str) -> eyre::Result<&Parameter> {
+        self.0
+            .user
+            .get(key)
+            .ok_or_else(|| eyre!("no metadata parameter `{key}`"))
+    }
+
+    fn set_bool(&mut self, key: &str, value: bool) {
+        self.set(key, Parameter::Bool(value))
+    }
+
+    fn set_int(&mut self, key: &str, value: i64) {
+        self.set(key, Parameter::Integer(value))
+    }
+
+    fn set_float(&mut self, key: &str, value: f64) {
+        self.set(key, Parameter::Float(value))
+    }
+
+    fn set_string(&mut self, key: &str, value: &str) {
+        self.set(key, Parameter::String(value.to_owned()))
+    }
+
+    fn set_bytes(&mut self, key: &str, value: &[u8]) {
+        self.set(key, Parameter::Bytes(value.to_owned()))
+    }
+
+    fn get_bool(&self, key: &str) -> eyre::Result<bool> {
+        match self.get(key)? {
+            Parameter::Bool(value) => Ok(*value),
+            other => bail!("metadata parameter `{key}` is not a bool: {other:?}"),
+        }
+    }
+
+    fn get_int(&self, key: &str) -> eyre::Result<i64> {
+        match self.get(key)? {
+            Parameter::Integer(value) => Ok(*value),
+            other => bail!("metadata parameter `{key}` is not an integer: {other:?}"),
+        }
+    }
+
+    fn get_float(&self, key: &str) -> eyre::Result<f64> {
+        match self.get(key)? {
+            Parameter::Float(value) => Ok(*value),
+            other => bail!("metadata parameter `{key}` is not a float: {other:?}"),
+        }
+    }
+
+    fn get_string(&self, key: &str) -> eyre::Result<String> {
+        match self.get(key)? {
+            Parameter::String(value) => Ok(value.clone()),
+            other => bail!("metadata parameter `{key}` is not a string: {other:?}"),
+        }
+    }
+
+    fn get_bytes(&self, key: &str) -> eyre::Result<Vec<u8>> {
+        match self.get(key)? {
+            Parameter::Bytes(value) => Ok(value.clone()),
+            other => bail!("metadata parameter `{key}` is not a byte array: {other:?}"),
+        }
+    }
+}
+
 pub struct MergedEvents {
     events: Option<Box<dyn Stream<Item = MergedEvent<ExternalEvent>> + Unpin>>,
     next_id: u32,
@@ -2,7 +2,8 @@
 #![warn(unsafe_op_in_unsafe_fn)]
 
 use dora_operator_api::{
-    self, register_operator, DoraOperator, DoraOutputSender, DoraStatus, Event, IntoArrow,
+    self, register_operator, types::Parameter, DoraOperator, DoraOutputSender, DoraStatus, Event,
+    IntoArrow,
 };
 use ffi::DoraSendOutputResult;
 
@@ -20,8 +21,22 @@ mod ffi {
 
     extern "Rust" {
         type OutputSender<'a, 'b>;
+        type Metadata;
 
         fn send_output(sender: &mut OutputSender, id: &str, data: &[u8]) -> DoraSendOutputResult;
+        fn send_output_with_metadata(
+            sender: &mut OutputSender,
+            id: &str,
+            data: &[u8],
+            metadata: &Metadata,
+        ) -> DoraSendOutputResult;
+
+        fn new_metadata() -> Box<Metadata>;
+        fn set_bool(self: &mut Metadata, key: &str, value: bool);
+        fn set_int(self: &mut Metadata, key: &str, value: i64);
+        fn set_float(self: &mut Metadata, key: &str, value: f64);
+        fn set_string(self: &mut Metadata, key: &str, value: &str);
+        fn set_bytes(self: &mut Metadata, key: &str, value: &[u8]);
     }
 
     unsafe extern "C++" {
@@ -51,6 +66,54 @@ fn send_output(sender: &mut OutputSender, id: &str, data: &[u8]) -> DoraSendOutp
     DoraSendOutputResult { error }
 }
 
+fn send_output_with_metadata(
+    sender: &mut OutputSender,
+    id: &str,
+    data: &[u8],
+    metadata: &Metadata,
+) -> DoraSendOutputResult {
+    let error = sender
+        .0
+        .send_with_parameters(id.into(), data.to_owned().into_arrow(), metadata.0.clone())
+        .err()
+        .unwrap_or_default();
+    DoraSendOutputResult { error }
+}
+
+/// User parameters that should be attached to an output.
+pub struct Metadata(Vec<Parameter>);
+
+fn new_metadata() -> Box<Metadata> {
+    Box::new(Metadata(Vec::new()))
+}
+
+impl Metadata {
+    fn set(&mut self, parameter: Parameter) {
+        self.0.retain(|p| *p.key != *parameter.key);
+        self.0.push(parameter);
+    }
+
+    fn set_bool(&mut self, key: &str, value: bool) {
+        self.set(Parameter::bool(key.to_owned(), value))
+    }
+
+    fn set_int(&mut self, key: &str, value: i64) {
+        self.set(Parameter::integer(key.to_owned(), value))
+    }
+
+    fn set_float(&mut self, key: &str, value: f64) {
+        self.set(Parameter::float(key.to_owned(), value))
+    }
+
+    fn set_string(&mut self, key: &str, value: &str) {
+        self.set(Parameter::string(key.to_owned(), value.to_owned()))
+    }
+
+    fn set_bytes(&mut self, key: &str, value: &[u8]) {
+        self.set(Parameter::bytes(key.to_owned(), value.to_owned()))
+    }
+}
+
 register_operator!(OperatorWrapper);
 
 struct OperatorWrapper {
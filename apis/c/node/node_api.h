@@ -1,4 +1,5 @@
 #include <stddef.h>
+#include <stdbool.h>
 
 void *init_dora_context_from_env();
 void free_dora_context(void *dora_context);
@@ -20,3 +21,18 @@ void read_dora_input_id(void *dora_event, char **out_ptr, size_t *out_len);
 void read_dora_input_data(void *dora_event, char **out_ptr, size_t *out_len);
 unsigned long long read_dora_input_timestamp(void *dora_event);
 int dora_send_output(void *dora_context, char *id_ptr, size_t id_len, char *data_ptr, size_t data_len);
+int dora_send_output_with_metadata(void *dora_context, char *id_ptr, size_t id_len, char *data_ptr, size_t data_len, void *metadata);
+
+void *dora_new_metadata();
+void free_dora_metadata(void *metadata);
+int dora_metadata_set_bool(void *metadata, char *key_ptr, size_t key_len, bool value);
+int dora_metadata_set_integer(void *metadata, char *key_ptr, size_t key_len, long long value);
+int dora_metadata_set_float(void *metadata, char *key_ptr, size_t key_len, double value);
+int dora_metadata_set_string(void *metadata, char *key_ptr, size_t key_len, char *value_ptr, size_t value_len);
+int dora_metadata_set_bytes(void *metadata, char *key_ptr, size_t key_len, char *value_ptr, size_t value_len);
+
+void *read_dora_input_parameter(void *dora_event, char *key_ptr, size_t key_len);
+int read_dora_parameter_bool(void *parameter, bool *out);
+int read_dora_parameter_integer(void *parameter, long long *out);
+int read_dora_parameter_float(void *parameter, double *out);
+int read_dora_parameter_bytes(void *parameter, char **out_ptr, size_t *out_len);
@@ -1,7 +1,9 @@
 #![deny(unsafe_op_in_unsafe_fn)]
 
 use arrow_array::UInt8Array;
-use dora_node_api::{arrow::array::AsArray, DoraNode, Event, EventStream};
+use dora_node_api::{
+    arrow::array::AsArray, DoraNode, Event, EventStream, MetadataParameters, Parameter,
+};
 use eyre::Context;
 use std::{ffi::c_void, ptr, slice};
 
@@ -246,7 +248,55 @@ pub unsafe extern "C" fn dora_send_output(
     data_ptr: *const u8,
     data_len: usize,
 ) -> isize {
-    match unsafe { try_send_output(context, id_ptr, id_len, data_ptr, data_len) } {
+    match unsafe {
+        try_send_output(
+            context,
+            id_ptr,
+            id_len,
+            data_ptr,
+            data_len,
+            Default::default(),
+        )
+    } {
+        Ok(()) => 0,
+        Err(err) => {
+            tracing::error!("{err:?}");
+            -1
+        }
+    }
+}
+
+/// Sends the given output together with the given metadata.
+///
+/// Works like [`dora_send_output`], but additionally attaches the user
+/// parameters of the given `metadata` to the output. The `metadata` must be
+/// created through [`dora_new_metadata`]. It is not consumed by this function,
+/// so it can be reused for multiple outputs.
+///
+/// ## Safety
+///
+/// Same as for [`dora_send_output`]. In addition, `metadata` must be a valid
+/// pointer created through [`dora_new_metadata`] that was not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn dora_send_output_with_metadata(
+    context: *mut c_void,
+    id_ptr: *const u8,
+    id_len: usize,
+    data_ptr: *const u8,
+    data_len: usize,
+    metadata: *const c_void,
+) -> isize {
+    let metadata: &MetadataParameters = unsafe { &*metadata.cast() };
+    match unsafe {
+        try_send_output(
+            context,
+            id_ptr,
+            id_len,
+            data_ptr,
+            data_len,
+            metadata.clone(),
+        )
+    } {
         Ok(()) => 0,
         Err(err) => {
             tracing::error!("{err:?}");
@@ -261,6 +311,7 @@ unsafe fn try_send_output(
     id_len: usize,
     data_ptr: *const u8,
     data_len: usize,
+    parameters: MetadataParameters,
 ) -> eyre::Result<()> {
     let context: &mut DoraContext = unsafe { &mut *context.cast() };
     let id = std::str::from_utf8(unsafe { slice::from_raw_parts(id_ptr, id_len) })?;
@@ -268,7 +319,273 @@ unsafe fn try_send_output(
     let data = unsafe { slice::from_raw_parts(data_ptr, data_len) };
     context
         .node
-        .send_output_raw(output_id, Default::default(), data.len(), |out| {
+        .send_output_raw(output_id, parameters, data.len(), |out| {
             out.copy_from_slice(data);
         })
 }
+
+/// Creates a new, empty output metadata object.
+///
+/// User parameters can be added through the `dora_metadata_set_*` functions.
+/// The returned pointer must be freed through [`free_dora_metadata`].
+#[no_mangle]
+pub extern "C" fn dora_new_metadata() -> *mut c_void {
+    Box::into_raw(Box::<MetadataParameters>::default()).cast()
+}
+
+/// Frees the given metadata object.
+///
+/// ## Safety
+///
+/// Only pointers created through [`dora_new_metadata`] are allowed as
+/// arguments. Each pointer must be freed exactly once.
+#[no_mangle]
+pub unsafe extern "C" fn free_dora_metadata(metadata: *mut c_void) {
+    let _: Box<MetadataParameters> = unsafe { Box::from_raw(metadata.cast()) };
+}
+
+/// Sets a boolean user parameter. Returns `0` on success and `-1` if the
+/// key is not valid UTF-8.
+///
+/// ## Safety
+///
+/// The `metadata` must be created through [`dora_new_metadata`]. The `key_ptr`
+/// and `key_len` fields must be the start pointer and length of a string.
+#[no_mangle]
+pub unsafe extern "C" fn dora_metadata_set_bool(
+    metadata: *mut c_void,
+    key_ptr: *const u8,
+    key_len: usize,
+    value: bool,
+) -> isize {
+    unsafe { set_parameter(metadata, key_ptr, key_len, Parameter::Bool(value)) }
+}
+
+/// Sets an integer user parameter. Returns `0` on success and `-1` if the
+/// key is not valid UTF-8.
+///
+/// ## Safety
+///
+/// See [`dora_metadata_set_bool`].
+#[no_mangle]
+pub unsafe extern "C" fn dora_metadata_set_integer(
+    metadata: *mut c_void,
+    key_ptr: *const u8,
+    key_len: usize,
+    value: i64,
+) -> isize {
+    unsafe { set_parameter(metadata, key_ptr, key_len, Parameter::Integer(value)) }
+}
+
+/// Sets a floating point user parameter. Returns `0` on success and `-1` if
+/// the key is not valid UTF-8.
+///
+/// ## Safety
+///
+/// See [`dora_metadata_set_bool`].
+#[no_mangle]
+pub unsafe extern "C" fn dora_metadata_set_float(
+    metadata: *mut c_void,
+    key_ptr: *const u8,
+    key_len: usize,
+    value: f64,
+) -> isize {
+    unsafe { set_parameter(metadata, key_ptr, key_len, Parameter::Float(value)) }
+}
+
+/// Sets a string user parameter. Returns `0` on success and `-1` if the key
+/// or value is not valid UTF-8.
+///
+/// ## Safety
+///
+/// See [`dora_metadata_set_bool`]. In addition, `value_ptr` and `value_len`
+/// must be the start pointer and length of a string.
+#[no_mangle]
+pub unsafe extern "C" fn dora_metadata_set_string(
+    metadata: *mut c_void,
+    key_ptr: *const u8,
+    key_len: usize,
+    value_ptr: *const u8,
+    value_len: usize,
+) -> isize {
+    let value = unsafe { slice::from_raw_parts(value_ptr, value_len) };
+    match std::str::from_utf8(value) {
+        Ok(value) => unsafe {
+            set_parameter(
+                metadata,
+                key_ptr,
+                key_len,
+                Parameter::String(value.to_owned()),
+            )
+        },
+        Err(err) => {
+            tracing::error!("invalid metadata string value: {err}");
+            -1
+        }
+    }
+}
+
+/// Sets a byte array user parameter. Returns `0` on success and `-1` if the
+/// key is not valid UTF-8.
+///
+/// ## Safety
+///
+/// See [`dora_metadata_set_bool`]. In addition, `value_ptr` and `value_len`
+/// must be the start pointer and length of a byte array.
+#[no_mangle]
+pub unsafe extern "C" fn dora_metadata_set_bytes(
+    metadata: *mut c_void,
+    key_ptr: *const u8,
+    key_len: usize,
+    value_ptr: *const u8,
+    value_len: usize,
+) -> isize {
+    let value = unsafe { slice::from_raw_parts(value_ptr, value_len) };
+    unsafe {
+        set_parameter(
+            metadata,
+            key_ptr,
+            key_len,
+            Parameter::Bytes(value.to_owned()),
+        )
+    }
+}
+
+unsafe fn set_parameter(
+    metadata: *mut c_void,
+    key_ptr: *const u8,
+    key_len: usize,
+    value: Parameter,
+) -> isize {
+    let metadata: &mut MetadataParameters = unsafe { &mut *metadata.cast() };
+    match std::str::from_utf8(unsafe { slice::from_raw_parts(key_ptr, key_len) }) {
+        Ok(key) => {
+            metadata.user.insert(key.to_owned(), value);
+            0
+        }
+        Err(err) => {
+            tracing::error!("invalid metadata key: {err}");
+            -1
+        }
+    }
+}
+
+/// Reads out the user parameter with the given key of the given input event.
+///
+/// Returns a null pointer if the event is not an input event, the key is not
+/// valid UTF-8, or the input has no such parameter. The returned pointer can
+/// be passed to the `read_dora_parameter_*` functions. It must not be used
+/// after freeing the `event`.
+///
+/// ## Safety
+///
+/// The `event` argument must be a dora event received through
+/// [`dora_next_event`] that was not freed yet. The `key_ptr` and `key_len`
+/// fields must be the start pointer and length of a string.
+#[no_mangle]
+pub unsafe extern "C" fn read_dora_input_parameter(
+    event: *const c_void,
+    key_ptr: *const u8,
+    key_len: usize,
+) -> *const c_void {
+    let event: &Event = unsafe { &*event.cast() };
+    let Ok(key) = std::str::from_utf8(unsafe { slice::from_raw_parts(key_ptr, key_len) }) else {
+        return ptr::null();
+    };
+    match event {
+        Event::Input { metadata, .. } => match metadata.parameters.user.get(key) {
+            Some(parameter) => (parameter as *const Parameter).cast(),
+            None => ptr::null(),
+        },
+        _ => ptr::null(),
+    }
+}
+
+/// Reads out a boolean parameter. Returns `0` on success and `-1` if the
+/// parameter has a different type.
+///
+/// ## Safety
+///
+/// The `parameter` must be a valid pointer returned by
+/// [`read_dora_input_parameter`]. The `out` pointer must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn read_dora_parameter_bool(
+    parameter: *const c_void,
+    out: *mut bool,
+) -> isize {
+    match unsafe { &*parameter.cast::<Parameter>() } {
+        Parameter::Bool(value) => {
+            unsafe { *out = *value };
+            0
+        }
+        _ => -1,
+    }
+}
+
+/// Reads out an integer parameter. Returns `0` on success and `-1` if the
+/// parameter has a different type.
+///
+/// ## Safety
+///
+/// See [`read_dora_parameter_bool`].
+#[no_mangle]
+pub unsafe extern "C" fn read_dora_parameter_integer(
+    parameter: *const c_void,
+    out: *mut i64,
+) -> isize {
+    match unsafe { &*parameter.cast::<Parameter>() } {
+        Parameter::Integer(value) => {
+            unsafe { *out = *value };
+            0
+        }
+        _ => -1,
+    }
+}
+
+/// Reads out a floating point parameter. Returns `0` on success and `-1` if
+/// the parameter has a different type.
+///
+/// ## Safety
+///
+/// See [`read_dora_parameter_bool`].
+#[no_mangle]
+pub unsafe extern "C" fn read_dora_parameter_float(
+    parameter: *const c_void,
+    out: *mut f64,
+) -> isize {
+    match unsafe { &*parameter.cast::<Parameter>() } {
+        Parameter::Float(value) => {
+            unsafe { *out = *value };
+            0
+        }
+        _ => -1,
+    }
+}
+
+/// Reads out a string or byte array parameter.
+///
+/// Writes the start pointer and length of the value to `out_ptr` and
+/// `out_len`. String values are UTF-8 encoded and not null-terminated.
+/// Returns `0` on success and `-1` if the parameter has a different type.
+///
+/// ## Safety
+///
+/// See [`read_dora_parameter_bool`]. The written pointer must not be used
+/// after freeing the corresponding event.
+#[no_mangle]
+pub unsafe extern "C" fn read_dora_parameter_bytes(
+    parameter: *const c_void,
+    out_ptr: *mut *const u8,
+    out_len: *mut usize,
+) -> isize {
+    let bytes: &[u8] = match unsafe { &*parameter.cast::<Parameter>() } {
+        Parameter::String(value) => value.as_bytes(),
+        Parameter::Bytes(value) => value,
+        _ => return -1,
+    };
+    unsafe {
+        *out_ptr = bytes.as_ptr();
+        *out_len = bytes.len();
+    }
+    0
+}
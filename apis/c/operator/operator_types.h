@@ -139,9 +139,82 @@ typedef struct DoraOnEvent {
 } DoraOnEvent_t;
 
 /** <No documentation available> */
+/** \remark Has the same ABI as `uint8_t` **/
+#ifdef DOXYGEN
+typedef
+#endif
+enum ParameterKind {
+    /** <No documentation available> */
+    PARAMETER_KIND_BOOL = 0,
+    /** <No documentation available> */
+    PARAMETER_KIND_INTEGER = 1,
+    /** <No documentation available> */
+    PARAMETER_KIND_FLOAT = 2,
+    /** <No documentation available> */
+    PARAMETER_KIND_STRING = 3,
+    /** <No documentation available> */
+    PARAMETER_KIND_BYTES = 4,
+}
+#ifndef DOXYGEN
+; typedef uint8_t
+#endif
+ParameterKind_t;
+
+/** \brief
+ *  A typed user-defined metadata parameter.
+ *
+ *  Only the value field that corresponds to `kind` is valid. String
+ *  parameters are stored as UTF-8 encoded `bytes_value`.
+ */
+typedef struct Parameter {
+    /** <No documentation available> */
+    Vec_uint8_t key;
+
+    /** <No documentation available> */
+    ParameterKind_t kind;
+
+    /** <No documentation available> */
+    bool bool_value;
+
+    /** <No documentation available> */
+    int64_t int_value;
+
+    /** <No documentation available> */
+    double float_value;
+
+    /** <No documentation available> */
+    Vec_uint8_t bytes_value;
+} Parameter_t;
+
+/** \brief
+ *  Same as [`Vec<T>`][`rust::Vec`], but with guaranteed `#[repr(C)]` layout
+ */
+typedef struct Vec_Parameter {
+    /** <No documentation available> */
+    Parameter_t * ptr;
+
+    /** <No documentation available> */
+    size_t len;
+
+    /** <No documentation available> */
+    size_t cap;
+} Vec_Parameter_t;
+
+/** \brief
+ *  Metadata of an input or output.
+ *
+ *  The `parameters` field changed the layout of this struct, so shared-library
+ *  operators must be rebuilt against the same dora version as the runtime that
+ *  loads them.
+ */
 typedef struct Metadata {
     /** <No documentation available> */
     Vec_uint8_t open_telemetry_context;
+
+    /** \brief
+     *  User-defined key/value parameters, forwarded untouched by dora.
+     */
+    Vec_Parameter_t parameters;
 } Metadata_t;
 
 /** <No documentation available> */
@@ -154,6 +227,56 @@ void
 dora_free_input_id (
     char * _input_id);
 
+/** \brief
+ *  Frees a parameter list that was not passed to
+ *  `dora_send_operator_output_with_parameters`.
+ */
+void
+dora_free_parameters (
+    Vec_Parameter_t _parameters);
+
+/** \brief
+ *  Creates a new, empty list of user parameters.
+ */
+Vec_Parameter_t
+dora_new_parameters (void);
+
+/** <No documentation available> */
+void
+dora_push_bool_parameter (
+    Vec_Parameter_t * parameters,
+    char const * key,
+    bool value);
+
+/** <No documentation available> */
+void
+dora_push_bytes_parameter (
+    Vec_Parameter_t * parameters,
+    char const * key,
+    uint8_t const * data_ptr,
+    size_t data_len);
+
+/** <No documentation available> */
+void
+dora_push_float_parameter (
+    Vec_Parameter_t * parameters,
+    char const * key,
+    double value);
+
+/** <No documentation available> */
+void
+dora_push_integer_parameter (
+    Vec_Parameter_t * parameters,
+    char const * key,
+    int64_t value);
+
+/** <No documentation available> */
+void
+dora_push_string_parameter (
+    Vec_Parameter_t * parameters,
+    char const * key,
+    char const * value);
+
 /** <No documentation available> */
 Vec_uint8_t
 dora_read_data (
@@ -164,6 +287,17 @@ char *
 dora_read_input_id (
     Input_t const * input);
 
+/** \brief
+ *  Returns the user parameter of the given input with the given key.
+ *
+ *  Returns a null pointer if the input has no such parameter. The returned
+ *  pointer is only valid as long as the input is valid.
+ */
+Parameter_t const *
+dora_read_input_parameter (
+    Input_t const * input,
+    char const * key);
+
 /** <No documentation available> */
 DoraResult_t
 dora_send_operator_output (
@@ -172,6 +306,20 @@ dora_send_operator_output (
     uint8_t const * data_ptr,
     size_t data_len);
 
+/** \brief
+ *  Sends the given output together with the given user parameters.
+ *
+ *  The `parameters` are moved into the output; they must not be used or freed
+ *  after calling this function.
+ */
+DoraResult_t
+dora_send_operator_output_with_parameters (
+    SendOutput_t const * send_output,
+    char const * id,
+    uint8_t const * data_ptr,
+    size_t data_len,
+    Vec_Parameter_t parameters);
+
 
 #ifdef __cplusplus
 } /* extern \"C\" */
@@ -80,6 +80,13 @@ ex:
 
 ```python
 node.send_output("string", b"string", {"open_telemetry_context": "7632e76"})
+```
+
+Additional metadata keys are forwarded as user parameters to the receivers.
+Their values must be of type `bool`, `int`, `float`, `str`, or `bytes`:
+
+```python
+node.send_output("image", data, {"frame_id": 42, "encoding": "rgb8"})
 ```"""
 
     def __iter__(self) -> typing.Any:
@@ -132,6 +132,13 @@ impl Node {
     /// node.send_output("string", b"string", {"open_telemetry_context": "7632e76"})
     /// ```
     ///
+    /// Additional metadata keys are forwarded as user parameters to the receivers.
+    /// Their values must be of type `bool`, `int`, `float`, `str`, or `bytes`:
+    ///
+    /// ```python
+    /// node.send_output("image", data, {"frame_id": 42, "encoding": "rgb8"})
+    /// ```
+    ///
     /// :type output_id: str
     /// :type data: pyarrow.Array
     /// :type metadata: dict, optional
@@ -1,12 +1,12 @@
 use std::collections::HashMap;
 
 use arrow::pyarrow::ToPyArrow;
-use dora_node_api::{merged::MergedEvent, Event, Metadata, MetadataParameters};
+use dora_node_api::{merged::MergedEvent, Event, Metadata, MetadataParameters, Parameter};
 use eyre::{Context, Result};
 use pyo3::{
     prelude::*,
     pybacked::PyBackedStr,
-    types::{IntoPyDict, PyDict},
+    types::{IntoPyDict, PyBool, PyBytes, PyDict, PyFloat, PyInt, PyString},
 };
 
 /// Dora Event
@@ -127,7 +127,11 @@ pub fn pydict_to_metadata(dict: Option<Bound<'_, PyDict>>) -> Result<MetadataPar
                         .context("parsing open telemetry context failed")?;
                     default_metadata.open_telemetry_context = otel_context.to_string();
                 }
-                _ => (),
+                other => {
+                    let parameter = pyobject_to_parameter(&value)
+                        .with_context(|| format!("parsing metadata parameter `{other}` failed"))?;
+                    default_metadata.user.insert(other.to_owned(), parameter);
+                }
             }
         }
     }
@@ -142,9 +146,42 @@ pub fn metadata_to_pydict<'a>(metadata: &'a Metadata, py: Python<'a>) -> pyo3::B
     )
     .wrap_err("could not make metadata a python dictionary item")
     .unwrap();
+    for (key, value) in &metadata.parameters.user {
+        let value = match value {
+            Parameter::Bool(v) => v.to_object(py),
+            Parameter::Integer(v) => v.to_object(py),
+            Parameter::Float(v) => v.to_object(py),
+            Parameter::String(v) => v.to_object(py),
+            Parameter::Bytes(v) => PyBytes::new_bound(py, v).to_object(py),
+        };
+        dict.set_item(key, value)
+            .wrap_err("could not make metadata a python dictionary item")
+            .unwrap();
+    }
     dict
 }
 
+fn pyobject_to_parameter(value: &Bound<'_, PyAny>) -> Result<Parameter> {
+    // `bool` is a subclass of `int` in Python, so it needs to be checked first
+    let parameter = if value.is_instance_of::<PyBool>() {
+        Parameter::Bool(value.extract()?)
+    } else if value.is_instance_of::<PyInt>() {
+        Parameter::Integer(value.extract()?)
+    } else if value.is_instance_of::<PyFloat>() {
+        Parameter::Float(value.extract()?)
+    } else if value.is_instance_of::<PyString>() {
+        Parameter::String(value.extract::<PyBackedStr>()?.to_string())
+    } else if value.is_instance_of::<PyBytes>() {
+        Parameter::Bytes(value.extract()?)
+    } else {
+        eyre::bail!(
+            "unsupported metadata value type `{}` (expected bool, int, float, str, or bytes)",
+            value.get_type().name()?
+        );
+    };
+    Ok(parameter)
+}
+
 #[cfg(test)]
 mod tests {
     use std::sync::Arc;
@@ -165,6 +202,39 @@ mod tests {
     };
     use eyre::{Context, Result};
 
+    #[test]
+    fn metadata_parameters_pydict_roundtrip() -> Result<()> {
+        use super::{metadata_to_pydict, pydict_to_metadata};
+        use dora_node_api::{
+            dora_core::message::{uhlc::HLC, ArrowTypeInfo},
+            Metadata, MetadataParameters,
+        };
+        use pyo3::Python;
+
+        let parameters = MetadataParameters {
+            open_telemetry_context: "7632e76".into(),
+            ..Default::default()
+        }
+        .with_user_parameter("flag", true)
+        .with_user_parameter("frame_id", 42i64)
+        .with_user_parameter("scale", 0.5)
+        .with_user_parameter("encoding", "rgb8")
+        .with_user_parameter("raw", vec![1u8, 2, 3]);
+        let metadata = Metadata::from_parameters(
+            HLC::default().new_timestamp(),
+            ArrowTypeInfo::empty(),
+            parameters.clone(),
+        );
+
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let dict = metadata_to_pydict(&metadata, py);
+            let parsed = pydict_to_metadata(Some(dict))?;
+            assert_eq!(parsed, parameters);
+            Ok(())
+        })
+    }
+
     fn assert_roundtrip(arrow_array: &ArrayData) -> Result<()> {
         let size = required_data_size(arrow_array);
         let mut sample: AVec<u8, ConstAlign<128>> = AVec::__from_elem(128, 0, size);
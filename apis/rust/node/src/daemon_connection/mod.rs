@@ -2,6 +2,7 @@ use dora_core::{
     config::NodeId,
     daemon_messages::{DaemonReply, DaemonRequest, DataflowId, Timestamped},
     message::uhlc::Timestamp,
+    protocol::NODE_DAEMON_PROTOCOL,
 };
 use eyre::{bail, eyre, Context};
 use shared_memory_server::{ShmemClient, ShmemConf};
@@ -9,12 +10,16 @@ use std::{
     net::{SocketAddr, TcpStream},
     time::Duration,
 };
+#[cfg(unix)]
+use std::{os::unix::net::UnixStream, path::Path};
 
 mod tcp;
 
 pub enum DaemonChannel {
     Shmem(ShmemClient<Timestamped<DaemonRequest>, DaemonReply>),
     Tcp(TcpStream),
+    #[cfg(unix)]
+    UnixDomain(UnixStream),
 }
 
 impl DaemonChannel {
@@ -25,6 +30,18 @@ impl DaemonChannel {
         Ok(DaemonChannel::Tcp(stream))
     }
 
+    #[cfg(unix)]
+    #[tracing::instrument(level = "trace")]
+    pub fn new_unix_socket(path: &Path) -> eyre::Result<Self> {
+        let stream = UnixStream::connect(path).wrap_err_with(|| {
+            format!(
+                "failed to connect to unix domain socket `{}`",
+                path.display()
+            )
+        })?;
+        Ok(DaemonChannel::UnixDomain(stream))
+    }
+
     #[tracing::instrument(level = "trace")]
     pub unsafe fn new_shmem(daemon_control_region_id: &str) -> eyre::Result<Self> {
         let daemon_events_region = ShmemConf::new()
@@ -49,6 +66,7 @@ impl DaemonChannel {
                 dataflow_id,
                 node_id,
                 dora_version: env!("CARGO_PKG_VERSION").to_owned(),
+                protocol_version: NODE_DAEMON_PROTOCOL.version(),
             },
             timestamp,
         };
@@ -69,6 +87,8 @@ impl DaemonChannel {
         match self {
             DaemonChannel::Shmem(client) => client.request(request),
             DaemonChannel::Tcp(stream) => tcp::request(stream, request),
+            #[cfg(unix)]
+            DaemonChannel::UnixDomain(stream) => tcp::request(stream, request),
         }
     }
 }
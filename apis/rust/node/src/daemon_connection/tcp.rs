@@ -1,16 +1,16 @@
 use dora_core::daemon_messages::{DaemonReply, DaemonRequest, Timestamped};
 use eyre::{eyre, Context};
-use std::{
-    io::{Read, Write},
-    net::TcpStream,
-};
+use std::io::{Read, Write};
 
 enum Serializer {
     Bincode,
     SerdeJson,
 }
+/// Sends the given request over a stream-based connection and waits for the reply.
+///
+/// Used for both TCP and unix domain socket connections.
 pub fn request(
-    connection: &mut TcpStream,
+    connection: &mut (impl Read + Write),
     request: &Timestamped<DaemonRequest>,
 ) -> eyre::Result<DaemonReply> {
     send_message(connection, request)?;
@@ -27,7 +27,7 @@ pub fn request(
 }
 
 fn send_message(
-    connection: &mut TcpStream,
+    connection: &mut impl Write,
     message: &Timestamped<DaemonRequest>,
 ) -> eyre::Result<()> {
     let serialized = bincode::serialize(&message).wrap_err("failed to serialize DaemonRequest")?;
@@ -36,7 +36,7 @@ fn send_message(
 }
 
 fn receive_reply(
-    connection: &mut TcpStream,
+    connection: &mut impl Read,
     serializer: Serializer,
 ) -> eyre::Result<Option<DaemonReply>> {
     let raw = match tcp_receive(connection) {
@@ -64,7 +64,7 @@ fn receive_reply(
     }
 }
 
-fn tcp_send(connection: &mut (impl Write + Unpin), message: &[u8]) -> std::io::Result<()> {
+fn tcp_send(connection: &mut impl Write, message: &[u8]) -> std::io::Result<()> {
     let len_raw = (message.len() as u64).to_le_bytes();
     connection.write_all(&len_raw)?;
     connection.write_all(message)?;
@@ -72,7 +72,7 @@ fn tcp_send(connection: &mut (impl Write + Unpin), message: &[u8]) -> std::io::R
     Ok(())
 }
 
-fn tcp_receive(connection: &mut (impl Read + Unpin)) -> std::io::Result<Vec<u8>> {
+fn tcp_receive(connection: &mut impl Read) -> std::io::Result<Vec<u8>> {
     let reply_len = {
         let mut raw = [0; 8];
         connection.read_exact(&mut raw)?;
@@ -82,3 +82,43 @@ fn tcp_receive(connection: &mut (impl Read + Unpin)) -> std::io::Result<Vec<u8>>
     connection.read_exact(&mut reply)?;
     Ok(reply)
 }
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use dora_core::message::uhlc::HLC;
+    use std::os::unix::net::UnixStream;
+
+    #[test]
+    fn request_reply_over_stream() {
+        let (mut node, mut daemon) = UnixStream::pair().unwrap();
+        let daemon = std::thread::spawn(move || {
+            let raw = tcp_receive(&mut daemon).unwrap();
+            let request: Timestamped<DaemonRequest> = bincode::deserialize(&raw).unwrap();
+            assert!(matches!(request.inner, DaemonRequest::Subscribe));
+            let reply = bincode::serialize(&DaemonReply::Result(Ok(()))).unwrap();
+            tcp_send(&mut daemon, &reply).unwrap();
+            daemon
+        });
+
+        let request = Timestamped {
+            inner: DaemonRequest::Subscribe,
+            timestamp: HLC::default().new_timestamp(),
+        };
+        let reply = super::request(&mut node, &request).unwrap();
+        assert!(matches!(reply, DaemonReply::Result(Ok(()))));
+        let _daemon = daemon.join().unwrap();
+
+        // replies to requests without a reply are not awaited
+        let request = Timestamped {
+            inner: DaemonRequest::ReportDropTokens {
+                drop_tokens: Vec::new(),
+            },
+            timestamp: HLC::default().new_timestamp(),
+        };
+        assert!(matches!(
+            super::request(&mut node, &request).unwrap(),
+            DaemonReply::Empty
+        ));
+    }
+}
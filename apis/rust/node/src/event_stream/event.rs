@@ -7,7 +7,7 @@ use dora_core::{
     message::{ArrowTypeInfo, BufferOffset, Metadata},
 };
 use eyre::{Context, Result};
-use shared_memory_extended::{Shmem, ShmemConf};
+use shared_memory_server::DataRegion;
 
 #[derive(Debug)]
 #[non_exhaustive]
@@ -99,18 +99,14 @@ impl std::fmt::Debug for RawData {
 }
 
 pub struct MappedInputData {
-    memory: Box<Shmem>,
+    memory: Box<DataRegion>,
     len: usize,
 }
 
 impl MappedInputData {
     pub(crate) unsafe fn map(shared_memory_id: &str, len: usize) -> eyre::Result<Self> {
         let memory = Box::new(
-            ShmemConf::new()
-                .os_id(shared_memory_id)
-                .writable(false)
-                .open()
-                .wrap_err("failed to map shared memory input")?,
+            DataRegion::open(shared_memory_id).wrap_err("failed to map shared memory input")?,
         );
         Ok(MappedInputData { memory, len })
     }
@@ -36,6 +36,16 @@ impl DropStream {
             }
             DaemonCommunication::Tcp { socket_addr } => DaemonChannel::new_tcp(*socket_addr)
                 .wrap_err_with(|| format!("failed to connect drop stream for node `{node_id}`"))?,
+            #[cfg(unix)]
+            DaemonCommunication::UnixDomain { socket_file } => {
+                DaemonChannel::new_unix_socket(socket_file).wrap_err_with(|| {
+                    format!("failed to connect drop stream for node `{node_id}`")
+                })?
+            }
+            #[cfg(not(unix))]
+            DaemonCommunication::UnixDomain { .. } => {
+                eyre::bail!("unix domain sockets are only supported on unix systems")
+            }
         };
 
         Self::init_on_channel(dataflow_id, node_id, channel, hlc)
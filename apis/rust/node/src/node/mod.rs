@@ -16,10 +16,11 @@ use dora_core::{
 };
 
 use eyre::{bail, WrapErr};
-use shared_memory_extended::{Shmem, ShmemConf};
+use shared_memory_server::DataRegion;
 use std::{
     collections::{HashMap, VecDeque},
     ops::{Deref, DerefMut},
+    path::PathBuf,
     sync::Arc,
     time::Duration,
 };
@@ -46,6 +47,7 @@ pub struct DoraNode {
     cache: VecDeque<ShmemHandle>,
 
     dataflow_descriptor: Descriptor,
+    shared_memory_dir: Option<PathBuf>,
 }
 
 impl DoraNode {
@@ -65,7 +67,7 @@ impl DoraNode {
             serde_yaml::from_str(&raw).context("failed to deserialize operator config")?
         };
         #[cfg(feature = "tracing")]
-        set_up_tracing(&node_config.node_id.to_string())
+        set_up_tracing(node_config.node_id.as_ref())
             .context("failed to set up tracing subscriber")?;
         Self::init(node_config)
     }
@@ -122,6 +124,7 @@ impl DoraNode {
             daemon_communication,
             dataflow_descriptor,
             dynamic: _,
+            shared_memory_dir,
         } = node_config;
         let clock = Arc::new(uhlc::HLC::default());
 
@@ -145,6 +148,7 @@ impl DoraNode {
             drop_stream,
             cache: VecDeque::new(),
             dataflow_descriptor,
+            shared_memory_dir,
         };
         Ok((node, event_stream))
     }
@@ -332,10 +336,7 @@ impl DoraNode {
                 self.cache.remove(i).unwrap()
             }
             None => ShmemHandle(Box::new(
-                ShmemConf::new()
-                    .size(data_len)
-                    .writable(true)
-                    .create()
+                DataRegion::create(data_len, self.shared_memory_dir.as_deref())
                     .wrap_err("failed to allocate shared memory")?,
             )),
         };
@@ -441,7 +442,7 @@ impl DataSample {
             DataSampleInner::Shmem(shared_memory) => {
                 let drop_token = DropToken::generate();
                 let data = DataMessage::SharedMemory {
-                    shared_memory_id: shared_memory.get_os_id().to_owned(),
+                    shared_memory_id: shared_memory.id().to_owned(),
                     len: self.len,
                     drop_token,
                 };
@@ -501,10 +502,10 @@ enum DataSampleInner {
     Vec(AVec<u8, ConstAlign<128>>),
 }
 
-struct ShmemHandle(Box<Shmem>);
+struct ShmemHandle(Box<DataRegion>);
 
 impl Deref for ShmemHandle {
-    type Target = Shmem;
+    type Target = DataRegion;
 
     fn deref(&self) -> &Self::Target {
         &self.0
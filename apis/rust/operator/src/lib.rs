@@ -24,7 +24,7 @@ pub use dora_operator_api_types as types;
 pub use types::DoraStatus;
 use types::{
     arrow::{self, array::Array},
-    Metadata, Output, SendOutput,
+    Metadata, Output, Parameter, SendOutput,
 };
 
 pub mod raw;
@@ -54,6 +54,17 @@ impl DoraOutputSender<'_> {
     ///  - `id` is the `output_id` as defined in your dataflow.
     ///  - `data` is the data that should be sent
     pub fn send(&mut self, id: String, data: impl Array) -> Result<(), String> {
+        self.send_with_parameters(id, data, Vec::new())
+    }
+
+    ///  Send an output from the operator with additional user parameters
+    ///  that are attached to the output metadata.
+    pub fn send_with_parameters(
+        &mut self,
+        id: String,
+        data: impl Array,
+        parameters: Vec<Parameter>,
+    ) -> Result<(), String> {
         let (data_array, schema) =
             arrow::ffi::to_ffi(&data.into_data()).map_err(|err| err.to_string())?;
         let result = self.0.send_output.call(Output {
@@ -62,6 +73,7 @@ impl DoraOutputSender<'_> {
             schema,
             metadata: Metadata {
                 open_telemetry_context: String::new().into(), // TODO
+                parameters: parameters.into(),
             },
         });
         result.into_result()
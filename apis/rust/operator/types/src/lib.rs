@@ -111,12 +111,116 @@ pub struct Input {
     pub metadata: Metadata,
 }
 
+/// Metadata of an input or output.
+///
+/// The `parameters` field changed the layout of this struct, so shared-library
+/// operators must be rebuilt against the same dora version as the runtime that
+/// loads them.
 #[derive_ReprC]
 #[ffi_export]
 #[repr(C)]
 #[derive(Debug)]
 pub struct Metadata {
     pub open_telemetry_context: safer_ffi::String,
+    /// User-defined key/value parameters, forwarded untouched by dora.
+    pub parameters: safer_ffi::Vec<Parameter>,
+}
+
+impl Metadata {
+    pub fn new() -> Self {
+        Self {
+            open_telemetry_context: String::new().into(),
+            parameters: Vec::new().into(),
+        }
+    }
+
+    /// Returns the user parameter with the given key, if any.
+    pub fn parameter(&self, key: &str) -> Option<&Parameter> {
+        self.parameters.iter().find(|p| &*p.key == key)
+    }
+}
+
+impl Default for Metadata {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A typed user-defined metadata parameter.
+///
+/// Only the value field that corresponds to `kind` is valid. String
+/// parameters are stored as UTF-8 encoded `bytes_value`.
+#[derive_ReprC]
+#[ffi_export]
+#[repr(C)]
+#[derive(Debug, Clone)]
+pub struct Parameter {
+    pub key: safer_ffi::String,
+    pub kind: ParameterKind,
+    pub bool_value: bool,
+    pub int_value: i64,
+    pub float_value: f64,
+    pub bytes_value: safer_ffi::Vec<u8>,
+}
+
+impl Parameter {
+    fn new(key: String, kind: ParameterKind) -> Self {
+        Self {
+            key: key.into(),
+            kind,
+            bool_value: false,
+            int_value: 0,
+            float_value: 0.0,
+            bytes_value: Vec::new().into(),
+        }
+    }
+
+    pub fn bool(key: String, value: bool) -> Self {
+        Self {
+            bool_value: value,
+            ..Self::new(key, ParameterKind::Bool)
+        }
+    }
+
+    pub fn integer(key: String, value: i64) -> Self {
+        Self {
+            int_value: value,
+            ..Self::new(key, ParameterKind::Integer)
+        }
+    }
+
+    pub fn float(key: String, value: f64) -> Self {
+        Self {
+            float_value: value,
+            ..Self::new(key, ParameterKind::Float)
+        }
+    }
+
+    pub fn string(key: String, value: String) -> Self {
+        Self {
+            bytes_value: value.into_bytes().into(),
+            ..Self::new(key, ParameterKind::String)
+        }
+    }
+
+    pub fn bytes(key: String, value: Vec<u8>) -> Self {
+        Self {
+            bytes_value: value.into(),
+            ..Self::new(key, ParameterKind::Bytes)
+        }
+    }
+}
+
+#[derive_ReprC]
+#[ffi_export]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParameterKind {
+    Bool = 0,
+    Integer = 1,
+    Float = 2,
+    String = 3,
+    Bytes = 4,
 }
 
 #[derive_ReprC]
@@ -181,6 +285,29 @@ pub unsafe fn dora_send_operator_output(
     id: safer_ffi::char_p::char_p_ref<'_>,
     data_ptr: *const u8,
     data_len: usize,
+) -> DoraResult {
+    unsafe {
+        dora_send_operator_output_with_parameters(
+            send_output,
+            id,
+            data_ptr,
+            data_len,
+            Vec::new().into(),
+        )
+    }
+}
+
+/// Sends the given output together with the given user parameters.
+///
+/// The `parameters` are moved into the output; they must not be used or freed
+/// after calling this function.
+#[ffi_export]
+pub unsafe fn dora_send_operator_output_with_parameters(
+    send_output: &SendOutput,
+    id: safer_ffi::char_p::char_p_ref<'_>,
+    data_ptr: *const u8,
+    data_len: usize,
+    parameters: safer_ffi::Vec<Parameter>,
 ) -> DoraResult {
     let result = || {
         let data = unsafe { slice::from_raw_parts(data_ptr, data_len) };
@@ -192,7 +319,8 @@ pub unsafe fn dora_send_operator_output(
             data_array,
             schema,
             metadata: Metadata {
-                open_telemetry_context: String::new().into(), // TODO
+                parameters,
+                ..Metadata::new()
             },
         };
         Result::<_, String>::Ok(output)
@@ -205,6 +333,89 @@ pub unsafe fn dora_send_operator_output(
     }
 }
 
+/// Creates a new, empty list of user parameters.
+#[ffi_export]
+pub fn dora_new_parameters() -> safer_ffi::Vec<Parameter> {
+    Vec::new().into()
+}
+
+/// Frees a parameter list that was not passed to
+/// `dora_send_operator_output_with_parameters`.
+#[ffi_export]
+pub fn dora_free_parameters(_parameters: safer_ffi::Vec<Parameter>) {}
+
+#[ffi_export]
+pub fn dora_push_bool_parameter(
+    parameters: &mut safer_ffi::Vec<Parameter>,
+    key: safer_ffi::char_p::char_p_ref<'_>,
+    value: bool,
+) {
+    push_parameter(parameters, Parameter::bool(key.to_string(), value));
+}
+
+#[ffi_export]
+pub fn dora_push_integer_parameter(
+    parameters: &mut safer_ffi::Vec<Parameter>,
+    key: safer_ffi::char_p::char_p_ref<'_>,
+    value: i64,
+) {
+    push_parameter(parameters, Parameter::integer(key.to_string(), value));
+}
+
+#[ffi_export]
+pub fn dora_push_float_parameter(
+    parameters: &mut safer_ffi::Vec<Parameter>,
+    key: safer_ffi::char_p::char_p_ref<'_>,
+    value: f64,
+) {
+    push_parameter(parameters, Parameter::float(key.to_string(), value));
+}
+
+#[ffi_export]
+pub fn dora_push_string_parameter(
+    parameters: &mut safer_ffi::Vec<Parameter>,
+    key: safer_ffi::char_p::char_p_ref<'_>,
+    value: safer_ffi::char_p::char_p_ref<'_>,
+) {
+    push_parameter(
+        parameters,
+        Parameter::string(key.to_string(), value.to_string()),
+    );
+}
+
+#[ffi_export]
+pub unsafe fn dora_push_bytes_parameter(
+    parameters: &mut safer_ffi::Vec<Parameter>,
+    key: safer_ffi::char_p::char_p_ref<'_>,
+    data_ptr: *const u8,
+    data_len: usize,
+) {
+    let data = unsafe { slice::from_raw_parts(data_ptr, data_len) };
+    push_parameter(
+        parameters,
+        Parameter::bytes(key.to_string(), data.to_owned()),
+    );
+}
+
+fn push_parameter(parameters: &mut safer_ffi::Vec<Parameter>, parameter: Parameter) {
+    let mut vec: Vec<_> = std::mem::replace(parameters, Vec::new().into()).into();
+    vec.retain(|p| *p.key != *parameter.key);
+    vec.push(parameter);
+    *parameters = vec.into();
+}
+
+/// Returns the user parameter of the given input with the given key.
+///
+/// Returns a null pointer if the input has no such parameter. The returned
+/// pointer is only valid as long as the input is valid.
+#[ffi_export]
+pub fn dora_read_input_parameter<'a>(
+    input: &'a Input,
+    key: safer_ffi::char_p::char_p_ref<'_>,
+) -> Option<&'a Parameter> {
+    input.metadata.parameter(key.to_str())
+}
+
 pub fn generate_headers(target_file: &Path) -> ::std::io::Result<()> {
     ::safer_ffi::headers::builder()
         .to_file(target_file)?
@@ -0,0 +1,84 @@
+use colored::Colorize;
+use communication_layer_request_reply::TcpRequestReplyConnection;
+use dora_core::{
+    descriptor::Descriptor,
+    topics::{ControlRequest, ControlRequestReply},
+};
+use eyre::{bail, Context, Result};
+use std::path::Path;
+use uuid::Uuid;
+
+use crate::query_running_dataflows;
+
+/// Applies the changed descriptor at the given path to a running dataflow.
+///
+/// The dataflow is selected by UUID or name. If neither is given, the only running
+/// dataflow is used.
+pub fn apply(
+    session: &mut TcpRequestReplyConnection,
+    dataflow: &Path,
+    uuid: Option<Uuid>,
+    name: Option<String>,
+) -> Result<()> {
+    let descriptor =
+        Descriptor::blocking_read(dataflow).wrap_err("Failed to read yaml dataflow")?;
+    let working_dir = dataflow
+        .canonicalize()
+        .context("failed to canonicalize dataflow path")?
+        .parent()
+        .ok_or_else(|| eyre::eyre!("dataflow path has no parent dir"))?
+        .to_owned();
+    descriptor
+        .check(&working_dir)
+        .wrap_err("Could not validate yaml")?;
+
+    let dataflow_uuid = match uuid {
+        Some(uuid) => uuid,
+        None => {
+            let list = query_running_dataflows(session)?;
+            let active = list.get_active();
+            let mut matching = active.iter().filter(|d| name.is_none() || d.name == name);
+            match (matching.next(), matching.next(), &name) {
+                (Some(dataflow), None, _) => dataflow.uuid,
+                (None, _, Some(name)) => bail!("no running dataflow with name `{name}`"),
+                (None, _, None) => bail!("no dataflows are running"),
+                (Some(_), Some(_), _) => {
+                    bail!("multiple dataflows are running, select one with `--uuid` or `--name`")
+                }
+            }
+        }
+    };
+
+    let reply_raw = session
+        .request(
+            &serde_json::to_vec(&ControlRequest::Apply {
+                dataflow_uuid,
+                dataflow: descriptor,
+                local_working_dir: working_dir,
+            })
+            .wrap_err("failed to serialize message")?,
+        )
+        .wrap_err("failed to send apply message")?;
+    let changes = match serde_json::from_slice(&reply_raw).wrap_err("failed to parse reply")? {
+        ControlRequestReply::DataflowApplied { changes, .. } => changes,
+        ControlRequestReply::Error(err) => bail!("{err}"),
+        other => bail!("unexpected apply dataflow reply: {other:?}"),
+    };
+
+    if changes.is_empty() {
+        println!("dataflow `{dataflow_uuid}` is unchanged");
+        return Ok(());
+    }
+    println!("applied changes to dataflow `{dataflow_uuid}`:");
+    for node_id in &changes.added_nodes {
+        println!("  {} {node_id}", "+".green());
+    }
+    for node_id in &changes.removed_nodes {
+        println!("  {} {node_id}", "-".red());
+    }
+    for (node_id, input_id) in &changes.changed_inputs {
+        println!("  {} {node_id}/{input_id}", "~".yellow());
+    }
+
+    Ok(())
+}
@@ -1,23 +1,27 @@
 use colored::Colorize;
 use communication_layer_request_reply::{TcpConnection, TcpRequestReplyConnection};
 use dora_core::{
+    config::NodeId,
     coordinator_messages::LogMessage,
     descriptor::{resolve_path, CoreNodeKind, Descriptor},
     topics::{ControlRequest, ControlRequestReply},
 };
+use dora_transport_security::TransportSecurity;
 use eyre::Context;
 use notify::event::ModifyKind;
 use notify::{Config, Event as NotifyEvent, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     net::{SocketAddr, TcpStream},
+    time::{Instant, SystemTime},
 };
 use std::{path::PathBuf, sync::mpsc, time::Duration};
-use tracing::{error, info};
+use tracing::{error, info, warn};
 use uuid::Uuid;
 
-use crate::handle_dataflow_result;
+use crate::{handle_dataflow_result, history, report};
 
+#[allow(clippy::too_many_arguments)]
 pub fn attach_dataflow(
     dataflow: Descriptor,
     dataflow_path: PathBuf,
@@ -25,7 +29,10 @@ pub fn attach_dataflow(
     session: &mut TcpRequestReplyConnection,
     hot_reload: bool,
     coordinator_socket: SocketAddr,
+    security: &TransportSecurity,
     log_level: log::LevelFilter,
+    timeout: Option<Duration>,
+    report: Option<(PathBuf, report::NodeExits)>,
 ) -> Result<(), eyre::ErrReport> {
     let (tx, rx) = mpsc::sync_channel(2);
 
@@ -126,9 +133,12 @@ pub fn attach_dataflow(
     .wrap_err("failed to set ctrl-c handler")?;
 
     // subscribe to log messages
+    let stream =
+        TcpStream::connect(coordinator_socket).wrap_err("failed to connect to dora coordinator")?;
     let mut log_session = TcpConnection {
-        stream: TcpStream::connect(coordinator_socket)
-            .wrap_err("failed to connect to dora coordinator")?,
+        stream: security
+            .connect_blocking(stream)
+            .wrap_err("failed to secure connection to dora coordinator")?,
     };
     log_session
         .send(
@@ -139,6 +149,35 @@ pub fn attach_dataflow(
             .wrap_err("failed to serialize message")?,
         )
         .wrap_err("failed to send log subscribe request to coordinator")?;
+    // record the exit times of the nodes for the report
+    let mut node_exit_times = BTreeMap::new();
+    let report_path = match report {
+        Some((path, node_exits)) => {
+            let events_tx = tx.clone();
+            std::thread::spawn(move || {
+                for (node_id, time) in node_exits.of_dataflow(dataflow_id) {
+                    if events_tx
+                        .send(AttachEvent::NodeExited { node_id, time })
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+            });
+            Some(path)
+        }
+        None => None,
+    };
+
+    // stop the dataflow if it doesn't finish in time
+    if let Some(timeout) = timeout {
+        let timeout_tx = tx.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(timeout);
+            let _ = timeout_tx.send(AttachEvent::Timeout(timeout));
+        });
+    }
+
     std::thread::spawn(move || {
         while let Ok(raw) = log_session.receive() {
             let parsed: eyre::Result<LogMessage> =
@@ -149,12 +188,25 @@ pub fn attach_dataflow(
         }
     });
 
+    let mut timed_out = false;
     loop {
         let control_request = match rx.recv_timeout(Duration::from_secs(1)) {
             Err(_err) => ControlRequest::Check {
                 dataflow_uuid: dataflow_id,
             },
             Ok(AttachEvent::Control(control_request)) => control_request,
+            Ok(AttachEvent::Timeout(timeout)) => {
+                warn!("dataflow did not finish within {timeout:?}, stopping it");
+                timed_out = true;
+                ControlRequest::Stop {
+                    dataflow_uuid: dataflow_id,
+                    grace_duration: None,
+                }
+            }
+            Ok(AttachEvent::NodeExited { node_id, time }) => {
+                node_exit_times.insert(node_id, time);
+                continue;
+            }
             Ok(AttachEvent::Log(Ok(log_message))) => {
                 let LogMessage {
                     dataflow_id: _,
@@ -199,6 +251,39 @@ pub fn attach_dataflow(
             ControlRequestReply::DataflowStarted { uuid: _ } => (),
             ControlRequestReply::DataflowStopped { uuid, result } => {
                 info!("dataflow {uuid} stopped");
+                let timed_out_after = timeout.filter(|_| timed_out);
+                if let Some(path) = &report_path {
+                    // exit events are sent on a separate connection, so some of them might
+                    // arrive after the reply
+                    let deadline = Instant::now() + Duration::from_secs(1);
+                    while result
+                        .node_results
+                        .keys()
+                        .any(|node_id| !node_exit_times.contains_key(node_id))
+                    {
+                        match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                            Ok(AttachEvent::NodeExited { node_id, time }) => {
+                                node_exit_times.insert(node_id, time);
+                            }
+                            Ok(_) => {}
+                            Err(_) => break,
+                        }
+                    }
+                    let record = history::query_record(session, Some(uuid), None)?;
+                    report::write_report(
+                        path,
+                        &record,
+                        &result,
+                        &node_exit_times,
+                        timed_out_after,
+                    )?;
+                    println!("wrote report to `{}`", path.display());
+                }
+                if let Some(timeout) = timed_out_after {
+                    break Err(eyre::eyre!(
+                        "dataflow {uuid} did not finish within {timeout:?}"
+                    ));
+                }
                 break handle_dataflow_result(result, Some(uuid));
             }
             ControlRequestReply::DataflowReloaded { uuid } => {
@@ -212,4 +297,6 @@ pub fn attach_dataflow(
 enum AttachEvent {
     Control(ControlRequest),
     Log(eyre::Result<LogMessage>),
+    Timeout(Duration),
+    NodeExited { node_id: NodeId, time: SystemTime },
 }
@@ -1,6 +1,6 @@
 use dora_core::{
     config::OperatorId,
-    descriptor::{Descriptor, SINGLE_OPERATOR_DEFAULT_ID},
+    descriptor::{Descriptor, PythonConfig, SINGLE_OPERATOR_DEFAULT_ID},
 };
 use eyre::{eyre, Context};
 use std::{path::Path, process::Command};
@@ -17,6 +17,11 @@ pub fn build(dataflow: &Path) -> eyre::Result<()> {
     let default_op_id = OperatorId::from(SINGLE_OPERATOR_DEFAULT_ID.to_string());
 
     for node in descriptor.nodes {
+        if let Some(python) = &node.python {
+            create_venv(python, working_dir).with_context(|| {
+                format!("failed to create Python environment for node `{}`", node.id)
+            })?;
+        }
         match node.kind()? {
             dora_core::descriptor::NodeKind::Standard(_) => {
                 run_build_command(node.build.as_deref(), working_dir).with_context(|| {
@@ -79,3 +84,58 @@ fn run_build_command(build: Option<&str>, working_dir: &Path) -> eyre::Result<()
         Ok(())
     }
 }
+
+/// Creates the `venv` of the given Python config if it doesn't exist yet.
+fn create_venv(python: &PythonConfig, working_dir: &Path) -> eyre::Result<()> {
+    let Some(venv) = &python.venv else {
+        return Ok(());
+    };
+    let venv = working_dir.join(venv);
+    if venv.exists() {
+        return Ok(());
+    }
+
+    let base_python = python.base_interpreter_path(working_dir)?;
+    println!("Creating virtual environment `{}`", venv.display());
+    run_command(Command::new(base_python).args(["-m", "venv"]).arg(&venv))
+        .context("failed to create virtual environment")?;
+
+    if let Some(requirements) = python.requirements_path(working_dir) {
+        println!("Installing `{}`", requirements.display());
+        let mut install = Command::new(python.interpreter_path(working_dir)?);
+        install
+            .args(["-m", "pip", "install"])
+            .current_dir(working_dir);
+        if requirements
+            .file_name()
+            .is_some_and(|f| f == "pyproject.toml")
+        {
+            install.arg(requirements.parent().unwrap_or(working_dir));
+        } else {
+            install.arg("-r").arg(&requirements);
+        }
+        if let Err(err) = run_command(&mut install) {
+            // remove the incomplete environment, so that the next build retries
+            let _ = std::fs::remove_dir_all(&venv);
+            return Err(err.wrap_err(format!(
+                "failed to install `{}` into virtual environment",
+                requirements.display()
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn run_command(command: &mut Command) -> eyre::Result<()> {
+    let exit_status = command
+        .status()
+        .wrap_err_with(|| format!("failed to run `{:?}`", command.get_program()))?;
+    if exit_status.success() {
+        Ok(())
+    } else {
+        Err(eyre!(
+            "`{:?}` returned an error code",
+            command.get_program()
+        ))
+    }
+}
@@ -1,6 +1,7 @@
 use crate::connect_to_coordinator;
 use communication_layer_request_reply::TcpRequestReplyConnection;
 use dora_core::topics::{ControlRequest, ControlRequestReply};
+use dora_transport_security::TransportSecurity;
 use eyre::{bail, Context};
 use std::{
     io::{IsTerminal, Write},
@@ -8,7 +9,10 @@ use std::{
 };
 use termcolor::{Color, ColorChoice, ColorSpec, WriteColor};
 
-pub fn check_environment(coordinator_addr: SocketAddr) -> eyre::Result<()> {
+pub fn check_environment(
+    coordinator_addr: SocketAddr,
+    security: &TransportSecurity,
+) -> eyre::Result<()> {
     let mut error_occurred = false;
 
     let color_choice = if std::io::stdout().is_terminal() {
@@ -20,7 +24,7 @@ pub fn check_environment(coordinator_addr: SocketAddr) -> eyre::Result<()> {
 
     // check whether coordinator is running
     write!(stdout, "Dora Coordinator: ")?;
-    let mut session = match connect_to_coordinator(coordinator_addr) {
+    let mut session = match connect_to_coordinator(coordinator_addr, security) {
         Ok(session) => {
             let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::Green)));
             writeln!(stdout, "ok")?;
@@ -0,0 +1,138 @@
+use colored::Colorize;
+use communication_layer_request_reply::TcpConnection;
+use dora_core::topics::{ControlRequest, ControlRequestReply, LifecycleEvent, LifecycleEventKind};
+use dora_transport_security::TransportSecurity;
+use eyre::{bail, Context, Result};
+use std::net::{SocketAddr, TcpStream};
+
+use crate::formatting::error_summary;
+
+/// Prints the lifecycle events of the coordinator until the coordinator stops.
+///
+/// Prints one JSON object per line if `json` is set.
+pub fn events(
+    coordinator_socket: SocketAddr,
+    security: &TransportSecurity,
+    json: bool,
+) -> Result<()> {
+    let mut connection = subscribe(coordinator_socket, security)?;
+    while let Ok(raw) = connection.receive() {
+        if json {
+            println!("{}", String::from_utf8_lossy(&raw));
+            continue;
+        }
+        let event: LifecycleEvent =
+            serde_json::from_slice(&raw).wrap_err("failed to parse lifecycle event")?;
+        let time = chrono::DateTime::<chrono::Local>::from(event.time).format("%H:%M:%S%.3f");
+        println!(
+            "{} {}",
+            time.to_string().dimmed(),
+            format_event(&event.kind)
+        );
+    }
+
+    Ok(())
+}
+
+/// Opens a new connection to the coordinator that receives all lifecycle events.
+pub fn subscribe(
+    coordinator_socket: SocketAddr,
+    security: &TransportSecurity,
+) -> Result<TcpConnection> {
+    let stream =
+        TcpStream::connect(coordinator_socket).wrap_err("failed to connect to dora coordinator")?;
+    let mut connection = TcpConnection {
+        stream: security
+            .connect_blocking(stream)
+            .wrap_err("failed to secure connection to dora coordinator")?,
+    };
+    connection
+        .send(
+            &serde_json::to_vec(&ControlRequest::EventSubscribe)
+                .wrap_err("failed to serialize message")?,
+        )
+        .wrap_err("failed to send event subscribe request to coordinator")?;
+
+    let reply_raw = connection
+        .receive()
+        .wrap_err("failed to receive event subscribe reply")?;
+    match serde_json::from_slice(&reply_raw).wrap_err("failed to parse reply")? {
+        ControlRequestReply::EventSubscribed => Ok(connection),
+        ControlRequestReply::Error(err) => bail!("{err}"),
+        other => bail!("unexpected reply to event subscribe request: {other:?}"),
+    }
+}
+
+fn format_event(kind: &LifecycleEventKind) -> String {
+    match kind {
+        LifecycleEventKind::DataflowSpawned { dataflow, machines } => {
+            let machines: Vec<_> = machines.iter().map(|m| machine_name(m)).collect();
+            format!(
+                "{} {dataflow} on {}",
+                "dataflow spawned".bold(),
+                machines.join(", ")
+            )
+        }
+        LifecycleEventKind::NodesReady {
+            dataflow,
+            exited_before_subscribe,
+        } => {
+            if exited_before_subscribe.is_empty() {
+                format!("{} {dataflow}", "nodes ready".bold())
+            } else {
+                let nodes: Vec<_> = exited_before_subscribe
+                    .iter()
+                    .map(|n| n.to_string())
+                    .collect();
+                format!(
+                    "{} {dataflow} (exited before subscribing: {})",
+                    "nodes ready".bold(),
+                    nodes.join(", ")
+                )
+            }
+        }
+        LifecycleEventKind::NodeExited {
+            dataflow,
+            node_id,
+            result,
+            ..
+        } => match result {
+            Ok(()) => format!(
+                "{} {dataflow} {node_id}: {}",
+                "node exited".bold(),
+                "ok".green()
+            ),
+            Err(err) => format!(
+                "{} {dataflow} {node_id}: {} {}",
+                "node exited".bold(),
+                "failed:".red(),
+                error_summary(err)
+            ),
+        },
+        LifecycleEventKind::DataflowFinished { dataflow, result } => {
+            let status = if result.is_ok() {
+                "succeeded".green()
+            } else {
+                "failed".red()
+            };
+            format!("{} {dataflow}: {status}", "dataflow finished".bold())
+        }
+        LifecycleEventKind::DaemonConnected { machine_id } => {
+            format!("{} {}", "daemon connected".bold(), machine_name(machine_id))
+        }
+        LifecycleEventKind::DaemonDisconnected { machine_id } => {
+            format!(
+                "{} {}",
+                "daemon disconnected".bold(),
+                machine_name(machine_id)
+            )
+        }
+    }
+}
+
+fn machine_name(machine_id: &str) -> &str {
+    match machine_id {
+        "" => "<default machine>",
+        other => other,
+    }
+}
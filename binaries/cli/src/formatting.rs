@@ -1,4 +1,4 @@
-use dora_core::topics::{DataflowResult, NodeErrorCause};
+use dora_core::topics::{DataflowResult, NodeError, NodeErrorCause};
 
 pub struct FormatDataflowError<'a>(pub &'a DataflowResult);
 
@@ -48,3 +48,17 @@ impl std::fmt::Display for FormatDataflowError<'_> {
         Ok(())
     }
 }
+
+/// Formats the given error without the stderr output of the node.
+pub fn error_summary(err: &NodeError) -> String {
+    let summary = NodeError {
+        cause: match &err.cause {
+            NodeErrorCause::Other { .. } => NodeErrorCause::Other {
+                stderr: String::new(),
+            },
+            other => other.clone(),
+        },
+        ..err.clone()
+    };
+    summary.to_string()
+}
@@ -0,0 +1,134 @@
+use colored::Colorize;
+use communication_layer_request_reply::TcpRequestReplyConnection;
+use dora_core::topics::{ControlRequest, ControlRequestReply, DataflowRecord, DataflowStatus};
+use eyre::{bail, Context};
+use std::{io::Write, time::SystemTime};
+use tabwriter::TabWriter;
+use uuid::Uuid;
+
+/// Prints all running and finished dataflows known to the coordinator.
+pub fn list_all(session: &mut TcpRequestReplyConnection) -> eyre::Result<()> {
+    let reply_raw = session
+        .request(&serde_json::to_vec(&ControlRequest::History).unwrap())
+        .wrap_err("failed to send history request")?;
+    let records = match serde_json::from_slice(&reply_raw).wrap_err("failed to parse reply")? {
+        ControlRequestReply::History(records) => records,
+        ControlRequestReply::Error(err) => bail!("{err}"),
+        other => bail!("unexpected history reply: {other:?}"),
+    };
+
+    let mut tw = TabWriter::new(vec![]);
+    tw.write_all(b"UUID\tName\tStatus\tStarted\tFinished\n")?;
+    for record in &records {
+        tw.write_all(
+            format!(
+                "{}\t{}\t{}\t{}\t{}\n",
+                record.id.uuid,
+                run_name(record),
+                status_str(record.status),
+                format_time(record.started),
+                format_time(record.finished),
+            )
+            .as_bytes(),
+        )?;
+    }
+    tw.flush()?;
+    let formatted = String::from_utf8(tw.into_inner()?)?;
+
+    println!("{formatted}");
+
+    Ok(())
+}
+
+/// Prints the details and node results of a single dataflow.
+pub fn inspect(
+    session: &mut TcpRequestReplyConnection,
+    uuid: Option<Uuid>,
+    name: Option<String>,
+) -> eyre::Result<()> {
+    let record = query_record(session, uuid, name)?;
+
+    let machines: Vec<_> = record
+        .machines
+        .iter()
+        .map(|m| if m.is_empty() { "-" } else { m.as_str() })
+        .collect();
+    println!("UUID:      {}", record.id.uuid);
+    println!("Name:      {}", run_name(&record));
+    println!("Status:    {}", status_str(record.status));
+    println!(
+        "Path:      {}",
+        record
+            .dataflow_path
+            .as_ref()
+            .map(|p| p.display().to_string())
+            .unwrap_or_else(|| "-".into())
+    );
+    println!("Machines:  {}", machines.join(", "));
+    println!("Started:   {}", format_time(record.started));
+    let duration = record
+        .started
+        .zip(record.finished)
+        .and_then(|(started, finished)| finished.duration_since(started).ok());
+    match duration {
+        Some(duration) => println!(
+            "Finished:  {} (after {:.1}s)",
+            format_time(record.finished),
+            duration.as_secs_f64()
+        ),
+        None => println!("Finished:  {}", format_time(record.finished)),
+    }
+
+    println!("Nodes:");
+    for node_id in &record.nodes {
+        match record.node_results.get(node_id) {
+            Some(Ok(())) => println!("  {node_id}: {}", "ok".green()),
+            Some(Err(err)) => println!("  {node_id}: {} {err}", "failed:".red()),
+            None if record.status == DataflowStatus::Running => {
+                println!("  {node_id}: running")
+            }
+            None => println!("  {node_id}: -"),
+        }
+    }
+
+    Ok(())
+}
+
+pub fn query_record(
+    session: &mut TcpRequestReplyConnection,
+    uuid: Option<Uuid>,
+    name: Option<String>,
+) -> eyre::Result<DataflowRecord> {
+    let reply_raw = session
+        .request(&serde_json::to_vec(&ControlRequest::Inspect { uuid, name }).unwrap())
+        .wrap_err("failed to send inspect request")?;
+    match serde_json::from_slice(&reply_raw).wrap_err("failed to parse reply")? {
+        ControlRequestReply::DataflowRecord(record) => Ok(record),
+        ControlRequestReply::Error(err) => bail!("{err}"),
+        other => bail!("unexpected inspect reply: {other:?}"),
+    }
+}
+
+fn run_name(record: &DataflowRecord) -> String {
+    match &record.id.name {
+        Some(name) => format!("{name}@{}", record.index),
+        None => String::new(),
+    }
+}
+
+fn status_str(status: DataflowStatus) -> &'static str {
+    match status {
+        DataflowStatus::Running => "Running",
+        DataflowStatus::Finished => "Succeeded",
+        DataflowStatus::Failed => "Failed",
+    }
+}
+
+fn format_time(time: Option<SystemTime>) -> String {
+    match time {
+        Some(time) => chrono::DateTime::<chrono::Local>::from(time)
+            .format("%Y-%m-%d %H:%M:%S")
+            .to_string(),
+        None => "-".into(),
+    }
+}
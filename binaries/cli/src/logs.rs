@@ -1,6 +1,16 @@
-use communication_layer_request_reply::TcpRequestReplyConnection;
-use dora_core::topics::{ControlRequest, ControlRequestReply};
+use colored::Colorize;
+use communication_layer_request_reply::{TcpConnection, TcpRequestReplyConnection};
+use dora_core::{
+    config::NodeId,
+    coordinator_messages::NodeOutputLine,
+    topics::{ControlRequest, ControlRequestReply},
+};
+use dora_transport_security::TransportSecurity;
 use eyre::{bail, Context, Result};
+use std::{
+    net::{SocketAddr, TcpStream},
+    time::SystemTime,
+};
 use uuid::Uuid;
 
 use bat::{Input, PrettyPrinter};
@@ -10,6 +20,8 @@ pub fn logs(
     uuid: Option<Uuid>,
     name: Option<String>,
     node: String,
+    tail: Option<usize>,
+    crash: bool,
 ) -> Result<()> {
     let logs = {
         let reply_raw = session
@@ -18,6 +30,8 @@ pub fn logs(
                     uuid,
                     name,
                     node: node.clone(),
+                    crash,
+                    tail,
                 })
                 .wrap_err("")?,
             )
@@ -26,10 +40,16 @@ pub fn logs(
         let reply = serde_json::from_slice(&reply_raw).wrap_err("failed to parse reply")?;
         match reply {
             ControlRequestReply::Logs(logs) => logs,
+            ControlRequestReply::Error(err) => bail!("{err}"),
             other => bail!("unexpected reply to daemon logs: {other:?}"),
         }
     };
 
+    let title = if crash {
+        format!("Crash bundle of {node}.")
+    } else {
+        format!("Logs from {node}.")
+    };
     PrettyPrinter::new()
         .header(false)
         .grid(false)
@@ -37,9 +57,60 @@ pub fn logs(
         .paging_mode(bat::PagingMode::QuitIfOneScreen)
         .inputs(vec![Input::from_bytes(&logs)
             .name("Logs")
-            .title(format!("Logs from {node}.").as_str())])
+            .title(title.as_str())])
         .print()
         .wrap_err("Something went wrong with viewing log file")?;
 
     Ok(())
 }
+
+/// Streams the output of the given nodes until the dataflow finishes.
+///
+/// Uses a separate connection to the coordinator, which is taken over for
+/// streaming the output lines after the initial reply.
+pub fn follow_logs(
+    coordinator_socket: SocketAddr,
+    security: &TransportSecurity,
+    uuid: Option<Uuid>,
+    name: Option<String>,
+    nodes: Vec<NodeId>,
+    tail: Option<usize>,
+    since: Option<SystemTime>,
+) -> Result<()> {
+    let stream =
+        TcpStream::connect(coordinator_socket).wrap_err("failed to connect to dora coordinator")?;
+    let mut connection = TcpConnection {
+        stream: security
+            .connect_blocking(stream)
+            .wrap_err("failed to secure connection to dora coordinator")?,
+    };
+    connection
+        .send(
+            &serde_json::to_vec(&ControlRequest::LogFollow {
+                uuid,
+                name,
+                nodes,
+                tail,
+                since,
+            })
+            .wrap_err("failed to serialize message")?,
+        )
+        .wrap_err("failed to send log follow request to coordinator")?;
+
+    let reply_raw = connection
+        .receive()
+        .wrap_err("failed to receive log follow reply")?;
+    match serde_json::from_slice(&reply_raw).wrap_err("failed to parse reply")? {
+        ControlRequestReply::LogFollowStarted => {}
+        ControlRequestReply::Error(err) => bail!("{err}"),
+        other => bail!("unexpected reply to log follow request: {other:?}"),
+    }
+
+    while let Ok(raw) = connection.receive() {
+        let line: NodeOutputLine =
+            serde_json::from_slice(&raw).wrap_err("failed to parse output line")?;
+        println!("{} {}", format!("[{}]", line.node_id).bold(), line.line);
+    }
+
+    Ok(())
+}
@@ -4,6 +4,7 @@ use colored::Colorize;
 use communication_layer_request_reply::{RequestReplyLayer, TcpLayer, TcpRequestReplyConnection};
 use dora_coordinator::Event;
 use dora_core::{
+    config::{DataId, NodeId},
     descriptor::Descriptor,
     topics::{
         ControlRequest, ControlRequestReply, DataflowList, DORA_COORDINATOR_PORT_CONTROL_DEFAULT,
@@ -14,6 +15,7 @@ use dora_daemon::Daemon;
 #[cfg(feature = "tracing")]
 use dora_tracing::set_up_tracing;
 use dora_tracing::set_up_tracing_opts;
+use dora_transport_security::TransportSecurity;
 use duration_str::parse;
 use eyre::{bail, Context};
 use formatting::FormatDataflowError;
@@ -21,19 +23,28 @@ use std::{io::Write, net::SocketAddr};
 use std::{
     net::{IpAddr, Ipv4Addr},
     path::PathBuf,
-    time::Duration,
+    time::{Duration, SystemTime},
 };
 use tabwriter::TabWriter;
 use tokio::runtime::Builder;
 use uuid::Uuid;
 
+mod apply;
 mod attach;
 mod build;
 mod check;
+mod events;
 mod formatting;
 mod graph;
+mod history;
 mod logs;
+mod record;
+mod replay;
+mod report;
+mod run;
+mod security;
 mod template;
+mod top;
 mod up;
 
 const LOCALHOST: IpAddr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
@@ -44,6 +55,8 @@ const LISTEN_WILDCARD: IpAddr = IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0));
 struct Args {
     #[clap(subcommand)]
     command: Command,
+    #[clap(flatten)]
+    security: security::SecurityArgs,
 }
 
 /// dora-rs cli client
@@ -86,6 +99,25 @@ enum Command {
         #[clap(hide = true, long)]
         internal_create_with_path_dependencies: bool,
     },
+    /// Run the given dataflow in a single process, without a coordinator and daemon.
+    ///
+    /// The output of all nodes is printed. Press ctrl-c to stop the dataflow.
+    Run {
+        /// Path to the dataflow descriptor file
+        #[clap(value_name = "PATH", value_hint = clap::ValueHint::FilePath)]
+        dataflow: PathBuf,
+        /// Run the build commands of the dataflow before starting it
+        #[clap(long, action)]
+        build: bool,
+        /// Stop the dataflow after the given duration
+        #[clap(long, value_name = "DURATION")]
+        #[arg(value_parser = parse)]
+        stop_after: Option<Duration>,
+        /// Kill the nodes if they don't stop within the given duration after stopping the dataflow
+        #[clap(long, value_name = "DURATION")]
+        #[arg(value_parser = parse)]
+        grace_duration: Option<Duration>,
+    },
     /// Spawn coordinator and daemon in local mode (with default config)
     Up {
         /// Use a custom configuration
@@ -109,7 +141,7 @@ enum Command {
         /// Path to the dataflow descriptor file
         #[clap(value_name = "PATH", value_hint = clap::ValueHint::FilePath)]
         dataflow: PathBuf,
-        /// Assign a name to the dataflow
+        /// Assign a name to the dataflow, which must not contain `@`
         #[clap(long)]
         name: Option<String>,
         /// Address of the dora coordinator
@@ -127,6 +159,34 @@ enum Command {
         /// Enable hot reloading (Python only)
         #[clap(long, action)]
         hot_reload: bool,
+        /// Write a report of the node results to the given `.xml` (JUnit) or `.json` file
+        #[clap(long, value_name = "PATH", conflicts_with = "detach")]
+        report: Option<PathBuf>,
+        /// Stop the dataflow and fail if it doesn't finish within the given duration
+        #[clap(long, value_name = "DURATION", conflicts_with = "detach")]
+        #[arg(value_parser = parse)]
+        timeout: Option<Duration>,
+    },
+    /// Apply a changed dataflow descriptor to a running dataflow.
+    ///
+    /// Added nodes are spawned, removed nodes are stopped, and the inputs of existing
+    /// nodes are rewired. Other changes are rejected.
+    Apply {
+        /// Path to the changed dataflow descriptor file
+        #[clap(value_name = "PATH", value_hint = clap::ValueHint::FilePath)]
+        dataflow: PathBuf,
+        /// UUID of the running dataflow
+        #[clap(long)]
+        uuid: Option<Uuid>,
+        /// Name of the running dataflow
+        #[clap(long, conflicts_with = "uuid")]
+        name: Option<String>,
+        /// Address of the dora coordinator
+        #[clap(long, value_name = "IP", default_value_t = LOCALHOST)]
+        coordinator_addr: IpAddr,
+        /// Port number of the coordinator control server
+        #[clap(long, value_name = "PORT", default_value_t = DORA_COORDINATOR_PORT_CONTROL_DEFAULT)]
+        coordinator_port: u16,
     },
     /// Stop the given dataflow UUID. If no id is provided, you will be able to choose between the running dataflows.
     Stop {
@@ -146,8 +206,43 @@ enum Command {
         #[clap(long, value_name = "PORT", default_value_t = DORA_COORDINATOR_PORT_CONTROL_DEFAULT)]
         coordinator_port: u16,
     },
+    /// Restart a single node of a running dataflow.
+    ///
+    /// The outputs of the node stay open while it restarts, and inputs that arrive in
+    /// the meantime are buffered up to the queue size of each input.
+    Restart {
+        /// UUID or name of the dataflow
+        dataflow: String,
+        /// ID of the node that should be restarted
+        node: String,
+        /// Kill the node if it doesn't stop after the given duration
+        #[clap(long, value_name = "DURATION")]
+        #[arg(value_parser = parse)]
+        grace_duration: Option<Duration>,
+        /// Address of the dora coordinator
+        #[clap(long, value_name = "IP", default_value_t = LOCALHOST)]
+        coordinator_addr: IpAddr,
+        /// Port number of the coordinator control server
+        #[clap(long, value_name = "PORT", default_value_t = DORA_COORDINATOR_PORT_CONTROL_DEFAULT)]
+        coordinator_port: u16,
+    },
     /// List running dataflows.
     List {
+        /// Also list finished dataflows, with their start and stop times
+        #[clap(long, short)]
+        all: bool,
+        /// Address of the dora coordinator
+        #[clap(long, value_name = "IP", default_value_t = LOCALHOST)]
+        coordinator_addr: IpAddr,
+        /// Port number of the coordinator control server
+        #[clap(long, value_name = "PORT", default_value_t = DORA_COORDINATOR_PORT_CONTROL_DEFAULT)]
+        coordinator_port: u16,
+    },
+    /// Show the details and node results of a running or finished dataflow.
+    Inspect {
+        /// Identifier of the dataflow, use `<name>@<index>` to select a previous run
+        #[clap(value_name = "UUID_OR_NAME")]
+        dataflow: String,
         /// Address of the dora coordinator
         #[clap(long, value_name = "IP", default_value_t = LOCALHOST)]
         coordinator_addr: IpAddr,
@@ -160,12 +255,29 @@ enum Command {
     /// Show logs of a given dataflow and node.
     #[command(allow_missing_positional = true)]
     Logs {
-        /// Identifier of the dataflow
+        /// Identifier of the dataflow, use `<name>@<index>` to select a previous run
         #[clap(value_name = "UUID_OR_NAME")]
         dataflow: Option<String>,
-        /// Show logs for the given node
-        #[clap(value_name = "NAME")]
-        node: String,
+        /// Show logs for the given nodes (comma-separated)
+        #[clap(value_name = "NAME", value_delimiter = ',', required = true)]
+        nodes: Vec<String>,
+        /// Keep streaming new output lines until the dataflow finishes
+        #[clap(long, short)]
+        follow: bool,
+        /// Only show the last N lines of each node
+        #[clap(long, value_name = "N")]
+        tail: Option<usize>,
+        /// Only show lines that were emitted within the given duration (requires `--follow`)
+        ///
+        /// Lines are read from the log files if the dataflow uses the `jsonl` log
+        /// format. Otherwise, only the recent output that the daemon keeps in memory
+        /// is searched.
+        #[clap(long, value_name = "DURATION", requires = "follow")]
+        #[arg(value_parser = parse)]
+        since: Option<Duration>,
+        /// Show the crash bundle of failed nodes instead of their logs
+        #[clap(long, conflicts_with_all = ["follow", "since"])]
+        crash: bool,
         /// Address of the dora coordinator
         #[clap(long, value_name = "IP", default_value_t = LOCALHOST)]
         coordinator_addr: IpAddr,
@@ -173,7 +285,76 @@ enum Command {
         #[clap(long, value_name = "PORT", default_value_t = DORA_COORDINATOR_PORT_CONTROL_DEFAULT)]
         coordinator_port: u16,
     },
-    // Metrics,
+    /// Show the resource usage and message rates of all running nodes.
+    Top {
+        /// Refresh interval
+        #[clap(long, value_name = "DURATION", default_value = "2s")]
+        #[arg(value_parser = parse)]
+        interval: Duration,
+        /// Address of the dora coordinator
+        #[clap(long, value_name = "IP", default_value_t = LOCALHOST)]
+        coordinator_addr: IpAddr,
+        /// Port number of the coordinator control server
+        #[clap(long, value_name = "PORT", default_value_t = DORA_COORDINATOR_PORT_CONTROL_DEFAULT)]
+        coordinator_port: u16,
+    },
+    /// Stream lifecycle events of the coordinator, e.g. spawned and finished dataflows.
+    Events {
+        /// Print the events as JSON objects, one per line
+        #[clap(long)]
+        json: bool,
+        /// Address of the dora coordinator
+        #[clap(long, value_name = "IP", default_value_t = LOCALHOST)]
+        coordinator_addr: IpAddr,
+        /// Port number of the coordinator control server
+        #[clap(long, value_name = "PORT", default_value_t = DORA_COORDINATOR_PORT_CONTROL_DEFAULT)]
+        coordinator_port: u16,
+    },
+    /// Record outputs of a running dataflow into Parquet files.
+    Record {
+        /// Identifier of the dataflow
+        #[clap(value_name = "UUID_OR_NAME")]
+        dataflow: String,
+        /// Outputs to record, e.g. `camera/image` (records all outputs if none are given)
+        #[clap(value_name = "NODE/OUTPUT")]
+        outputs: Vec<String>,
+        /// Directory to write the recordings to
+        #[clap(long, short, value_name = "PATH", default_value = "out")]
+        output_dir: PathBuf,
+        /// Address of the dora coordinator
+        #[clap(long, value_name = "IP", default_value_t = LOCALHOST)]
+        coordinator_addr: IpAddr,
+        /// Port number of the coordinator control server
+        #[clap(long, value_name = "PORT", default_value_t = DORA_COORDINATOR_PORT_CONTROL_DEFAULT)]
+        coordinator_port: u16,
+    },
+    /// Start a dataflow with some nodes replaced by outputs recorded with `dora record`.
+    Replay {
+        /// Path to the dataflow descriptor file
+        #[clap(value_name = "PATH", value_hint = clap::ValueHint::FilePath)]
+        dataflow: PathBuf,
+        /// Recording directory of a dataflow run, e.g. `out/<DATAFLOW_ID>`
+        #[clap(value_name = "RECORDING", value_hint = clap::ValueHint::DirPath)]
+        recording: PathBuf,
+        /// Nodes to replace (comma-separated, defaults to all recorded nodes)
+        #[clap(long, value_name = "NAME", value_delimiter = ',')]
+        nodes: Vec<String>,
+        /// Replay speed relative to the recording, `0` replays as fast as possible
+        #[clap(long, value_name = "FACTOR")]
+        speed: Option<f64>,
+        /// Assign a name to the dataflow
+        #[clap(long)]
+        name: Option<String>,
+        /// Address of the dora coordinator
+        #[clap(long, value_name = "IP", default_value_t = LOCALHOST)]
+        coordinator_addr: IpAddr,
+        /// Port number of the coordinator control server
+        #[clap(long, value_name = "PORT", default_value_t = DORA_COORDINATOR_PORT_CONTROL_DEFAULT)]
+        coordinator_port: u16,
+        /// Run the dataflow in background
+        #[clap(long, action)]
+        detach: bool,
+    },
     // Stats,
     // Get,
     // Upgrade,
@@ -213,6 +394,15 @@ enum Command {
         /// Port number to bind to for control communication
         #[clap(long, default_value_t = DORA_COORDINATOR_PORT_CONTROL_DEFAULT)]
         control_port: u16,
+        /// Network interface to bind to for the HTTP control API
+        #[clap(long, default_value_t = LOCALHOST)]
+        http_interface: IpAddr,
+        /// Serve the HTTP/WebSocket control API on the given port
+        #[clap(long)]
+        http_port: Option<u16>,
+        /// Persist the coordinator state in the given directory to restore it after restarts
+        #[clap(long, value_name = "PATH")]
+        state_dir: Option<PathBuf>,
         /// Suppresses all log output to stdout.
         #[clap(long)]
         quiet: bool,
@@ -291,6 +481,8 @@ fn run() -> eyre::Result<()> {
         .build()
         .filter();
 
+    let security = args.security.load()?;
+
     match args.command {
         Command::Check {
             dataflow,
@@ -305,9 +497,11 @@ fn run() -> eyre::Result<()> {
                     .ok_or_else(|| eyre::eyre!("dataflow path has no parent dir"))?
                     .to_owned();
                 Descriptor::blocking_read(&dataflow)?.check(&working_dir)?;
-                check::check_environment((coordinator_addr, coordinator_port).into())?
+                check::check_environment((coordinator_addr, coordinator_port).into(), &security)?
+            }
+            None => {
+                check::check_environment((coordinator_addr, coordinator_port).into(), &security)?
             }
-            None => check::check_environment((coordinator_addr, coordinator_port).into())?,
         },
         Command::Graph {
             dataflow,
@@ -319,27 +513,38 @@ fn run() -> eyre::Result<()> {
         Command::Build { dataflow } => {
             build::build(&dataflow)?;
         }
+        Command::Run {
+            dataflow,
+            build,
+            stop_after,
+            grace_duration,
+        } => run::run(&dataflow, build, stop_after, grace_duration)?,
         Command::New {
             args,
             internal_create_with_path_dependencies,
         } => template::create(args, internal_create_with_path_dependencies)?,
         Command::Up { config } => {
-            up::up(config.as_deref())?;
+            up::up(config.as_deref(), &args.security, &security)?;
         }
         Command::Logs {
             dataflow,
-            node,
+            nodes,
+            follow,
+            tail,
+            since,
+            crash,
             coordinator_addr,
             coordinator_port,
         } => {
-            let mut session = connect_to_coordinator((coordinator_addr, coordinator_port).into())
+            let coordinator_socket = (coordinator_addr, coordinator_port).into();
+            let mut session = connect_to_coordinator(coordinator_socket, &security)
                 .wrap_err("failed to connect to dora coordinator")?;
             let list = query_running_dataflows(&mut *session)
                 .wrap_err("failed to query running dataflows")?;
-            if let Some(dataflow) = dataflow {
+            let (uuid, name) = if let Some(dataflow) = dataflow {
                 let uuid = Uuid::parse_str(&dataflow).ok();
                 let name = if uuid.is_some() { None } else { Some(dataflow) };
-                logs::logs(&mut *session, uuid, name, node)?
+                (uuid, name)
             } else {
                 let active = list.get_active();
                 let uuid = match &active[..] {
@@ -347,7 +552,24 @@ fn run() -> eyre::Result<()> {
                     [uuid] => uuid.clone(),
                     _ => inquire::Select::new("Choose dataflow to show logs:", active).prompt()?,
                 };
-                logs::logs(&mut *session, Some(uuid.uuid), None, node)?
+                (Some(uuid.uuid), None)
+            };
+            if follow {
+                let since = since.map(|d| SystemTime::now() - d);
+                let nodes = nodes.into_iter().map(NodeId::from).collect();
+                logs::follow_logs(
+                    coordinator_socket,
+                    &security,
+                    uuid,
+                    name,
+                    nodes,
+                    tail,
+                    since,
+                )?
+            } else {
+                for node in nodes {
+                    logs::logs(&mut *session, uuid, name.clone(), node, tail, crash)?
+                }
             }
         }
         Command::Start {
@@ -358,7 +580,12 @@ fn run() -> eyre::Result<()> {
             attach,
             detach,
             hot_reload,
+            report,
+            timeout,
         } => {
+            if let Some(path) = &report {
+                report::ReportFormat::from_path(path)?;
+            }
             let dataflow_descriptor =
                 Descriptor::blocking_read(&dataflow).wrap_err("Failed to read yaml dataflow")?;
             let working_dir = dataflow
@@ -376,12 +603,20 @@ fn run() -> eyre::Result<()> {
             }
 
             let coordinator_socket = (coordinator_addr, coordinator_port).into();
-            let mut session = connect_to_coordinator(coordinator_socket)
+            let mut session = connect_to_coordinator(coordinator_socket, &security)
                 .wrap_err("failed to connect to dora coordinator")?;
+            // subscribe before starting, so that nodes that exit right away are reported
+            let report = report
+                .map(|path| {
+                    report::NodeExits::subscribe(coordinator_socket, &security)
+                        .map(|node_exits| (path, node_exits))
+                })
+                .transpose()?;
             let dataflow_id = start_dataflow(
                 dataflow_descriptor.clone(),
                 name,
                 working_dir,
+                dataflow.canonicalize().ok(),
                 &mut *session,
             )?;
 
@@ -403,19 +638,149 @@ fn run() -> eyre::Result<()> {
                     &mut *session,
                     hot_reload,
                     coordinator_socket,
+                    &security,
                     log_level,
+                    timeout,
+                    report,
                 )?
             }
         }
         Command::List {
+            all,
             coordinator_addr,
             coordinator_port,
-        } => match connect_to_coordinator((coordinator_addr, coordinator_port).into()) {
+        } => match connect_to_coordinator((coordinator_addr, coordinator_port).into(), &security) {
+            Ok(mut session) if all => history::list_all(&mut *session)?,
             Ok(mut session) => list(&mut *session)?,
             Err(_) => {
                 bail!("No dora coordinator seems to be running.");
             }
         },
+        Command::Inspect {
+            dataflow,
+            coordinator_addr,
+            coordinator_port,
+        } => {
+            let mut session =
+                connect_to_coordinator((coordinator_addr, coordinator_port).into(), &security)
+                    .wrap_err("failed to connect to dora coordinator")?;
+            let uuid = Uuid::parse_str(&dataflow).ok();
+            let name = if uuid.is_some() { None } else { Some(dataflow) };
+            history::inspect(&mut *session, uuid, name)?;
+        }
+        Command::Record {
+            dataflow,
+            outputs,
+            output_dir,
+            coordinator_addr,
+            coordinator_port,
+        } => {
+            let uuid = Uuid::parse_str(&dataflow).ok();
+            let name = if uuid.is_some() { None } else { Some(dataflow) };
+            let outputs = outputs
+                .iter()
+                .map(|output| match output.split_once('/') {
+                    Some((node, output)) => Ok((
+                        NodeId::from(node.to_owned()),
+                        DataId::from(output.to_owned()),
+                    )),
+                    None => bail!("invalid output `{output}`, expected `<node>/<output>`"),
+                })
+                .collect::<eyre::Result<_>>()?;
+            record::record(
+                (coordinator_addr, coordinator_port).into(),
+                &security,
+                uuid,
+                name,
+                outputs,
+                &output_dir,
+            )?
+        }
+        Command::Replay {
+            dataflow,
+            recording,
+            nodes,
+            speed,
+            name,
+            coordinator_addr,
+            coordinator_port,
+            detach,
+        } => {
+            let mut dataflow_descriptor =
+                Descriptor::blocking_read(&dataflow).wrap_err("Failed to read yaml dataflow")?;
+            let working_dir = dataflow
+                .canonicalize()
+                .context("failed to canonicalize dataflow path")?
+                .parent()
+                .ok_or_else(|| eyre::eyre!("dataflow path has no parent dir"))?
+                .to_owned();
+            let nodes = nodes.into_iter().map(NodeId::from).collect();
+            let replaced =
+                replay::replace_recorded_nodes(&mut dataflow_descriptor, &recording, nodes, speed)?;
+            dataflow_descriptor
+                .check(&working_dir)
+                .wrap_err("Could not validate yaml")?;
+            println!(
+                "replaying recorded outputs of {}",
+                replaced
+                    .iter()
+                    .map(|id| format!("`{id}`"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+
+            let coordinator_socket = (coordinator_addr, coordinator_port).into();
+            let mut session = connect_to_coordinator(coordinator_socket, &security)
+                .wrap_err("failed to connect to dora coordinator")?;
+            let dataflow_id = start_dataflow(
+                dataflow_descriptor.clone(),
+                name,
+                working_dir,
+                dataflow.canonicalize().ok(),
+                &mut *session,
+            )?;
+            if !detach {
+                attach_dataflow(
+                    dataflow_descriptor,
+                    dataflow,
+                    dataflow_id,
+                    &mut *session,
+                    false,
+                    coordinator_socket,
+                    &security,
+                    log_level,
+                    None,
+                    None,
+                )?
+            }
+        }
+        Command::Top {
+            interval,
+            coordinator_addr,
+            coordinator_port,
+        } => {
+            let mut session =
+                connect_to_coordinator((coordinator_addr, coordinator_port).into(), &security)
+                    .wrap_err("could not connect to dora coordinator")?;
+            top::top(&mut *session, interval)?;
+        }
+        Command::Events {
+            json,
+            coordinator_addr,
+            coordinator_port,
+        } => events::events((coordinator_addr, coordinator_port).into(), &security, json)?,
+        Command::Apply {
+            dataflow,
+            uuid,
+            name,
+            coordinator_addr,
+            coordinator_port,
+        } => {
+            let mut session =
+                connect_to_coordinator((coordinator_addr, coordinator_port).into(), &security)
+                    .wrap_err("could not connect to dora coordinator")?;
+            apply::apply(&mut *session, &dataflow, uuid, name)?
+        }
         Command::Stop {
             uuid,
             name,
@@ -423,14 +788,31 @@ fn run() -> eyre::Result<()> {
             coordinator_addr,
             coordinator_port,
         } => {
-            let mut session = connect_to_coordinator((coordinator_addr, coordinator_port).into())
-                .wrap_err("could not connect to dora coordinator")?;
+            let mut session =
+                connect_to_coordinator((coordinator_addr, coordinator_port).into(), &security)
+                    .wrap_err("could not connect to dora coordinator")?;
             match (uuid, name) {
                 (Some(uuid), _) => stop_dataflow(uuid, grace_duration, &mut *session)?,
                 (None, Some(name)) => stop_dataflow_by_name(name, grace_duration, &mut *session)?,
                 (None, None) => stop_dataflow_interactive(grace_duration, &mut *session)?,
             }
         }
+        Command::Restart {
+            dataflow,
+            node,
+            grace_duration,
+            coordinator_addr,
+            coordinator_port,
+        } => {
+            let mut session =
+                connect_to_coordinator((coordinator_addr, coordinator_port).into(), &security)
+                    .wrap_err("could not connect to dora coordinator")?;
+            let (uuid, name) = match Uuid::parse_str(&dataflow) {
+                Ok(uuid) => (Some(uuid), None),
+                Err(_) => (None, Some(dataflow)),
+            };
+            restart_node(uuid, name, node.into(), grace_duration, &mut *session)?
+        }
         Command::Destroy {
             config,
             coordinator_addr,
@@ -438,12 +820,16 @@ fn run() -> eyre::Result<()> {
         } => up::destroy(
             config.as_deref(),
             (coordinator_addr, coordinator_port).into(),
+            &security,
         )?,
         Command::Coordinator {
             interface,
             port,
             control_interface,
             control_port,
+            http_interface,
+            http_port,
+            state_dir,
             quiet,
         } => {
             let rt = Builder::new_multi_thread()
@@ -453,11 +839,21 @@ fn run() -> eyre::Result<()> {
             rt.block_on(async {
                 let bind = SocketAddr::new(interface, port);
                 let bind_control = SocketAddr::new(control_interface, control_port);
-                let (port, task) =
-                    dora_coordinator::start(bind, bind_control, futures::stream::empty::<Event>())
-                        .await?;
+                let bind_http = http_port.map(|port| SocketAddr::new(http_interface, port));
+                let (port, task) = dora_coordinator::start(
+                    bind,
+                    bind_control,
+                    bind_http,
+                    state_dir,
+                    security,
+                    futures::stream::empty::<Event>(),
+                )
+                .await?;
                 if !quiet {
                     println!("Listening for incoming daemon connection on {port}");
+                    if let Some(bind_http) = bind_http {
+                        println!("Serving HTTP control API on http://{bind_http}/api");
+                    }
                 }
                 task.await
             })
@@ -493,7 +889,7 @@ fn run() -> eyre::Result<()> {
                         if coordinator_addr.ip() == LOCALHOST {
                             tracing::info!("Starting in local mode");
                         }
-                        Daemon::run(coordinator_addr, machine_id.unwrap_or_default(), inter_daemon_addr, local_listen_port).await
+                        Daemon::run(coordinator_addr, machine_id.unwrap_or_default(), inter_daemon_addr, local_listen_port, security).await
                     }
                 }
             })
@@ -509,6 +905,7 @@ fn start_dataflow(
     dataflow: Descriptor,
     name: Option<String>,
     local_working_dir: PathBuf,
+    dataflow_path: Option<PathBuf>,
     session: &mut TcpRequestReplyConnection,
 ) -> Result<Uuid, eyre::ErrReport> {
     let reply_raw = session
@@ -517,6 +914,7 @@ fn start_dataflow(
                 dataflow,
                 name,
                 local_working_dir,
+                dataflow_path,
             })
             .unwrap(),
         )
@@ -618,6 +1016,36 @@ fn stop_dataflow_by_name(
     }
 }
 
+fn restart_node(
+    uuid: Option<Uuid>,
+    name: Option<String>,
+    node: NodeId,
+    grace_duration: Option<Duration>,
+    session: &mut TcpRequestReplyConnection,
+) -> Result<(), eyre::ErrReport> {
+    let reply_raw = session
+        .request(
+            &serde_json::to_vec(&ControlRequest::Restart {
+                uuid,
+                name,
+                node,
+                grace_duration,
+            })
+            .unwrap(),
+        )
+        .wrap_err("failed to send restart node message")?;
+    let result: ControlRequestReply =
+        serde_json::from_slice(&reply_raw).wrap_err("failed to parse reply")?;
+    match result {
+        ControlRequestReply::NodeRestarted { uuid, node_id } => {
+            eprintln!("restarting node `{node_id}` of dataflow `{uuid}`");
+            Ok(())
+        }
+        ControlRequestReply::Error(err) => bail!("{err}"),
+        other => bail!("unexpected restart node reply: {other:?}"),
+    }
+}
+
 fn list(session: &mut TcpRequestReplyConnection) -> Result<(), eyre::ErrReport> {
     let list = query_running_dataflows(session)?;
 
@@ -658,6 +1086,7 @@ fn query_running_dataflows(session: &mut TcpRequestReplyConnection) -> eyre::Res
 
 fn connect_to_coordinator(
     coordinator_addr: SocketAddr,
+    security: &TransportSecurity,
 ) -> std::io::Result<Box<TcpRequestReplyConnection>> {
-    TcpLayer::new().connect(coordinator_addr)
+    TcpLayer::with_security(security.clone()).connect(coordinator_addr)
 }
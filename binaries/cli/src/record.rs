@@ -0,0 +1,174 @@
+use aligned_vec::{AVec, ConstAlign};
+use communication_layer_request_reply::TcpConnection;
+use dora_core::{
+    config::{DataId, NodeId},
+    coordinator_messages::TappedOutput,
+    topics::{ControlRequest, ControlRequestReply},
+};
+use dora_node_api::{
+    arrow::array::{make_array, Array},
+    RawData,
+};
+use dora_record::{recording_path, ParquetWriter};
+use dora_transport_security::TransportSecurity;
+use eyre::{bail, Context, Result};
+use std::{
+    collections::BTreeMap,
+    net::{Shutdown, SocketAddr, TcpStream},
+    path::Path,
+};
+use uuid::Uuid;
+
+/// Records the given outputs of a running dataflow into Parquet files until the
+/// dataflow finishes or Ctrl-C is pressed.
+///
+/// The files are written to `<output_dir>/<dataflow_uuid>/<input>.parquet`, using the
+/// same layout as the `dora-record` node (see [`dora_record`]). Each output is
+/// recorded as if it was mapped to an input named `<node>-<output>`.
+pub fn record(
+    coordinator_socket: SocketAddr,
+    security: &TransportSecurity,
+    uuid: Option<Uuid>,
+    name: Option<String>,
+    outputs: Vec<(NodeId, DataId)>,
+    output_dir: &Path,
+) -> Result<()> {
+    let stream =
+        TcpStream::connect(coordinator_socket).wrap_err("failed to connect to dora coordinator")?;
+    let mut connection = TcpConnection {
+        stream: security
+            .connect_blocking(stream)
+            .wrap_err("failed to secure connection to dora coordinator")?,
+    };
+    connection
+        .send(
+            &serde_json::to_vec(&ControlRequest::Record {
+                uuid,
+                name,
+                outputs,
+            })
+            .wrap_err("failed to serialize message")?,
+        )
+        .wrap_err("failed to send record request to coordinator")?;
+
+    let reply_raw = connection
+        .receive()
+        .wrap_err("failed to receive record reply")?;
+    let (dataflow_id, outputs) =
+        match serde_json::from_slice(&reply_raw).wrap_err("failed to parse reply")? {
+            ControlRequestReply::RecordStarted {
+                dataflow_id,
+                outputs,
+            } => (dataflow_id, outputs),
+            ControlRequestReply::Error(err) => bail!("{err}"),
+            other => bail!("unexpected reply to record request: {other:?}"),
+        };
+
+    // stop receiving on ctrl-c, which closes all files cleanly
+    let socket = connection
+        .stream
+        .tcp_stream()
+        .try_clone()
+        .wrap_err("failed to clone coordinator connection")?;
+    ctrlc::set_handler(move || {
+        let _ = socket.shutdown(Shutdown::Both);
+    })
+    .wrap_err("failed to set ctrl-c handler")?;
+
+    let dataflow_dir = output_dir.join(dataflow_id.to_string());
+    eprintln!(
+        "Recording {} outputs of dataflow `{dataflow_id}` to `{}`. Press Ctrl-C to stop.",
+        outputs.len(),
+        dataflow_dir.display()
+    );
+
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .context("tokio runtime failed")?;
+    let mut writers: BTreeMap<(NodeId, DataId), ParquetWriter> = BTreeMap::new();
+    let mut message_count = 0;
+    let result = rt.block_on(async {
+        while let Ok(raw) = connection.receive() {
+            let output: TappedOutput =
+                serde_json::from_slice(&raw).wrap_err("failed to parse tapped output")?;
+            // the data follows as a separate binary frame
+            let Ok(data) = connection.receive() else {
+                break;
+            };
+            let raw_data = if data.is_empty() {
+                RawData::Empty
+            } else {
+                RawData::Vec(AVec::<u8, ConstAlign<128>>::from_slice(128, &data))
+            };
+            let data = make_array(
+                raw_data
+                    .into_arrow_array(&output.metadata.type_info)
+                    .wrap_err("failed to read tapped output data")?,
+            );
+
+            let key = (output.node_id, output.output_id);
+            let writer = match writers.entry(key) {
+                std::collections::btree_map::Entry::Occupied(entry) => entry.into_mut(),
+                std::collections::btree_map::Entry::Vacant(entry) => {
+                    let (node_id, output_id) = entry.key();
+                    let input_id = recorded_input_id(node_id, output_id);
+                    let path = recording_path(&dataflow_dir, &input_id);
+                    let source = Some((node_id.as_ref(), output_id.as_str()));
+                    let writer = ParquetWriter::create(&path, &input_id, source, data.data_type())
+                        .await
+                        .wrap_err_with(|| format!("failed to create `{}`", path.display()))?;
+                    entry.insert(writer)
+                }
+            };
+            writer
+                .write(data, &output.metadata)
+                .await
+                .wrap_err("failed to write recorded output")?;
+            message_count += 1;
+        }
+        eyre::Result::<()>::Ok(())
+    });
+
+    let file_count = writers.len();
+    rt.block_on(async {
+        for ((node_id, output_id), writer) in writers {
+            if let Err(err) = writer.close().await {
+                tracing::warn!("failed to close recording of `{node_id}/{output_id}`: {err:?}");
+            }
+        }
+    });
+    result?;
+
+    eprintln!("Recorded {message_count} messages into {file_count} files.");
+
+    Ok(())
+}
+
+/// Name of the input that the given output is recorded as.
+///
+/// Operator outputs contain a `/`, which is replaced to keep all files in the
+/// dataflow directory.
+fn recorded_input_id(node_id: &NodeId, output_id: &DataId) -> String {
+    format!("{node_id}-{output_id}").replace('/', "-")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recorded_input_ids() {
+        let input_id = |node: &str, output: &str| {
+            recorded_input_id(
+                &NodeId::from(node.to_owned()),
+                &DataId::from(output.to_owned()),
+            )
+        };
+        assert_eq!(input_id("camera", "image"), "camera-image");
+        assert_eq!(
+            input_id("runtime-node", "op/image"),
+            "runtime-node-op-image"
+        );
+    }
+}
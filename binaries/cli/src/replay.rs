@@ -0,0 +1,177 @@
+use dora_core::{
+    config::NodeId,
+    descriptor::{Descriptor, EnvValue, NodeKind},
+};
+use dora_record::RecordingReader;
+use eyre::{bail, Context, ContextCompat};
+use std::{collections::BTreeSet, path::Path};
+
+/// Replaces the given nodes with `dora-replay` nodes that send the outputs recorded
+/// by `dora record` or `dora-record` instead.
+///
+/// Replaces all nodes that have recorded outputs if `nodes` is empty. Returns the IDs
+/// of the replaced nodes.
+pub fn replace_recorded_nodes(
+    descriptor: &mut Descriptor,
+    recording: &Path,
+    nodes: Vec<NodeId>,
+    speed: Option<f64>,
+) -> eyre::Result<BTreeSet<NodeId>> {
+    let recording = recording
+        .canonicalize()
+        .wrap_err_with(|| format!("failed to open recording `{}`", recording.display()))?;
+    let resolved = descriptor.resolve_aliases_and_set_defaults()?;
+    let recorded_nodes = recorded_nodes(&recording)?;
+    let recorded = |node_id: &NodeId| recorded_nodes.contains(node_id.as_ref());
+
+    let nodes: BTreeSet<NodeId> = if nodes.is_empty() {
+        resolved
+            .iter()
+            .map(|n| &n.id)
+            .filter(|id| recorded(id))
+            .cloned()
+            .collect()
+    } else {
+        for node_id in &nodes {
+            if !resolved.iter().any(|n| &n.id == node_id) {
+                bail!("dataflow has no node `{node_id}`");
+            }
+            if !recorded(node_id) {
+                bail!(
+                    "recording `{}` contains no outputs of node `{node_id}`",
+                    recording.display()
+                );
+            }
+        }
+        nodes.into_iter().collect()
+    };
+    if nodes.is_empty() {
+        bail!(
+            "recording `{}` contains no outputs of any node of the dataflow \
+            (expected a directory written by `dora record` or `dora-record`, e.g. `out/<DATAFLOW_ID>`)",
+            recording.display()
+        );
+    }
+
+    let replay_node = replay_node_path();
+    for node in &mut descriptor.nodes {
+        if !nodes.contains(&node.id) {
+            continue;
+        }
+        if let NodeKind::Operator(_) = node.kind()? {
+            bail!(
+                "replaying single-operator node `{}` is not supported",
+                node.id
+            );
+        }
+        let outputs = resolved
+            .iter()
+            .find(|n| n.id == node.id)
+            .context("node was not resolved")?
+            .kind
+            .run_config()
+            .outputs;
+
+        let env = node.env.get_or_insert_with(Default::default);
+        env.insert(
+            "REPLAY_PATH".into(),
+            EnvValue::String(recording.display().to_string()),
+        );
+        env.insert("REPLAY_NODE".into(), EnvValue::String(node.id.to_string()));
+        if let Some(speed) = speed {
+            env.insert("REPLAY_SPEED".into(), EnvValue::String(speed.to_string()));
+        }
+        node.replace_executable(replay_node.clone(), outputs);
+    }
+
+    Ok(nodes)
+}
+
+/// Returns the IDs of the nodes whose outputs are recorded in the given directory.
+///
+/// Files without the recorded output in their metadata are ignored, since they can't
+/// be assigned to a node.
+fn recorded_nodes(recording: &Path) -> eyre::Result<BTreeSet<String>> {
+    let mut nodes = BTreeSet::new();
+    let entries = std::fs::read_dir(recording)
+        .wrap_err_with(|| format!("failed to read recording `{}`", recording.display()))?;
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "parquet") {
+            let reader = RecordingReader::open(&path)?;
+            if let Some((node_id, _)) = reader.source() {
+                nodes.insert(node_id.to_owned());
+            }
+        }
+    }
+    Ok(nodes)
+}
+
+/// Prefers the `dora-replay` executable next to the `dora` executable over the one
+/// in `PATH`.
+fn replay_node_path() -> String {
+    let name = format!("dora-replay{}", std::env::consts::EXE_SUFFIX);
+    std::env::current_exe()
+        .ok()
+        .and_then(|exe| Some(exe.parent()?.join(&name)))
+        .filter(|path| path.exists())
+        .map(|path| path.display().to_string())
+        .unwrap_or(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dora_node_api::arrow::datatypes::DataType;
+    use dora_record::{recording_path, ParquetWriter};
+
+    #[test]
+    fn nodes_are_found_by_recorded_source() {
+        let recording = tempfile::tempdir().unwrap();
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            for (input, source) in [("camera-image", Some(("camera", "image"))), ("tick", None)] {
+                let path = recording_path(recording.path(), input);
+                let writer = ParquetWriter::create(&path, input, source, &DataType::UInt8)
+                    .await
+                    .unwrap();
+                writer.close().await.unwrap();
+            }
+        });
+
+        let mut descriptor: Descriptor = serde_yaml::from_str(
+            r#"
+            nodes:
+              - id: camera
+                path: camera.py
+                outputs: [image]
+              - id: plot
+                path: plot.py
+                inputs:
+                  image: camera/image
+            "#,
+        )
+        .unwrap();
+        let replaced =
+            replace_recorded_nodes(&mut descriptor, recording.path(), Vec::new(), None).unwrap();
+        assert_eq!(
+            replaced,
+            BTreeSet::from([NodeId::from("camera".to_owned())])
+        );
+
+        let env = descriptor.nodes[0].env.as_ref().unwrap();
+        assert_eq!(
+            env.get("REPLAY_NODE").map(|v| v.to_string()),
+            Some("camera".to_owned())
+        );
+
+        let err = replace_recorded_nodes(
+            &mut descriptor,
+            recording.path(),
+            vec![NodeId::from("plot".to_owned())],
+            None,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("no outputs of node `plot`"));
+    }
+}
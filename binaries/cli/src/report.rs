@@ -0,0 +1,534 @@
+//! Test reports of attached dataflows, written by `dora start --report`.
+
+use dora_core::{
+    config::NodeId,
+    topics::{
+        DataflowRecord, DataflowResult, LifecycleEvent, LifecycleEventKind, NodeErrorCause,
+        NodeExitStatus,
+    },
+};
+use dora_transport_security::TransportSecurity;
+use eyre::{bail, Context};
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::mpsc,
+    time::{Duration, SystemTime},
+};
+use uuid::Uuid;
+
+use crate::{events, formatting::error_summary};
+
+/// Report formats, selected by the file extension of the report path.
+#[derive(Debug, Clone, Copy)]
+pub enum ReportFormat {
+    Junit,
+    Json,
+}
+
+impl ReportFormat {
+    pub fn from_path(path: &Path) -> eyre::Result<Self> {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("xml") => Ok(Self::Junit),
+            Some("json") => Ok(Self::Json),
+            _ => bail!(
+                "unsupported report file `{}`, expected a `.xml` (JUnit) or `.json` file",
+                path.display()
+            ),
+        }
+    }
+}
+
+/// The `NodeExited` lifecycle events of the coordinator, for the node durations
+/// of the report.
+///
+/// Must be subscribed before the dataflow is started because nodes might exit
+/// before the start request is answered.
+pub struct NodeExits {
+    events: mpsc::Receiver<(Uuid, NodeId, SystemTime)>,
+}
+
+impl NodeExits {
+    pub fn subscribe(
+        coordinator_socket: SocketAddr,
+        security: &TransportSecurity,
+    ) -> eyre::Result<Self> {
+        let mut event_session = events::subscribe(coordinator_socket, security)?;
+        // only node exits are kept, so the buffered events are limited by the number
+        // of nodes that exit until the dataflow is started
+        let (tx, events) = mpsc::channel();
+        std::thread::spawn(move || {
+            while let Ok(raw) = event_session.receive() {
+                let Ok(LifecycleEvent { time, kind }) = serde_json::from_slice(&raw) else {
+                    continue;
+                };
+                if let LifecycleEventKind::NodeExited {
+                    dataflow, node_id, ..
+                } = kind
+                {
+                    if tx.send((dataflow.uuid, node_id, time)).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+        Ok(Self { events })
+    }
+
+    /// Returns the exit times of the nodes of the given dataflow, including the
+    /// ones that were received before the dataflow ID was known.
+    ///
+    /// Blocks until the next exit event arrives.
+    pub fn of_dataflow(self, dataflow_id: Uuid) -> impl Iterator<Item = (NodeId, SystemTime)> {
+        self.events
+            .into_iter()
+            .filter(move |(uuid, _, _)| *uuid == dataflow_id)
+            .map(|(_, node_id, time)| (node_id, time))
+    }
+}
+
+#[derive(Debug, serde::Serialize)]
+struct DataflowReport {
+    uuid: Uuid,
+    name: Option<String>,
+    dataflow_path: Option<PathBuf>,
+    success: bool,
+    timed_out: bool,
+    started: Option<String>,
+    duration_secs: Option<f64>,
+    nodes: Vec<NodeReport>,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct NodeReport {
+    id: NodeId,
+    success: bool,
+    /// Time from the start of the dataflow until the node exited.
+    ///
+    /// Measured with the time at which the coordinator reported the exit of the node.
+    /// Not known if the exit event arrived more than one second after the dataflow
+    /// finished.
+    duration_secs: Option<f64>,
+    exit_code: Option<i32>,
+    signal: Option<i32>,
+    error: Option<String>,
+    /// The last lines of the stderr output of failed nodes.
+    stderr: Option<String>,
+}
+
+/// Writes a report of the finished dataflow to the given path.
+///
+/// The `timed_out_after` duration is set if the dataflow was stopped because it
+/// didn't finish in time, which fails the report. Nodes without a result, e.g.
+/// because their machine disconnected, are reported as failed.
+///
+/// The `node_exit_times` are the times of the `NodeExited` lifecycle events of the
+/// coordinator, see [`NodeExits`]. They are compared to the start time of the
+/// dataflow, so the node durations include the time that the node waited for other
+/// nodes to start.
+pub fn write_report(
+    path: &Path,
+    record: &DataflowRecord,
+    result: &DataflowResult,
+    node_exit_times: &BTreeMap<NodeId, SystemTime>,
+    timed_out_after: Option<Duration>,
+) -> eyre::Result<()> {
+    let format = ReportFormat::from_path(path)?;
+    let report = build_report(record, result, node_exit_times, timed_out_after.is_some());
+    let content = match format {
+        ReportFormat::Json => {
+            serde_json::to_string_pretty(&report).wrap_err("failed to serialize report")?
+        }
+        ReportFormat::Junit => junit_xml(&report, timed_out_after),
+    };
+    std::fs::write(path, content)
+        .wrap_err_with(|| format!("failed to write report to `{}`", path.display()))
+}
+
+fn build_report(
+    record: &DataflowRecord,
+    result: &DataflowResult,
+    node_exit_times: &BTreeMap<NodeId, SystemTime>,
+    timed_out: bool,
+) -> DataflowReport {
+    let since_start = |time: SystemTime| {
+        record
+            .started
+            .and_then(|started| time.duration_since(started).ok())
+            .map(|d| d.as_secs_f64())
+    };
+
+    let nodes = record
+        .nodes
+        .iter()
+        .map(|node_id| {
+            let duration_secs = node_exit_times.get(node_id).and_then(|t| since_start(*t));
+            match result.node_results.get(node_id) {
+                Some(Ok(())) => NodeReport {
+                    id: node_id.clone(),
+                    success: true,
+                    duration_secs,
+                    exit_code: Some(0),
+                    signal: None,
+                    error: None,
+                    stderr: None,
+                },
+                Some(Err(err)) => NodeReport {
+                    id: node_id.clone(),
+                    success: false,
+                    duration_secs,
+                    exit_code: match err.exit_status {
+                        NodeExitStatus::ExitCode(code) => Some(code),
+                        _ => None,
+                    },
+                    signal: match err.exit_status {
+                        NodeExitStatus::Signal(signal) => Some(signal),
+                        _ => None,
+                    },
+                    error: Some(error_summary(err)),
+                    stderr: match &err.cause {
+                        NodeErrorCause::Other { stderr } if !stderr.is_empty() => {
+                            Some(stderr.clone())
+                        }
+                        _ => None,
+                    },
+                },
+                None => NodeReport {
+                    id: node_id.clone(),
+                    success: false,
+                    duration_secs,
+                    exit_code: None,
+                    signal: None,
+                    error: Some("no result was reported for this node".into()),
+                    stderr: None,
+                },
+            }
+        })
+        .collect::<Vec<_>>();
+
+    DataflowReport {
+        uuid: record.id.uuid,
+        name: record.id.name.clone(),
+        dataflow_path: record.dataflow_path.clone(),
+        success: !timed_out && nodes.iter().all(|n| n.success),
+        timed_out,
+        started: record
+            .started
+            .map(|t| chrono::DateTime::<chrono::Utc>::from(t).to_rfc3339()),
+        duration_secs: record.finished.and_then(since_start),
+        nodes,
+    }
+}
+
+fn junit_xml(report: &DataflowReport, timed_out_after: Option<Duration>) -> String {
+    let suite_name = report
+        .name
+        .clone()
+        .unwrap_or_else(|| report.uuid.to_string());
+    let tests = report.nodes.len() + usize::from(report.timed_out);
+    let failures =
+        report.nodes.iter().filter(|n| !n.success).count() + usize::from(report.timed_out);
+    let time = report.duration_secs.unwrap_or_default();
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        xml,
+        "<testsuites name=\"dora\" tests=\"{tests}\" failures=\"{failures}\" time=\"{time:.3}\">"
+    );
+    let _ = write!(
+        xml,
+        "  <testsuite name=\"{}\" tests=\"{tests}\" failures=\"{failures}\" errors=\"0\" time=\"{time:.3}\"",
+        escape(&suite_name)
+    );
+    if let Some(started) = &report.started {
+        let _ = write!(xml, " timestamp=\"{}\"", escape(started));
+    }
+    xml.push_str(">\n");
+    let _ = writeln!(
+        xml,
+        "    <properties>\n      <property name=\"uuid\" value=\"{}\"/>\n    </properties>",
+        report.uuid
+    );
+
+    for node in &report.nodes {
+        let _ = write!(
+            xml,
+            "    <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\"",
+            escape(node.id.as_ref()),
+            escape(&suite_name),
+            node.duration_secs.unwrap_or_default()
+        );
+        if node.success {
+            xml.push_str("/>\n");
+            continue;
+        }
+        xml.push_str(">\n");
+        let failure_type = match (node.exit_code, node.signal) {
+            (Some(_), _) => "ExitCode",
+            (_, Some(_)) => "Signal",
+            _ => "Error",
+        };
+        let _ = writeln!(
+            xml,
+            "      <failure type=\"{failure_type}\" message=\"{}\"/>",
+            escape(node.error.as_deref().unwrap_or_default())
+        );
+        if let Some(stderr) = &node.stderr {
+            let _ = writeln!(xml, "      <system-err>{}</system-err>", escape(stderr));
+        }
+        xml.push_str("    </testcase>\n");
+    }
+
+    if let Some(timeout) = timed_out_after {
+        let _ = writeln!(
+            xml,
+            "    <testcase name=\"timeout\" classname=\"{}\" time=\"{time:.3}\">\n      \
+            <failure type=\"Timeout\" message=\"dataflow did not finish within {}\"/>\n    \
+            </testcase>",
+            escape(&suite_name),
+            escape(&format!("{timeout:?}"))
+        );
+    }
+
+    xml.push_str("  </testsuite>\n</testsuites>\n");
+    xml
+}
+
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // other control characters are not allowed in XML 1.0
+            c if c.is_control() && !matches!(c, '\t' | '\n' | '\r') => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dora_core::{
+        message::uhlc::HLC,
+        topics::{DataflowId, DataflowStatus, NodeError},
+    };
+
+    fn node(id: &str) -> NodeId {
+        NodeId::from(id.to_owned())
+    }
+
+    fn at(secs: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    fn error(cause: NodeErrorCause, exit_status: NodeExitStatus) -> NodeError {
+        NodeError {
+            timestamp: HLC::default().new_timestamp(),
+            cause,
+            exit_status,
+            crash_bundle: None,
+        }
+    }
+
+    /// A `pass` node that succeeded, a `fail` node that exited with an error, a
+    /// `crash` node that was killed by a signal, and a `lost` node without result.
+    fn report(timed_out_after: Option<Duration>, format: ReportFormat) -> String {
+        let uuid = Uuid::from_u128(0x0192_0000_0000_7000_8000_0000_0000_0001);
+        let record = DataflowRecord {
+            id: DataflowId {
+                uuid,
+                name: Some("ci <run>".into()),
+            },
+            index: 0,
+            status: DataflowStatus::Failed,
+            dataflow_path: Some("dataflow.yml".into()),
+            machines: Default::default(),
+            nodes: ["pass", "fail", "crash", "lost"].map(node).to_vec(),
+            started: Some(at(1_700_000_000)),
+            finished: Some(at(1_700_000_010)),
+            node_results: Default::default(),
+        };
+        let result = DataflowResult {
+            uuid,
+            timestamp: HLC::default().new_timestamp(),
+            node_results: BTreeMap::from([
+                (node("pass"), Ok(())),
+                (
+                    node("fail"),
+                    Err(error(
+                        NodeErrorCause::Other {
+                            stderr: "assertion `left == right` failed\n".into(),
+                        },
+                        NodeExitStatus::ExitCode(101),
+                    )),
+                ),
+                (
+                    node("crash"),
+                    Err(error(
+                        NodeErrorCause::Other {
+                            stderr: String::new(),
+                        },
+                        NodeExitStatus::Signal(11),
+                    )),
+                ),
+            ]),
+        };
+        let node_exit_times = BTreeMap::from([
+            (node("pass"), at(1_700_000_004)),
+            (node("fail"), at(1_700_000_002)),
+            (node("crash"), at(1_700_000_003)),
+        ]);
+
+        let report = build_report(
+            &record,
+            &result,
+            &node_exit_times,
+            timed_out_after.is_some(),
+        );
+        match format {
+            ReportFormat::Junit => junit_xml(&report, timed_out_after),
+            ReportFormat::Json => serde_json::to_string_pretty(&report).unwrap(),
+        }
+    }
+
+    #[test]
+    fn junit_report() {
+        let expected = r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites name="dora" tests="5" failures="4" time="10.000">
+  <testsuite name="ci &lt;run&gt;" tests="5" failures="4" errors="0" time="10.000" timestamp="2023-11-14T22:13:20+00:00">
+    <properties>
+      <property name="uuid" value="01920000-0000-7000-8000-000000000001"/>
+    </properties>
+    <testcase name="pass" classname="ci &lt;run&gt;" time="4.000"/>
+    <testcase name="fail" classname="ci &lt;run&gt;" time="2.000">
+      <failure type="ExitCode" message="exited with code 101"/>
+      <system-err>assertion `left == right` failed
+</system-err>
+    </testcase>
+    <testcase name="crash" classname="ci &lt;run&gt;" time="3.000">
+      <failure type="Signal" message="exited because of signal SIGSEGV"/>
+    </testcase>
+    <testcase name="lost" classname="ci &lt;run&gt;" time="0.000">
+      <failure type="Error" message="no result was reported for this node"/>
+    </testcase>
+    <testcase name="timeout" classname="ci &lt;run&gt;" time="10.000">
+      <failure type="Timeout" message="dataflow did not finish within 10s"/>
+    </testcase>
+  </testsuite>
+</testsuites>
+"#;
+        assert_eq!(
+            report(Some(Duration::from_secs(10)), ReportFormat::Junit),
+            expected
+        );
+    }
+
+    #[test]
+    fn json_report() {
+        let expected = r#"{
+  "uuid": "01920000-0000-7000-8000-000000000001",
+  "name": "ci <run>",
+  "dataflow_path": "dataflow.yml",
+  "success": false,
+  "timed_out": true,
+  "started": "2023-11-14T22:13:20+00:00",
+  "duration_secs": 10.0,
+  "nodes": [
+    {
+      "id": "pass",
+      "success": true,
+      "duration_secs": 4.0,
+      "exit_code": 0,
+      "signal": null,
+      "error": null,
+      "stderr": null
+    },
+    {
+      "id": "fail",
+      "success": false,
+      "duration_secs": 2.0,
+      "exit_code": 101,
+      "signal": null,
+      "error": "exited with code 101",
+      "stderr": "assertion `left == right` failed\n"
+    },
+    {
+      "id": "crash",
+      "success": false,
+      "duration_secs": 3.0,
+      "exit_code": null,
+      "signal": 11,
+      "error": "exited because of signal SIGSEGV",
+      "stderr": null
+    },
+    {
+      "id": "lost",
+      "success": false,
+      "duration_secs": null,
+      "exit_code": null,
+      "signal": null,
+      "error": "no result was reported for this node",
+      "stderr": null
+    }
+  ]
+}"#;
+        assert_eq!(
+            report(Some(Duration::from_secs(10)), ReportFormat::Json),
+            expected
+        );
+    }
+
+    #[test]
+    fn nodes_exiting_before_start_reply() {
+        let uuid = Uuid::from_u128(1);
+        // the exit events arrive before the start request is answered
+        let (tx, events) = mpsc::channel();
+        tx.send((Uuid::from_u128(2), node("other"), at(1_700_000_000)))
+            .unwrap();
+        tx.send((uuid, node("quick"), at(1_700_000_000))).unwrap();
+        drop(tx);
+        let node_exit_times: BTreeMap<_, _> = NodeExits { events }.of_dataflow(uuid).collect();
+        assert_eq!(
+            node_exit_times,
+            BTreeMap::from([(node("quick"), at(1_700_000_000))])
+        );
+
+        let record = DataflowRecord {
+            id: DataflowId { uuid, name: None },
+            index: 0,
+            status: DataflowStatus::Finished,
+            dataflow_path: None,
+            machines: Default::default(),
+            nodes: vec![node("quick")],
+            started: Some(at(1_700_000_000)),
+            finished: Some(at(1_700_000_001)),
+            node_results: Default::default(),
+        };
+        let result = DataflowResult {
+            uuid,
+            timestamp: HLC::default().new_timestamp(),
+            node_results: BTreeMap::from([(node("quick"), Ok(()))]),
+        };
+        let report = build_report(&record, &result, &node_exit_times, false);
+        assert!(report.success);
+        assert_eq!(report.nodes[0].duration_secs, Some(0.0));
+    }
+
+    #[test]
+    fn escape_xml() {
+        assert_eq!(
+            escape(r#"<a href="x">'&'</a>"#),
+            "&lt;a href=&quot;x&quot;&gt;&apos;&amp;&apos;&lt;/a&gt;"
+        );
+        // control characters other than whitespace are not allowed in XML 1.0
+        assert_eq!(escape("a\tb\nc\u{1b}[31md\u{0}"), "a\tb\nc[31md");
+    }
+}
@@ -0,0 +1,238 @@
+//! Runs a dataflow in a single process, without a coordinator (`dora run`).
+
+use colored::{Color, ColoredString, Colorize};
+use dora_core::{
+    config::NodeId, coordinator_messages::LogMessage, descriptor::Descriptor,
+    topics::DataflowResult,
+};
+use dora_daemon::{Daemon, LocalLog};
+use eyre::Context;
+use std::{collections::BTreeMap, path::Path, time::Duration};
+use tokio::{runtime::Builder, sync::mpsc};
+use tokio_stream::wrappers::ReceiverStream;
+
+use crate::{build, handle_dataflow_result};
+
+/// Colors that are assigned to the nodes in the order of the dataflow file.
+const NODE_COLORS: &[Color] = &[
+    Color::Green,
+    Color::Blue,
+    Color::Magenta,
+    Color::Cyan,
+    Color::Yellow,
+    Color::BrightGreen,
+    Color::BrightBlue,
+    Color::BrightMagenta,
+    Color::BrightCyan,
+    Color::BrightYellow,
+];
+
+/// Runs the given dataflow with an in-process daemon and prints the output of its nodes.
+///
+/// The dataflow is stopped on ctrl-c or after `stop_after`. Nodes that don't exit
+/// within the grace duration are killed.
+pub fn run(
+    dataflow: &Path,
+    build: bool,
+    stop_after: Option<Duration>,
+    grace_duration: Option<Duration>,
+) -> eyre::Result<()> {
+    if build {
+        build::build(dataflow)?;
+    }
+
+    let descriptor =
+        Descriptor::blocking_read(dataflow).wrap_err("Failed to read yaml dataflow")?;
+    let width = descriptor
+        .nodes
+        .iter()
+        .map(|node| node.id.as_ref().len())
+        .max()
+        .unwrap_or_default();
+    let colors: BTreeMap<NodeId, Color> = descriptor
+        .nodes
+        .iter()
+        .map(|node| node.id.clone())
+        .zip(NODE_COLORS.iter().copied().cycle())
+        .collect();
+
+    let (stop_tx, stop_rx) = mpsc::channel(2);
+    let ctrlc_tx = stop_tx.clone();
+    let mut ctrlc_sent = false;
+    ctrlc::set_handler(move || {
+        if ctrlc_sent {
+            std::process::abort();
+        } else {
+            eprintln!("stopping dataflow (press ctrl-c again to abort)");
+            let _ = ctrlc_tx.try_send(grace_duration);
+            ctrlc_sent = true;
+        }
+    })
+    .wrap_err("failed to set ctrl-c handler")?;
+
+    let rt = Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .context("tokio runtime failed")?;
+    let result = rt.block_on(run_dataflow(
+        dataflow,
+        (stop_tx, stop_rx),
+        stop_after,
+        grace_duration,
+        move |log| print_log(log, &colors, width),
+    ))?;
+
+    let uuid = result.uuid;
+    handle_dataflow_result(result, Some(uuid))
+}
+
+/// Runs the dataflow until it finishes, passing its log output to `on_log`.
+///
+/// The dataflow is stopped when a grace duration is received on the stop channel, or
+/// with the given `grace_duration` after `stop_after`.
+async fn run_dataflow(
+    dataflow: &Path,
+    (stop_tx, stop_rx): (
+        mpsc::Sender<Option<Duration>>,
+        mpsc::Receiver<Option<Duration>>,
+    ),
+    stop_after: Option<Duration>,
+    grace_duration: Option<Duration>,
+    mut on_log: impl FnMut(LocalLog) + Send + 'static,
+) -> eyre::Result<DataflowResult> {
+    if let Some(stop_after) = stop_after {
+        tokio::spawn(async move {
+            tokio::time::sleep(stop_after).await;
+            let _ = stop_tx.send(grace_duration).await;
+        });
+    }
+
+    let (log_tx, mut log_rx) = mpsc::channel(100);
+    let printer = tokio::spawn(async move {
+        while let Some(log) = log_rx.recv().await {
+            on_log(log);
+        }
+    });
+    let result =
+        Daemon::run_dataflow_with(dataflow, ReceiverStream::new(stop_rx), Some(log_tx)).await;
+    // the log channel is closed when the daemon is done
+    let _ = printer.await;
+    result
+}
+
+fn print_log(log: LocalLog, colors: &BTreeMap<NodeId, Color>, width: usize) {
+    match log {
+        LocalLog::Output(output) => {
+            println!(
+                "{} {}",
+                node_prefix(&output.node_id, colors, width),
+                output.line
+            )
+        }
+        LocalLog::Message(LogMessage {
+            node_id,
+            level,
+            message,
+            ..
+        }) => {
+            let prefix = match &node_id {
+                Some(node_id) => node_prefix(node_id, colors, width),
+                None => format!("{:width$} |", "dora").dimmed(),
+            };
+            let level = match level {
+                log::Level::Error => "ERROR".red(),
+                log::Level::Warn => "WARN ".yellow(),
+                log::Level::Info => "INFO ".green(),
+                other => format!("{other:5}").normal(),
+            };
+            println!("{prefix} {level} {message}");
+        }
+        LocalLog::Dropped(count) => {
+            println!(
+                "{} {} dropped {count} log lines because they were not printed fast enough",
+                format!("{:width$} |", "dora").dimmed(),
+                "WARN ".yellow()
+            );
+        }
+    }
+}
+
+fn node_prefix(node_id: &NodeId, colors: &BTreeMap<NodeId, Color>, width: usize) -> ColoredString {
+    let color = colors.get(node_id).copied().unwrap_or(Color::White);
+    format!("{:width$} |", node_id.as_ref()).color(color)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dora_core::topics::{NodeErrorCause, NodeExitStatus};
+    use std::{
+        sync::{Arc, Mutex},
+        time::Instant,
+    };
+
+    /// Runs a dataflow of shell nodes and returns its result and the output lines.
+    async fn run_shell_nodes(
+        nodes: &[(&str, &str)],
+        stop_after: Option<Duration>,
+        grace_duration: Option<Duration>,
+    ) -> (DataflowResult, Vec<String>) {
+        let dir = tempfile::tempdir().unwrap();
+        let mut yaml = String::from("nodes:\n");
+        for (id, command) in nodes {
+            yaml += &format!("  - id: {id}\n    path: shell\n    args: {command:?}\n");
+        }
+        let path = dir.path().join("dataflow.yml");
+        std::fs::write(&path, yaml).unwrap();
+
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let collected = lines.clone();
+        let result = run_dataflow(
+            &path,
+            mpsc::channel(2),
+            stop_after,
+            grace_duration,
+            move |log| {
+                if let LocalLog::Output(output) = log {
+                    collected.lock().unwrap().push(output.line);
+                }
+            },
+        )
+        .await
+        .unwrap();
+        let lines = lines.lock().unwrap().clone();
+        (result, lines)
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn exit_code_follows_the_result() {
+        let (result, lines) = run_shell_nodes(&[("hello", "echo hello")], None, None).await;
+        assert_eq!(lines, ["hello"]);
+        assert!(handle_dataflow_result(result, None).is_ok());
+
+        let (result, _) = run_shell_nodes(&[("fail", "exit 3")], None, None).await;
+        let error = &result.node_results[&NodeId::from("fail".to_owned())];
+        assert!(matches!(
+            error.as_ref().unwrap_err().exit_status,
+            NodeExitStatus::ExitCode(3)
+        ));
+        assert!(handle_dataflow_result(result, None).is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn nodes_are_killed_after_grace_duration() {
+        let start = Instant::now();
+        let (result, _) = run_shell_nodes(
+            &[("sleep", "exec sleep 60")],
+            Some(Duration::from_millis(500)),
+            Some(Duration::from_millis(200)),
+        )
+        .await;
+        assert!(start.elapsed() < Duration::from_secs(30));
+        let error = result.node_results[&NodeId::from("sleep".to_owned())]
+            .as_ref()
+            .unwrap_err();
+        assert!(matches!(error.cause, NodeErrorCause::GraceDuration));
+        assert!(handle_dataflow_result(result, None).is_err());
+    }
+}
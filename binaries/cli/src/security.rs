@@ -0,0 +1,275 @@
+use dora_transport_security::{SecurityConfig, TlsConfig, TransportSecurity};
+use eyre::{bail, Context};
+use std::{ffi::OsString, path::PathBuf};
+
+/// TLS and authentication settings for the connections between CLI, coordinator, and
+/// daemons. All of them need to use the same settings.
+#[derive(Debug, Clone, clap::Args)]
+pub struct SecurityArgs {
+    /// YAML file with the transport security settings (`tls` and `token`)
+    #[clap(long, global = true, value_name = "PATH", env = "DORA_SECURITY_CONFIG")]
+    security_config: Option<PathBuf>,
+    /// PEM file with the CA certificate that peer certificates must be signed by (enables TLS)
+    #[clap(long, global = true, value_name = "PATH")]
+    tls_ca: Option<PathBuf>,
+    /// PEM file with the TLS certificate chain of this peer
+    #[clap(long, global = true, value_name = "PATH")]
+    tls_cert: Option<PathBuf>,
+    /// PEM file with the TLS private key of this peer
+    #[clap(long, global = true, value_name = "PATH")]
+    tls_key: Option<PathBuf>,
+    /// Verify the certificates of the coordinator and remote daemons against this name
+    /// instead of their IP address
+    #[clap(long, global = true, value_name = "NAME")]
+    tls_server_name: Option<String>,
+    /// Pre-shared token that peers need to present when connecting
+    #[clap(
+        long,
+        global = true,
+        value_name = "TOKEN",
+        env = "DORA_AUTH_TOKEN",
+        hide_env_values = true
+    )]
+    auth_token: Option<String>,
+}
+
+impl SecurityArgs {
+    /// Combines the config file with the command line flags, which take precedence.
+    pub fn config(&self) -> eyre::Result<SecurityConfig> {
+        let mut config = match &self.security_config {
+            Some(path) => {
+                let raw = std::fs::read_to_string(path)
+                    .with_context(|| format!("failed to read `{}`", path.display()))?;
+                serde_yaml::from_str(&raw)
+                    .with_context(|| format!("failed to parse `{}`", path.display()))?
+            }
+            None => SecurityConfig::default(),
+        };
+
+        match (&self.tls_ca, &self.tls_cert, &self.tls_key) {
+            (Some(ca), Some(cert), Some(key)) => {
+                config.tls = Some(TlsConfig {
+                    ca: ca.clone(),
+                    cert: cert.clone(),
+                    key: key.clone(),
+                    server_name: None,
+                });
+            }
+            (None, None, None) => {}
+            _ => bail!("`--tls-ca`, `--tls-cert`, and `--tls-key` must be given together"),
+        }
+        if let Some(server_name) = &self.tls_server_name {
+            match &mut config.tls {
+                Some(tls) => tls.server_name = Some(server_name.clone()),
+                None => bail!("`--tls-server-name` requires TLS to be configured"),
+            }
+        }
+        if let Some(token) = &self.auth_token {
+            config.token = Some(token.clone());
+        }
+        Ok(config)
+    }
+
+    pub fn load(&self) -> eyre::Result<TransportSecurity> {
+        let config = self.config()?;
+        TransportSecurity::new(&config).wrap_err("failed to load transport security settings")
+    }
+
+    /// Command line arguments to pass the same settings to a spawned `dora` process.
+    ///
+    /// The auth token is passed through the inherited `DORA_AUTH_TOKEN` environment
+    /// variable instead, to avoid exposing it in the process list.
+    pub fn to_args(&self) -> Vec<OsString> {
+        let mut args = Vec::new();
+        let mut push = |flag: &str, value: Option<OsString>| {
+            if let Some(value) = value {
+                args.push(flag.into());
+                args.push(value);
+            }
+        };
+        push(
+            "--security-config",
+            self.security_config.clone().map(Into::into),
+        );
+        push("--tls-ca", self.tls_ca.clone().map(Into::into));
+        push("--tls-cert", self.tls_cert.clone().map(Into::into));
+        push("--tls-key", self.tls_key.clone().map(Into::into));
+        push(
+            "--tls-server-name",
+            self.tls_server_name.clone().map(Into::into),
+        );
+        args
+    }
+
+    pub fn auth_token(&self) -> Option<&str> {
+        self.auth_token.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use std::sync::Mutex;
+
+    /// Serializes the tests, as they read and modify the environment variables.
+    static ENV: Mutex<()> = Mutex::new(());
+
+    #[derive(clap::Parser)]
+    struct Cli {
+        #[clap(flatten)]
+        security: SecurityArgs,
+    }
+
+    fn config(args: &[&str]) -> eyre::Result<SecurityConfig> {
+        let args = std::iter::once("dora").chain(args.iter().copied());
+        Cli::try_parse_from(args)?.security.config()
+    }
+
+    fn write_config(dir: &tempfile::TempDir, yaml: &str) -> String {
+        let path = dir.path().join("security.yml");
+        std::fs::write(&path, yaml).unwrap();
+        path.to_str().unwrap().to_owned()
+    }
+
+    fn tls(ca: &str, cert: &str, key: &str, server_name: Option<&str>) -> Option<TlsConfig> {
+        Some(TlsConfig {
+            ca: ca.into(),
+            cert: cert.into(),
+            key: key.into(),
+            server_name: server_name.map(Into::into),
+        })
+    }
+
+    #[test]
+    fn tls_flags_must_be_given_together() {
+        let _env = ENV.lock().unwrap();
+        assert_eq!(config(&[]).unwrap(), SecurityConfig::default());
+
+        let complete = config(&[
+            "--tls-ca",
+            "ca.pem",
+            "--tls-cert",
+            "a.pem",
+            "--tls-key",
+            "a.key",
+        ]);
+        assert_eq!(complete.unwrap().tls, tls("ca.pem", "a.pem", "a.key", None));
+
+        for incomplete in [
+            &["--tls-ca", "ca.pem", "--tls-cert", "a.pem"][..],
+            &["--tls-cert", "a.pem", "--tls-key", "a.key"],
+            &["--tls-key", "a.key"],
+        ] {
+            let err = config(incomplete).unwrap_err();
+            assert!(err.to_string().contains("must be given together"), "{err}");
+        }
+    }
+
+    #[test]
+    fn server_name_requires_tls() {
+        let _env = ENV.lock().unwrap();
+        let err = config(&["--tls-server-name", "coordinator.dora"]).unwrap_err();
+        assert!(err.to_string().contains("requires TLS"), "{err}");
+
+        let named = config(&[
+            "--tls-ca",
+            "ca.pem",
+            "--tls-cert",
+            "a.pem",
+            "--tls-key",
+            "a.key",
+            "--tls-server-name",
+            "coordinator.dora",
+        ]);
+        assert_eq!(
+            named.unwrap().tls,
+            tls("ca.pem", "a.pem", "a.key", Some("coordinator.dora"))
+        );
+    }
+
+    #[test]
+    fn flags_take_precedence_over_config_file() {
+        let _env = ENV.lock().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_config(
+            &dir,
+            "tls:\n  ca: file-ca.pem\n  cert: file.pem\n  key: file.key\n  \
+            server_name: file.dora\ntoken: file-token\n",
+        );
+
+        let from_file = config(&["--security-config", &path]).unwrap();
+        assert_eq!(
+            from_file.tls,
+            tls("file-ca.pem", "file.pem", "file.key", Some("file.dora"))
+        );
+        assert_eq!(from_file.token.as_deref(), Some("file-token"));
+
+        // the server name of the file is kept if only `--tls-server-name` is given
+        let renamed = config(&["--security-config", &path, "--tls-server-name", "flag.dora"]);
+        assert_eq!(
+            renamed.unwrap().tls,
+            tls("file-ca.pem", "file.pem", "file.key", Some("flag.dora"))
+        );
+
+        // the TLS flags replace the whole TLS section of the file
+        let overridden = config(&[
+            "--security-config",
+            &path,
+            "--tls-ca",
+            "ca.pem",
+            "--tls-cert",
+            "a.pem",
+            "--tls-key",
+            "a.key",
+            "--auth-token",
+            "flag-token",
+        ])
+        .unwrap();
+        assert_eq!(overridden.tls, tls("ca.pem", "a.pem", "a.key", None));
+        assert_eq!(overridden.token.as_deref(), Some("flag-token"));
+    }
+
+    #[test]
+    fn settings_are_read_from_env() {
+        let _env = ENV.lock().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_config(&dir, "token: file-token\n");
+
+        std::env::set_var("DORA_SECURITY_CONFIG", path);
+        let from_env_file = config(&[]);
+        std::env::set_var("DORA_AUTH_TOKEN", "env-token");
+        let from_env_token = config(&[]);
+        let from_flag = config(&["--auth-token", "flag-token"]);
+        std::env::remove_var("DORA_SECURITY_CONFIG");
+        std::env::remove_var("DORA_AUTH_TOKEN");
+
+        assert_eq!(from_env_file.unwrap().token.as_deref(), Some("file-token"));
+        assert_eq!(from_env_token.unwrap().token.as_deref(), Some("env-token"));
+        assert_eq!(from_flag.unwrap().token.as_deref(), Some("flag-token"));
+    }
+
+    #[test]
+    fn invalid_config_files_are_rejected() {
+        let _env = ENV.lock().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+
+        // a TLS section without a key
+        let path = write_config(&dir, "tls:\n  ca: ca.pem\n  cert: a.pem\n");
+        let err = config(&["--security-config", &path]).unwrap_err();
+        assert!(
+            format!("{err:?}").contains("missing field `key`"),
+            "{err:?}"
+        );
+
+        let path = write_config(&dir, "tokn: typo\n");
+        let err = config(&["--security-config", &path]).unwrap_err();
+        assert!(
+            format!("{err:?}").contains("unknown field `tokn`"),
+            "{err:?}"
+        );
+
+        let missing = dir.path().join("missing.yml");
+        assert!(config(&["--security-config", missing.to_str().unwrap()]).is_err());
+    }
+}
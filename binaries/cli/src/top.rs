@@ -0,0 +1,177 @@
+use communication_layer_request_reply::TcpRequestReplyConnection;
+use dora_core::topics::{ControlRequest, ControlRequestReply, DataflowMetrics};
+use eyre::{bail, Context};
+use std::{
+    io::{IsTerminal, Write},
+    time::Duration,
+};
+use tabwriter::TabWriter;
+
+/// Periodically prints the resource usage of all running nodes.
+///
+/// Prints the table only once if stdout is not a terminal.
+pub fn top(session: &mut TcpRequestReplyConnection, interval: Duration) -> eyre::Result<()> {
+    let interactive = std::io::stdout().is_terminal();
+    loop {
+        let metrics = query_node_metrics(session)?;
+        let table = format_table(&metrics)?;
+        if interactive {
+            // clear the screen and move the cursor to the top left corner
+            print!("\x1b[2J\x1b[H");
+        }
+        print!("{table}");
+        std::io::stdout().flush()?;
+
+        if !interactive {
+            break Ok(());
+        }
+        std::thread::sleep(interval);
+    }
+}
+
+fn query_node_metrics(
+    session: &mut TcpRequestReplyConnection,
+) -> eyre::Result<Vec<DataflowMetrics>> {
+    let reply_raw = session
+        .request(&serde_json::to_vec(&ControlRequest::NodeMetrics).unwrap())
+        .wrap_err("failed to send node metrics request")?;
+    let reply: ControlRequestReply =
+        serde_json::from_slice(&reply_raw).wrap_err("failed to parse reply")?;
+    match reply {
+        ControlRequestReply::NodeMetrics(metrics) => Ok(metrics),
+        ControlRequestReply::Error(err) => bail!("{err}"),
+        other => bail!("unexpected node metrics reply: {other:?}"),
+    }
+}
+
+fn format_table(dataflows: &[DataflowMetrics]) -> eyre::Result<String> {
+    let mut tw = TabWriter::new(vec![]);
+    tw.write_all(
+        b"DATAFLOW\tNODE\tMACHINE\tCPU\tMEMORY\tTHREADS\tDISK READ\tDISK WRITE\tOUT/s\tIN/s\tDROPPED\n",
+    )?;
+    for dataflow in dataflows {
+        let name = match &dataflow.id.name {
+            Some(name) => name.clone(),
+            None => dataflow.id.uuid.to_string(),
+        };
+        for node in &dataflow.nodes {
+            let machine = match node.machine_id.as_str() {
+                "" => "-",
+                other => other,
+            };
+            let line = match &node.metrics {
+                Some(m) => format!(
+                    "{name}\t{}\t{machine}\t{:.1}%\t{}\t{}\t{}/s\t{}/s\t{:.1}\t{:.1}\t{}\n",
+                    node.node_id,
+                    m.cpu_usage,
+                    format_bytes(m.memory_bytes as f64),
+                    m.threads
+                        .map(|t| t.to_string())
+                        .unwrap_or_else(|| "-".into()),
+                    format_bytes(m.disk_read_bytes_per_sec),
+                    format_bytes(m.disk_written_bytes_per_sec),
+                    m.outputs_per_sec,
+                    m.inputs_per_sec,
+                    m.dropped_inputs,
+                ),
+                None => format!(
+                    "{name}\t{}\t{machine}\t-\t-\t-\t-\t-\t-\t-\t-\n",
+                    node.node_id
+                ),
+            };
+            tw.write_all(line.as_bytes())?;
+        }
+    }
+    tw.flush()?;
+    Ok(String::from_utf8(tw.into_inner()?)?)
+}
+
+fn format_bytes(bytes: f64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{value:.0} {}", UNITS[unit])
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dora_core::{
+        config::NodeId,
+        coordinator_messages::NodeMetrics,
+        topics::{DataflowId, NodeMetricsEntry},
+    };
+    use uuid::Uuid;
+
+    fn entry(node_id: &str, machine_id: &str, metrics: Option<NodeMetrics>) -> NodeMetricsEntry {
+        NodeMetricsEntry {
+            node_id: NodeId::from(node_id.to_owned()),
+            machine_id: machine_id.to_owned(),
+            metrics,
+        }
+    }
+
+    #[test]
+    fn table() {
+        let metrics = NodeMetrics {
+            cpu_usage: 12.34,
+            memory_bytes: 3 * 1024 * 1024 + 512 * 1024,
+            threads: Some(4),
+            disk_read_bytes_per_sec: 2048.0,
+            disk_written_bytes_per_sec: 100.0,
+            outputs_per_sec: 30.0,
+            inputs_per_sec: 12.25,
+            dropped_inputs: 7,
+        };
+        let dataflows = [
+            DataflowMetrics {
+                id: DataflowId {
+                    uuid: Uuid::from_u128(1),
+                    name: Some("camera".into()),
+                },
+                nodes: vec![
+                    entry("webcam", "", Some(metrics.clone())),
+                    entry("plot", "gpu", None),
+                ],
+            },
+            DataflowMetrics {
+                id: DataflowId {
+                    uuid: Uuid::from_u128(2),
+                    name: None,
+                },
+                nodes: vec![entry(
+                    "node",
+                    "a",
+                    Some(NodeMetrics {
+                        threads: None,
+                        ..metrics
+                    }),
+                )],
+            },
+        ];
+        let expected = "\
+DATAFLOW                              NODE    MACHINE  CPU    MEMORY   THREADS  DISK READ  DISK WRITE  OUT/s  IN/s  DROPPED
+camera                                webcam  -        12.3%  3.5 MiB  4        2.0 KiB/s  100 B/s     30.0   12.2  7
+camera                                plot    gpu      -      -        -        -          -           -      -     -
+00000000-0000-0000-0000-000000000002  node    a        12.3%  3.5 MiB  -        2.0 KiB/s  100 B/s     30.0   12.2  7
+";
+        assert_eq!(format_table(&dataflows).unwrap(), expected);
+    }
+
+    #[test]
+    fn bytes() {
+        assert_eq!(format_bytes(0.0), "0 B");
+        assert_eq!(format_bytes(1023.0), "1023 B");
+        assert_eq!(format_bytes(1536.0), "1.5 KiB");
+        assert_eq!(format_bytes(5.0 * 1024.0 * 1024.0 * 1024.0), "5.0 GiB");
+        assert_eq!(format_bytes(2048.0 * 1024f64.powi(4)), "2048.0 TiB");
+    }
+}
@@ -1,8 +1,13 @@
 use crate::{
+    log_subscriber::LogConnection,
     tcp_utils::{tcp_receive, tcp_send},
     Event,
 };
-use dora_core::topics::{ControlRequest, ControlRequestReply};
+use dora_core::{
+    config::{DataId, NodeId},
+    topics::{ControlRequest, ControlRequestReply, LifecycleEvent},
+};
+use dora_transport_security::{AsyncStream, TransportSecurity};
 use eyre::{eyre, Context};
 use futures::{
     future::{self, Either},
@@ -10,7 +15,7 @@ use futures::{
     FutureExt, Stream, StreamExt,
 };
 use futures_concurrency::future::Race;
-use std::{io::ErrorKind, net::SocketAddr};
+use std::{io::ErrorKind, net::SocketAddr, time::SystemTime};
 use tokio::{
     net::{TcpListener, TcpStream},
     sync::{mpsc, oneshot},
@@ -21,12 +26,18 @@ use uuid::Uuid;
 
 pub(crate) async fn control_events(
     control_listen_addr: SocketAddr,
+    security: TransportSecurity,
     tasks: &FuturesUnordered<JoinHandle<()>>,
 ) -> eyre::Result<impl Stream<Item = Event>> {
     let (tx, rx) = mpsc::channel(10);
 
     let (finish_tx, mut finish_rx) = mpsc::channel(1);
-    tasks.push(tokio::spawn(listen(control_listen_addr, tx, finish_tx)));
+    tasks.push(tokio::spawn(listen(
+        control_listen_addr,
+        security,
+        tx,
+        finish_tx,
+    )));
     tasks.push(tokio::spawn(async move {
         while let Some(()) = finish_rx.recv().await {}
     }));
@@ -36,6 +47,7 @@ pub(crate) async fn control_events(
 
 async fn listen(
     control_listen_addr: SocketAddr,
+    security: TransportSecurity,
     tx: mpsc::Sender<ControlEvent>,
     _finish_tx: mpsc::Sender<()>,
 ) {
@@ -63,7 +75,12 @@ async fn listen(
         match connection.wrap_err("failed to connect") {
             Ok((connection, _)) => {
                 let tx = tx.clone();
-                tokio::spawn(handle_requests(connection, tx, _finish_tx.clone()));
+                tokio::spawn(handle_requests(
+                    connection,
+                    security.clone(),
+                    tx,
+                    _finish_tx.clone(),
+                ));
             }
             Err(err) => {
                 if tx.blocking_send(err.into()).is_err() {
@@ -75,10 +92,18 @@ async fn listen(
 }
 
 async fn handle_requests(
-    mut connection: TcpStream,
+    connection: TcpStream,
+    security: TransportSecurity,
     tx: mpsc::Sender<ControlEvent>,
     _finish_tx: mpsc::Sender<()>,
 ) {
+    let mut connection = match security.accept(connection).await {
+        Ok(connection) => connection,
+        Err(err) => {
+            tracing::warn!("rejected control connection: {err}");
+            return;
+        }
+    };
     loop {
         let next_request = tcp_receive(&mut connection).map(Either::Left);
         let coordinator_stopped = tx.closed().map(Either::Right);
@@ -108,11 +133,52 @@ async fn handle_requests(
                 .send(ControlEvent::LogSubscribe {
                     dataflow_id,
                     level,
-                    connection,
+                    connection: LogConnection::Tcp(connection),
                 })
                 .await;
             break;
         }
+        let request = match request {
+            Ok(ControlRequest::LogFollow {
+                uuid,
+                name,
+                nodes,
+                tail,
+                since,
+            }) => {
+                let _ = tx
+                    .send(ControlEvent::LogFollow {
+                        uuid,
+                        name,
+                        nodes,
+                        tail,
+                        since,
+                        connection,
+                    })
+                    .await;
+                break;
+            }
+            Ok(ControlRequest::EventSubscribe) => {
+                let _ = tx.send(ControlEvent::EventSubscribe { connection }).await;
+                break;
+            }
+            Ok(ControlRequest::Record {
+                uuid,
+                name,
+                outputs,
+            }) => {
+                let _ = tx
+                    .send(ControlEvent::Record {
+                        uuid,
+                        name,
+                        outputs,
+                        connection,
+                    })
+                    .await;
+                break;
+            }
+            other => other,
+        };
 
         let result = match request {
             Ok(request) => handle_request(request, &tx).await,
@@ -149,7 +215,7 @@ async fn handle_requests(
     }
 }
 
-async fn handle_request(
+pub(crate) async fn handle_request(
     request: ControlRequest,
     tx: &mpsc::Sender<ControlEvent>,
 ) -> eyre::Result<ControlRequestReply> {
@@ -177,7 +243,28 @@ pub enum ControlEvent {
     LogSubscribe {
         dataflow_id: Uuid,
         level: log::LevelFilter,
-        connection: TcpStream,
+        connection: LogConnection,
+    },
+    LogFollow {
+        uuid: Option<Uuid>,
+        name: Option<String>,
+        nodes: Vec<NodeId>,
+        tail: Option<usize>,
+        since: Option<SystemTime>,
+        connection: AsyncStream,
+    },
+    EventSubscribe {
+        connection: AsyncStream,
+    },
+    /// Lifecycle events for a WebSocket client of the HTTP API.
+    EventChannelSubscribe {
+        sender: mpsc::Sender<LifecycleEvent>,
+    },
+    Record {
+        uuid: Option<Uuid>,
+        name: Option<String>,
+        outputs: Vec<(NodeId, DataId)>,
+        connection: AsyncStream,
     },
     Error(eyre::Report),
 }
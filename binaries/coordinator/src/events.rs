@@ -0,0 +1,72 @@
+use std::time::SystemTime;
+
+use dora_core::topics::{LifecycleEvent, LifecycleEventKind};
+use dora_transport_security::AsyncStream;
+use tokio::{sync::mpsc, task::JoinHandle};
+
+use crate::tcp_utils::tcp_send;
+
+/// Maximum number of events that are queued for a subscriber before it is disconnected.
+pub const SUBSCRIBER_QUEUE_SIZE: usize = 100;
+
+/// Forwards lifecycle events to all `dora events` connections and to the WebSocket
+/// clients of the HTTP API.
+///
+/// Each subscriber is served by a separate task, so that slow connections don't
+/// block the coordinator.
+#[derive(Default)]
+pub struct EventBroadcaster {
+    subscribers: Vec<mpsc::Sender<LifecycleEvent>>,
+}
+
+impl EventBroadcaster {
+    /// Starts forwarding events to the given connection.
+    ///
+    /// The returned task finishes when the connection is closed or the broadcaster is
+    /// dropped.
+    pub fn subscribe(&mut self, mut connection: AsyncStream) -> JoinHandle<()> {
+        let (tx, mut rx) = mpsc::channel(SUBSCRIBER_QUEUE_SIZE);
+        self.subscribe_channel(tx);
+        tokio::spawn(async move {
+            while let Some(event) = rx.recv().await {
+                let message = match serde_json::to_vec(&event) {
+                    Ok(message) => message,
+                    Err(err) => {
+                        tracing::warn!("failed to serialize lifecycle event: {err}");
+                        continue;
+                    }
+                };
+                if let Err(err) = tcp_send(&mut connection, &message).await {
+                    tracing::debug!("event subscriber disconnected: {err}");
+                    break;
+                }
+            }
+        })
+    }
+
+    /// Starts forwarding events to the given channel.
+    ///
+    /// The channel is dropped if it is full when an event is published.
+    pub fn subscribe_channel(&mut self, sender: mpsc::Sender<LifecycleEvent>) {
+        self.subscribers.push(sender);
+    }
+
+    pub fn publish(&mut self, kind: LifecycleEventKind) {
+        if self.subscribers.is_empty() {
+            return;
+        }
+        let event = LifecycleEvent {
+            time: SystemTime::now(),
+            kind,
+        };
+        self.subscribers
+            .retain(|subscriber| match subscriber.try_send(event.clone()) {
+                Ok(()) => true,
+                Err(mpsc::error::TrySendError::Full(_)) => {
+                    tracing::warn!("disconnecting event subscriber that doesn't keep up");
+                    false
+                }
+                Err(mpsc::error::TrySendError::Closed(_)) => false,
+            });
+    }
+}
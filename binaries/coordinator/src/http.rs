@@ -0,0 +1,963 @@
+//! Optional HTTP API for dashboards and CI tooling.
+//!
+//! The endpoints are documented in `openapi.json`, which is also served at
+//! `/api/openapi.json`. Requests are forwarded to the coordinator main loop as
+//! [`ControlEvent`]s, just like the requests of the `dora` CLI.
+//!
+//! The API uses the transport security of the coordinator: with TLS, it is served
+//! over HTTPS with client certificate authentication, and a configured token must be
+//! sent as bearer token. Listening on a non-loopback address requires a token.
+
+use crate::{
+    control::{handle_request, ControlEvent},
+    events::SUBSCRIBER_QUEUE_SIZE,
+    log_subscriber::LogConnection,
+    Event,
+};
+use axum::{
+    extract::{
+        rejection::JsonRejection,
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Path, Query, State,
+    },
+    http::{header, HeaderMap, Request, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use dora_core::{
+    descriptor::Descriptor,
+    topics::{
+        ControlRequest, ControlRequestReply, DataflowResult, DataflowStatus, LifecycleEvent,
+        LifecycleEventKind,
+    },
+};
+use dora_transport_security::{AsyncStream, TransportSecurity};
+use eyre::{bail, Context};
+use futures::{stream::FuturesUnordered, Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::{
+    convert::Infallible,
+    net::{IpAddr, SocketAddr},
+    path::PathBuf,
+    pin::Pin,
+    task::Poll,
+    time::Duration,
+};
+use tokio::{net::TcpListener, sync::mpsc, task::JoinHandle};
+use tokio_stream::wrappers::ReceiverStream;
+use uuid::Uuid;
+
+const OPENAPI: &str = include_str!("../openapi.json");
+
+/// Maximum number of log messages that are queued for a WebSocket client before it
+/// is disconnected.
+const LOG_QUEUE_SIZE: usize = 100;
+
+pub(crate) async fn http_events(
+    http_listen_addr: SocketAddr,
+    security: TransportSecurity,
+    tasks: &FuturesUnordered<JoinHandle<()>>,
+) -> eyre::Result<impl Stream<Item = Event>> {
+    let loopback = http_listen_addr.ip().is_loopback();
+    if !loopback && !security.has_token() {
+        bail!(
+            "the HTTP API requires a security token when listening on the non-loopback \
+            address {http_listen_addr} (set an auth token or listen on a loopback address)"
+        );
+    }
+    let listener = TcpListener::bind(http_listen_addr)
+        .await
+        .wrap_err_with(|| format!("failed to listen for HTTP requests on {http_listen_addr}"))?;
+
+    let (tx, rx) = mpsc::channel(10);
+    let state = HttpState {
+        tx: tx.clone(),
+        security: security.clone(),
+        loopback,
+    };
+    let app = router(state);
+    let incoming = accept_connections(listener, security, tasks);
+
+    tasks.push(tokio::spawn(async move {
+        let coordinator_stopped = async move { tx.closed().await };
+        let result = axum::Server::builder(incoming)
+            .serve(app.into_make_service())
+            .with_graceful_shutdown(coordinator_stopped)
+            .await;
+        if let Err(err) = result {
+            tracing::error!("HTTP server failed: {err}");
+        }
+    }));
+
+    Ok(ReceiverStream::new(rx).map(Event::Control))
+}
+
+fn router(state: HttpState) -> Router {
+    Router::new()
+        .route("/api/openapi.json", get(openapi))
+        .route("/api/check", get(check))
+        .route("/api/dataflows", get(list).post(start))
+        .route("/api/dataflows/:dataflow", get(status))
+        .route("/api/dataflows/:dataflow/stop", post(stop))
+        .route("/api/dataflows/:dataflow/logs/:node", get(logs))
+        .route("/api/dataflows/:dataflow/events", get(events))
+        .layer(middleware::from_fn_with_state(state.clone(), authorize))
+        .with_state(state)
+}
+
+/// Accepts HTTP connections and performs their TLS handshake, if TLS is configured.
+///
+/// The token is checked per request, see [`authorize`].
+fn accept_connections(
+    listener: TcpListener,
+    security: TransportSecurity,
+    tasks: &FuturesUnordered<JoinHandle<()>>,
+) -> Incoming {
+    let (tx, rx) = mpsc::channel(10);
+    tasks.push(tokio::spawn(async move {
+        loop {
+            // stop once the server is shut down
+            let accepted = tokio::select! {
+                _ = tx.closed() => break,
+                accepted = listener.accept() => accepted,
+            };
+            let stream = match accepted {
+                Ok((stream, _)) => stream,
+                Err(err) => {
+                    tracing::warn!("failed to accept HTTP connection: {err}");
+                    continue;
+                }
+            };
+            let security = security.clone();
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                match security.accept_tls(stream).await {
+                    Ok(stream) => {
+                        let _ = tx.send(stream).await;
+                    }
+                    Err(err) => tracing::debug!("HTTP connection handshake failed: {err}"),
+                }
+            });
+        }
+    }));
+    Incoming(rx)
+}
+
+/// HTTP connections whose handshake completed.
+struct Incoming(mpsc::Receiver<AsyncStream>);
+
+impl hyper::server::accept::Accept for Incoming {
+    type Conn = AsyncStream;
+    type Error = Infallible;
+
+    fn poll_accept(
+        mut self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<Option<Result<Self::Conn, Self::Error>>> {
+        self.0.poll_recv(cx).map(|stream| stream.map(Ok))
+    }
+}
+
+#[derive(Clone)]
+struct HttpState {
+    tx: mpsc::Sender<ControlEvent>,
+    security: TransportSecurity,
+    /// The API listens on a loopback address, so only local `Host`s are valid.
+    loopback: bool,
+}
+
+impl HttpState {
+    async fn request(&self, request: ControlRequest) -> Result<ControlRequestReply, ApiError> {
+        match handle_request(request, &self.tx).await {
+            Ok(ControlRequestReply::Error(err)) => Err(ApiError::bad_request(err)),
+            Ok(ControlRequestReply::CoordinatorStopped) => Err(ApiError::coordinator_stopped()),
+            Ok(reply) => Ok(reply),
+            Err(err) => Err(ApiError::bad_request(format!("{err:?}"))),
+        }
+    }
+
+    async fn list(&self) -> Result<Vec<DataflowEntry>, ApiError> {
+        match self.request(ControlRequest::List).await? {
+            ControlRequestReply::DataflowList(list) => Ok(list
+                .0
+                .into_iter()
+                .map(|entry| DataflowEntry {
+                    uuid: entry.id.uuid,
+                    name: entry.id.name,
+                    status: status_str(entry.status),
+                })
+                .collect()),
+            other => Err(ApiError::unexpected_reply(other)),
+        }
+    }
+
+    /// Resolves a dataflow UUID or name, preferring running dataflows.
+    async fn resolve(&self, dataflow: &str) -> Result<Uuid, ApiError> {
+        if let Ok(uuid) = Uuid::parse_str(dataflow) {
+            return Ok(uuid);
+        }
+        let mut matching: Vec<_> = self
+            .list()
+            .await?
+            .into_iter()
+            .filter(|entry| entry.name.as_deref() == Some(dataflow))
+            .collect();
+        if matching.iter().any(|entry| entry.status == "running") {
+            matching.retain(|entry| entry.status == "running");
+        }
+        match matching.as_slice() {
+            [entry] => Ok(entry.uuid),
+            [] => Err(ApiError::not_found(format!(
+                "no dataflow with name `{dataflow}`"
+            ))),
+            _ => Err(ApiError::bad_request(format!(
+                "multiple dataflows found with name `{dataflow}`, use the UUID instead"
+            ))),
+        }
+    }
+
+    async fn status(&self, uuid: Uuid) -> Result<DataflowState, ApiError> {
+        match self
+            .request(ControlRequest::Check {
+                dataflow_uuid: uuid,
+            })
+            .await?
+        {
+            ControlRequestReply::DataflowStarted { uuid } => Ok(DataflowState {
+                uuid,
+                status: "running",
+                result: None,
+            }),
+            ControlRequestReply::DataflowStopped { uuid, result } => {
+                Ok(DataflowState::stopped(uuid, result))
+            }
+            other => Err(ApiError::unexpected_reply(other)),
+        }
+    }
+}
+
+/// Rejects cross-site requests and requires the configured security token as
+/// bearer token, if any.
+///
+/// Browsers send an `Origin` header with cross-origin requests and all WebSocket
+/// upgrades, so other websites can't use the API even if no token is configured.
+/// Requiring a local `Host` on loopback addresses prevents DNS rebinding.
+async fn authorize<B>(
+    State(state): State<HttpState>,
+    headers: HeaderMap,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    if let Err(err) = check_origin(&headers, state.loopback) {
+        return err.into_response();
+    }
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if state.security.verify_token(token) {
+        next.run(request).await
+    } else {
+        ApiError {
+            status: StatusCode::UNAUTHORIZED,
+            message: "missing or invalid bearer token".into(),
+        }
+        .into_response()
+    }
+}
+
+fn check_origin(headers: &HeaderMap, loopback: bool) -> Result<(), ApiError> {
+    let host = headers
+        .get(header::HOST)
+        .and_then(|value| value.to_str().ok());
+    if loopback && !host.is_some_and(is_local_host) {
+        return Err(ApiError::forbidden("requests must use a local `Host`"));
+    }
+    if let Some(origin) = headers.get(header::ORIGIN) {
+        let origin_host = origin.to_str().ok().and_then(|origin| {
+            origin
+                .strip_prefix("http://")
+                .or_else(|| origin.strip_prefix("https://"))
+        });
+        let allowed = origin_host
+            .is_some_and(|origin_host| is_local_host(origin_host) || Some(origin_host) == host);
+        if !allowed {
+            return Err(ApiError::forbidden("cross-origin requests are not allowed"));
+        }
+    }
+    Ok(())
+}
+
+/// Checks whether the given `Host` value, with optional port, is the local machine.
+fn is_local_host(host: &str) -> bool {
+    let name = match host.strip_prefix('[') {
+        Some(ipv6) => ipv6.split(']').next().unwrap_or_default(),
+        None => host.split(':').next().unwrap_or_default(),
+    };
+    name.eq_ignore_ascii_case("localhost")
+        || name.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback())
+}
+
+async fn openapi() -> Response {
+    ([(header::CONTENT_TYPE, "application/json")], OPENAPI).into_response()
+}
+
+async fn check(State(state): State<HttpState>) -> Result<Response, ApiError> {
+    match state.request(ControlRequest::ConnectedMachines).await? {
+        ControlRequestReply::ConnectedMachines(daemons) => {
+            Ok(json(StatusCode::OK, &CheckResult { daemons }))
+        }
+        other => Err(ApiError::unexpected_reply(other)),
+    }
+}
+
+async fn list(State(state): State<HttpState>) -> Result<Response, ApiError> {
+    Ok(json(StatusCode::OK, &state.list().await?))
+}
+
+/// Requires a JSON content type, which browsers can't send cross-site without CORS.
+async fn start(
+    State(state): State<HttpState>,
+    body: Result<Json<StartRequest>, JsonRejection>,
+) -> Result<Response, ApiError> {
+    let Json(StartRequest {
+        dataflow,
+        name,
+        working_dir,
+    }) = body.map_err(|err| ApiError {
+        status: err.status(),
+        message: format!("invalid request body: {}", err.body_text()),
+    })?;
+    if !working_dir.is_absolute() {
+        return Err(ApiError::bad_request(
+            "`working_dir` must be an absolute path",
+        ));
+    }
+    match state
+        .request(ControlRequest::Start {
+            dataflow,
+            name,
+            local_working_dir: working_dir,
+            dataflow_path: None,
+        })
+        .await?
+    {
+        ControlRequestReply::DataflowStarted { uuid } => {
+            Ok(json(StatusCode::CREATED, &StartResult { uuid }))
+        }
+        other => Err(ApiError::unexpected_reply(other)),
+    }
+}
+
+async fn status(
+    State(state): State<HttpState>,
+    Path(dataflow): Path<String>,
+) -> Result<Response, ApiError> {
+    let uuid = state.resolve(&dataflow).await?;
+    Ok(json(StatusCode::OK, &state.status(uuid).await?))
+}
+
+async fn stop(
+    State(state): State<HttpState>,
+    Path(dataflow): Path<String>,
+    Query(query): Query<StopQuery>,
+) -> Result<Response, ApiError> {
+    let grace_duration = query
+        .grace_duration
+        .map(Duration::try_from_secs_f64)
+        .transpose()
+        .map_err(|err| ApiError::bad_request(format!("invalid `grace_duration`: {err}")))?;
+    let dataflow_uuid = state.resolve(&dataflow).await?;
+    match state
+        .request(ControlRequest::Stop {
+            dataflow_uuid,
+            grace_duration,
+        })
+        .await?
+    {
+        ControlRequestReply::DataflowStopped { uuid, result } => {
+            Ok(json(StatusCode::OK, &DataflowState::stopped(uuid, result)))
+        }
+        other => Err(ApiError::unexpected_reply(other)),
+    }
+}
+
+async fn logs(
+    State(state): State<HttpState>,
+    Path((dataflow, node)): Path<(String, String)>,
+    Query(query): Query<LogsQuery>,
+) -> Result<Response, ApiError> {
+    let uuid = state.resolve(&dataflow).await?;
+    match state
+        .request(ControlRequest::Logs {
+            uuid: Some(uuid),
+            name: None,
+            node,
+            crash: query.crash,
+            tail: None,
+        })
+        .await?
+    {
+        ControlRequestReply::Logs(logs) => {
+            Ok(([(header::CONTENT_TYPE, "text/plain; charset=utf-8")], logs).into_response())
+        }
+        other => Err(ApiError::unexpected_reply(other)),
+    }
+}
+
+async fn events(
+    State(state): State<HttpState>,
+    Path(dataflow): Path<String>,
+    Query(query): Query<EventsQuery>,
+    ws: WebSocketUpgrade,
+) -> Result<Response, ApiError> {
+    let uuid = state.resolve(&dataflow).await?;
+    let level = match query.level {
+        Some(level) => level
+            .parse()
+            .map_err(|_| ApiError::bad_request(format!("invalid log level `{level}`")))?,
+        None => log::LevelFilter::Info,
+    };
+    Ok(ws.on_upgrade(move |socket| stream_events(state, uuid, level, socket)))
+}
+
+/// Forwards the log messages and lifecycle events of the dataflow until it finishes,
+/// followed by its result.
+async fn stream_events(
+    state: HttpState,
+    dataflow_id: Uuid,
+    level: log::LevelFilter,
+    mut socket: WebSocket,
+) {
+    let (events_tx, mut events) = mpsc::channel(SUBSCRIBER_QUEUE_SIZE);
+    let (logs_tx, mut logs) = mpsc::channel(LOG_QUEUE_SIZE);
+    let subscriptions = [
+        ControlEvent::EventChannelSubscribe { sender: events_tx },
+        ControlEvent::LogSubscribe {
+            dataflow_id,
+            level,
+            connection: LogConnection::Channel(logs_tx),
+        },
+    ];
+    for subscribe in subscriptions {
+        if state.tx.send(subscribe).await.is_err() {
+            return;
+        }
+    }
+
+    // the dataflow might have finished before the subscription
+    let mut finished = match state.status(dataflow_id).await {
+        Ok(state) if state.result.is_some() => Some(StreamEvent::Finished(state)),
+        Ok(_) => None,
+        Err(err) => Some(StreamEvent::Error {
+            message: err.message,
+        }),
+    };
+    let mut logs_open = true;
+    while finished.is_none() {
+        let event = tokio::select! {
+            message = logs.recv(), if logs_open => match message {
+                Some(message) => StreamEvent::Log(message),
+                None => {
+                    logs_open = false;
+                    continue;
+                }
+            },
+            event = events.recv() => match event {
+                Some(event) => match &event.kind {
+                    LifecycleEventKind::DataflowFinished { dataflow, result }
+                        if dataflow.uuid == dataflow_id =>
+                    {
+                        let state = DataflowState::stopped(dataflow_id, result.clone());
+                        finished = Some(StreamEvent::Finished(state));
+                        continue;
+                    }
+                    kind if event_dataflow(kind) == Some(dataflow_id) => {
+                        StreamEvent::Lifecycle(event)
+                    }
+                    _ => continue,
+                },
+                // the coordinator stopped or this client didn't keep up
+                None => {
+                    finished = Some(StreamEvent::Error {
+                        message: "stopped receiving events from the coordinator".into(),
+                    });
+                    continue;
+                }
+            },
+        };
+        if send_event(&mut socket, &event).await.is_err() {
+            return;
+        }
+    }
+
+    // log messages that were sent before the dataflow finished
+    while let Ok(message) = logs.try_recv() {
+        if send_event(&mut socket, &StreamEvent::Log(message))
+            .await
+            .is_err()
+        {
+            return;
+        }
+    }
+    if let Some(finished) = finished {
+        let _ = send_event(&mut socket, &finished).await;
+    }
+    let _ = socket.close().await;
+}
+
+/// Returns the dataflow that the given event belongs to, if any.
+fn event_dataflow(kind: &LifecycleEventKind) -> Option<Uuid> {
+    match kind {
+        LifecycleEventKind::DataflowSpawned { dataflow, .. }
+        | LifecycleEventKind::NodesReady { dataflow, .. }
+        | LifecycleEventKind::NodeExited { dataflow, .. }
+        | LifecycleEventKind::DataflowFinished { dataflow, .. } => Some(dataflow.uuid),
+        LifecycleEventKind::DaemonConnected { .. }
+        | LifecycleEventKind::DaemonDisconnected { .. } => None,
+    }
+}
+
+async fn send_event(socket: &mut WebSocket, event: &StreamEvent) -> eyre::Result<()> {
+    let text = serde_json::to_string(event).context("failed to serialize event")?;
+    socket
+        .send(Message::Text(text))
+        .await
+        .context("failed to send WebSocket message")
+}
+
+fn json<T: Serialize>(status: StatusCode, value: &T) -> Response {
+    match serde_json::to_vec(value) {
+        Ok(body) => (status, [(header::CONTENT_TYPE, "application/json")], body).into_response(),
+        Err(err) => ApiError {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            message: format!("failed to serialize response: {err}"),
+        }
+        .into_response(),
+    }
+}
+
+fn status_str(status: DataflowStatus) -> &'static str {
+    match status {
+        DataflowStatus::Running => "running",
+        DataflowStatus::Finished => "finished",
+        DataflowStatus::Failed => "failed",
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StartRequest {
+    dataflow: Descriptor,
+    name: Option<String>,
+    /// Directory that relative paths of the dataflow are resolved against.
+    working_dir: PathBuf,
+}
+
+#[derive(Deserialize)]
+struct StopQuery {
+    /// Seconds until nodes are killed.
+    grace_duration: Option<f64>,
+}
+
+#[derive(Deserialize)]
+struct LogsQuery {
+    /// Return the crash bundle instead of the log file.
+    #[serde(default)]
+    crash: bool,
+}
+
+#[derive(Deserialize)]
+struct EventsQuery {
+    level: Option<String>,
+}
+
+#[derive(Serialize)]
+struct CheckResult {
+    daemons: std::collections::BTreeSet<String>,
+}
+
+#[derive(Serialize)]
+struct StartResult {
+    uuid: Uuid,
+}
+
+#[derive(Serialize)]
+struct DataflowEntry {
+    uuid: Uuid,
+    name: Option<String>,
+    status: &'static str,
+}
+
+#[derive(Serialize)]
+struct DataflowState {
+    uuid: Uuid,
+    status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<DataflowResult>,
+}
+
+impl DataflowState {
+    fn stopped(uuid: Uuid, result: DataflowResult) -> Self {
+        Self {
+            uuid,
+            status: if result.is_ok() { "finished" } else { "failed" },
+            result: Some(result),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum StreamEvent {
+    Log(dora_core::coordinator_messages::LogMessage),
+    Lifecycle(LifecycleEvent),
+    Finished(DataflowState),
+    Error { message: String },
+}
+
+struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ApiError {
+    fn bad_request(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::BAD_REQUEST,
+            message: message.into(),
+        }
+    }
+
+    fn forbidden(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::FORBIDDEN,
+            message: message.into(),
+        }
+    }
+
+    fn not_found(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::NOT_FOUND,
+            message: message.into(),
+        }
+    }
+
+    fn coordinator_stopped() -> Self {
+        Self {
+            status: StatusCode::SERVICE_UNAVAILABLE,
+            message: "coordinator stopped".into(),
+        }
+    }
+
+    fn unexpected_reply(reply: ControlRequestReply) -> Self {
+        Self {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            message: format!("unexpected reply from coordinator: {reply:?}"),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        #[derive(Serialize)]
+        struct Body {
+            error: String,
+        }
+        let body = serde_json::to_vec(&Body {
+            error: self.message,
+        })
+        .unwrap_or_default();
+        (
+            self.status,
+            [(header::CONTENT_TYPE, "application/json")],
+            body,
+        )
+            .into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use dora_core::topics::{DataflowId, DataflowList, DataflowListEntry};
+    use dora_transport_security::SecurityConfig;
+    use hyper::service::Service;
+
+    const DATAFLOW: Uuid = Uuid::from_u128(1);
+
+    /// Answers the requests of the HTTP API like a coordinator with one running
+    /// dataflow named `demo`.
+    fn coordinator() -> mpsc::Sender<ControlEvent> {
+        let (tx, mut rx) = mpsc::channel(10);
+        tokio::spawn(async move {
+            while let Some(event) = rx.recv().await {
+                let ControlEvent::IncomingRequest {
+                    request,
+                    reply_sender,
+                } = event
+                else {
+                    continue;
+                };
+                let reply = match request {
+                    ControlRequest::List => {
+                        ControlRequestReply::DataflowList(DataflowList(vec![DataflowListEntry {
+                            id: DataflowId {
+                                uuid: DATAFLOW,
+                                name: Some("demo".into()),
+                            },
+                            status: DataflowStatus::Running,
+                        }]))
+                    }
+                    ControlRequest::Check { dataflow_uuid } => {
+                        ControlRequestReply::DataflowStarted {
+                            uuid: dataflow_uuid,
+                        }
+                    }
+                    ControlRequest::Start { .. } => {
+                        ControlRequestReply::DataflowStarted { uuid: DATAFLOW }
+                    }
+                    other => ControlRequestReply::Error(format!("unexpected request {other:?}")),
+                };
+                let _ = reply_sender.send(Ok(reply));
+            }
+        });
+        tx
+    }
+
+    fn app_with(token: Option<&str>, loopback: bool) -> Router {
+        let security = TransportSecurity::new(&SecurityConfig {
+            tls: None,
+            token: token.map(String::from),
+        })
+        .unwrap();
+        router(HttpState {
+            tx: coordinator(),
+            security,
+            loopback,
+        })
+    }
+
+    fn request(method: &str, uri: &str) -> axum::http::request::Builder {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::HOST, "127.0.0.1:6014")
+    }
+
+    async fn call(app: &mut Router, request: Request<Body>) -> (StatusCode, serde_json::Value) {
+        let response = app.call(request).await.unwrap();
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or_default())
+    }
+
+    #[tokio::test]
+    async fn cross_site_requests_are_rejected() {
+        let mut app = app_with(None, true);
+
+        let (status, _) = call(
+            &mut app,
+            request("GET", "/api/dataflows")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let local_origin = request("GET", "/api/dataflows")
+            .header(header::ORIGIN, "http://localhost:3000")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(call(&mut app, local_origin).await.0, StatusCode::OK);
+
+        let foreign_origin = request("POST", "/api/dataflows/demo/stop")
+            .header(header::ORIGIN, "https://example.com")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(
+            call(&mut app, foreign_origin).await.0,
+            StatusCode::FORBIDDEN
+        );
+
+        let websocket = request("GET", "/api/dataflows/demo/events")
+            .header(header::ORIGIN, "https://example.com")
+            .header(header::CONNECTION, "upgrade")
+            .header(header::UPGRADE, "websocket")
+            .header(header::SEC_WEBSOCKET_VERSION, "13")
+            .header(header::SEC_WEBSOCKET_KEY, "dGhlIHNhbXBsZSBub25jZQ==")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(call(&mut app, websocket).await.0, StatusCode::FORBIDDEN);
+
+        // DNS rebinding
+        let foreign_host = Request::get("/api/dataflows")
+            .header(header::HOST, "attacker.example.com:6014")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(call(&mut app, foreign_host).await.0, StatusCode::FORBIDDEN);
+
+        // same-origin requests on non-loopback addresses
+        let mut app = app_with(Some("secret"), false);
+        let same_origin = Request::get("/api/dataflows")
+            .header(header::HOST, "robot:6014")
+            .header(header::ORIGIN, "http://robot:6014")
+            .header(header::AUTHORIZATION, "Bearer secret")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(call(&mut app, same_origin).await.0, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn token_is_required() {
+        let mut app = app_with(Some("secret"), true);
+        let with_token = |token: Option<&str>| {
+            let mut request = request("GET", "/api/dataflows");
+            if let Some(token) = token {
+                request = request.header(header::AUTHORIZATION, format!("Bearer {token}"));
+            }
+            request.body(Body::empty()).unwrap()
+        };
+
+        assert_eq!(
+            call(&mut app, with_token(None)).await.0,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            call(&mut app, with_token(Some("wrong"))).await.0,
+            StatusCode::UNAUTHORIZED
+        );
+        let (status, body) = call(&mut app, with_token(Some("secret"))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            body,
+            serde_json::json!([{ "uuid": DATAFLOW, "name": "demo", "status": "running" }])
+        );
+    }
+
+    #[tokio::test]
+    async fn start_requires_json() {
+        let mut app = app_with(None, true);
+        let body = serde_json::json!({
+            "dataflow": { "nodes": [] },
+            "working_dir": "/tmp",
+        })
+        .to_string();
+
+        let form = request("POST", "/api/dataflows")
+            .header(header::CONTENT_TYPE, "text/plain")
+            .body(Body::from(body.clone()))
+            .unwrap();
+        assert_eq!(
+            call(&mut app, form).await.0,
+            StatusCode::UNSUPPORTED_MEDIA_TYPE
+        );
+
+        let json = request("POST", "/api/dataflows")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body))
+            .unwrap();
+        let (status, body) = call(&mut app, json).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(body, serde_json::json!({ "uuid": DATAFLOW }));
+
+        let relative = request("POST", "/api/dataflows")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(
+                r#"{"dataflow": {"nodes": []}, "working_dir": "relative"}"#,
+            ))
+            .unwrap();
+        assert_eq!(call(&mut app, relative).await.0, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn dataflows_are_resolved_by_name() {
+        let mut app = app_with(None, true);
+        let (status, body) = call(
+            &mut app,
+            request("GET", "/api/dataflows/demo")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            body,
+            serde_json::json!({ "uuid": DATAFLOW, "status": "running" })
+        );
+
+        let (status, _) = call(
+            &mut app,
+            request("GET", "/api/dataflows/unknown")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn non_loopback_address_requires_token() {
+        let tasks = FuturesUnordered::new();
+        let addr = SocketAddr::new(IpAddr::from([0, 0, 0, 0]), 0);
+        assert!(http_events(addr, TransportSecurity::default(), &tasks)
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn local_hosts() {
+        for host in [
+            "localhost",
+            "LOCALHOST:80",
+            "127.0.0.1",
+            "127.0.0.1:6014",
+            "[::1]:6014",
+        ] {
+            assert!(is_local_host(host), "{host}");
+        }
+        for host in [
+            "example.com",
+            "10.0.0.1:6014",
+            "localhost.example.com",
+            "[::2]",
+        ] {
+            assert!(!is_local_host(host), "{host}");
+        }
+    }
+
+    #[test]
+    fn openapi_documents_all_routes() {
+        let openapi: serde_json::Value = serde_json::from_str(OPENAPI).unwrap();
+        let mut documented: Vec<_> = openapi["paths"]
+            .as_object()
+            .unwrap()
+            .iter()
+            .flat_map(|(path, operations)| {
+                operations
+                    .as_object()
+                    .unwrap()
+                    .keys()
+                    .filter(|key| *key != "parameters")
+                    .map(move |method| format!("{} {path}", method.to_uppercase()))
+            })
+            .collect();
+        documented.sort();
+
+        // the routes of `router`
+        let mut routes = vec![
+            "GET /api/openapi.json",
+            "GET /api/check",
+            "GET /api/dataflows",
+            "POST /api/dataflows",
+            "GET /api/dataflows/{dataflow}",
+            "POST /api/dataflows/{dataflow}/stop",
+            "GET /api/dataflows/{dataflow}/logs/{node}",
+            "GET /api/dataflows/{dataflow}/events",
+        ];
+        routes.sort();
+        assert_eq!(documented, routes);
+    }
+}
@@ -1,44 +1,60 @@
 use crate::{
-    run::spawn_dataflow,
+    run::{apply_dataflow, spawn_dataflow},
     tcp_utils::{tcp_receive, tcp_send},
 };
 pub use control::ControlEvent;
 use dora_core::{
-    config::{NodeId, OperatorId},
-    coordinator_messages::{LogMessage, RegisterResult},
+    config::{DataId, NodeId, OperatorId},
+    coordinator_messages::{
+        LogMessage, NodeMetrics, NodeOutputLine, RegisterResult, RunningDataflowInfo, TappedOutput,
+        DAEMON_RECONNECT_BACKOFF_MAX,
+    },
     daemon_messages::{DaemonCoordinatorEvent, DaemonCoordinatorReply, Timestamped},
     descriptor::{Descriptor, ResolvedNode},
     message::uhlc::{self, HLC},
+    protocol::{ProtocolVersion, DAEMON_COORDINATOR_PROTOCOL},
     topics::{
         ControlRequest, ControlRequestReply, DataflowDaemonResult, DataflowId, DataflowListEntry,
-        DataflowResult,
+        DataflowMetrics, DataflowRecord, DataflowResult, DataflowStatus, LifecycleEventKind,
+        NodeError, NodeErrorCause, NodeExitStatus, NodeMetricsEntry,
     },
 };
+use dora_transport_security::{AsyncStream, TransportSecurity};
+use events::EventBroadcaster;
 use eyre::{bail, eyre, ContextCompat, WrapErr};
 use futures::{stream::FuturesUnordered, Future, Stream, StreamExt};
 use futures_concurrency::stream::Merge;
-use log_subscriber::LogSubscriber;
+use log_subscriber::{LogFollower, LogSubscriber};
+use recorder::OutputRecorder;
 use run::SpawnedDataflow;
+use state::{JournalEntry, StateStore};
 use std::{
     collections::{BTreeMap, BTreeSet, HashMap},
     net::SocketAddr,
     path::PathBuf,
     sync::Arc,
-    time::{Duration, Instant},
+    time::{Duration, Instant, SystemTime},
 };
 use tokio::{net::TcpStream, sync::mpsc, task::JoinHandle};
 use tokio_stream::wrappers::{ReceiverStream, TcpListenerStream};
 use uuid::Uuid;
 
 mod control;
+mod events;
+mod http;
 mod listener;
 mod log_subscriber;
+mod recorder;
 mod run;
+mod state;
 mod tcp_utils;
 
 pub async fn start(
     bind: SocketAddr,
     bind_control: SocketAddr,
+    bind_http: Option<SocketAddr>,
+    state_dir: Option<PathBuf>,
+    security: TransportSecurity,
     external_events: impl Stream<Item = Event> + Unpin,
 ) -> Result<(u16, impl Future<Output = eyre::Result<()>>), eyre::ErrReport> {
     let listener = listener::create_listener(bind).await?;
@@ -53,9 +69,17 @@ pub async fn start(
     });
 
     let mut tasks = FuturesUnordered::new();
-    let control_events = control::control_events(bind_control, &tasks)
+    let control_events = control::control_events(bind_control, security.clone(), &tasks)
         .await
         .wrap_err("failed to create control events")?;
+    let http_events = match bind_http {
+        Some(addr) => futures::future::Either::Left(
+            http::http_events(addr, security.clone(), &tasks)
+                .await
+                .wrap_err("failed to create HTTP events")?,
+        ),
+        None => futures::future::Either::Right(futures::stream::empty()),
+    };
 
     // Setup ctrl-c handler
     let ctrlc_events = set_up_ctrlc_handler()?;
@@ -64,12 +88,13 @@ pub async fn start(
         external_events,
         new_daemon_connections,
         control_events,
+        http_events,
         ctrlc_events,
     )
         .merge();
 
     let future = async move {
-        start_inner(events, &tasks).await?;
+        start_inner(events, &tasks, state_dir, security).await?;
 
         tracing::debug!("coordinator main loop finished, waiting on spawned tasks");
         while let Some(join_result) = tasks.next().await {
@@ -83,44 +108,82 @@ pub async fn start(
     Ok((port, future))
 }
 
-// Resolve the dataflow name.
+/// Resolves a dataflow name to the UUID of a running or archived dataflow.
+///
+/// The name can be suffixed with `@<index>` to select a previous run, where `@0` is
+/// the latest run with that name. Without an index, a running dataflow is preferred
+/// over the latest archived one.
 fn resolve_name(
     name: String,
     running_dataflows: &HashMap<Uuid, RunningDataflow>,
     archived_dataflows: &HashMap<Uuid, ArchivedDataflow>,
 ) -> eyre::Result<Uuid> {
-    let uuids: Vec<_> = running_dataflows
-        .iter()
-        .filter(|(_, v)| v.name.as_deref() == Some(name.as_str()))
-        .map(|(k, _)| k)
-        .copied()
-        .collect();
-    let archived_uuids: Vec<_> = archived_dataflows
+    let (name, index) = match name.rsplit_once('@') {
+        Some((base, index)) => match index.parse::<usize>() {
+            Ok(index) => (base, Some(index)),
+            Err(_) => (name.as_str(), None),
+        },
+        None => (name.as_str(), None),
+    };
+
+    let runs = runs_with_name(name, running_dataflows, archived_dataflows);
+    if let Some(index) = index {
+        return runs.get(index).copied().ok_or_else(|| match runs.len() {
+            0 => eyre!("no dataflow with name `{name}`"),
+            len => eyre!(
+                "dataflow `{name}` has only {len} runs (`@0` to `@{}`)",
+                len - 1
+            ),
+        });
+    }
+
+    let running: Vec<_> = runs
         .iter()
-        .filter(|(_, v)| v.name.as_deref() == Some(name.as_str()))
-        .map(|(k, _)| k)
-        .copied()
+        .filter(|uuid| running_dataflows.contains_key(uuid))
         .collect();
+    match (running.as_slice(), runs.first()) {
+        ([uuid], _) => Ok(**uuid),
+        ([], Some(uuid)) => Ok(*uuid),
+        ([], None) => bail!("no dataflow with name `{name}`"),
+        _ => bail!("multiple dataflows found with name `{name}`"),
+    }
+}
 
-    if uuids.is_empty() {
-        if archived_uuids.is_empty() {
-            bail!("no dataflow with name `{name}`");
-        } else if let [uuid] = archived_uuids.as_slice() {
-            Ok(*uuid)
-        } else {
-            // TODO: Index the archived dataflows in order to return logs based on the index.
-            bail!("multiple archived dataflows found with name `{name}`, Please provide the UUID instead.");
-        }
-    } else if let [uuid] = uuids.as_slice() {
-        Ok(*uuid)
-    } else {
-        bail!("multiple dataflows found with name `{name}`");
+/// Checks that the name can be used for a new dataflow.
+///
+/// Names must not contain `@`, since it separates the name from the run index in
+/// [`resolve_name`].
+fn check_dataflow_name(name: &str) -> eyre::Result<()> {
+    if name.contains('@') {
+        bail!("invalid dataflow name `{name}`: names must not contain `@`");
     }
+    Ok(())
+}
+
+/// Returns the UUIDs of all dataflows with the given name, latest first.
+fn runs_with_name(
+    name: &str,
+    running_dataflows: &HashMap<Uuid, RunningDataflow>,
+    archived_dataflows: &HashMap<Uuid, ArchivedDataflow>,
+) -> Vec<Uuid> {
+    let running = running_dataflows
+        .iter()
+        .filter(|(_, d)| d.name.as_deref() == Some(name))
+        .map(|(uuid, _)| *uuid);
+    let archived = archived_dataflows
+        .iter()
+        .filter(|(_, d)| d.name.as_deref() == Some(name))
+        .map(|(uuid, _)| *uuid);
+    // UUIDs are v7, so their order matches the start order
+    let runs: BTreeSet<_> = running.chain(archived).collect();
+    runs.into_iter().rev().collect()
 }
 
 async fn start_inner(
     events: impl Stream<Item = Event> + Unpin,
     tasks: &FuturesUnordered<JoinHandle<()>>,
+    state_dir: Option<PathBuf>,
+    security: TransportSecurity,
 ) -> eyre::Result<()> {
     let clock = Arc::new(HLC::default());
 
@@ -142,7 +205,47 @@ async fn start_inner(
     let mut dataflow_results: HashMap<Uuid, BTreeMap<String, DataflowDaemonResult>> =
         HashMap::new();
     let mut archived_dataflows: HashMap<Uuid, ArchivedDataflow> = HashMap::new();
+
+    let mut state_store = match state_dir {
+        Some(state_dir) => {
+            let (store, stored) = StateStore::open(&state_dir)?;
+            for (uuid, dataflow) in stored.dataflows {
+                if dataflow.machines.is_empty() {
+                    archived_dataflows.insert(
+                        uuid,
+                        ArchivedDataflow {
+                            name: dataflow.name,
+                            dataflow_path: dataflow.dataflow_path,
+                            nodes: dataflow.nodes,
+                        },
+                    );
+                } else {
+                    running_dataflows.insert(
+                        uuid,
+                        RunningDataflow::restored(
+                            uuid,
+                            dataflow.name,
+                            dataflow.descriptor,
+                            dataflow.dataflow_path,
+                            dataflow.nodes,
+                            dataflow.machines,
+                        ),
+                    );
+                }
+            }
+            dataflow_results = stored.results;
+            tracing::info!(
+                "restored {} running and {} finished dataflows from {state_dir:?}",
+                running_dataflows.len(),
+                archived_dataflows.len()
+            );
+            Some(store)
+        }
+        None => None,
+    };
     let mut daemon_connections: HashMap<_, DaemonConnection> = HashMap::new();
+    let mut lifecycle_events = EventBroadcaster::default();
+    let mut lost_machines = LostMachines::default();
 
     while let Some(event) = events.next().await {
         if event.log() {
@@ -155,6 +258,7 @@ async fn start_inner(
                 if let Some(events_tx) = events_tx {
                     let task = tokio::spawn(listener::handle_connection(
                         connection,
+                        security.clone(),
                         events_tx,
                         clock.clone(),
                     ));
@@ -174,15 +278,28 @@ async fn start_inner(
                     mut connection,
                     dora_version: daemon_version,
                     listen_port,
+                    protocol_version,
                 } => {
-                    let coordinator_version: &&str = &env!("CARGO_PKG_VERSION");
-                    let version_check = if &daemon_version == coordinator_version {
-                        Ok(())
-                    } else {
-                        Err(format!(
-                            "version mismatch: daemon v{daemon_version} is \
-                        not compatible with coordinator v{coordinator_version}"
-                        ))
+                    let coordinator_version = env!("CARGO_PKG_VERSION");
+                    let version_check = match protocol_version {
+                        Some(protocol_version) => DAEMON_COORDINATOR_PROTOCOL
+                            .negotiate(
+                                &protocol_version,
+                                "coordinator",
+                                coordinator_version,
+                                "daemon",
+                                &daemon_version,
+                            )
+                            .map(|version| {
+                                tracing::debug!(
+                                    "daemon `{machine_id}` uses protocol version {version}"
+                                );
+                            }),
+                        None => Err(format!(
+                            "version mismatch: daemon v{daemon_version} does not report a \
+                            protocol version and is not compatible with coordinator \
+                            v{coordinator_version}"
+                        )),
                     };
                     let peer_ip = connection
                         .peer_addr()
@@ -212,7 +329,18 @@ async fn start_inner(
                                 tracing::info!(
                                     "closing previous connection `{machine_id}` on new register"
                                 );
+                                lifecycle_events.publish(LifecycleEventKind::DaemonDisconnected {
+                                    machine_id: machine_id.clone(),
+                                });
+                            }
+                            if lost_machines.reconnected(&machine_id) {
+                                tracing::info!(
+                                    "machine `{machine_id}` reconnected within grace period"
+                                );
                             }
+                            lifecycle_events.publish(LifecycleEventKind::DaemonConnected {
+                                machine_id: machine_id.clone(),
+                            });
                         }
                         (Err(err), _) => {
                             tracing::warn!("failed to register daemon connection for machine `{machine_id}`: {err}");
@@ -222,6 +350,53 @@ async fn start_inner(
                         }
                     }
                 }
+                DaemonEvent::RunningDataflows {
+                    machine_id,
+                    dataflows,
+                } => {
+                    let finished = reconcile_running_dataflows(
+                        &machine_id,
+                        dataflows,
+                        &mut running_dataflows,
+                        &mut archived_dataflows,
+                        &mut dataflow_results,
+                        &mut state_store,
+                        &mut lifecycle_events,
+                        &clock,
+                    );
+                    for uuid in finished {
+                        tracing::warn!(
+                            "machine `{machine_id}` reported dataflow `{uuid}` as running, \
+                            which already finished on that machine -> stopping it"
+                        );
+                        if let Err(err) = stop_dataflow_on_machine(
+                            uuid,
+                            &machine_id,
+                            &mut daemon_connections,
+                            clock.new_timestamp(),
+                            None,
+                        )
+                        .await
+                        {
+                            tracing::warn!("{err:?}");
+                        }
+                    }
+                }
+                DaemonEvent::NodeMetrics {
+                    machine_id,
+                    metrics,
+                } => handle_node_metrics(&mut running_dataflows, &machine_id, metrics),
+                DaemonEvent::Exit { machine_id } => {
+                    if let Some(connection) = daemon_connections.remove(&machine_id) {
+                        tracing::info!("daemon `{machine_id}` exited");
+                        lifecycle_events.publish(LifecycleEventKind::DaemonDisconnected {
+                            machine_id: machine_id.clone(),
+                        });
+                        // dataflows that are still running on the machine are handled
+                        // like after a disconnect, in case the daemon is restarted
+                        lost_machines.insert(machine_id, connection.last_heartbeat);
+                    }
+                }
             },
             Event::Dataflow { uuid, event } => match event {
                 DataflowEvent::ReadyOnMachine {
@@ -236,6 +411,12 @@ async fn start_inner(
                                 .exited_before_subscribe
                                 .extend(exited_before_subscribe);
                             if dataflow.pending_machines.is_empty() {
+                                lifecycle_events.publish(LifecycleEventKind::NodesReady {
+                                    dataflow: dataflow.id(),
+                                    exited_before_subscribe: dataflow
+                                        .exited_before_subscribe
+                                        .clone(),
+                                });
                                 let message = serde_json::to_vec(&Timestamped {
                                     inner: DaemonCoordinatorEvent::AllNodesReady {
                                         dataflow_id: uuid,
@@ -272,38 +453,47 @@ async fn start_inner(
                         }
                     }
                 }
+                DataflowEvent::ReadyLocally {
+                    exited_before_subscribe,
+                } => match running_dataflows.get(&uuid) {
+                    Some(dataflow) => {
+                        lifecycle_events.publish(LifecycleEventKind::NodesReady {
+                            dataflow: dataflow.id(),
+                            exited_before_subscribe,
+                        });
+                    }
+                    None => tracing::warn!("dataflow not running on ReadyLocally"),
+                },
+                DataflowEvent::NodeExited {
+                    machine_id,
+                    node_id,
+                    result,
+                } => match running_dataflows.get(&uuid) {
+                    Some(dataflow) => {
+                        lifecycle_events.publish(LifecycleEventKind::NodeExited {
+                            dataflow: dataflow.id(),
+                            node_id,
+                            machine_id,
+                            result,
+                        });
+                    }
+                    None => tracing::warn!("dataflow not running on NodeExited"),
+                },
                 DataflowEvent::DataflowFinishedOnMachine { machine_id, result } => {
-                    match running_dataflows.entry(uuid) {
-                        std::collections::hash_map::Entry::Occupied(mut entry) => {
-                            // Archive finished dataflow
-                            if archived_dataflows.get(&uuid).is_none() {
-                                archived_dataflows
-                                    .insert(uuid, ArchivedDataflow::from(entry.get()));
-                            }
-                            entry.get_mut().machines.remove(&machine_id);
-                            dataflow_results
-                                .entry(uuid)
-                                .or_default()
-                                .insert(machine_id, result);
-                            if entry.get_mut().machines.is_empty() {
-                                let finished_dataflow = entry.remove();
-                                let reply = ControlRequestReply::DataflowStopped {
-                                    uuid,
-                                    result: dataflow_results
-                                        .get(&uuid)
-                                        .map(|r| dataflow_result(r, uuid, &clock))
-                                        .unwrap_or_else(|| {
-                                            DataflowResult::ok_empty(uuid, clock.new_timestamp())
-                                        }),
-                                };
-                                for sender in finished_dataflow.reply_senders {
-                                    let _ = sender.send(Ok(reply.clone()));
-                                }
-                            }
-                        }
-                        std::collections::hash_map::Entry::Vacant(_) => {
-                            tracing::warn!("dataflow not running on DataflowFinishedOnMachine");
-                        }
+                    if running_dataflows.contains_key(&uuid) {
+                        finish_dataflow_on_machine(
+                            uuid,
+                            machine_id,
+                            result,
+                            &mut running_dataflows,
+                            &mut archived_dataflows,
+                            &mut dataflow_results,
+                            &mut state_store,
+                            &mut lifecycle_events,
+                            &clock,
+                        );
+                    } else {
+                        tracing::warn!("dataflow not running on DataflowFinishedOnMachine");
                     }
                 }
             },
@@ -318,11 +508,13 @@ async fn start_inner(
                             dataflow,
                             name,
                             local_working_dir,
+                            dataflow_path,
                         } => {
                             let name = name.or_else(|| names::Generator::default().next());
 
                             let inner = async {
                                 if let Some(name) = name.as_deref() {
+                                    check_dataflow_name(name)?;
                                     // check that name is unique
                                     if running_dataflows
                                         .values()
@@ -334,6 +526,7 @@ async fn start_inner(
                                 let dataflow = start_dataflow(
                                     dataflow,
                                     local_working_dir,
+                                    dataflow_path,
                                     name,
                                     &mut daemon_connections,
                                     &clock,
@@ -343,6 +536,21 @@ async fn start_inner(
                             };
                             let reply = inner.await.map(|dataflow| {
                                 let uuid = dataflow.uuid;
+                                state::record(
+                                    &mut state_store,
+                                    JournalEntry::DataflowStarted {
+                                        uuid,
+                                        name: dataflow.name.clone(),
+                                        descriptor: dataflow.descriptor.clone(),
+                                        dataflow_path: dataflow.dataflow_path.clone(),
+                                        nodes: dataflow.nodes.clone(),
+                                        machines: dataflow.machines.clone(),
+                                    },
+                                );
+                                lifecycle_events.publish(LifecycleEventKind::DataflowSpawned {
+                                    dataflow: dataflow.id(),
+                                    machines: dataflow.machines.clone(),
+                                });
                                 running_dataflows.insert(uuid, dataflow);
                                 ControlRequestReply::DataflowStarted { uuid }
                             });
@@ -393,6 +601,65 @@ async fn start_inner(
                                     });
                             let _ = reply_sender.send(reply);
                         }
+                        ControlRequest::Apply {
+                            dataflow_uuid,
+                            dataflow,
+                            local_working_dir,
+                        } => {
+                            let inner = async {
+                                let Some(running) = running_dataflows.get_mut(&dataflow_uuid)
+                                else {
+                                    bail!("no running dataflow with UUID `{dataflow_uuid}`")
+                                };
+                                apply_dataflow(
+                                    running,
+                                    dataflow,
+                                    local_working_dir,
+                                    &mut daemon_connections,
+                                    &mut state_store,
+                                    &clock,
+                                )
+                                .await
+                            };
+                            let reply =
+                                inner
+                                    .await
+                                    .map(|changes| ControlRequestReply::DataflowApplied {
+                                        uuid: dataflow_uuid,
+                                        changes,
+                                    });
+                            let _ = reply_sender.send(reply);
+                        }
+                        ControlRequest::Restart {
+                            uuid,
+                            name,
+                            node,
+                            grace_duration,
+                        } => {
+                            let inner = async {
+                                let dataflow_uuid = match (uuid, name) {
+                                    (Some(uuid), _) => uuid,
+                                    (None, Some(name)) => {
+                                        resolve_name(name, &running_dataflows, &archived_dataflows)?
+                                    }
+                                    (None, None) => bail!("no dataflow UUID or name given"),
+                                };
+                                restart_node(
+                                    &running_dataflows,
+                                    dataflow_uuid,
+                                    node.clone(),
+                                    grace_duration,
+                                    &mut daemon_connections,
+                                    clock.new_timestamp(),
+                                )
+                                .await?;
+                                Ok(ControlRequestReply::NodeRestarted {
+                                    uuid: dataflow_uuid,
+                                    node_id: node,
+                                })
+                            };
+                            let _ = reply_sender.send(inner.await);
+                        }
                         ControlRequest::Stop {
                             dataflow_uuid,
                             grace_duration,
@@ -430,25 +697,35 @@ async fn start_inner(
                                 let _ = reply_sender.send(Err(err));
                             }
                         },
-                        ControlRequest::Logs { uuid, name, node } => {
-                            let dataflow_uuid = if let Some(uuid) = uuid {
-                                uuid
-                            } else if let Some(name) = name {
-                                resolve_name(name, &running_dataflows, &archived_dataflows)?
-                            } else {
-                                bail!("No uuid")
+                        ControlRequest::Logs {
+                            uuid,
+                            name,
+                            node,
+                            crash,
+                            tail,
+                        } => {
+                            let dataflow_uuid = match (uuid, name) {
+                                (Some(uuid), _) => Ok(uuid),
+                                (None, Some(name)) => {
+                                    resolve_name(name, &running_dataflows, &archived_dataflows)
+                                }
+                                (None, None) => Err(eyre!("no dataflow UUID or name given")),
+                            };
+                            let reply = match dataflow_uuid {
+                                Ok(dataflow_uuid) => retrieve_logs(
+                                    &running_dataflows,
+                                    &archived_dataflows,
+                                    dataflow_uuid,
+                                    node.into(),
+                                    crash,
+                                    tail,
+                                    &mut daemon_connections,
+                                    clock.new_timestamp(),
+                                )
+                                .await
+                                .map(ControlRequestReply::Logs),
+                                Err(err) => Err(err),
                             };
-
-                            let reply = retrieve_logs(
-                                &running_dataflows,
-                                &archived_dataflows,
-                                dataflow_uuid,
-                                node.into(),
-                                &mut daemon_connections,
-                                clock.new_timestamp(),
-                            )
-                            .await
-                            .map(ControlRequestReply::Logs);
                             let _ = reply_sender.send(reply);
                         }
                         ControlRequest::Destroy => {
@@ -459,6 +736,7 @@ async fn start_inner(
                                 &mut daemon_connections,
                                 &abort_handle,
                                 &mut daemon_events_tx,
+                                &mut lifecycle_events,
                                 &clock,
                             )
                             .await
@@ -496,6 +774,37 @@ async fn start_inner(
                             ));
                             let _ = reply_sender.send(reply);
                         }
+                        ControlRequest::History => {
+                            let reply = Ok(ControlRequestReply::History(dataflow_records(
+                                &running_dataflows,
+                                &archived_dataflows,
+                                &dataflow_results,
+                            )));
+                            let _ = reply_sender.send(reply);
+                        }
+                        ControlRequest::Inspect { uuid, name } => {
+                            let inner = || {
+                                let dataflow_uuid = match (uuid, name) {
+                                    (Some(uuid), _) => uuid,
+                                    (None, Some(name)) => {
+                                        resolve_name(name, &running_dataflows, &archived_dataflows)?
+                                    }
+                                    (None, None) => bail!("no dataflow UUID or name given"),
+                                };
+                                dataflow_records(
+                                    &running_dataflows,
+                                    &archived_dataflows,
+                                    &dataflow_results,
+                                )
+                                .into_iter()
+                                .find(|r| r.id.uuid == dataflow_uuid)
+                                .map(ControlRequestReply::DataflowRecord)
+                                .ok_or_else(|| {
+                                    eyre!("no dataflow found with UUID `{dataflow_uuid}`")
+                                })
+                            };
+                            let _ = reply_sender.send(inner());
+                        }
                         ControlRequest::DaemonConnected => {
                             let running = !daemon_connections.is_empty();
                             let _ = reply_sender
@@ -507,9 +816,30 @@ async fn start_inner(
                             ));
                             let _ = reply_sender.send(reply);
                         }
-                        ControlRequest::LogSubscribe { .. } => {
+                        ControlRequest::NodeMetrics => {
+                            let mut dataflows: Vec<_> = running_dataflows.values().collect();
+                            dataflows.sort_by_key(|d| (&d.name, d.uuid));
+
+                            let metrics = dataflows
+                                .into_iter()
+                                .map(|d| DataflowMetrics {
+                                    id: DataflowId {
+                                        uuid: d.uuid,
+                                        name: d.name.clone(),
+                                    },
+                                    nodes: d.node_metrics_entries(),
+                                })
+                                .collect();
+                            let _ =
+                                reply_sender.send(Ok(ControlRequestReply::NodeMetrics(metrics)));
+                        }
+                        ControlRequest::LogSubscribe { .. }
+                        | ControlRequest::LogFollow { .. }
+                        | ControlRequest::EventSubscribe
+                        | ControlRequest::Record { .. } => {
                             let _ = reply_sender.send(Err(eyre::eyre!(
-                                "LogSubscribe request should be handled separately"
+                                "LogSubscribe, LogFollow, EventSubscribe, and Record requests \
+                                should be handled separately"
                             )));
                         }
                     }
@@ -526,6 +856,103 @@ async fn start_inner(
                             .push(LogSubscriber::new(level, connection));
                     }
                 }
+                ControlEvent::LogFollow {
+                    uuid,
+                    name,
+                    nodes,
+                    tail,
+                    since,
+                    mut connection,
+                } => {
+                    let result = follow_logs(
+                        &mut running_dataflows,
+                        &archived_dataflows,
+                        uuid,
+                        name,
+                        nodes,
+                        tail,
+                        since,
+                        &mut daemon_connections,
+                        clock.new_timestamp(),
+                    )
+                    .await;
+                    match result {
+                        Ok((dataflow_id, nodes, history)) => {
+                            if let Some(dataflow) = running_dataflows.get_mut(&dataflow_id) {
+                                dataflow
+                                    .log_followers
+                                    .push(LogFollower::new(nodes, connection, history));
+                            }
+                            if let Some(dataflow) = running_dataflows.get(&dataflow_id) {
+                                unfollow_unused_logs(
+                                    dataflow,
+                                    &mut daemon_connections,
+                                    clock.new_timestamp(),
+                                )
+                                .await;
+                            }
+                        }
+                        Err(err) => {
+                            let reply = ControlRequestReply::Error(format!("{err:?}"));
+                            if let Ok(reply) = serde_json::to_vec(&reply) {
+                                let _ = tcp_send(&mut connection, &reply).await;
+                            }
+                        }
+                    }
+                }
+                ControlEvent::EventSubscribe { mut connection } => {
+                    let sent = match serde_json::to_vec(&ControlRequestReply::EventSubscribed) {
+                        Ok(reply) => tcp_send(&mut connection, &reply).await,
+                        Err(err) => Err(err.into()),
+                    };
+                    match sent {
+                        Ok(()) => tasks.push(lifecycle_events.subscribe(connection)),
+                        Err(err) => tracing::warn!("failed to send event subscribe reply: {err}"),
+                    }
+                }
+                ControlEvent::EventChannelSubscribe { sender } => {
+                    lifecycle_events.subscribe_channel(sender);
+                }
+                ControlEvent::Record {
+                    uuid,
+                    name,
+                    outputs,
+                    mut connection,
+                } => {
+                    let result = resolve_recorded_outputs(
+                        &running_dataflows,
+                        &archived_dataflows,
+                        uuid,
+                        name,
+                        outputs,
+                    );
+                    let reply = match &result {
+                        Ok((dataflow_id, outputs)) => ControlRequestReply::RecordStarted {
+                            dataflow_id: *dataflow_id,
+                            outputs: outputs.clone(),
+                        },
+                        Err(err) => ControlRequestReply::Error(format!("{err:?}")),
+                    };
+                    let sent = match serde_json::to_vec(&reply) {
+                        Ok(reply) => tcp_send(&mut connection, &reply).await,
+                        Err(err) => Err(err.into()),
+                    };
+                    if let Err(err) = sent {
+                        tracing::warn!("failed to send record reply: {err}");
+                    } else if let Ok((dataflow_id, outputs)) = result {
+                        if let Some(dataflow) = running_dataflows.get_mut(&dataflow_id) {
+                            dataflow
+                                .recorders
+                                .push(OutputRecorder::new(outputs, connection));
+                            update_output_taps(
+                                dataflow,
+                                &mut daemon_connections,
+                                clock.new_timestamp(),
+                            )
+                            .await;
+                        }
+                    }
+                }
             },
             Event::DaemonHeartbeatInterval => {
                 let mut disconnected = BTreeSet::new();
@@ -536,7 +963,7 @@ async fn start_inner(
                             connection.last_heartbeat.elapsed()
                         )
                     }
-                    if connection.last_heartbeat.elapsed() > Duration::from_secs(30) {
+                    if connection.last_heartbeat.elapsed() > DAEMON_HEARTBEAT_TIMEOUT {
                         disconnected.insert(machine_id.clone());
                         continue;
                     }
@@ -558,9 +985,34 @@ async fn start_inner(
                 if !disconnected.is_empty() {
                     tracing::error!("Disconnecting daemons that failed watchdog: {disconnected:?}");
                     for machine_id in disconnected {
-                        daemon_connections.remove(&machine_id);
+                        let Some(connection) = daemon_connections.remove(&machine_id) else {
+                            continue;
+                        };
+                        lifecycle_events.publish(LifecycleEventKind::DaemonDisconnected {
+                            machine_id: machine_id.clone(),
+                        });
+                        // the daemon keeps its dataflows running and tries to reconnect
+                        lost_machines.insert(machine_id, connection.last_heartbeat);
                     }
                 }
+
+                for machine_id in lost_machines.expired(Instant::now()) {
+                    tracing::error!(
+                        "machine `{machine_id}` did not reconnect within {MACHINE_RECONNECT_GRACE:?} \
+                        after its last heartbeat"
+                    );
+                    handle_machine_lost(
+                        machine_id,
+                        &mut running_dataflows,
+                        &mut archived_dataflows,
+                        &mut dataflow_results,
+                        &mut daemon_connections,
+                        &mut state_store,
+                        &mut lifecycle_events,
+                        &clock,
+                    )
+                    .await;
+                }
             }
             Event::CtrlC => {
                 tracing::info!("Destroying coordinator after receiving Ctrl-C signal");
@@ -569,6 +1021,7 @@ async fn start_inner(
                     &mut daemon_connections,
                     &abort_handle,
                     &mut daemon_events_tx,
+                    &mut lifecycle_events,
                     &clock,
                 )
                 .await?;
@@ -593,6 +1046,41 @@ async fn start_inner(
                     dataflow.log_subscribers.retain(|s| !s.is_closed());
                 }
             }
+            Event::NodeOutput(lines) => {
+                let Some(dataflow_id) = lines.first().map(|line| line.dataflow_id) else {
+                    continue;
+                };
+                if let Some(dataflow) = running_dataflows.get_mut(&dataflow_id) {
+                    for follower in &mut dataflow.log_followers {
+                        follower.send_lines(&lines);
+                    }
+                    if dataflow.log_followers.iter().any(|f| f.is_closed()) {
+                        dataflow.log_followers.retain(|f| !f.is_closed());
+                        unfollow_unused_logs(
+                            dataflow,
+                            &mut daemon_connections,
+                            clock.new_timestamp(),
+                        )
+                        .await;
+                    }
+                }
+            }
+            Event::TappedOutput { output, data } => {
+                if let Some(dataflow) = running_dataflows.get_mut(&output.dataflow_id) {
+                    for recorder in &mut dataflow.recorders {
+                        recorder.send_output(&output, &data);
+                    }
+                    if dataflow.recorders.iter().any(|r| r.is_closed()) {
+                        dataflow.recorders.retain(|r| !r.is_closed());
+                        update_output_taps(
+                            dataflow,
+                            &mut daemon_connections,
+                            clock.new_timestamp(),
+                        )
+                        .await;
+                    }
+                }
+            }
         }
     }
 
@@ -645,13 +1133,406 @@ async fn stop_dataflow_by_uuid(
     Ok(())
 }
 
+/// Daemons are disconnected if they don't send a heartbeat within this duration.
+const DAEMON_HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Time after the last heartbeat of a disconnected daemon until the nodes on its
+/// machine are considered failed, unless the daemon reconnects.
+///
+/// Daemons retry reconnecting with a delay of up to [`DAEMON_RECONNECT_BACKOFF_MAX`],
+/// so the grace period covers one full reconnect interval. Since it starts at the
+/// last heartbeat, it has already passed when a daemon is disconnected because of
+/// the [`DAEMON_HEARTBEAT_TIMEOUT`]. So in the worst case, i.e. if a machine dies
+/// without closing its connection, its nodes are reported as failed 30s after its
+/// last heartbeat, plus up to one 3s heartbeat check interval.
+const MACHINE_RECONNECT_GRACE: Duration = DAEMON_RECONNECT_BACKOFF_MAX;
+
+/// Machines whose daemon disconnected and didn't reconnect yet.
+#[derive(Default)]
+struct LostMachines {
+    /// The time of the last heartbeat of each machine.
+    since: BTreeMap<String, Instant>,
+}
+
+impl LostMachines {
+    fn insert(&mut self, machine_id: String, last_heartbeat: Instant) {
+        self.since.entry(machine_id).or_insert(last_heartbeat);
+    }
+
+    /// Returns `true` if the machine was lost.
+    fn reconnected(&mut self, machine_id: &str) -> bool {
+        self.since.remove(machine_id).is_some()
+    }
+
+    /// Removes and returns the machines that did not reconnect within
+    /// [`MACHINE_RECONNECT_GRACE`].
+    fn expired(&mut self, now: Instant) -> Vec<String> {
+        let expired: Vec<_> = self
+            .since
+            .iter()
+            .filter(|(_, since)| now.duration_since(**since) >= MACHINE_RECONNECT_GRACE)
+            .map(|(machine_id, _)| machine_id.clone())
+            .collect();
+        for machine_id in &expired {
+            self.since.remove(machine_id);
+        }
+        expired
+    }
+}
+
+/// Stores the node metrics that the daemon of the given machine reported.
+///
+/// The metrics of each machine replace its previous report, so nodes that exited
+/// in the meantime have no metrics anymore.
+fn handle_node_metrics(
+    running_dataflows: &mut HashMap<Uuid, RunningDataflow>,
+    machine_id: &str,
+    mut metrics: BTreeMap<Uuid, BTreeMap<NodeId, NodeMetrics>>,
+) {
+    for dataflow in running_dataflows.values_mut() {
+        if dataflow.machines.contains(machine_id) {
+            let nodes = metrics.remove(&dataflow.uuid).unwrap_or_default();
+            dataflow.node_metrics.insert(machine_id.to_owned(), nodes);
+        }
+    }
+}
+
+/// Fails all nodes of running dataflows that were deployed on the given machine
+/// and notifies the remaining machines of these dataflows.
+#[allow(clippy::too_many_arguments)]
+async fn handle_machine_lost(
+    machine_id: String,
+    running_dataflows: &mut HashMap<Uuid, RunningDataflow>,
+    archived_dataflows: &mut HashMap<Uuid, ArchivedDataflow>,
+    dataflow_results: &mut HashMap<Uuid, BTreeMap<String, DataflowDaemonResult>>,
+    daemon_connections: &mut HashMap<String, DaemonConnection>,
+    state_store: &mut Option<StateStore>,
+    lifecycle_events: &mut EventBroadcaster,
+    clock: &HLC,
+) {
+    let affected: Vec<_> = running_dataflows
+        .values()
+        .filter(|d| d.machines.contains(&machine_id))
+        .map(|d| d.uuid)
+        .collect();
+    for uuid in affected {
+        let Some(dataflow) = running_dataflows.get_mut(&uuid) else {
+            continue;
+        };
+        tracing::error!("dataflow `{uuid}` lost machine `{machine_id}`");
+        let lost_nodes: Vec<_> = dataflow
+            .nodes
+            .iter()
+            .filter(|n| n.deploy.machine == machine_id)
+            .map(|n| n.id.clone())
+            .collect();
+
+        let timestamp = clock.new_timestamp();
+        let mut messages = vec![DaemonCoordinatorEvent::MachineLost {
+            dataflow_id: uuid,
+            machine_id: machine_id.clone(),
+        }];
+        if dataflow.pending_machines.remove(&machine_id) {
+            // the nodes on the lost machine will never subscribe
+            dataflow.exited_before_subscribe.extend(lost_nodes.clone());
+            if dataflow.pending_machines.is_empty() {
+                messages.push(DaemonCoordinatorEvent::AllNodesReady {
+                    dataflow_id: uuid,
+                    exited_before_subscribe: dataflow.exited_before_subscribe.clone(),
+                });
+            }
+        }
+        for message in messages {
+            let message = match serde_json::to_vec(&Timestamped {
+                inner: message,
+                timestamp,
+            }) {
+                Ok(message) => message,
+                Err(err) => {
+                    tracing::warn!("failed to serialize message: {err}");
+                    continue;
+                }
+            };
+            for other_machine in dataflow.machines.iter().filter(|m| **m != machine_id) {
+                let Some(connection) = daemon_connections.get_mut(other_machine) else {
+                    continue;
+                };
+                if let Err(err) = tcp_send(&mut connection.stream, &message).await {
+                    tracing::warn!(
+                        "failed to notify machine `{other_machine}` about lost machine: {err}"
+                    );
+                }
+            }
+        }
+
+        let result = failed_result(
+            lost_nodes,
+            NodeErrorCause::MachineLost {
+                machine_id: machine_id.clone(),
+            },
+            timestamp,
+        );
+        for (node_id, node_result) in &result.node_results {
+            lifecycle_events.publish(LifecycleEventKind::NodeExited {
+                dataflow: dataflow.id(),
+                node_id: node_id.clone(),
+                machine_id: machine_id.clone(),
+                result: node_result.clone(),
+            });
+        }
+        finish_dataflow_on_machine(
+            uuid,
+            machine_id.clone(),
+            result,
+            running_dataflows,
+            archived_dataflows,
+            dataflow_results,
+            state_store,
+            lifecycle_events,
+            clock,
+        );
+    }
+}
+
+/// Builds a result in which all given nodes failed with the given cause.
+fn failed_result(
+    node_ids: impl IntoIterator<Item = NodeId>,
+    cause: NodeErrorCause,
+    timestamp: uhlc::Timestamp,
+) -> DataflowDaemonResult {
+    DataflowDaemonResult {
+        timestamp,
+        node_results: node_ids
+            .into_iter()
+            .map(|node_id| {
+                let error = NodeError {
+                    timestamp,
+                    cause: cause.clone(),
+                    exit_status: NodeExitStatus::Unknown,
+                    crash_bundle: None,
+                };
+                (node_id, Err(error))
+            })
+            .collect(),
+    }
+}
+
+/// Reconciles the dataflows that a (re)registered daemon reports as running with
+/// the dataflows that the coordinator considers running on that machine.
+///
+/// Reported dataflows that the coordinator doesn't know are restored. Dataflows that
+/// the machine no longer reports are finished on that machine, with all of its nodes
+/// failing with [`NodeErrorCause::NoResult`].
+///
+/// Returns the reported dataflows that already finished on the machine, e.g. because
+/// the machine was considered lost. These are not restored and should be stopped.
+#[allow(clippy::too_many_arguments)]
+fn reconcile_running_dataflows(
+    machine_id: &str,
+    dataflows: Vec<RunningDataflowInfo>,
+    running_dataflows: &mut HashMap<Uuid, RunningDataflow>,
+    archived_dataflows: &mut HashMap<Uuid, ArchivedDataflow>,
+    dataflow_results: &mut HashMap<Uuid, BTreeMap<String, DataflowDaemonResult>>,
+    state_store: &mut Option<StateStore>,
+    lifecycle_events: &mut EventBroadcaster,
+    clock: &HLC,
+) -> Vec<Uuid> {
+    let reported: BTreeSet<_> = dataflows.iter().map(|d| d.dataflow_id).collect();
+    let mut finished = Vec::new();
+    for info in dataflows {
+        let uuid = info.dataflow_id;
+        let finished_on_machine = dataflow_results
+            .get(&uuid)
+            .is_some_and(|results| results.contains_key(machine_id));
+        if archived_dataflows.contains_key(&uuid) || finished_on_machine {
+            finished.push(uuid);
+            continue;
+        }
+        let dataflow = running_dataflows.entry(uuid).or_insert_with(|| {
+            tracing::info!("restoring dataflow `{uuid}` reported by machine `{machine_id}`");
+            RunningDataflow::restored(uuid, info.name, None, None, info.nodes, BTreeSet::new())
+        });
+        if dataflow.machines.insert(machine_id.to_owned()) {
+            state::record(
+                state_store,
+                JournalEntry::DataflowStarted {
+                    uuid,
+                    name: dataflow.name.clone(),
+                    descriptor: dataflow.descriptor.clone(),
+                    dataflow_path: dataflow.dataflow_path.clone(),
+                    nodes: dataflow.nodes.clone(),
+                    machines: dataflow.machines.clone(),
+                },
+            );
+        }
+    }
+
+    // reconcile dataflows that the machine no longer reports as running
+    let stale: Vec<_> = running_dataflows
+        .values()
+        .filter(|d| d.machines.contains(machine_id) && !reported.contains(&d.uuid))
+        .map(|d| d.uuid)
+        .collect();
+    for uuid in stale {
+        tracing::warn!(
+            "machine `{machine_id}` no longer runs dataflow `{uuid}`, \
+            marking its nodes on that machine as failed"
+        );
+        let nodes = running_dataflows[&uuid]
+            .nodes
+            .iter()
+            .filter(|n| n.deploy.machine == machine_id)
+            .map(|n| n.id.clone());
+        let result = failed_result(
+            nodes,
+            NodeErrorCause::NoResult {
+                machine_id: machine_id.to_owned(),
+            },
+            clock.new_timestamp(),
+        );
+        finish_dataflow_on_machine(
+            uuid,
+            machine_id.to_owned(),
+            result,
+            running_dataflows,
+            archived_dataflows,
+            dataflow_results,
+            state_store,
+            lifecycle_events,
+            clock,
+        );
+    }
+    finished
+}
+
+/// Marks the dataflow as finished on the given machine and archives it once it
+/// finished on all machines.
+#[allow(clippy::too_many_arguments)]
+fn finish_dataflow_on_machine(
+    uuid: Uuid,
+    machine_id: String,
+    result: DataflowDaemonResult,
+    running_dataflows: &mut HashMap<Uuid, RunningDataflow>,
+    archived_dataflows: &mut HashMap<Uuid, ArchivedDataflow>,
+    dataflow_results: &mut HashMap<Uuid, BTreeMap<String, DataflowDaemonResult>>,
+    state_store: &mut Option<StateStore>,
+    lifecycle_events: &mut EventBroadcaster,
+    clock: &uhlc::HLC,
+) {
+    let std::collections::hash_map::Entry::Occupied(mut entry) = running_dataflows.entry(uuid)
+    else {
+        return;
+    };
+    state::record(
+        state_store,
+        JournalEntry::DataflowFinishedOnMachine {
+            uuid,
+            machine_id: machine_id.clone(),
+            result: Some(result.clone()),
+        },
+    );
+
+    // Archive finished dataflow
+    if archived_dataflows.get(&uuid).is_none() {
+        archived_dataflows.insert(uuid, ArchivedDataflow::from(entry.get()));
+    }
+    entry.get_mut().machines.remove(&machine_id);
+    dataflow_results
+        .entry(uuid)
+        .or_default()
+        .insert(machine_id, result);
+    if entry.get_mut().machines.is_empty() {
+        let finished_dataflow = entry.remove();
+        let result = dataflow_results
+            .get(&uuid)
+            .map(|r| dataflow_result(r, uuid, clock))
+            .unwrap_or_else(|| DataflowResult::ok_empty(uuid, clock.new_timestamp()));
+        lifecycle_events.publish(LifecycleEventKind::DataflowFinished {
+            dataflow: finished_dataflow.id(),
+            result: result.clone(),
+        });
+        let reply = ControlRequestReply::DataflowStopped { uuid, result };
+        for sender in finished_dataflow.reply_senders {
+            let _ = sender.send(Ok(reply.clone()));
+        }
+    }
+}
+
+/// Builds the history records of all running and archived dataflows, ordered by
+/// start time.
+fn dataflow_records(
+    running_dataflows: &HashMap<Uuid, RunningDataflow>,
+    archived_dataflows: &HashMap<Uuid, ArchivedDataflow>,
+    dataflow_results: &HashMap<Uuid, BTreeMap<String, DataflowDaemonResult>>,
+) -> Vec<DataflowRecord> {
+    // UUIDs are v7, so their order matches the start order
+    let uuids: BTreeSet<Uuid> = running_dataflows
+        .keys()
+        .chain(archived_dataflows.keys())
+        .copied()
+        .collect();
+
+    let mut run_counts: HashMap<Option<String>, usize> = HashMap::new();
+    let mut records = Vec::new();
+    for uuid in uuids.into_iter().rev() {
+        let (name, dataflow_path, nodes) = if let Some(d) = running_dataflows.get(&uuid) {
+            (&d.name, &d.dataflow_path, &d.nodes)
+        } else if let Some(d) = archived_dataflows.get(&uuid) {
+            (&d.name, &d.dataflow_path, &d.nodes)
+        } else {
+            continue;
+        };
+        let results = dataflow_results.get(&uuid);
+        let running = running_dataflows.contains_key(&uuid);
+
+        let status = if running {
+            DataflowStatus::Running
+        } else if results.map_or(true, |r| r.values().all(|r| r.is_ok())) {
+            DataflowStatus::Finished
+        } else {
+            DataflowStatus::Failed
+        };
+        let finished = results
+            .filter(|_| !running)
+            .and_then(|r| r.values().map(|r| r.timestamp).max())
+            .map(|t| t.get_time().to_system_time());
+        let started = uuid.get_timestamp().map(|t| {
+            let (secs, nanos) = t.to_unix();
+            SystemTime::UNIX_EPOCH + Duration::new(secs, nanos)
+        });
+        let index = run_counts.entry(name.clone()).or_default();
+
+        records.push(DataflowRecord {
+            id: DataflowId {
+                uuid,
+                name: name.clone(),
+            },
+            index: *index,
+            status,
+            dataflow_path: dataflow_path.clone(),
+            machines: nodes.iter().map(|n| n.deploy.machine.clone()).collect(),
+            nodes: nodes.iter().map(|n| n.id.clone()).collect(),
+            started,
+            finished,
+            node_results: results
+                .into_iter()
+                .flat_map(|r| r.values())
+                .flat_map(|r| r.node_results.clone())
+                .collect(),
+        });
+        *index += 1;
+    }
+    records.reverse();
+    records
+}
+
 fn dataflow_result(
     results: &BTreeMap<String, DataflowDaemonResult>,
     dataflow_uuid: Uuid,
     clock: &uhlc::HLC,
 ) -> DataflowResult {
     let mut node_results = BTreeMap::new();
-    for (_machine, result) in results {
+    for result in results.values() {
         node_results.extend(result.node_results.clone());
         if let Err(err) = clock.update_with_timestamp(&result.timestamp) {
             tracing::warn!("failed to update HLC: {err}");
@@ -666,7 +1547,7 @@ fn dataflow_result(
 }
 
 struct DaemonConnection {
-    stream: TcpStream,
+    stream: AsyncStream,
     listen_socket: SocketAddr,
     last_heartbeat: Instant,
 }
@@ -676,6 +1557,7 @@ async fn handle_destroy(
     daemon_connections: &mut HashMap<String, DaemonConnection>,
     abortable_events: &futures::stream::AbortHandle,
     daemon_events_tx: &mut Option<mpsc::Sender<Event>>,
+    lifecycle_events: &mut EventBroadcaster,
     clock: &HLC,
 ) -> Result<(), eyre::ErrReport> {
     abortable_events.abort();
@@ -689,13 +1571,17 @@ async fn handle_destroy(
         )
         .await?;
     }
+    let machines: Vec<_> = daemon_connections.keys().cloned().collect();
     destroy_daemons(daemon_connections, clock.new_timestamp()).await?;
+    for machine_id in machines {
+        lifecycle_events.publish(LifecycleEventKind::DaemonDisconnected { machine_id });
+    }
     *daemon_events_tx = None;
     Ok(())
 }
 
 async fn send_heartbeat_message(
-    connection: &mut TcpStream,
+    connection: &mut AsyncStream,
     timestamp: uhlc::Timestamp,
 ) -> eyre::Result<()> {
     let message = serde_json::to_vec(&Timestamped {
@@ -717,15 +1603,74 @@ struct RunningDataflow {
     /// IDs of machines that are waiting until all nodes are started.
     pending_machines: BTreeSet<String>,
     exited_before_subscribe: Vec<NodeId>,
+    /// Not known for dataflows that were reported by a reconnecting daemon.
+    descriptor: Option<Descriptor>,
+    /// Path of the descriptor file, if the dataflow was started by the CLI.
+    dataflow_path: Option<PathBuf>,
     nodes: Vec<ResolvedNode>,
 
     reply_senders: Vec<tokio::sync::oneshot::Sender<eyre::Result<ControlRequestReply>>>,
 
     log_subscribers: Vec<LogSubscriber>,
+    log_followers: Vec<LogFollower>,
+    recorders: Vec<OutputRecorder>,
+
+    /// Latest node metrics reported by each machine.
+    node_metrics: BTreeMap<String, BTreeMap<NodeId, NodeMetrics>>,
+}
+
+impl RunningDataflow {
+    fn id(&self) -> DataflowId {
+        DataflowId {
+            uuid: self.uuid,
+            name: self.name.clone(),
+        }
+    }
+
+    fn node_metrics_entries(&self) -> Vec<NodeMetricsEntry> {
+        self.nodes
+            .iter()
+            .map(|node| NodeMetricsEntry {
+                node_id: node.id.clone(),
+                machine_id: node.deploy.machine.clone(),
+                metrics: self
+                    .node_metrics
+                    .get(&node.deploy.machine)
+                    .and_then(|nodes| nodes.get(&node.id))
+                    .cloned(),
+            })
+            .collect()
+    }
+
+    fn restored(
+        uuid: Uuid,
+        name: Option<String>,
+        descriptor: Option<Descriptor>,
+        dataflow_path: Option<PathBuf>,
+        nodes: Vec<ResolvedNode>,
+        machines: BTreeSet<String>,
+    ) -> Self {
+        Self {
+            name,
+            uuid,
+            machines,
+            pending_machines: BTreeSet::new(),
+            exited_before_subscribe: Default::default(),
+            descriptor,
+            dataflow_path,
+            nodes,
+            reply_senders: Vec::new(),
+            log_subscribers: Vec::new(),
+            log_followers: Vec::new(),
+            recorders: Vec::new(),
+            node_metrics: BTreeMap::new(),
+        }
+    }
 }
 
 struct ArchivedDataflow {
     name: Option<String>,
+    dataflow_path: Option<PathBuf>,
     nodes: Vec<ResolvedNode>,
 }
 
@@ -733,6 +1678,7 @@ impl From<&RunningDataflow> for ArchivedDataflow {
     fn from(dataflow: &RunningDataflow) -> ArchivedDataflow {
         ArchivedDataflow {
             name: dataflow.name.clone(),
+            dataflow_path: dataflow.dataflow_path.clone(),
             nodes: dataflow.nodes.clone(),
         }
     }
@@ -752,6 +1698,28 @@ async fn stop_dataflow(
     daemon_connections: &mut HashMap<String, DaemonConnection>,
     timestamp: uhlc::Timestamp,
     grace_duration: Option<Duration>,
+) -> eyre::Result<()> {
+    for machine_id in &dataflow.machines {
+        stop_dataflow_on_machine(
+            uuid,
+            machine_id,
+            daemon_connections,
+            timestamp,
+            grace_duration,
+        )
+        .await?;
+    }
+    tracing::info!("successfully send stop dataflow `{uuid}` to all daemons");
+
+    Ok(())
+}
+
+async fn stop_dataflow_on_machine(
+    uuid: Uuid,
+    machine_id: &str,
+    daemon_connections: &mut HashMap<String, DaemonConnection>,
+    timestamp: uhlc::Timestamp,
+    grace_duration: Option<Duration>,
 ) -> eyre::Result<()> {
     let message = serde_json::to_vec(&Timestamped {
         inner: DaemonCoordinatorEvent::StopDataflow {
@@ -760,31 +1728,25 @@ async fn stop_dataflow(
         },
         timestamp,
     })?;
+    let daemon_connection = daemon_connections
+        .get_mut(machine_id)
+        .wrap_err("no daemon connection")?; // TODO: take from dataflow spec
+    tcp_send(&mut daemon_connection.stream, &message)
+        .await
+        .wrap_err("failed to send stop message to daemon")?;
 
-    for machine_id in &dataflow.machines {
-        let daemon_connection = daemon_connections
-            .get_mut(machine_id)
-            .wrap_err("no daemon connection")?; // TODO: take from dataflow spec
-        tcp_send(&mut daemon_connection.stream, &message)
-            .await
-            .wrap_err("failed to send stop message to daemon")?;
-
-        // wait for reply
-        let reply_raw = tcp_receive(&mut daemon_connection.stream)
-            .await
-            .wrap_err("failed to receive stop reply from daemon")?;
-        match serde_json::from_slice(&reply_raw)
-            .wrap_err("failed to deserialize stop reply from daemon")?
-        {
-            DaemonCoordinatorReply::StopResult(result) => result
-                .map_err(|e| eyre!(e))
-                .wrap_err("failed to stop dataflow")?,
-            other => bail!("unexpected reply after sending stop: {other:?}"),
-        }
+    // wait for reply
+    let reply_raw = tcp_receive(&mut daemon_connection.stream)
+        .await
+        .wrap_err("failed to receive stop reply from daemon")?;
+    match serde_json::from_slice(&reply_raw)
+        .wrap_err("failed to deserialize stop reply from daemon")?
+    {
+        DaemonCoordinatorReply::StopResult(result) => result
+            .map_err(|e| eyre!(e))
+            .wrap_err("failed to stop dataflow"),
+        other => bail!("unexpected reply after sending stop: {other:?}"),
     }
-    tracing::info!("successfully send stop dataflow `{uuid}` to all daemons");
-
-    Ok(())
 }
 
 async fn reload_dataflow(
@@ -833,11 +1795,263 @@ async fn reload_dataflow(
     Ok(())
 }
 
+async fn restart_node(
+    running_dataflows: &HashMap<Uuid, RunningDataflow>,
+    dataflow_id: Uuid,
+    node_id: NodeId,
+    grace_duration: Option<Duration>,
+    daemon_connections: &mut HashMap<String, DaemonConnection>,
+    timestamp: uhlc::Timestamp,
+) -> eyre::Result<()> {
+    let Some(dataflow) = running_dataflows.get(&dataflow_id) else {
+        bail!("no running dataflow found with UUID `{dataflow_id}`")
+    };
+    let Some(node) = dataflow.nodes.iter().find(|n| n.id == node_id) else {
+        bail!("dataflow `{dataflow_id}` has no node `{node_id}`")
+    };
+    let machine_id = &node.deploy.machine;
+    let message = serde_json::to_vec(&Timestamped {
+        inner: DaemonCoordinatorEvent::RestartNode {
+            dataflow_id,
+            node_id: node_id.clone(),
+            grace_duration,
+        },
+        timestamp,
+    })?;
+
+    let daemon_connection = daemon_connections
+        .get_mut(machine_id)
+        .wrap_err_with(|| format!("no daemon connection to machine `{machine_id}`"))?;
+    tcp_send(&mut daemon_connection.stream, &message)
+        .await
+        .wrap_err("failed to send restart message to daemon")?;
+    let reply_raw = tcp_receive(&mut daemon_connection.stream)
+        .await
+        .wrap_err("failed to receive restart reply from daemon")?;
+    match serde_json::from_slice(&reply_raw)
+        .wrap_err("failed to deserialize restart reply from daemon")?
+    {
+        DaemonCoordinatorReply::RestartResult(result) => result
+            .map_err(|e| eyre!(e))
+            .wrap_err_with(|| format!("failed to restart node `{node_id}`"))?,
+        other => bail!("unexpected reply after sending restart: {other:?}"),
+    }
+    tracing::info!("restarting node `{dataflow_id}/{node_id}`");
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn follow_logs(
+    running_dataflows: &mut HashMap<Uuid, RunningDataflow>,
+    archived_dataflows: &HashMap<Uuid, ArchivedDataflow>,
+    uuid: Option<Uuid>,
+    name: Option<String>,
+    nodes: Vec<NodeId>,
+    tail: Option<usize>,
+    since: Option<SystemTime>,
+    daemon_connections: &mut HashMap<String, DaemonConnection>,
+    timestamp: uhlc::Timestamp,
+) -> eyre::Result<(Uuid, BTreeSet<NodeId>, Vec<NodeOutputLine>)> {
+    let dataflow_id = match (uuid, name) {
+        (Some(uuid), _) => uuid,
+        (None, Some(name)) => resolve_name(name, running_dataflows, archived_dataflows)?,
+        (None, None) => bail!("No uuid"),
+    };
+    let dataflow = running_dataflows
+        .get(&dataflow_id)
+        .wrap_err_with(|| format!("dataflow `{dataflow_id}` is not running"))?;
+
+    let nodes: BTreeSet<NodeId> = if nodes.is_empty() {
+        dataflow.nodes.iter().map(|n| n.id.clone()).collect()
+    } else {
+        nodes.into_iter().collect()
+    };
+    let mut nodes_by_machine: BTreeMap<&str, BTreeSet<NodeId>> = BTreeMap::new();
+    for node_id in &nodes {
+        let node = dataflow
+            .nodes
+            .iter()
+            .find(|n| &n.id == node_id)
+            .wrap_err_with(|| format!("dataflow `{dataflow_id}` has no node `{node_id}`"))?;
+        nodes_by_machine
+            .entry(node.deploy.machine.as_str())
+            .or_default()
+            .insert(node_id.clone());
+    }
+
+    let mut history = Vec::new();
+    for (machine_id, node_ids) in nodes_by_machine {
+        let message = serde_json::to_vec(&Timestamped {
+            inner: DaemonCoordinatorEvent::FollowLogs {
+                dataflow_id,
+                node_ids,
+                tail,
+                since,
+            },
+            timestamp,
+        })?;
+        let daemon_connection = daemon_connections
+            .get_mut(machine_id)
+            .wrap_err_with(|| format!("no daemon connection to machine `{machine_id}`"))?;
+        tcp_send(&mut daemon_connection.stream, &message)
+            .await
+            .wrap_err("failed to send follow logs message to daemon")?;
+        let reply_raw = tcp_receive(&mut daemon_connection.stream)
+            .await
+            .wrap_err("failed to receive follow logs reply from daemon")?;
+        match serde_json::from_slice(&reply_raw)
+            .wrap_err("failed to deserialize follow logs reply from daemon")?
+        {
+            DaemonCoordinatorReply::FollowLogsResult(result) => {
+                history.extend(result.map_err(|err| eyre!(err))?)
+            }
+            other => bail!("unexpected reply after sending follow logs: {other:?}"),
+        }
+    }
+    history.sort_by_key(|line| line.timestamp);
+
+    Ok((dataflow_id, nodes, history))
+}
+
+/// Instructs the daemons to stop forwarding the output of nodes that are no
+/// longer followed by any log follower.
+async fn unfollow_unused_logs(
+    dataflow: &RunningDataflow,
+    daemon_connections: &mut HashMap<String, DaemonConnection>,
+    timestamp: uhlc::Timestamp,
+) {
+    let followed: BTreeSet<_> = dataflow
+        .log_followers
+        .iter()
+        .flat_map(|f| f.nodes.iter())
+        .collect();
+    let mut unfollowed: BTreeMap<&str, BTreeSet<NodeId>> = BTreeMap::new();
+    for node in &dataflow.nodes {
+        if !followed.contains(&node.id) {
+            unfollowed
+                .entry(node.deploy.machine.as_str())
+                .or_default()
+                .insert(node.id.clone());
+        }
+    }
+    for (machine_id, node_ids) in unfollowed {
+        let Some(daemon_connection) = daemon_connections.get_mut(machine_id) else {
+            continue;
+        };
+        let result = async {
+            let message = serde_json::to_vec(&Timestamped {
+                inner: DaemonCoordinatorEvent::UnfollowLogs {
+                    dataflow_id: dataflow.uuid,
+                    node_ids,
+                },
+                timestamp,
+            })?;
+            tcp_send(&mut daemon_connection.stream, &message)
+                .await
+                .wrap_err("failed to send unfollow logs message to daemon")
+        };
+        if let Err(err) = result.await {
+            tracing::warn!("{err:?}");
+        }
+    }
+}
+
+/// Checks that the given outputs exist in the running dataflow.
+///
+/// Selects all outputs of the dataflow if `outputs` is empty.
+fn resolve_recorded_outputs(
+    running_dataflows: &HashMap<Uuid, RunningDataflow>,
+    archived_dataflows: &HashMap<Uuid, ArchivedDataflow>,
+    uuid: Option<Uuid>,
+    name: Option<String>,
+    outputs: Vec<(NodeId, DataId)>,
+) -> eyre::Result<(Uuid, BTreeSet<(NodeId, DataId)>)> {
+    let dataflow_id = match (uuid, name) {
+        (Some(uuid), _) => uuid,
+        (None, Some(name)) => resolve_name(name, running_dataflows, archived_dataflows)?,
+        (None, None) => bail!("No uuid"),
+    };
+    let dataflow = running_dataflows
+        .get(&dataflow_id)
+        .wrap_err_with(|| format!("dataflow `{dataflow_id}` is not running"))?;
+
+    let all_outputs: BTreeSet<_> = dataflow
+        .nodes
+        .iter()
+        .flat_map(|node| {
+            let outputs = node.kind.run_config().outputs;
+            outputs
+                .into_iter()
+                .map(|output_id| (node.id.clone(), output_id))
+        })
+        .collect();
+    if outputs.is_empty() {
+        return Ok((dataflow_id, all_outputs));
+    }
+    for (node_id, output_id) in &outputs {
+        if !all_outputs.contains(&(node_id.clone(), output_id.clone())) {
+            bail!("dataflow `{dataflow_id}` has no output `{node_id}/{output_id}`");
+        }
+    }
+    Ok((dataflow_id, outputs.into_iter().collect()))
+}
+
+/// Instructs the daemons to mirror exactly the outputs that are recorded by the
+/// `dora record` connections of the dataflow.
+async fn update_output_taps(
+    dataflow: &RunningDataflow,
+    daemon_connections: &mut HashMap<String, DaemonConnection>,
+    timestamp: uhlc::Timestamp,
+) {
+    let recorded: BTreeSet<_> = dataflow
+        .recorders
+        .iter()
+        .flat_map(|r| r.outputs.iter())
+        .collect();
+    let mut taps: BTreeMap<&str, BTreeSet<(NodeId, DataId)>> = dataflow
+        .machines
+        .iter()
+        .map(|machine_id| (machine_id.as_str(), BTreeSet::new()))
+        .collect();
+    for node in &dataflow.nodes {
+        let machine_taps = taps.entry(node.deploy.machine.as_str()).or_default();
+        for (node_id, output_id) in &recorded {
+            if node_id == &node.id {
+                machine_taps.insert((node_id.clone(), output_id.clone()));
+            }
+        }
+    }
+    for (machine_id, outputs) in taps {
+        let Some(daemon_connection) = daemon_connections.get_mut(machine_id) else {
+            continue;
+        };
+        let result = async {
+            let message = serde_json::to_vec(&Timestamped {
+                inner: DaemonCoordinatorEvent::TapOutputs {
+                    dataflow_id: dataflow.uuid,
+                    outputs,
+                },
+                timestamp,
+            })?;
+            tcp_send(&mut daemon_connection.stream, &message)
+                .await
+                .wrap_err("failed to send tap outputs message to daemon")
+        };
+        if let Err(err) = result.await {
+            tracing::warn!("{err:?}");
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 async fn retrieve_logs(
     running_dataflows: &HashMap<Uuid, RunningDataflow>,
     archived_dataflows: &HashMap<Uuid, ArchivedDataflow>,
     dataflow_id: Uuid,
     node_id: NodeId,
+    crash: bool,
+    tail: Option<usize>,
     daemon_connections: &mut HashMap<String, DaemonConnection>,
     timestamp: uhlc::Timestamp,
 ) -> eyre::Result<Vec<u8>> {
@@ -853,6 +2067,8 @@ async fn retrieve_logs(
         inner: DaemonCoordinatorEvent::Logs {
             dataflow_id,
             node_id: node_id.clone(),
+            crash,
+            tail,
         },
         timestamp,
     })?;
@@ -900,6 +2116,7 @@ async fn retrieve_logs(
 async fn start_dataflow(
     dataflow: Descriptor,
     working_dir: PathBuf,
+    dataflow_path: Option<PathBuf>,
     name: Option<String>,
     daemon_connections: &mut HashMap<String, DaemonConnection>,
     clock: &HLC,
@@ -908,7 +2125,14 @@ async fn start_dataflow(
         uuid,
         machines,
         nodes,
-    } = spawn_dataflow(dataflow, working_dir, daemon_connections, clock).await?;
+    } = spawn_dataflow(
+        dataflow.clone(),
+        working_dir,
+        name.clone(),
+        daemon_connections,
+        clock,
+    )
+    .await?;
     Ok(RunningDataflow {
         uuid,
         name,
@@ -919,9 +2143,14 @@ async fn start_dataflow(
         },
         exited_before_subscribe: Default::default(),
         machines,
+        descriptor: Some(dataflow),
+        dataflow_path,
         nodes,
         reply_senders: Vec::new(),
         log_subscribers: Vec::new(),
+        log_followers: Vec::new(),
+        recorders: Vec::new(),
+        node_metrics: BTreeMap::new(),
     })
 }
 
@@ -962,13 +2191,23 @@ async fn destroy_daemons(
 pub enum Event {
     NewDaemonConnection(TcpStream),
     DaemonConnectError(eyre::Report),
-    DaemonHeartbeat { machine_id: String },
-    Dataflow { uuid: Uuid, event: DataflowEvent },
+    DaemonHeartbeat {
+        machine_id: String,
+    },
+    Dataflow {
+        uuid: Uuid,
+        event: DataflowEvent,
+    },
     Control(ControlEvent),
     Daemon(DaemonEvent),
     DaemonHeartbeatInterval,
     CtrlC,
     Log(LogMessage),
+    NodeOutput(Vec<NodeOutputLine>),
+    TappedOutput {
+        output: TappedOutput,
+        data: Arc<[u8]>,
+    },
 }
 
 impl Event {
@@ -977,6 +2216,7 @@ impl Event {
     pub fn log(&self) -> bool {
         match self {
             Event::DaemonHeartbeatInterval => false,
+            Event::TappedOutput { .. } => false,
             _ => true,
         }
     }
@@ -984,6 +2224,15 @@ impl Event {
 
 #[derive(Debug)]
 pub enum DataflowEvent {
+    /// All nodes of a single-machine dataflow are ready.
+    ReadyLocally {
+        exited_before_subscribe: Vec<NodeId>,
+    },
+    NodeExited {
+        machine_id: String,
+        node_id: NodeId,
+        result: Result<(), NodeError>,
+    },
     DataflowFinishedOnMachine {
         machine_id: String,
         result: DataflowDaemonResult,
@@ -998,10 +2247,23 @@ pub enum DataflowEvent {
 pub enum DaemonEvent {
     Register {
         dora_version: String,
+        protocol_version: Option<ProtocolVersion>,
         machine_id: String,
-        connection: TcpStream,
+        connection: AsyncStream,
         listen_port: u16,
     },
+    /// Dataflows that are still running on a reconnected daemon.
+    RunningDataflows {
+        machine_id: String,
+        dataflows: Vec<RunningDataflowInfo>,
+    },
+    /// Latest resource usage of the nodes running on a daemon.
+    NodeMetrics {
+        machine_id: String,
+        metrics: BTreeMap<Uuid, BTreeMap<NodeId, NodeMetrics>>,
+    },
+    /// The daemon exits on purpose, so it is not expected to reconnect.
+    Exit { machine_id: String },
 }
 
 fn set_up_ctrlc_handler() -> Result<impl Stream<Item = Event>, eyre::ErrReport> {
@@ -1025,3 +2287,454 @@ fn set_up_ctrlc_handler() -> Result<impl Stream<Item = Event>, eyre::ErrReport>
 
     Ok(ReceiverStream::new(ctrlc_rx))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control::ControlEvent;
+    use dora_core::{coordinator_messages::CoordinatorRequest, topics::LifecycleEvent};
+
+    /// Two nodes, one on machine `a` and one on machine `b`.
+    fn nodes() -> Vec<ResolvedNode> {
+        let descriptor = Descriptor::parse(
+            br#"
+nodes:
+  - id: node-a
+    path: node-a
+    _unstable_deploy:
+      machine: a
+  - id: node-b
+    path: node-b
+    _unstable_deploy:
+      machine: b
+"#
+            .to_vec(),
+        )
+        .unwrap();
+        descriptor.resolve_aliases_and_set_defaults().unwrap()
+    }
+
+    fn info(dataflow_id: Uuid) -> RunningDataflowInfo {
+        RunningDataflowInfo {
+            dataflow_id,
+            name: Some("test".into()),
+            nodes: nodes(),
+        }
+    }
+
+    #[derive(Default)]
+    struct State {
+        running: HashMap<Uuid, RunningDataflow>,
+        archived: HashMap<Uuid, ArchivedDataflow>,
+        results: HashMap<Uuid, BTreeMap<String, DataflowDaemonResult>>,
+        store: Option<StateStore>,
+        events: EventBroadcaster,
+        clock: HLC,
+    }
+
+    impl State {
+        fn reconcile(
+            &mut self,
+            machine_id: &str,
+            dataflows: Vec<RunningDataflowInfo>,
+        ) -> Vec<Uuid> {
+            reconcile_running_dataflows(
+                machine_id,
+                dataflows,
+                &mut self.running,
+                &mut self.archived,
+                &mut self.results,
+                &mut self.store,
+                &mut self.events,
+                &self.clock,
+            )
+        }
+
+        async fn machine_lost(&mut self, machine_id: &str) {
+            handle_machine_lost(
+                machine_id.to_owned(),
+                &mut self.running,
+                &mut self.archived,
+                &mut self.results,
+                &mut HashMap::new(),
+                &mut self.store,
+                &mut self.events,
+                &self.clock,
+            )
+            .await;
+        }
+
+        fn result(&self, uuid: Uuid) -> DataflowResult {
+            dataflow_result(&self.results[&uuid], uuid, &self.clock)
+        }
+    }
+
+    /// Adds a dataflow with the given name that was started `secs` after the epoch.
+    fn add_run(state: &mut State, name: &str, secs: u64, running: bool) -> Uuid {
+        let uuid = Uuid::new_v7(uuid::Timestamp::from_unix(uuid::NoContext, secs, 0));
+        let dataflow = RunningDataflow::restored(
+            uuid,
+            Some(name.to_owned()),
+            None,
+            None,
+            nodes(),
+            BTreeSet::from(["a".to_owned(), "b".to_owned()]),
+        );
+        if running {
+            state.running.insert(uuid, dataflow);
+        } else {
+            state
+                .archived
+                .insert(uuid, ArchivedDataflow::from(&dataflow));
+        }
+        uuid
+    }
+
+    #[test]
+    fn node_metrics_are_aggregated_per_machine() {
+        let mut state = State::default();
+        let uuid = add_run(&mut state, "cam", 1, true);
+        let other = add_run(&mut state, "other", 2, true);
+        let metrics = |cpu_usage| NodeMetrics {
+            cpu_usage,
+            ..Default::default()
+        };
+        let report = |nodes: &[(&str, f32)]| {
+            let nodes = nodes
+                .iter()
+                .map(|(id, cpu)| (NodeId::from(id.to_string()), metrics(*cpu)))
+                .collect();
+            BTreeMap::from([(uuid, nodes)])
+        };
+        let entries = |state: &State| -> Vec<_> {
+            state.running[&uuid]
+                .node_metrics_entries()
+                .into_iter()
+                .map(|e| (e.node_id.to_string(), e.machine_id, e.metrics))
+                .collect()
+        };
+
+        // no metrics before the first report
+        assert!(entries(&state).iter().all(|(_, _, m)| m.is_none()));
+
+        handle_node_metrics(&mut state.running, "a", report(&[("node-a", 10.0)]));
+        handle_node_metrics(&mut state.running, "b", report(&[("node-b", 20.0)]));
+        assert_eq!(
+            entries(&state),
+            [
+                ("node-a".into(), "a".into(), Some(metrics(10.0))),
+                ("node-b".into(), "b".into(), Some(metrics(20.0))),
+            ]
+        );
+        // the report of a dataflow doesn't affect other dataflows
+        assert!(state.running[&other]
+            .node_metrics_entries()
+            .iter()
+            .all(|e| e.metrics.is_none()));
+
+        // a node that is no longer reported has exited
+        handle_node_metrics(&mut state.running, "a", report(&[]));
+        assert_eq!(
+            entries(&state),
+            [
+                ("node-a".into(), "a".into(), None),
+                ("node-b".into(), "b".into(), Some(metrics(20.0))),
+            ]
+        );
+        // metrics of nodes on other machines are ignored
+        handle_node_metrics(&mut state.running, "a", report(&[("node-b", 30.0)]));
+        assert_eq!(entries(&state)[1].2, Some(metrics(20.0)));
+    }
+
+    #[test]
+    fn names_are_resolved_to_runs() {
+        let mut state = State::default();
+        let first = add_run(&mut state, "cam", 1, false);
+        let second = add_run(&mut state, "cam", 2, false);
+        let resolve = |state: &State, name: &str| {
+            resolve_name(name.to_owned(), &state.running, &state.archived)
+        };
+
+        assert_eq!(resolve(&state, "cam").unwrap(), second);
+        assert_eq!(resolve(&state, "cam@0").unwrap(), second);
+        assert_eq!(resolve(&state, "cam@1").unwrap(), first);
+        assert!(resolve(&state, "cam@2").is_err());
+        assert!(resolve(&state, "other").is_err());
+
+        // a running dataflow is preferred over later archived ones
+        let running = add_run(&mut state, "cam", 0, true);
+        assert_eq!(resolve(&state, "cam").unwrap(), running);
+        assert_eq!(resolve(&state, "cam@2").unwrap(), running);
+        add_run(&mut state, "cam", 3, true);
+        assert!(resolve(&state, "cam").is_err());
+    }
+
+    #[test]
+    fn names_with_index_separator_are_rejected() {
+        assert!(check_dataflow_name("cam").is_ok());
+        assert!(check_dataflow_name("cam-2").is_ok());
+        assert!(check_dataflow_name("cam@2").is_err());
+        assert!(check_dataflow_name("cam@").is_err());
+    }
+
+    #[test]
+    fn records_are_indexed_per_name() {
+        let mut state = State::default();
+        let first = add_run(&mut state, "cam", 1, false);
+        let other = add_run(&mut state, "lidar", 2, false);
+        let second = add_run(&mut state, "cam", 3, true);
+        state.results.insert(
+            first,
+            BTreeMap::from([(
+                "a".to_owned(),
+                DataflowDaemonResult {
+                    timestamp: state.clock.new_timestamp(),
+                    node_results: BTreeMap::from([(
+                        NodeId::from("node-a".to_owned()),
+                        Err(NodeError {
+                            timestamp: state.clock.new_timestamp(),
+                            cause: NodeErrorCause::Other {
+                                stderr: String::new(),
+                            },
+                            exit_status: NodeExitStatus::ExitCode(1),
+                            crash_bundle: None,
+                        }),
+                    )]),
+                },
+            )]),
+        );
+
+        let records = dataflow_records(&state.running, &state.archived, &state.results);
+        let summary: Vec<_> = records
+            .iter()
+            .map(|r| (r.id.uuid, r.index, r.status))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (first, 1, DataflowStatus::Failed),
+                (other, 0, DataflowStatus::Finished),
+                (second, 0, DataflowStatus::Running),
+            ]
+        );
+        assert!(records[0].finished.is_some());
+        assert!(records[2].finished.is_none());
+        assert_eq!(records[0].node_results.len(), 1);
+        assert_eq!(
+            records[0].started,
+            Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1))
+        );
+    }
+
+    /// Opens a daemon connection to the coordinator event loop.
+    async fn connect(events: &mpsc::Sender<Event>) -> AsyncStream {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (server, _) = listener.accept().await.unwrap();
+        events
+            .send(Event::NewDaemonConnection(server))
+            .await
+            .unwrap();
+        AsyncStream::Plain(client)
+    }
+
+    async fn send_request(connection: &mut AsyncStream, request: CoordinatorRequest) {
+        let message = serde_json::to_vec(&Timestamped {
+            inner: request,
+            timestamp: HLC::default().new_timestamp(),
+        })
+        .unwrap();
+        tcp_send(connection, &message).await.unwrap();
+    }
+
+    async fn register(events: &mpsc::Sender<Event>, machine_id: &str) -> AsyncStream {
+        let mut connection = connect(events).await;
+        let request = CoordinatorRequest::Register {
+            dora_version: env!("CARGO_PKG_VERSION").to_owned(),
+            machine_id: machine_id.to_owned(),
+            listen_port: 1234,
+            protocol_version: Some(DAEMON_COORDINATOR_PROTOCOL.version()),
+        };
+        send_request(&mut connection, request).await;
+        let reply = tcp_receive(&mut connection).await.unwrap();
+        let reply: Timestamped<RegisterResult> = serde_json::from_slice(&reply).unwrap();
+        reply.inner.to_result().unwrap();
+        connection
+    }
+
+    async fn next_daemon_event(events: &mut mpsc::Receiver<LifecycleEvent>) -> String {
+        let event = tokio::time::timeout(Duration::from_secs(5), events.recv());
+        match event.await.unwrap().unwrap().kind {
+            LifecycleEventKind::DaemonConnected { machine_id } => {
+                format!("connected {machine_id}")
+            }
+            LifecycleEventKind::DaemonDisconnected { machine_id } => {
+                format!("disconnected {machine_id}")
+            }
+            other => panic!("unexpected event {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn daemon_disconnects_are_published() {
+        let (events_tx, events_rx) = mpsc::channel(10);
+        let tasks = FuturesUnordered::new();
+        let coordinator = tokio::spawn(async move {
+            start_inner(
+                ReceiverStream::new(events_rx),
+                &tasks,
+                None,
+                TransportSecurity::default(),
+            )
+            .await
+        });
+        let (lifecycle_tx, mut lifecycle_rx) = mpsc::channel(10);
+        events_tx
+            .send(Event::Control(ControlEvent::EventChannelSubscribe {
+                sender: lifecycle_tx,
+            }))
+            .await
+            .unwrap();
+        let _first = register(&events_tx, "a").await;
+        assert_eq!(next_daemon_event(&mut lifecycle_rx).await, "connected a");
+
+        // a new register of the same machine replaces the previous connection
+        let _second = register(&events_tx, "a").await;
+        assert_eq!(next_daemon_event(&mut lifecycle_rx).await, "disconnected a");
+        assert_eq!(next_daemon_event(&mut lifecycle_rx).await, "connected a");
+
+        let mut events = connect(&events_tx).await;
+        let exit = CoordinatorRequest::Event {
+            machine_id: "a".to_owned(),
+            event: dora_core::coordinator_messages::DaemonEvent::Exit,
+        };
+        send_request(&mut events, exit).await;
+        assert_eq!(next_daemon_event(&mut lifecycle_rx).await, "disconnected a");
+
+        coordinator.abort();
+    }
+
+    #[test]
+    fn reported_dataflows_are_restored() {
+        let mut state = State::default();
+        let uuid = Uuid::new_v4();
+        state.reconcile("a", vec![info(uuid)]);
+        state.reconcile("b", vec![info(uuid)]);
+
+        let dataflow = &state.running[&uuid];
+        assert_eq!(dataflow.name.as_deref(), Some("test"));
+        assert_eq!(
+            dataflow.machines,
+            BTreeSet::from(["a".to_owned(), "b".to_owned()])
+        );
+        assert!(state.archived.is_empty());
+    }
+
+    #[test]
+    fn unreported_dataflows_fail_on_restarted_machine() {
+        let mut state = State::default();
+        let uuid = Uuid::new_v4();
+        state.reconcile("a", vec![info(uuid)]);
+        state.reconcile("b", vec![info(uuid)]);
+
+        // the daemon on machine `a` was restarted and lost its dataflows
+        state.reconcile("a", Vec::new());
+        assert_eq!(
+            state.running[&uuid].machines,
+            BTreeSet::from(["b".to_owned()])
+        );
+
+        state.reconcile("b", Vec::new());
+        assert!(!state.running.contains_key(&uuid));
+        assert!(state.archived.contains_key(&uuid));
+        let result = state.result(uuid);
+        assert!(!result.is_ok());
+        for node in ["node-a", "node-b"] {
+            let error = result.node_results[&NodeId::from(node.to_owned())]
+                .as_ref()
+                .unwrap_err();
+            assert!(
+                matches!(&error.cause, NodeErrorCause::NoResult { machine_id } if node.ends_with(machine_id.as_str())),
+                "{error:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn reconciled_results_are_restored_from_journal() {
+        let dir = tempfile::tempdir().unwrap();
+        let uuid = Uuid::new_v4();
+        {
+            let (store, _) = StateStore::open(dir.path()).unwrap();
+            let mut state = State {
+                store: Some(store),
+                ..Default::default()
+            };
+            state.reconcile("a", vec![info(uuid)]);
+            state.reconcile("b", vec![info(uuid)]);
+            state.reconcile("a", Vec::new());
+        }
+
+        let (_store, stored) = StateStore::open(dir.path()).unwrap();
+        let dataflow = &stored.dataflows[&uuid];
+        assert_eq!(dataflow.machines, BTreeSet::from(["b".to_owned()]));
+        let result = &stored.results[&uuid]["a"];
+        assert!(!result.is_ok());
+        assert!(!stored.results[&uuid].contains_key("b"));
+    }
+
+    #[tokio::test]
+    async fn lost_machine_does_not_resurrect_finished_dataflows() {
+        let mut state = State::default();
+        let uuid = Uuid::new_v4();
+        state.reconcile("a", vec![info(uuid)]);
+        state.reconcile("b", vec![info(uuid)]);
+
+        // machine `a` didn't reconnect in time, so its nodes are failed
+        state.machine_lost("a").await;
+        assert_eq!(
+            state.running[&uuid].machines,
+            BTreeSet::from(["b".to_owned()])
+        );
+
+        // a late reconnect must not add the machine back to the dataflow
+        assert_eq!(state.reconcile("a", vec![info(uuid)]), vec![uuid]);
+        assert_eq!(
+            state.running[&uuid].machines,
+            BTreeSet::from(["b".to_owned()])
+        );
+
+        state.reconcile("b", Vec::new());
+        assert!(state.archived.contains_key(&uuid));
+        assert_eq!(state.reconcile("a", vec![info(uuid)]), vec![uuid]);
+        assert_eq!(state.reconcile("b", vec![info(uuid)]), vec![uuid]);
+        assert!(!state.running.contains_key(&uuid));
+    }
+
+    #[test]
+    fn reconnect_within_grace_period_keeps_machine() {
+        let start = Instant::now();
+        let mut lost = LostMachines::default();
+        lost.insert("a".into(), start);
+        lost.insert("b".into(), start);
+        assert!(lost.expired(start + MACHINE_RECONNECT_GRACE / 2).is_empty());
+
+        assert!(lost.reconnected("a"));
+        assert!(!lost.reconnected("a"));
+        assert_eq!(lost.expired(start + MACHINE_RECONNECT_GRACE), vec!["b"]);
+        assert!(lost.expired(start + MACHINE_RECONNECT_GRACE * 2).is_empty());
+
+        // a reconnected daemon keeps its dataflows
+        let mut state = State::default();
+        let uuid = Uuid::new_v4();
+        state.reconcile("a", vec![info(uuid)]);
+        state.reconcile("b", vec![info(uuid)]);
+        assert!(state.reconcile("a", vec![info(uuid)]).is_empty());
+        assert_eq!(
+            state.running[&uuid].machines,
+            BTreeSet::from(["a".to_owned(), "b".to_owned()])
+        );
+    }
+}
@@ -1,5 +1,6 @@
 use crate::{tcp_utils::tcp_receive, DaemonEvent, DataflowEvent, Event};
 use dora_core::{coordinator_messages, daemon_messages::Timestamped, message::uhlc::HLC};
+use dora_transport_security::TransportSecurity;
 use eyre::Context;
 use std::{io::ErrorKind, net::SocketAddr, sync::Arc};
 use tokio::{
@@ -18,10 +19,18 @@ pub async fn create_listener(bind: SocketAddr) -> eyre::Result<TcpListener> {
 }
 
 pub async fn handle_connection(
-    mut connection: TcpStream,
+    connection: TcpStream,
+    security: TransportSecurity,
     events_tx: mpsc::Sender<Event>,
     clock: Arc<HLC>,
 ) {
+    let mut connection = match security.accept(connection).await {
+        Ok(connection) => connection,
+        Err(err) => {
+            tracing::warn!("rejected daemon connection: {err}");
+            return;
+        }
+    };
     loop {
         // receive the next message and parse it
         let raw = match tcp_receive(&mut connection).await {
@@ -53,9 +62,11 @@ pub async fn handle_connection(
                 machine_id,
                 dora_version,
                 listen_port,
+                protocol_version,
             } => {
                 let event = DaemonEvent::Register {
                     dora_version,
+                    protocol_version,
                     machine_id,
                     connection,
                     listen_port,
@@ -91,6 +102,37 @@ pub async fn handle_connection(
                         break;
                     }
                 }
+                coordinator_messages::DaemonEvent::DataflowReady {
+                    dataflow_id,
+                    exited_before_subscribe,
+                } => {
+                    let event = Event::Dataflow {
+                        uuid: dataflow_id,
+                        event: DataflowEvent::ReadyLocally {
+                            exited_before_subscribe,
+                        },
+                    };
+                    if events_tx.send(event).await.is_err() {
+                        break;
+                    }
+                }
+                coordinator_messages::DaemonEvent::NodeExited {
+                    dataflow_id,
+                    node_id,
+                    result,
+                } => {
+                    let event = Event::Dataflow {
+                        uuid: dataflow_id,
+                        event: DataflowEvent::NodeExited {
+                            machine_id,
+                            node_id,
+                            result,
+                        },
+                    };
+                    if events_tx.send(event).await.is_err() {
+                        break;
+                    }
+                }
                 coordinator_messages::DaemonEvent::Heartbeat => {
                     let event = Event::DaemonHeartbeat { machine_id };
                     if events_tx.send(event).await.is_err() {
@@ -103,6 +145,52 @@ pub async fn handle_connection(
                         break;
                     }
                 }
+                coordinator_messages::DaemonEvent::RunningDataflows(dataflows) => {
+                    let event = Event::Daemon(DaemonEvent::RunningDataflows {
+                        machine_id,
+                        dataflows,
+                    });
+                    if events_tx.send(event).await.is_err() {
+                        break;
+                    }
+                }
+                coordinator_messages::DaemonEvent::NodeMetrics(metrics) => {
+                    let event = Event::Daemon(DaemonEvent::NodeMetrics {
+                        machine_id,
+                        metrics,
+                    });
+                    if events_tx.send(event).await.is_err() {
+                        break;
+                    }
+                }
+                coordinator_messages::DaemonEvent::TappedOutput(output) => {
+                    // the data follows as a separate binary frame
+                    let data = match tcp_receive(&mut connection).await {
+                        Ok(data) => data,
+                        Err(err) => {
+                            tracing::warn!("failed to receive tapped output data: {err}");
+                            break;
+                        }
+                    };
+                    let event = Event::TappedOutput {
+                        output,
+                        data: data.into(),
+                    };
+                    if events_tx.send(event).await.is_err() {
+                        break;
+                    }
+                }
+                coordinator_messages::DaemonEvent::NodeOutput(lines) => {
+                    let event = Event::NodeOutput(lines);
+                    if events_tx.send(event).await.is_err() {
+                        break;
+                    }
+                }
+                coordinator_messages::DaemonEvent::Exit => {
+                    let event = Event::Daemon(DaemonEvent::Exit { machine_id });
+                    let _ = events_tx.send(event).await;
+                    break;
+                }
             },
         };
     }
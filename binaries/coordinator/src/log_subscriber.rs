@@ -1,15 +1,40 @@
-use dora_core::coordinator_messages::LogMessage;
+use std::collections::BTreeSet;
+
+use dora_core::{
+    config::NodeId,
+    coordinator_messages::{LogMessage, NodeOutputLine},
+    topics::ControlRequestReply,
+};
+use dora_transport_security::AsyncStream;
 use eyre::{Context, ContextCompat};
+use tokio::sync::mpsc;
 
 use crate::tcp_utils::tcp_send;
 
+/// Connection that the log messages of a dataflow are forwarded to.
+pub enum LogConnection {
+    /// A control connection, e.g. of `dora start --attach`.
+    Tcp(AsyncStream),
+    /// A WebSocket client of the HTTP API.
+    Channel(mpsc::Sender<LogMessage>),
+}
+
+impl std::fmt::Debug for LogConnection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Tcp(_) => f.write_str("Tcp"),
+            Self::Channel(_) => f.write_str("Channel"),
+        }
+    }
+}
+
 pub struct LogSubscriber {
     pub level: log::LevelFilter,
-    connection: Option<tokio::net::TcpStream>,
+    connection: Option<LogConnection>,
 }
 
 impl LogSubscriber {
-    pub fn new(level: log::LevelFilter, connection: tokio::net::TcpStream) -> Self {
+    pub fn new(level: log::LevelFilter, connection: LogConnection) -> Self {
         Self {
             level,
             connection: Some(connection),
@@ -20,11 +45,23 @@ impl LogSubscriber {
         if message.level > self.level {
             return Ok(());
         }
-        let message = serde_json::to_vec(&message)?;
-        let connection = self.connection.as_mut().context("connection is closed")?;
-        tcp_send(connection, &message)
-            .await
-            .context("failed to send message")?;
+        match self.connection.as_mut().context("connection is closed")? {
+            LogConnection::Tcp(connection) => {
+                let message = serde_json::to_vec(&message)?;
+                tcp_send(connection, &message)
+                    .await
+                    .context("failed to send message")?;
+            }
+            // don't wait for slow WebSocket clients
+            LogConnection::Channel(sender) => match sender.try_send(message.clone()) {
+                Ok(()) => {}
+                Err(mpsc::error::TrySendError::Full(_)) => {
+                    tracing::warn!("disconnecting log subscriber that doesn't keep up");
+                    self.close();
+                }
+                Err(mpsc::error::TrySendError::Closed(_)) => self.close(),
+            },
+        }
         Ok(())
     }
 
@@ -36,3 +73,79 @@ impl LogSubscriber {
         self.connection = None;
     }
 }
+
+/// Maximum number of output batches that are queued for a log follower before it
+/// is disconnected.
+const FOLLOWER_QUEUE_SIZE: usize = 100;
+
+/// A `dora logs --follow` connection that streams the output of some nodes.
+///
+/// Each follower is served by a separate task, so that slow connections don't
+/// block the coordinator.
+pub struct LogFollower {
+    pub nodes: BTreeSet<NodeId>,
+    sender: Option<mpsc::Sender<Vec<NodeOutputLine>>>,
+}
+
+impl LogFollower {
+    /// Confirms the follow request and sends the given output history, followed by
+    /// all lines that are passed to [`send_lines`](Self::send_lines).
+    pub fn new(
+        nodes: BTreeSet<NodeId>,
+        mut connection: AsyncStream,
+        history: Vec<NodeOutputLine>,
+    ) -> Self {
+        let (tx, mut rx) = mpsc::channel(FOLLOWER_QUEUE_SIZE);
+        tokio::spawn(async move {
+            let result = async {
+                let reply = serde_json::to_vec(&ControlRequestReply::LogFollowStarted)?;
+                tcp_send(&mut connection, &reply).await?;
+                for line in &history {
+                    tcp_send(&mut connection, &serde_json::to_vec(line)?).await?;
+                }
+                while let Some(lines) = rx.recv().await {
+                    for line in &lines {
+                        tcp_send(&mut connection, &serde_json::to_vec(line)?).await?;
+                    }
+                }
+                eyre::Result::<()>::Ok(())
+            };
+            if let Err(err) = result.await {
+                tracing::debug!("log follower disconnected: {err}");
+            }
+        });
+        Self {
+            nodes,
+            sender: Some(tx),
+        }
+    }
+
+    /// Queues the lines of the followed nodes without waiting for the connection.
+    ///
+    /// Followers that don't keep up are disconnected.
+    pub fn send_lines(&mut self, lines: &[NodeOutputLine]) {
+        let Some(sender) = &self.sender else {
+            return;
+        };
+        let lines: Vec<_> = lines
+            .iter()
+            .filter(|line| self.nodes.contains(&line.node_id))
+            .cloned()
+            .collect();
+        if lines.is_empty() {
+            return;
+        }
+        match sender.try_send(lines) {
+            Ok(()) => {}
+            Err(mpsc::error::TrySendError::Full(_)) => {
+                tracing::warn!("disconnecting log follower that doesn't keep up");
+                self.sender = None;
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => self.sender = None,
+        }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.sender.as_ref().map_or(true, |s| s.is_closed())
+    }
+}
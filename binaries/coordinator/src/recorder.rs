@@ -0,0 +1,138 @@
+use std::{collections::BTreeSet, sync::Arc};
+
+use dora_core::{
+    config::{DataId, NodeId},
+    coordinator_messages::TappedOutput,
+};
+use dora_transport_security::AsyncStream;
+use tokio::sync::mpsc;
+
+use crate::tcp_utils::tcp_send;
+
+/// Maximum number of messages that are queued for a recorder before it is
+/// disconnected.
+const RECORDER_QUEUE_SIZE: usize = 1000;
+
+/// A `dora record` connection that receives the messages of some outputs.
+///
+/// Each recorder is served by a separate task, so that slow connections don't
+/// block the coordinator.
+pub struct OutputRecorder {
+    pub outputs: BTreeSet<(NodeId, DataId)>,
+    sender: Option<mpsc::Sender<(TappedOutput, Arc<[u8]>)>>,
+}
+
+impl OutputRecorder {
+    pub fn new(outputs: BTreeSet<(NodeId, DataId)>, mut connection: AsyncStream) -> Self {
+        let (tx, mut rx) = mpsc::channel::<(TappedOutput, Arc<[u8]>)>(RECORDER_QUEUE_SIZE);
+        tokio::spawn(async move {
+            let result = async {
+                while let Some((output, data)) = rx.recv().await {
+                    tcp_send(&mut connection, &serde_json::to_vec(&output)?).await?;
+                    tcp_send(&mut connection, &data).await?;
+                }
+                eyre::Result::<()>::Ok(())
+            };
+            if let Err(err) = result.await {
+                tracing::debug!("recorder disconnected: {err}");
+            }
+        });
+        Self {
+            outputs,
+            sender: Some(tx),
+        }
+    }
+
+    /// Queues the output if it is recorded, without waiting for the connection.
+    ///
+    /// Recorders that don't keep up are disconnected.
+    pub fn send_output(&mut self, output: &TappedOutput, data: &Arc<[u8]>) {
+        let Some(sender) = &self.sender else {
+            return;
+        };
+        if !self
+            .outputs
+            .contains(&(output.node_id.clone(), output.output_id.clone()))
+        {
+            return;
+        }
+        match sender.try_send((output.clone(), data.clone())) {
+            Ok(()) => {}
+            Err(mpsc::error::TrySendError::Full(_)) => {
+                tracing::warn!("disconnecting recorder that doesn't keep up");
+                self.sender = None;
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => self.sender = None,
+        }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.sender.as_ref().map_or(true, |s| s.is_closed())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tcp_utils::tcp_receive;
+    use dora_core::message::{uhlc::HLC, ArrowTypeInfo, Metadata};
+    use tokio::net::{TcpListener, TcpStream};
+    use uuid::Uuid;
+
+    async fn stream_pair() -> (AsyncStream, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (server, _) = listener.accept().await.unwrap();
+        (AsyncStream::Plain(server), client)
+    }
+
+    fn output(node_id: &str, output_id: &str) -> TappedOutput {
+        TappedOutput {
+            dataflow_id: Uuid::new_v4(),
+            node_id: NodeId::from(node_id.to_owned()),
+            output_id: DataId::from(output_id.to_owned()),
+            metadata: Metadata::new(HLC::default().new_timestamp(), ArrowTypeInfo::empty()),
+        }
+    }
+
+    #[tokio::test]
+    async fn recorded_outputs_are_followed_by_binary_data() {
+        let (server, mut client) = stream_pair().await;
+        let outputs =
+            BTreeSet::from([(NodeId::from("a".to_owned()), DataId::from("x".to_owned()))]);
+        let mut recorder = OutputRecorder::new(outputs, server);
+
+        recorder.send_output(&output("a", "y"), &Arc::from(&[0u8][..]));
+        recorder.send_output(&output("a", "x"), &Arc::from(&[1u8, 2, 3][..]));
+        recorder.send_output(&output("a", "x"), &Arc::from(&[][..]));
+
+        for expected in [&[1u8, 2, 3][..], &[]] {
+            let header = tcp_receive(&mut client).await.unwrap();
+            let header: TappedOutput = serde_json::from_slice(&header).unwrap();
+            assert_eq!(header.output_id, DataId::from("x".to_owned()));
+            assert_eq!(tcp_receive(&mut client).await.unwrap(), expected);
+        }
+        assert!(!recorder.is_closed());
+    }
+
+    #[tokio::test]
+    async fn closed_connection_closes_recorder() {
+        let (server, client) = stream_pair().await;
+        let outputs =
+            BTreeSet::from([(NodeId::from("a".to_owned()), DataId::from("x".to_owned()))]);
+        let mut recorder = OutputRecorder::new(outputs, server);
+        drop(client);
+
+        let data: Arc<[u8]> = Arc::from(vec![0; 1 << 20]);
+        for _ in 0..100 {
+            recorder.send_output(&output("a", "x"), &data);
+            if recorder.is_closed() {
+                return;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        panic!("recorder was not closed");
+    }
+}
@@ -1,14 +1,17 @@
 use crate::{
+    state::{self, JournalEntry, StateStore},
     tcp_utils::{tcp_receive, tcp_send},
-    DaemonConnection,
+    DaemonConnection, RunningDataflow,
 };
 
 use dora_core::{
+    config::{DataId, Input},
     daemon_messages::{
         DaemonCoordinatorEvent, DaemonCoordinatorReply, SpawnDataflowNodes, Timestamped,
     },
-    descriptor::{Descriptor, ResolvedNode},
+    descriptor::{runtime_node_inputs, CoreNodeKind, Descriptor, ResolvedNode},
     message::uhlc::HLC,
+    topics::DataflowChanges,
 };
 use eyre::{bail, eyre, ContextCompat, WrapErr};
 use std::{
@@ -21,6 +24,7 @@ use uuid::{NoContext, Timestamp, Uuid};
 pub(super) async fn spawn_dataflow(
     dataflow: Descriptor,
     working_dir: PathBuf,
+    name: Option<String>,
     daemon_connections: &mut HashMap<String, DaemonConnection>,
     clock: &HLC,
 ) -> eyre::Result<SpawnedDataflow> {
@@ -52,6 +56,7 @@ pub(super) async fn spawn_dataflow(
 
     let spawn_command = SpawnDataflowNodes {
         dataflow_id: uuid,
+        name,
         working_dir,
         nodes: nodes.clone(),
         machine_listen_ports,
@@ -108,3 +113,346 @@ pub struct SpawnedDataflow {
     pub machines: BTreeSet<String>,
     pub nodes: Vec<ResolvedNode>,
 }
+
+/// Applies a changed descriptor to a running dataflow.
+///
+/// The changes are validated by the coordinator and then by the daemons of all
+/// machines before they are applied, so a rejected descriptor leaves the dataflow
+/// untouched. Once the first daemon applied the changes, the new nodes are recorded
+/// even if a later daemon fails.
+#[tracing::instrument(skip_all, fields(uuid = %dataflow.uuid))]
+pub(super) async fn apply_dataflow(
+    dataflow: &mut RunningDataflow,
+    descriptor: Descriptor,
+    working_dir: PathBuf,
+    daemon_connections: &mut HashMap<String, DaemonConnection>,
+    state_store: &mut Option<StateStore>,
+    clock: &HLC,
+) -> eyre::Result<DataflowChanges> {
+    let uuid = dataflow.uuid;
+    let Some(running_descriptor) = &dataflow.descriptor else {
+        bail!("the descriptor of dataflow `{uuid}` is not known to the coordinator");
+    };
+    if !dataflow.pending_machines.is_empty() {
+        bail!("dataflow `{uuid}` is still starting");
+    }
+
+    let remote_machine_id: Vec<_> = daemon_connections
+        .iter()
+        .filter_map(|(id, c)| {
+            if !c.listen_socket.ip().is_loopback() {
+                Some(id.as_str())
+            } else {
+                None
+            }
+        })
+        .collect();
+    descriptor.check_in_daemon(&working_dir, &remote_machine_id, false)?;
+    if without_nodes(running_descriptor)? != without_nodes(&descriptor)? {
+        bail!("only the nodes of a running dataflow can be changed");
+    }
+    let nodes = descriptor.resolve_aliases_and_set_defaults()?;
+    let changes = diff_nodes(&dataflow.nodes, &nodes, &dataflow.machines)?;
+    if changes.is_empty() {
+        return Ok(changes);
+    }
+
+    let message = |dry_run| {
+        serde_json::to_vec(&Timestamped {
+            inner: DaemonCoordinatorEvent::UpdateDataflow {
+                dataflow_id: uuid,
+                working_dir: working_dir.clone(),
+                nodes: nodes.clone(),
+                dataflow_descriptor: descriptor.clone(),
+                dry_run,
+            },
+            timestamp: clock.new_timestamp(),
+        })
+    };
+
+    let check = message(true)?;
+    for machine in &dataflow.machines {
+        update_dataflow_on_machine(daemon_connections, machine, &check)
+            .await
+            .wrap_err_with(|| format!("changes were rejected by machine `{machine}`"))?;
+    }
+
+    let update = message(false)?;
+    let mut result = Ok(());
+    for (i, machine) in dataflow.machines.iter().enumerate() {
+        if let Err(err) = update_dataflow_on_machine(daemon_connections, machine, &update).await {
+            let err = err.wrap_err(format!("failed to update dataflow on machine `{machine}`"));
+            if i == 0 {
+                return Err(err);
+            }
+            tracing::error!("{err:?}");
+            result = Err(err);
+        }
+    }
+
+    dataflow.descriptor = Some(descriptor.clone());
+    dataflow.nodes = nodes.clone();
+    state::record(
+        state_store,
+        JournalEntry::NodesChanged {
+            uuid,
+            descriptor,
+            nodes,
+        },
+    );
+    result?;
+    tracing::info!("successfully applied changes to dataflow `{uuid}`");
+    Ok(changes)
+}
+
+async fn update_dataflow_on_machine(
+    daemon_connections: &mut HashMap<String, DaemonConnection>,
+    machine: &str,
+    message: &[u8],
+) -> eyre::Result<()> {
+    let daemon_connection = daemon_connections
+        .get_mut(machine)
+        .wrap_err_with(|| format!("no daemon connection for machine `{machine}`"))?;
+    tcp_send(&mut daemon_connection.stream, message)
+        .await
+        .wrap_err("failed to send update message to daemon")?;
+    let reply_raw = tcp_receive(&mut daemon_connection.stream)
+        .await
+        .wrap_err("failed to receive update reply from daemon")?;
+    match serde_json::from_slice(&reply_raw)
+        .wrap_err("failed to deserialize update reply from daemon")?
+    {
+        DaemonCoordinatorReply::UpdateResult(result) => result
+            .map_err(|e| eyre!(e))
+            .wrap_err("daemon returned an error")?,
+        other => bail!("unexpected reply after sending update: {other:?}"),
+    }
+    Ok(())
+}
+
+/// Checks that the new nodes differ from the running nodes only in ways that can be
+/// applied to a running dataflow.
+///
+/// Nodes can be added and removed. Existing nodes can only change the mappings of
+/// their inputs or remove inputs, since the inputs of a node are fixed once it is
+/// started.
+fn diff_nodes(
+    running: &[ResolvedNode],
+    nodes: &[ResolvedNode],
+    machines: &BTreeSet<String>,
+) -> eyre::Result<DataflowChanges> {
+    let running: BTreeMap<_, _> = running.iter().map(|n| (&n.id, n)).collect();
+    let mut changes = DataflowChanges::default();
+
+    for node in nodes {
+        let id = &node.id;
+        let Some(running_node) = running.get(id) else {
+            let machine = &node.deploy.machine;
+            if !machines.contains(machine) {
+                bail!(
+                    "new node `{id}` is deployed to machine `{machine}`, which \
+                    the dataflow is not running on"
+                );
+            }
+            changes.added_nodes.insert(id.clone());
+            continue;
+        };
+        if without_inputs(running_node)? != without_inputs(node)? {
+            bail!("node `{id}` was changed, but only the inputs of running nodes can be changed");
+        }
+
+        let running_inputs = node_inputs(running_node);
+        let inputs = node_inputs(node);
+        for (input_id, input) in &inputs {
+            match running_inputs.get(input_id) {
+                None => bail!("cannot add input `{input_id}` to running node `{id}`"),
+                Some(running_input) if running_input.queue_size != input.queue_size => {
+                    bail!(
+                        "cannot change the queue size of input `{input_id}` of running node `{id}`"
+                    )
+                }
+                Some(running_input) if running_input.mapping != input.mapping => {
+                    changes
+                        .changed_inputs
+                        .insert((id.clone(), input_id.clone()));
+                }
+                Some(_) => {}
+            }
+        }
+        for input_id in running_inputs.keys() {
+            if !inputs.contains_key(input_id) {
+                changes
+                    .changed_inputs
+                    .insert((id.clone(), input_id.clone()));
+            }
+        }
+    }
+
+    let ids: BTreeSet<_> = nodes.iter().map(|n| &n.id).collect();
+    changes.removed_nodes = running
+        .into_keys()
+        .filter(|id| !ids.contains(id))
+        .cloned()
+        .collect();
+
+    Ok(changes)
+}
+
+fn node_inputs(node: &ResolvedNode) -> BTreeMap<DataId, Input> {
+    match &node.kind {
+        CoreNodeKind::Custom(n) => n.run_config.inputs.clone(),
+        CoreNodeKind::Runtime(n) => runtime_node_inputs(n),
+    }
+}
+
+fn without_nodes(descriptor: &Descriptor) -> eyre::Result<serde_json::Value> {
+    let mut value = serde_json::to_value(descriptor).wrap_err("failed to serialize descriptor")?;
+    if let Some(fields) = value.as_object_mut() {
+        fields.remove("nodes");
+    }
+    Ok(value)
+}
+
+fn without_inputs(node: &ResolvedNode) -> eyre::Result<serde_json::Value> {
+    let mut node = node.clone();
+    match &mut node.kind {
+        CoreNodeKind::Custom(n) => n.run_config.inputs.clear(),
+        CoreNodeKind::Runtime(n) => {
+            for operator in &mut n.operators {
+                operator.config.inputs.clear();
+            }
+        }
+    }
+    serde_json::to_value(&node).wrap_err("failed to serialize node")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dora_core::config::NodeId;
+
+    fn resolve(yaml: &str) -> Vec<ResolvedNode> {
+        let descriptor: Descriptor = serde_yaml::from_str(yaml).unwrap();
+        descriptor.resolve_aliases_and_set_defaults().unwrap()
+    }
+
+    fn machines() -> BTreeSet<String> {
+        BTreeSet::from([String::new()])
+    }
+
+    fn node(id: &str) -> NodeId {
+        NodeId::from(id.to_owned())
+    }
+
+    const RUNNING: &str = r#"
+nodes:
+  - id: camera
+    path: camera
+    inputs:
+      tick: dora/timer/millis/100
+    outputs: [image]
+  - id: plot
+    path: plot
+    inputs:
+      image: camera/image
+      tick:
+        source: dora/timer/millis/100
+        queue_size: 2
+"#;
+
+    #[test]
+    fn nodes_are_added_removed_and_rewired() {
+        let running = resolve(RUNNING);
+        let nodes = resolve(
+            r#"
+nodes:
+  - id: camera
+    path: camera
+    inputs:
+      tick: dora/timer/millis/100
+    outputs: [image]
+  - id: filter
+    path: filter
+    inputs:
+      image: camera/image
+    outputs: [image]
+  - id: plot
+    path: plot
+    inputs:
+      image: filter/image
+"#,
+        );
+
+        let changes = diff_nodes(&running, &nodes, &machines()).unwrap();
+        assert_eq!(changes.added_nodes, BTreeSet::from([node("filter")]));
+        assert!(changes.removed_nodes.is_empty());
+        assert_eq!(
+            changes.changed_inputs,
+            BTreeSet::from([
+                (node("plot"), DataId::from("image".to_owned())),
+                (node("plot"), DataId::from("tick".to_owned())),
+            ])
+        );
+
+        let without_filter = resolve(
+            r#"
+nodes:
+  - id: camera
+    path: camera
+    inputs:
+      tick: dora/timer/millis/100
+    outputs: [image]
+  - id: plot
+    path: plot
+    inputs:
+      image: camera/image
+"#,
+        );
+        let changes = diff_nodes(&nodes, &without_filter, &machines()).unwrap();
+        assert!(changes.added_nodes.is_empty());
+        assert_eq!(changes.removed_nodes, BTreeSet::from([node("filter")]));
+        assert_eq!(
+            changes.changed_inputs,
+            BTreeSet::from([(node("plot"), DataId::from("image".to_owned()))])
+        );
+
+        assert!(diff_nodes(&running, &running, &machines())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn only_inputs_of_running_nodes_can_change() {
+        let running = resolve(RUNNING);
+        let rejected = [
+            // new input
+            RUNNING.replace(
+                "    outputs: [image]",
+                "      other: plot/x\n    outputs: [image]",
+            ),
+            // changed queue size
+            RUNNING.replace("queue_size: 2", "queue_size: 3"),
+            // changed path
+            RUNNING.replace("path: plot", "path: other"),
+            // changed outputs
+            RUNNING.replace("outputs: [image]", "outputs: [image, depth]"),
+        ];
+        for yaml in rejected {
+            let nodes = resolve(&yaml);
+            assert!(diff_nodes(&running, &nodes, &machines()).is_err(), "{yaml}");
+        }
+    }
+
+    #[test]
+    fn new_nodes_must_run_on_dataflow_machines() {
+        let running = resolve(RUNNING);
+        let nodes = resolve(&format!(
+            "{RUNNING}  - id: remote\n    path: remote\n    _unstable_deploy:\n      machine: b\n"
+        ));
+        assert!(diff_nodes(&running, &nodes, &machines()).is_err());
+
+        let machines = BTreeSet::from([String::new(), "b".to_owned()]);
+        let changes = diff_nodes(&running, &nodes, &machines).unwrap();
+        assert_eq!(changes.added_nodes, BTreeSet::from([node("remote")]));
+    }
+}
@@ -0,0 +1,418 @@
+//! Optional on-disk store for the coordinator state.
+//!
+//! The state is stored as a JSON-lines journal in `<state-dir>/journal.jsonl`. Every
+//! change to the set of known dataflows is appended as a new entry. On startup, the
+//! journal is replayed to restore the running and archived dataflows and their
+//! results. Restored dataflows are reconciled against the dataflows that are reported
+//! by re-registering daemons.
+//!
+//! The journal is compacted on startup and whenever it has grown to twice the number
+//! of entries that it had after the last compaction (but at least
+//! [`MIN_COMPACTION_ENTRIES`]), so that the total work of compacting is linear in the
+//! number of appended entries. Compacting rewrites the journal with the minimal
+//! entries that restore the current state. The descriptors of archived dataflows are
+//! dropped in the process, as they are only needed for running dataflows.
+
+use dora_core::{descriptor::Descriptor, descriptor::ResolvedNode, topics::DataflowDaemonResult};
+use eyre::Context;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
+use uuid::Uuid;
+
+const JOURNAL_FILE_NAME: &str = "journal.jsonl";
+/// The journal is not compacted before it has reached this number of entries.
+const MIN_COMPACTION_ENTRIES: usize = 100;
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub enum JournalEntry {
+    DataflowStarted {
+        uuid: Uuid,
+        name: Option<String>,
+        /// Not known for dataflows that were reported by a reconnecting daemon.
+        descriptor: Option<Descriptor>,
+        #[serde(default)]
+        dataflow_path: Option<PathBuf>,
+        nodes: Vec<ResolvedNode>,
+        machines: BTreeSet<String>,
+    },
+    /// The nodes of a running dataflow were changed through `dora apply`.
+    NodesChanged {
+        uuid: Uuid,
+        descriptor: Descriptor,
+        nodes: Vec<ResolvedNode>,
+    },
+    /// The dataflow is no longer running on the given machine.
+    ///
+    /// The result is `None` in journals of older coordinator versions if the machine
+    /// stopped reporting the dataflow without sending a result.
+    DataflowFinishedOnMachine {
+        uuid: Uuid,
+        machine_id: String,
+        result: Option<DataflowDaemonResult>,
+    },
+}
+
+/// A dataflow that was restored from the journal.
+#[derive(Clone)]
+pub struct StoredDataflow {
+    pub name: Option<String>,
+    pub descriptor: Option<Descriptor>,
+    pub dataflow_path: Option<PathBuf>,
+    pub nodes: Vec<ResolvedNode>,
+    /// Machines that the dataflow is still running on, empty for finished dataflows.
+    pub machines: BTreeSet<String>,
+}
+
+#[derive(Default, Clone)]
+pub struct StoredState {
+    pub dataflows: HashMap<Uuid, StoredDataflow>,
+    pub results: HashMap<Uuid, BTreeMap<String, DataflowDaemonResult>>,
+}
+
+pub struct StateStore {
+    path: PathBuf,
+    journal: File,
+    /// The number of entries in the journal.
+    entries: usize,
+    /// The number of entries in the journal after it was last compacted.
+    compacted_entries: usize,
+    /// The state described by the journal, used for compacting it.
+    state: StoredState,
+}
+
+impl StateStore {
+    /// Opens the journal in the given state directory, replays it, and compacts it.
+    pub fn open(state_dir: &Path) -> eyre::Result<(Self, StoredState)> {
+        std::fs::create_dir_all(state_dir)
+            .wrap_err_with(|| format!("failed to create state dir {state_dir:?}"))?;
+        let path = state_dir.join(JOURNAL_FILE_NAME);
+
+        let mut state = StoredState::default();
+        if path.exists() {
+            let file = File::open(&path)
+                .wrap_err_with(|| format!("failed to open coordinator journal {path:?}"))?;
+            for (index, line) in BufReader::new(file).lines().enumerate() {
+                let line = line.wrap_err("failed to read coordinator journal")?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                match serde_json::from_str(&line) {
+                    Ok(entry) => state.apply(entry),
+                    Err(err) => {
+                        // most likely a partially written last line after a crash
+                        tracing::warn!(
+                            "ignoring invalid entry in line {} of coordinator journal: {err}",
+                            index + 1
+                        );
+                    }
+                }
+            }
+        }
+
+        let journal = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .wrap_err_with(|| format!("failed to open coordinator journal {path:?}"))?;
+        let mut store = Self {
+            path,
+            journal,
+            entries: 0,
+            compacted_entries: 0,
+            state: state.clone(),
+        };
+        store.compact()?;
+        Ok((store, state))
+    }
+
+    pub fn append(&mut self, entry: &JournalEntry) -> eyre::Result<()> {
+        let mut line = serde_json::to_vec(entry).wrap_err("failed to serialize journal entry")?;
+        line.push(b'\n');
+        self.journal
+            .write_all(&line)
+            .wrap_err("failed to write to coordinator journal")?;
+        self.journal
+            .sync_data()
+            .wrap_err("failed to sync coordinator journal")?;
+
+        self.entries += 1;
+
+        self.state.apply(entry.clone());
+        if self.entries >= (2 * self.compacted_entries).max(MIN_COMPACTION_ENTRIES) {
+            self.compact()?;
+        }
+        Ok(())
+    }
+
+    /// Rewrites the journal with the entries of [`StoredState::snapshot`].
+    ///
+    /// The new journal is written to a temporary file first, which then replaces the
+    /// journal atomically. The state directory is synced afterwards to make the
+    /// replacement durable.
+    fn compact(&mut self) -> eyre::Result<()> {
+        let tmp_path = self.path.with_extension("jsonl.tmp");
+        let mut tmp =
+            File::create(&tmp_path).wrap_err_with(|| format!("failed to create {tmp_path:?}"))?;
+        let entries = self.state.snapshot();
+        for entry in &entries {
+            let mut line =
+                serde_json::to_vec(&entry).wrap_err("failed to serialize journal entry")?;
+            line.push(b'\n');
+            tmp.write_all(&line)
+                .wrap_err("failed to write compacted coordinator journal")?;
+        }
+        tmp.sync_all()
+            .wrap_err("failed to sync compacted coordinator journal")?;
+        std::fs::rename(&tmp_path, &self.path).wrap_err("failed to replace coordinator journal")?;
+        if let Some(state_dir) = self.path.parent() {
+            File::open(state_dir)
+                .and_then(|dir| dir.sync_all())
+                .wrap_err_with(|| format!("failed to sync state dir {state_dir:?}"))?;
+        }
+        self.journal = OpenOptions::new()
+            .append(true)
+            .open(&self.path)
+            .wrap_err_with(|| format!("failed to open coordinator journal {:?}", self.path))?;
+        self.entries = entries.len();
+        self.compacted_entries = entries.len();
+        Ok(())
+    }
+}
+
+/// Appends the given entry to the journal, if a state store is configured.
+pub fn record(store: &mut Option<StateStore>, entry: JournalEntry) {
+    if let Some(store) = store {
+        if let Err(err) = store.append(&entry) {
+            tracing::warn!("{err:?}");
+        }
+    }
+}
+
+impl StoredState {
+    /// Applies the given entry.
+    fn apply(&mut self, entry: JournalEntry) {
+        match entry {
+            JournalEntry::DataflowStarted {
+                uuid,
+                name,
+                descriptor,
+                dataflow_path,
+                nodes,
+                machines,
+            } => {
+                self.dataflows.insert(
+                    uuid,
+                    StoredDataflow {
+                        name,
+                        descriptor,
+                        dataflow_path,
+                        nodes,
+                        machines,
+                    },
+                );
+            }
+            JournalEntry::NodesChanged {
+                uuid,
+                descriptor,
+                nodes,
+            } => {
+                if let Some(dataflow) = self.dataflows.get_mut(&uuid) {
+                    dataflow.descriptor = Some(descriptor);
+                    dataflow.nodes = nodes;
+                }
+            }
+            JournalEntry::DataflowFinishedOnMachine {
+                uuid,
+                machine_id,
+                result,
+            } => {
+                if let Some(dataflow) = self.dataflows.get_mut(&uuid) {
+                    dataflow.machines.remove(&machine_id);
+                }
+                if let Some(result) = result {
+                    self.results
+                        .entry(uuid)
+                        .or_default()
+                        .insert(machine_id, result);
+                }
+            }
+        }
+    }
+
+    /// Returns the minimal journal entries that restore this state when replayed.
+    ///
+    /// The descriptors of archived dataflows are left out.
+    fn snapshot(&self) -> Vec<JournalEntry> {
+        let mut uuids: Vec<_> = self.dataflows.keys().copied().collect();
+        uuids.sort();
+        let mut entries = Vec::new();
+        for uuid in uuids {
+            let dataflow = &self.dataflows[&uuid];
+            let archived = dataflow.machines.is_empty();
+            entries.push(JournalEntry::DataflowStarted {
+                uuid,
+                name: dataflow.name.clone(),
+                descriptor: dataflow.descriptor.clone().filter(|_| !archived),
+                dataflow_path: dataflow.dataflow_path.clone(),
+                nodes: dataflow.nodes.clone(),
+                machines: dataflow.machines.clone(),
+            });
+            for (machine_id, result) in self.results.get(&uuid).into_iter().flatten() {
+                entries.push(JournalEntry::DataflowFinishedOnMachine {
+                    uuid,
+                    machine_id: machine_id.clone(),
+                    result: Some(result.clone()),
+                });
+            }
+        }
+        entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dora_core::message::uhlc::HLC;
+
+    fn started(uuid: Uuid, machines: &[&str]) -> JournalEntry {
+        JournalEntry::DataflowStarted {
+            uuid,
+            name: Some("test".into()),
+            descriptor: None,
+            dataflow_path: None,
+            nodes: Vec::new(),
+            machines: machines.iter().map(|m| m.to_string()).collect(),
+        }
+    }
+
+    fn finished(uuid: Uuid, machine_id: &str) -> JournalEntry {
+        JournalEntry::DataflowFinishedOnMachine {
+            uuid,
+            machine_id: machine_id.into(),
+            result: Some(DataflowDaemonResult {
+                timestamp: HLC::default().new_timestamp(),
+                node_results: BTreeMap::new(),
+            }),
+        }
+    }
+
+    fn machines(state: &StoredState, uuid: Uuid) -> Vec<&str> {
+        state.dataflows[&uuid]
+            .machines
+            .iter()
+            .map(|m| m.as_str())
+            .collect()
+    }
+
+    fn journal_lines(dir: &Path) -> usize {
+        std::fs::read_to_string(dir.join(JOURNAL_FILE_NAME))
+            .unwrap()
+            .lines()
+            .count()
+    }
+
+    #[test]
+    fn replay_restores_running_and_archived_dataflows() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        {
+            let (mut store, state) = StateStore::open(dir.path()).unwrap();
+            assert!(state.dataflows.is_empty());
+            for entry in [
+                started(a, &["m1", "m2"]),
+                started(b, &["m1"]),
+                finished(a, "m1"),
+                finished(b, "m1"),
+            ] {
+                store.append(&entry).unwrap();
+            }
+        }
+
+        let (_store, state) = StateStore::open(dir.path()).unwrap();
+        assert_eq!(machines(&state, a), ["m2"]);
+        assert!(machines(&state, b).is_empty());
+        assert!(state.results[&a].contains_key("m1"));
+        assert!(state.results[&b].contains_key("m1"));
+    }
+
+    #[test]
+    fn journal_is_compacted_when_it_doubled_in_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let (mut store, _) = StateStore::open(dir.path()).unwrap();
+        store.append(&started(a, &["m1", "m2"])).unwrap();
+        store.append(&started(b, &["m1"])).unwrap();
+        store.append(&finished(a, "m1")).unwrap();
+        store.append(&finished(a, "m2")).unwrap();
+        // archiving a dataflow doesn't compact the journal by itself
+        assert_eq!(journal_lines(dir.path()), 4);
+
+        // machines are added one by one when daemons report the dataflow
+        for _ in 4..MIN_COMPACTION_ENTRIES - 1 {
+            store.append(&started(b, &["m1", "m2"])).unwrap();
+        }
+        assert_eq!(journal_lines(dir.path()), MIN_COMPACTION_ENTRIES - 1);
+        store.append(&started(b, &["m1", "m2"])).unwrap();
+        // one `DataflowStarted` entry per dataflow and one entry per result
+        assert_eq!(journal_lines(dir.path()), 4);
+
+        // the journal is still appended to after compacting
+        store.append(&finished(b, "m1")).unwrap();
+        drop(store);
+        let (_store, state) = StateStore::open(dir.path()).unwrap();
+        assert!(machines(&state, a).is_empty());
+        assert_eq!(machines(&state, b), ["m2"]);
+        assert_eq!(state.results[&a].len(), 2);
+        assert_eq!(state.results[&b].len(), 1);
+    }
+
+    #[test]
+    fn descriptors_of_archived_dataflows_are_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let descriptor: Descriptor = serde_yaml::from_str("nodes: []").unwrap();
+        {
+            let (mut store, _) = StateStore::open(dir.path()).unwrap();
+            for uuid in [a, b] {
+                let mut entry = started(uuid, &["m1"]);
+                if let JournalEntry::DataflowStarted { descriptor: d, .. } = &mut entry {
+                    *d = Some(descriptor.clone());
+                }
+                store.append(&entry).unwrap();
+            }
+            store.append(&finished(a, "m1")).unwrap();
+        }
+
+        // the journal is compacted on startup
+        StateStore::open(dir.path()).unwrap();
+        let (_store, state) = StateStore::open(dir.path()).unwrap();
+        assert!(state.dataflows[&a].descriptor.is_none());
+        assert_eq!(state.dataflows[&a].name.as_deref(), Some("test"));
+        assert!(state.dataflows[&b].descriptor.is_some());
+    }
+
+    #[test]
+    fn invalid_entries_are_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = Uuid::new_v4();
+        {
+            let (mut store, _) = StateStore::open(dir.path()).unwrap();
+            store.append(&started(a, &["m1"])).unwrap();
+        }
+        // partially written entry after a crash
+        let mut journal = OpenOptions::new()
+            .append(true)
+            .open(dir.path().join(JOURNAL_FILE_NAME))
+            .unwrap();
+        journal.write_all(b"{\"DataflowStarted\":{\"uu").unwrap();
+        drop(journal);
+
+        let (_store, state) = StateStore::open(dir.path()).unwrap();
+        assert_eq!(machines(&state, a), ["m1"]);
+        assert_eq!(journal_lines(dir.path()), 1);
+    }
+}
@@ -3,12 +3,26 @@ use crate::{
     DaemonCoordinatorEvent,
 };
 use dora_core::{
-    coordinator_messages::{CoordinatorRequest, RegisterResult},
+    coordinator_messages::{
+        CoordinatorRequest, DaemonEvent, RegisterResult, TappedOutput,
+        DAEMON_RECONNECT_BACKOFF_MAX, DAEMON_RECONNECT_BACKOFF_MIN,
+    },
     daemon_messages::{DaemonCoordinatorReply, Timestamped},
     message::uhlc::HLC,
+    protocol::DAEMON_COORDINATOR_PROTOCOL,
 };
+use dora_transport_security::{AsyncStream, TransportSecurity};
 use eyre::{eyre, Context};
-use std::{io::ErrorKind, net::SocketAddr};
+use std::{
+    io::ErrorKind,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+/// The connection is considered lost if the coordinator sent no heartbeat for
+/// this duration.
+const COORDINATOR_HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(20);
+
 use tokio::{
     net::TcpStream,
     sync::{mpsc, oneshot},
@@ -21,23 +35,195 @@ pub struct CoordinatorEvent {
     pub reply_tx: oneshot::Sender<Option<DaemonCoordinatorReply>>,
 }
 
+/// The connection that is used for sending events to the coordinator.
+///
+/// Events that must not get lost can be queued while the coordinator is not
+/// reachable. They are replayed in order after reconnecting.
+pub struct CoordinatorLink {
+    pub connection: Option<AsyncStream>,
+    pub last_heartbeat: Instant,
+    queued: Vec<DaemonEvent>,
+}
+
+impl CoordinatorLink {
+    pub fn new(connection: Option<AsyncStream>) -> Self {
+        Self {
+            connection,
+            last_heartbeat: Instant::now(),
+            queued: Vec::new(),
+        }
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connection.is_some()
+    }
+
+    /// Sends the given event to the coordinator.
+    ///
+    /// Returns `false` if the event could not be sent. The connection is dropped if
+    /// sending failed or if the coordinator sent no recent heartbeat.
+    pub async fn try_send(
+        &mut self,
+        event: DaemonEvent,
+        machine_id: &str,
+        clock: &HLC,
+    ) -> eyre::Result<bool> {
+        self.send_frames(event, None, machine_id, clock).await
+    }
+
+    /// Sends the given tapped output to the coordinator, followed by the message data
+    /// as a separate binary frame.
+    ///
+    /// Like [`try_send`](Self::try_send), but the data is not encoded as JSON.
+    pub async fn try_send_tapped_output(
+        &mut self,
+        output: TappedOutput,
+        data: &[u8],
+        machine_id: &str,
+        clock: &HLC,
+    ) -> eyre::Result<bool> {
+        self.send_frames(
+            DaemonEvent::TappedOutput(output),
+            Some(data),
+            machine_id,
+            clock,
+        )
+        .await
+    }
+
+    async fn send_frames(
+        &mut self,
+        event: DaemonEvent,
+        data: Option<&[u8]>,
+        machine_id: &str,
+        clock: &HLC,
+    ) -> eyre::Result<bool> {
+        let Some(connection) = &mut self.connection else {
+            return Ok(false);
+        };
+        let msg = serde_json::to_vec(&Timestamped {
+            inner: CoordinatorRequest::Event {
+                machine_id: machine_id.to_owned(),
+                event,
+            },
+            timestamp: clock.new_timestamp(),
+        })?;
+        let result = async {
+            tcp_send(connection, &msg).await?;
+            if let Some(data) = data {
+                tcp_send(connection, data).await?;
+            }
+            std::io::Result::Ok(())
+        };
+        let sent = match result.await {
+            Ok(()) => true,
+            Err(err) => {
+                tracing::warn!("failed to send message to dora-coordinator: {err}");
+                false
+            }
+        };
+        if !sent || self.last_heartbeat.elapsed() > COORDINATOR_HEARTBEAT_TIMEOUT {
+            self.connection = None;
+        }
+        Ok(sent)
+    }
+
+    /// Queues the given event until [`reconnected`](Self::reconnected) is called.
+    pub fn queue(&mut self, event: DaemonEvent) {
+        self.queued.push(event);
+    }
+
+    /// Uses the given connection and replays the queued events.
+    ///
+    /// Events that can't be sent stay queued, e.g. if the new connection is lost
+    /// again.
+    pub async fn reconnected(
+        &mut self,
+        connection: AsyncStream,
+        machine_id: &str,
+        clock: &HLC,
+    ) -> eyre::Result<()> {
+        self.connection = Some(connection);
+        self.last_heartbeat = Instant::now();
+        let mut queued = std::mem::take(&mut self.queued).into_iter();
+        for event in queued.by_ref() {
+            if !self.try_send(event.clone(), machine_id, clock).await? {
+                self.queued.push(event);
+                break;
+            }
+        }
+        self.queued.extend(queued);
+        Ok(())
+    }
+}
+
+/// Delay before the next reconnect attempt after an attempt with the given delay
+/// failed.
+fn next_backoff(backoff: Duration) -> Duration {
+    (backoff * 2).min(DAEMON_RECONNECT_BACKOFF_MAX)
+}
+
+/// Opens the connection that is used for sending events to the coordinator.
+pub async fn connect(addr: SocketAddr, security: &TransportSecurity) -> eyre::Result<AsyncStream> {
+    let stream = TcpStream::connect(addr)
+        .await
+        .wrap_err("failed to connect to dora-coordinator")?;
+    stream
+        .set_nodelay(true)
+        .wrap_err("failed to set TCP_NODELAY")?;
+    security
+        .connect(stream)
+        .await
+        .wrap_err("failed to secure connection to dora-coordinator")
+}
+
+/// Registers at the coordinator again after the connection was lost, retrying with
+/// exponential backoff until it succeeds.
+pub async fn reconnect(
+    addr: SocketAddr,
+    machine_id: String,
+    listen_port: u16,
+    security: &TransportSecurity,
+    clock: &HLC,
+) -> (
+    impl Stream<Item = Timestamped<CoordinatorEvent>>,
+    AsyncStream,
+) {
+    let mut backoff = DAEMON_RECONNECT_BACKOFF_MIN;
+    loop {
+        tokio::time::sleep(backoff).await;
+        let result = async {
+            let events = register(addr, machine_id.clone(), listen_port, security, clock).await?;
+            let connection = connect(addr, security).await?;
+            eyre::Ok((events, connection))
+        }
+        .await;
+        match result {
+            Ok(result) => break result,
+            Err(err) => {
+                backoff = next_backoff(backoff);
+                tracing::debug!(
+                    "failed to reconnect to dora-coordinator (retrying in {backoff:?}): {err:?}"
+                );
+            }
+        }
+    }
+}
+
 pub async fn register(
     addr: SocketAddr,
     machine_id: String,
     listen_port: u16,
+    security: &TransportSecurity,
     clock: &HLC,
 ) -> eyre::Result<impl Stream<Item = Timestamped<CoordinatorEvent>>> {
-    let mut stream = TcpStream::connect(addr)
-        .await
-        .wrap_err("failed to connect to dora-coordinator")?;
-    stream
-        .set_nodelay(true)
-        .wrap_err("failed to set TCP_NODELAY")?;
+    let mut stream = connect(addr, security).await?;
     let register = serde_json::to_vec(&Timestamped {
         inner: CoordinatorRequest::Register {
             dora_version: env!("CARGO_PKG_VERSION").to_owned(),
             machine_id,
             listen_port,
+            protocol_version: Some(DAEMON_COORDINATOR_PROTOCOL.version()),
         },
         timestamp: clock.new_timestamp(),
     })?;
@@ -125,3 +311,147 @@ pub async fn register(
 
     Ok(ReceiverStream::new(rx))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dora_core::coordinator_messages::RunningDataflowInfo;
+    use tokio::net::TcpListener;
+    use uuid::Uuid;
+
+    /// Returns a connected pair of plain TCP streams.
+    async fn stream_pair() -> (AsyncStream, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (server, _) = listener.accept().await.unwrap();
+        (AsyncStream::Plain(client), server)
+    }
+
+    async fn receive_event(stream: &mut TcpStream) -> DaemonEvent {
+        let raw = tcp_receive(stream).await.unwrap();
+        let request: Timestamped<CoordinatorRequest> = serde_json::from_slice(&raw).unwrap();
+        match request.inner {
+            CoordinatorRequest::Event { machine_id, event } => {
+                assert_eq!(machine_id, "machine");
+                event
+            }
+            other => panic!("unexpected request {other:?}"),
+        }
+    }
+
+    fn running(dataflow_id: Uuid) -> DaemonEvent {
+        DaemonEvent::RunningDataflows(vec![RunningDataflowInfo {
+            dataflow_id,
+            name: None,
+            nodes: Vec::new(),
+        }])
+    }
+
+    fn running_id(event: DaemonEvent) -> Uuid {
+        match event {
+            DaemonEvent::RunningDataflows(dataflows) => dataflows[0].dataflow_id,
+            other => panic!("unexpected event {other:?}"),
+        }
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_max() {
+        let mut backoff = DAEMON_RECONNECT_BACKOFF_MIN;
+        let mut delays = vec![backoff];
+        for _ in 0..6 {
+            backoff = next_backoff(backoff);
+            delays.push(backoff);
+        }
+        assert_eq!(
+            delays,
+            [1, 2, 4, 8, 16, 30, 30].map(Duration::from_secs).to_vec()
+        );
+    }
+
+    #[tokio::test]
+    async fn queued_events_are_replayed_in_order() {
+        let clock = HLC::default();
+        let mut link = CoordinatorLink::new(None);
+        let ids = [Uuid::new_v4(), Uuid::new_v4()];
+        for id in ids {
+            assert!(!link.try_send(running(id), "machine", &clock).await.unwrap());
+            link.queue(running(id));
+        }
+
+        let (connection, mut coordinator) = stream_pair().await;
+        link.reconnected(connection, "machine", &clock)
+            .await
+            .unwrap();
+        assert!(link.is_connected());
+        assert!(link.queued.is_empty());
+        let after = Uuid::new_v4();
+        assert!(link
+            .try_send(running(after), "machine", &clock)
+            .await
+            .unwrap());
+
+        for expected in [ids[0], ids[1], after] {
+            assert_eq!(running_id(receive_event(&mut coordinator).await), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn missing_heartbeat_drops_connection() {
+        let clock = HLC::default();
+        let (connection, mut coordinator) = stream_pair().await;
+        let mut link = CoordinatorLink::new(Some(connection));
+        link.last_heartbeat = Instant::now() - COORDINATOR_HEARTBEAT_TIMEOUT * 2;
+
+        // the event is still sent, but the connection is considered lost afterwards
+        let id = Uuid::new_v4();
+        assert!(link.try_send(running(id), "machine", &clock).await.unwrap());
+        assert!(!link.is_connected());
+        assert_eq!(running_id(receive_event(&mut coordinator).await), id);
+    }
+
+    #[tokio::test]
+    async fn reconnect_retries_until_registered() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let coordinator = tokio::spawn(async move {
+            let mut registrations = 0;
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let raw = tcp_receive(&mut stream).await.unwrap();
+                let request: Timestamped<CoordinatorRequest> =
+                    serde_json::from_slice(&raw).unwrap();
+                assert!(matches!(request.inner, CoordinatorRequest::Register { .. }));
+                registrations += 1;
+                // reject the first attempt, e.g. because the coordinator is still
+                // starting up
+                let result = if registrations == 1 {
+                    RegisterResult::Err("not ready".into())
+                } else {
+                    RegisterResult::Ok
+                };
+                let reply = serde_json::to_vec(&Timestamped {
+                    inner: result,
+                    timestamp: HLC::default().new_timestamp(),
+                })
+                .unwrap();
+                tcp_send(&mut stream, &reply).await.unwrap();
+                if registrations == 2 {
+                    // event connection
+                    let (events, _) = listener.accept().await.unwrap();
+                    return (registrations, stream, events);
+                }
+            }
+        });
+
+        let clock = HLC::default();
+        let security = TransportSecurity::default();
+        let start = Instant::now();
+        let (_events, _connection) = reconnect(addr, "machine".into(), 0, &security, &clock).await;
+        // one delay before the first attempt and a doubled delay before the second
+        assert!(start.elapsed() >= DAEMON_RECONNECT_BACKOFF_MIN * 3);
+        let (registrations, _, _) = coordinator.await.unwrap();
+        assert_eq!(registrations, 2);
+    }
+}
@@ -0,0 +1,572 @@
+//! Crash bundles with diagnostics of failed nodes.
+//!
+//! Enabled through the `crash_bundles` option of the dataflow's `logging` config. The
+//! bundle of a node is written to `out/<dataflow-id>/crash_<node-id>`.
+
+use crate::log;
+use dora_core::{
+    config::{LogFormat, NodeId},
+    daemon_messages::{DataflowId, NodeConfig},
+    topics::NodeExitStatus,
+};
+use eyre::{bail, Context};
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::{Instant, SystemTime},
+};
+
+/// Number of log lines that are copied into the bundle.
+const LOG_TAIL_LINES: usize = 1000;
+
+const REPORT_FILE: &str = "report.json";
+const NODE_CONFIG_FILE: &str = "node_config.yml";
+const LOG_TAIL_FILE: &str = "log_tail.txt";
+
+pub fn crash_bundle_dir(working_dir: &Path, dataflow_id: &DataflowId, node_id: &NodeId) -> PathBuf {
+    log::dataflow_log_dir(working_dir, dataflow_id).join(format!("crash_{node_id}"))
+}
+
+/// Prepares the given node command for collecting a crash bundle.
+///
+/// Enables core dumps for the node and returns the environment that it is started
+/// with. Values of variables that look like secrets are redacted.
+pub fn prepare_command(command: &mut tokio::process::Command) -> BTreeMap<String, String> {
+    #[cfg(target_os = "linux")]
+    // SAFETY: `enable_core_dumps` only performs system calls that are safe to use after `fork`
+    unsafe {
+        command.pre_exec(enable_core_dumps);
+    }
+
+    let mut environment: BTreeMap<_, _> = std::env::vars_os()
+        .map(|(key, value)| {
+            (
+                key.to_string_lossy().into_owned(),
+                value.to_string_lossy().into_owned(),
+            )
+        })
+        .collect();
+    for (key, value) in command.as_std().get_envs() {
+        let key = key.to_string_lossy().into_owned();
+        match value {
+            Some(value) => environment.insert(key, value.to_string_lossy().into_owned()),
+            None => environment.remove(&key),
+        };
+    }
+    // contained in the node config
+    environment.remove("DORA_NODE_CONFIG");
+    environment.remove("DORA_RUNTIME_CONFIG");
+    for (key, value) in &mut environment {
+        let key = key.to_ascii_uppercase();
+        if ["TOKEN", "SECRET", "PASSWORD", "KEY"]
+            .iter()
+            .any(|word| key.contains(word))
+        {
+            *value = "<redacted>".into();
+        }
+    }
+    environment
+}
+
+#[cfg(target_os = "linux")]
+fn enable_core_dumps() -> std::io::Result<()> {
+    // raise the soft limit as far as allowed; a hard limit of 0 means that no core is dumped
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    unsafe {
+        if libc::getrlimit(libc::RLIMIT_CORE, &mut limit) == 0 {
+            limit.rlim_cur = limit.rlim_max;
+            libc::setrlimit(libc::RLIMIT_CORE, &limit);
+        }
+        libc::prctl(libc::PR_SET_DUMPABLE, 1);
+    }
+    Ok(())
+}
+
+/// Information about a spawned node that is needed for writing its crash bundle.
+pub struct CrashContext {
+    pub working_dir: PathBuf,
+    pub log_format: LogFormat,
+    /// PID of the spawned process.
+    pub pid: u32,
+    /// Whether the node runs in the built-in sandbox.
+    ///
+    /// The spawned process then only supervises the node, which runs in a child
+    /// process. The resource usage of the supervisor includes the node process.
+    pub sandboxed: bool,
+    /// PID of the node process, if known.
+    pub node_pid: Option<u32>,
+    pub environment: BTreeMap<String, String>,
+    pub node_config: NodeConfig,
+    pub spawned_at: SystemTime,
+    pub spawned: Instant,
+}
+
+/// Information about an exited node process that is collected before it is reaped.
+#[derive(Debug, Default)]
+pub struct ExitInfo {
+    resource_usage: Option<ResourceUsage>,
+    core_dumped: bool,
+    command_name: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct ResourceUsage {
+    user_cpu_secs: f64,
+    system_cpu_secs: f64,
+    max_rss_bytes: u64,
+    minor_page_faults: u64,
+    major_page_faults: u64,
+    voluntary_context_switches: u64,
+    involuntary_context_switches: u64,
+}
+
+/// Waits until the node process exits, without reaping it.
+///
+/// The process needs to be reaped afterwards, e.g. through `Child::wait`.
+#[cfg(target_os = "linux")]
+pub async fn wait_for_exit(pid: u32) -> ExitInfo {
+    use std::os::fd::{FromRawFd, OwnedFd};
+    use tokio::io::{unix::AsyncFd, Interest};
+
+    // a pidfd becomes readable when the process exits; it's not available before Linux 5.3
+    let pidfd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid as libc::pid_t, 0) };
+    let pidfd = (pidfd >= 0)
+        .then(|| unsafe { OwnedFd::from_raw_fd(pidfd as i32) })
+        .and_then(|fd| AsyncFd::with_interest(fd, Interest::READABLE).ok());
+    loop {
+        match exit_info(pid) {
+            Ok(Some(info)) => return info,
+            Ok(None) => {}
+            Err(err) => {
+                tracing::warn!("failed to wait for exit of process {pid}: {err}");
+                return ExitInfo::default();
+            }
+        }
+        match &pidfd {
+            Some(pidfd) => match pidfd.readable().await {
+                Ok(mut guard) => guard.clear_ready(),
+                Err(err) => {
+                    tracing::warn!("failed to wait for exit of process {pid}: {err}");
+                    return ExitInfo::default();
+                }
+            },
+            None => tokio::time::sleep(EXIT_POLL_INTERVAL).await,
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub async fn wait_for_exit(_pid: u32) -> ExitInfo {
+    ExitInfo::default()
+}
+
+/// Interval for checking whether a node exited when pidfds are not supported.
+#[cfg(target_os = "linux")]
+const EXIT_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(50);
+
+/// Collects the exit information of the process if it has exited, without blocking.
+#[cfg(target_os = "linux")]
+fn exit_info(pid: u32) -> std::io::Result<Option<ExitInfo>> {
+    let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    loop {
+        // the `waitid` function of libc doesn't expose the `rusage` argument of the system call
+        let result = unsafe {
+            libc::syscall(
+                libc::SYS_waitid,
+                libc::P_PID,
+                pid as libc::id_t,
+                &mut info as *mut libc::siginfo_t,
+                libc::WEXITED | libc::WNOWAIT | libc::WNOHANG,
+                &mut usage as *mut libc::rusage,
+            )
+        };
+        if result == 0 {
+            break;
+        }
+        let err = std::io::Error::last_os_error();
+        if err.kind() != std::io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+    // with `WNOHANG`, the pid stays zero if the process is still running
+    if unsafe { info.si_pid() } == 0 {
+        return Ok(None);
+    }
+
+    let secs = |time: libc::timeval| time.tv_sec as f64 + time.tv_usec as f64 / 1e6;
+    Ok(Some(ExitInfo {
+        resource_usage: Some(ResourceUsage {
+            user_cpu_secs: secs(usage.ru_utime),
+            system_cpu_secs: secs(usage.ru_stime),
+            max_rss_bytes: usage.ru_maxrss as u64 * 1024,
+            minor_page_faults: usage.ru_minflt as u64,
+            major_page_faults: usage.ru_majflt as u64,
+            voluntary_context_switches: usage.ru_nvcsw as u64,
+            involuntary_context_switches: usage.ru_nivcsw as u64,
+        }),
+        core_dumped: info.si_code == libc::CLD_DUMPED,
+        command_name: std::fs::read_to_string(format!("/proc/{pid}/comm"))
+            .ok()
+            .map(|name| name.trim_end().to_owned()),
+    }))
+}
+
+#[derive(Serialize)]
+struct CrashReport<'a> {
+    dataflow_id: DataflowId,
+    node_id: &'a NodeId,
+    pid: Option<u32>,
+    sandboxed: bool,
+    exit_status: &'a NodeExitStatus,
+    spawned_at: String,
+    runtime_secs: f64,
+    resource_usage: Option<ResourceUsage>,
+    core_dump: Option<String>,
+    environment: BTreeMap<String, String>,
+}
+
+/// Writes the crash bundle of an exited node and returns its directory.
+///
+/// Must be called after the log file of the node was closed.
+pub async fn write_bundle(
+    context: CrashContext,
+    exit_info: ExitInfo,
+    exit_status: &NodeExitStatus,
+) -> eyre::Result<PathBuf> {
+    let NodeConfig {
+        dataflow_id,
+        node_id,
+        ..
+    } = &context.node_config;
+    let dir = crash_bundle_dir(&context.working_dir, dataflow_id, node_id);
+    tokio::fs::create_dir_all(&dir)
+        .await
+        .wrap_err_with(|| format!("failed to create crash bundle directory {dir:#?}"))?;
+
+    let logs = log::read_logs(
+        &context.working_dir,
+        dataflow_id,
+        node_id,
+        context.log_format,
+        Some(LOG_TAIL_LINES),
+    )
+    .await
+    .unwrap_or_else(|err| format!("failed to read log file: {err:?}\n").into_bytes());
+    tokio::fs::write(dir.join(LOG_TAIL_FILE), logs)
+        .await
+        .context("failed to write log tail")?;
+
+    let node_config =
+        serde_yaml::to_string(&context.node_config).context("failed to serialize node config")?;
+    tokio::fs::write(dir.join(NODE_CONFIG_FILE), node_config)
+        .await
+        .context("failed to write node config")?;
+
+    // the supervisor of sandboxed nodes never dumps core itself
+    let core_dumped = exit_info.core_dumped
+        || (context.sandboxed
+            && matches!(exit_status, NodeExitStatus::Signal(signal) if dumps_core(*signal)));
+    let core_dump = if core_dumped {
+        Some(collect_core_dump(&context, &exit_info, &dir).await)
+    } else {
+        None
+    };
+
+    let report = CrashReport {
+        dataflow_id: *dataflow_id,
+        node_id,
+        pid: context.node_pid,
+        sandboxed: context.sandboxed,
+        exit_status,
+        spawned_at: chrono::DateTime::<chrono::Utc>::from(context.spawned_at).to_rfc3339(),
+        runtime_secs: context.spawned.elapsed().as_secs_f64(),
+        resource_usage: exit_info.resource_usage,
+        core_dump,
+        environment: context.environment,
+    };
+    let report = serde_json::to_vec_pretty(&report).context("failed to serialize crash report")?;
+    tokio::fs::write(dir.join(REPORT_FILE), report)
+        .await
+        .context("failed to write crash report")?;
+
+    Ok(dir)
+}
+
+/// Moves the core dump of the node into the bundle directory.
+///
+/// Returns a description of the core dump location for the crash report.
+async fn collect_core_dump(context: &CrashContext, exit_info: &ExitInfo, dir: &Path) -> String {
+    let core_pattern = match tokio::fs::read_to_string("/proc/sys/kernel/core_pattern").await {
+        Ok(pattern) => pattern.trim_end().to_owned(),
+        Err(err) => return format!("core dumped, but failed to read `core_pattern`: {err}"),
+    };
+    if let Some(handler) = core_pattern.strip_prefix('|') {
+        return format!("core dumped to handler `{handler}`");
+    }
+    let uses_pid = tokio::fs::read_to_string("/proc/sys/kernel/core_uses_pid")
+        .await
+        .is_ok_and(|value| value.trim() == "1");
+
+    let found = find_core_dump(
+        &context.working_dir,
+        &core_pattern,
+        uses_pid,
+        context.node_pid,
+        exit_info.command_name.as_deref(),
+        context.spawned_at,
+    );
+    let Some(core_file) = found else {
+        return format!(
+            "core dump expected, but no file matching `core_pattern` `{core_pattern}` found"
+        );
+    };
+    let Some(file_name) = core_file.file_name() else {
+        return format!("core dumped to {}", core_file.display());
+    };
+    let target = dir.join(file_name);
+    let moved = match tokio::fs::rename(&core_file, &target).await {
+        Ok(()) => Ok(()),
+        // e.g. on a different file system
+        Err(_) => match tokio::fs::copy(&core_file, &target).await {
+            Ok(_) => tokio::fs::remove_file(&core_file).await,
+            Err(err) => Err(err),
+        },
+    };
+    match moved {
+        Ok(()) => file_name.to_string_lossy().into_owned(),
+        Err(err) => {
+            tracing::warn!("failed to move core dump {core_file:#?} into crash bundle: {err}");
+            format!("core dumped to {}", core_file.display())
+        }
+    }
+}
+
+/// Finds the newest core dump file that matches the given `core_pattern`.
+///
+/// Relative patterns are resolved against the working directory of the node.
+/// Pattern specifiers whose value is unknown match any text.
+fn find_core_dump(
+    working_dir: &Path,
+    core_pattern: &str,
+    uses_pid: bool,
+    pid: Option<u32>,
+    command_name: Option<&str>,
+    since: SystemTime,
+) -> Option<PathBuf> {
+    let mut pattern = core_pattern.to_owned();
+    if uses_pid && !pattern.contains("%p") {
+        pattern.push_str(".%p");
+    }
+    let pattern = working_dir.join(pattern);
+    let dir = pattern.parent()?;
+    if dir.to_string_lossy().contains('%') {
+        return None;
+    }
+    let parts = parse_core_pattern(&pattern.file_name()?.to_string_lossy(), pid, command_name);
+
+    std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(Result::ok)
+        .filter(|entry| matches_pattern(&parts, &entry.file_name().to_string_lossy()))
+        .filter_map(|entry| {
+            let modified = entry.metadata().ok()?.modified().ok()?;
+            (modified >= since).then(|| (modified, entry.path()))
+        })
+        .max_by_key(|(modified, _)| *modified)
+        .map(|(_, path)| path)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum PatternPart {
+    Literal(String),
+    Any,
+}
+
+fn parse_core_pattern(
+    pattern: &str,
+    pid: Option<u32>,
+    command_name: Option<&str>,
+) -> Vec<PatternPart> {
+    let mut parts = Vec::new();
+    let mut literal = String::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            literal.push(c);
+            continue;
+        }
+        match (chars.next(), pid, command_name) {
+            (Some('%'), _, _) => literal.push('%'),
+            (Some('p' | 'P' | 'i' | 'I'), Some(pid), _) => literal.push_str(&pid.to_string()),
+            (Some('e'), _, Some(name)) => literal.push_str(name),
+            _ => {
+                if !literal.is_empty() {
+                    parts.push(PatternPart::Literal(std::mem::take(&mut literal)));
+                }
+                if parts.last() != Some(&PatternPart::Any) {
+                    parts.push(PatternPart::Any);
+                }
+            }
+        }
+    }
+    if !literal.is_empty() {
+        parts.push(PatternPart::Literal(literal));
+    }
+    parts
+}
+
+/// Whether the default action of the given signal dumps core.
+fn dumps_core(signal: i32) -> bool {
+    #[cfg(target_os = "linux")]
+    {
+        [
+            libc::SIGQUIT,
+            libc::SIGILL,
+            libc::SIGTRAP,
+            libc::SIGABRT,
+            libc::SIGBUS,
+            libc::SIGFPE,
+            libc::SIGSEGV,
+            libc::SIGXCPU,
+            libc::SIGXFSZ,
+            libc::SIGSYS,
+        ]
+        .contains(&signal)
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = signal;
+        false
+    }
+}
+
+fn matches_pattern(parts: &[PatternPart], name: &str) -> bool {
+    match parts.split_first() {
+        None => name.is_empty(),
+        Some((PatternPart::Literal(literal), rest)) => name
+            .strip_prefix(literal.as_str())
+            .is_some_and(|name| matches_pattern(rest, name)),
+        Some((PatternPart::Any, rest)) => name
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain([name.len()])
+            .any(|i| matches_pattern(rest, &name[i..])),
+    }
+}
+
+/// Reads the crash bundle of the given node and formats it for `dora logs --crash`.
+pub async fn read_bundle(
+    working_dir: &Path,
+    dataflow_id: &DataflowId,
+    node_id: &NodeId,
+) -> eyre::Result<Vec<u8>> {
+    let dir = crash_bundle_dir(working_dir, dataflow_id, node_id);
+    if !dir.exists() {
+        bail!(
+            "no crash bundle found for node `{node_id}` (crash bundles are collected for \
+            failed nodes if `crash_bundles` is enabled in the `logging` config of the dataflow)"
+        );
+    }
+
+    let mut bundle = Vec::new();
+    for file in [REPORT_FILE, NODE_CONFIG_FILE, LOG_TAIL_FILE] {
+        let path = dir.join(file);
+        let content = tokio::fs::read(&path)
+            .await
+            .wrap_err_with(|| format!("failed to read {path:#?}"))?;
+        bundle.extend_from_slice(format!("==> {} <==\n", path.display()).as_bytes());
+        bundle.extend_from_slice(&content);
+        if !content.ends_with(b"\n") {
+            bundle.push(b'\n');
+        }
+        bundle.push(b'\n');
+    }
+
+    // core dumps are binary files, so only list them
+    let mut entries = tokio::fs::read_dir(&dir)
+        .await
+        .wrap_err("failed to read crash bundle directory")?;
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name();
+        if [REPORT_FILE, NODE_CONFIG_FILE, LOG_TAIL_FILE]
+            .iter()
+            .any(|file| name == *file)
+        {
+            continue;
+        }
+        let size = entry.metadata().await.map(|m| m.len()).unwrap_or_default();
+        bundle.extend_from_slice(
+            format!(
+                "==> {} <==\n(core dump, {size} bytes)\n",
+                entry.path().display()
+            )
+            .as_bytes(),
+        );
+    }
+    Ok(bundle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn match_core_pattern() {
+        let parts = parse_core_pattern("core.%e.%p.%t", Some(42), Some("node"));
+        assert_eq!(
+            parts,
+            [
+                PatternPart::Literal("core.node.42.".into()),
+                PatternPart::Any
+            ]
+        );
+        assert!(matches_pattern(&parts, "core.node.42.1700000000"));
+        assert!(!matches_pattern(&parts, "core.node.43.1700000000"));
+
+        let parts = parse_core_pattern("core", Some(42), None);
+        assert!(matches_pattern(&parts, "core"));
+        assert!(!matches_pattern(&parts, "core.42"));
+
+        let parts = parse_core_pattern("%e-%%-%p", Some(7), None);
+        assert!(matches_pattern(&parts, "my-node-%-7"));
+        assert!(!matches_pattern(&parts, "my-node-%-8"));
+
+        // the PID of sandboxed nodes might be unknown
+        let parts = parse_core_pattern("core.%p", None, None);
+        assert_eq!(
+            parts,
+            [PatternPart::Literal("core.".into()), PatternPart::Any]
+        );
+        assert!(matches_pattern(&parts, "core.1234"));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn core_dumping_signals() {
+        assert!(dumps_core(libc::SIGSEGV));
+        assert!(dumps_core(libc::SIGABRT));
+        assert!(!dumps_core(libc::SIGKILL));
+        assert!(!dumps_core(libc::SIGTERM));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn exit_info_is_collected_without_reaping() {
+        let mut child = tokio::process::Command::new("sh")
+            .args(["-c", "sleep 0.2"])
+            .spawn()
+            .unwrap();
+        let pid = child.id().unwrap();
+        assert!(exit_info(pid).unwrap().is_none());
+
+        let info = wait_for_exit(pid).await;
+        assert!(info.resource_usage.is_some());
+        assert!(!info.core_dumped);
+        assert!(child.wait().await.unwrap().success());
+    }
+}
@@ -1,24 +1,30 @@
 use crate::tcp_utils::{tcp_receive, tcp_send};
-use dora_core::daemon_messages::{InterDaemonEvent, Timestamped};
-use eyre::{Context, ContextCompat};
+use dora_core::{
+    daemon_messages::{InterDaemonEvent, Timestamped},
+    protocol::{InterDaemonHandshake, INTER_DAEMON_PROTOCOL},
+};
+use dora_transport_security::{AsyncStream, TransportSecurity};
+use eyre::{eyre, Context, ContextCompat};
 use std::{collections::BTreeMap, io::ErrorKind, net::SocketAddr};
 use tokio::net::{TcpListener, TcpStream};
 
 pub struct InterDaemonConnection {
     socket: SocketAddr,
-    connection: Option<TcpStream>,
+    security: TransportSecurity,
+    connection: Option<AsyncStream>,
 }
 
 impl InterDaemonConnection {
-    pub fn new(socket: SocketAddr) -> Self {
+    pub fn new(socket: SocketAddr, security: TransportSecurity) -> Self {
         Self {
             socket,
+            security,
             connection: None,
         }
     }
 
     #[tracing::instrument(skip(self), fields(%self.socket))]
-    async fn connect(&mut self) -> eyre::Result<&mut TcpStream> {
+    async fn connect(&mut self) -> eyre::Result<&mut AsyncStream> {
         match &mut self.connection {
             Some(c) => Ok(c),
             entry @ None => {
@@ -28,6 +34,12 @@ impl InterDaemonConnection {
                 connection
                     .set_nodelay(true)
                     .wrap_err("failed to set nodelay")?;
+                let mut connection = self
+                    .security
+                    .connect(connection)
+                    .await
+                    .wrap_err("failed to secure connection")?;
+                handshake(&mut connection).await?;
                 Ok(entry.insert(connection))
             }
         }
@@ -38,6 +50,26 @@ impl InterDaemonConnection {
     }
 }
 
+/// Exchanges and checks the protocol versions with the remote daemon.
+async fn handshake(connection: &mut AsyncStream) -> eyre::Result<()> {
+    let handshake = InterDaemonHandshake {
+        protocol_version: INTER_DAEMON_PROTOCOL.version(),
+        dora_version: env!("CARGO_PKG_VERSION").to_owned(),
+    };
+    let message =
+        bincode::serialize(&handshake).wrap_err("failed to serialize InterDaemonHandshake")?;
+    tcp_send(connection, &message)
+        .await
+        .wrap_err("failed to send handshake")?;
+    let raw = tcp_receive(connection)
+        .await
+        .wrap_err("failed to receive handshake reply")?;
+    let reply: Result<(), String> = bincode::deserialize(&raw).wrap_err(
+        "failed to deserialize handshake reply (remote daemon is probably incompatible)",
+    )?;
+    reply.map_err(|err| eyre!(err))
+}
+
 #[tracing::instrument(skip(inter_daemon_connections))]
 pub async fn send_inter_daemon_event(
     target_machines: &[String],
@@ -63,6 +95,7 @@ pub async fn send_inter_daemon_event(
 pub async fn spawn_listener_loop(
     bind: SocketAddr,
     machine_id: String,
+    security: TransportSecurity,
     events_tx: flume::Sender<Timestamped<InterDaemonEvent>>,
 ) -> eyre::Result<u16> {
     let socket = match TcpListener::bind(bind).await {
@@ -77,7 +110,7 @@ pub async fn spawn_listener_loop(
         .port();
 
     tokio::spawn(async move {
-        listener_loop(socket, events_tx).await;
+        listener_loop(socket, security, events_tx).await;
         tracing::debug!("inter-daemon listener loop finished for machine `{machine_id}`");
     });
 
@@ -86,6 +119,7 @@ pub async fn spawn_listener_loop(
 
 async fn listener_loop(
     listener: TcpListener,
+    security: TransportSecurity,
     events_tx: flume::Sender<Timestamped<InterDaemonEvent>>,
 ) {
     loop {
@@ -98,19 +132,36 @@ async fn listener_loop(
                 tracing::info!("{err}");
             }
             Ok((connection, _)) => {
-                tokio::spawn(handle_connection_loop(connection, events_tx.clone()));
+                tokio::spawn(handle_connection_loop(
+                    connection,
+                    security.clone(),
+                    events_tx.clone(),
+                ));
             }
         }
     }
 }
 
 async fn handle_connection_loop(
-    mut connection: TcpStream,
+    connection: TcpStream,
+    security: TransportSecurity,
     events_tx: flume::Sender<Timestamped<InterDaemonEvent>>,
 ) {
     if let Err(err) = connection.set_nodelay(true) {
         tracing::warn!("failed to set nodelay for connection: {err}");
     }
+    let mut connection = match security.accept(connection).await {
+        Ok(connection) => connection,
+        Err(err) => {
+            tracing::warn!("rejected inter-daemon connection: {err}");
+            return;
+        }
+    };
+
+    if let Err(err) = receive_handshake(&mut connection).await {
+        tracing::warn!("{:?}", err.wrap_err("inter-daemon handshake failed"));
+        return;
+    }
 
     loop {
         match receive_message(&mut connection).await {
@@ -128,8 +179,35 @@ async fn handle_connection_loop(
     }
 }
 
+async fn receive_handshake(connection: &mut AsyncStream) -> eyre::Result<()> {
+    let raw = tcp_receive(connection)
+        .await
+        .wrap_err("failed to receive handshake")?;
+    let result = match bincode::deserialize::<InterDaemonHandshake>(&raw) {
+        Ok(handshake) => INTER_DAEMON_PROTOCOL
+            .negotiate(
+                &handshake.protocol_version,
+                "daemon",
+                env!("CARGO_PKG_VERSION"),
+                "remote daemon",
+                &handshake.dora_version,
+            )
+            .map(|version| tracing::debug!("remote daemon uses protocol version {version}")),
+        Err(err) => Err(format!(
+            "failed to deserialize InterDaemonHandshake (remote daemon is probably \
+            incompatible with daemon v{}): {err}",
+            env!("CARGO_PKG_VERSION")
+        )),
+    };
+    let reply = bincode::serialize(&result).wrap_err("failed to serialize handshake reply")?;
+    tcp_send(connection, &reply)
+        .await
+        .wrap_err("failed to send handshake reply")?;
+    result.map_err(|err| eyre!(err))
+}
+
 async fn receive_message(
-    connection: &mut TcpStream,
+    connection: &mut AsyncStream,
 ) -> eyre::Result<Option<Timestamped<InterDaemonEvent>>> {
     let raw = match tcp_receive(connection).await {
         Ok(raw) => raw,
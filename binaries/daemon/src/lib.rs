@@ -1,8 +1,13 @@
 use aligned_vec::{AVec, ConstAlign};
-use coordinator::CoordinatorEvent;
+use coordinator::{CoordinatorEvent, CoordinatorLink};
 use crossbeam::queue::ArrayQueue;
-use dora_core::config::{Input, OperatorId};
-use dora_core::coordinator_messages::{CoordinatorRequest, Level, LogMessage};
+use dora_core::config::{
+    Input, LogFormat, MissedTickBehavior, OperatorId, ShutdownMode, TimerConfig, TIMER_ACTUAL_TIME,
+    TIMER_SCHEDULED_TIME,
+};
+use dora_core::coordinator_messages::{
+    Level, LogMessage, NodeOutputLine, RunningDataflowInfo, TappedOutput,
+};
 use dora_core::daemon_messages::{
     DataMessage, DynamicNodeEvent, InterDaemonEvent, NodeConfig, Timestamped,
 };
@@ -23,26 +28,24 @@ use dora_core::{
     descriptor::{CoreNodeKind, Descriptor, ResolvedNode},
 };
 
+use dora_transport_security::{AsyncStream, TransportSecurity};
 use eyre::{bail, eyre, Context, ContextCompat, Result};
 use futures::{future, stream, FutureExt, TryFutureExt};
 use futures_concurrency::stream::Merge;
 use inter_daemon::InterDaemonConnection;
 use local_listener::DynamicNodeEventWrapper;
+use metrics::{MetricsSampler, NodeCounters};
 use pending::PendingNodes;
-use shared_memory_server::ShmemConf;
+use shared_memory_server::DataRegion;
 use std::sync::Arc;
 use std::time::Instant;
 use std::{
-    collections::{BTreeMap, BTreeSet, HashMap},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
     net::SocketAddr,
     path::{Path, PathBuf},
-    time::Duration,
+    time::{Duration, SystemTime},
 };
 use sysinfo::Pid;
-use tcp_utils::tcp_send;
-use tokio::fs::File;
-use tokio::io::AsyncReadExt;
-use tokio::net::TcpStream;
 use tokio::sync::mpsc::UnboundedSender;
 use tokio::sync::oneshot::Sender;
 use tokio::sync::{mpsc, oneshot};
@@ -51,11 +54,16 @@ use tracing::{error, warn};
 use uuid::{NoContext, Timestamp, Uuid};
 
 mod coordinator;
+mod crash;
 mod inter_daemon;
 mod local_listener;
 mod log;
+mod metrics;
 mod node_communication;
 mod pending;
+mod sandbox;
+#[cfg(unix)]
+mod shared_memory;
 mod spawn;
 mod tcp_utils;
 
@@ -67,6 +75,7 @@ use tracing_opentelemetry::OpenTelemetrySpanExt;
 use crate::pending::DataflowStatus;
 
 const STDERR_LOG_LINES: usize = 10;
+const OUTPUT_HISTORY_LINES: usize = 1000;
 
 pub struct Daemon {
     running: HashMap<DataflowId, RunningDataflow>,
@@ -74,8 +83,13 @@ pub struct Daemon {
 
     events_tx: mpsc::Sender<Timestamped<Event>>,
 
-    coordinator_connection: Option<TcpStream>,
-    last_coordinator_heartbeat: Instant,
+    coordinator: CoordinatorLink,
+    /// Used for reconnecting after the connection to the coordinator was lost.
+    coordinator_addr: Option<SocketAddr>,
+    inter_daemon_listen_port: u16,
+    /// TLS and token settings for the coordinator and inter-daemon connections.
+    security: TransportSecurity,
+    reconnecting_to_coordinator: bool,
     inter_daemon_connections: BTreeMap<String, InterDaemonConnection>,
     machine_id: String,
 
@@ -85,6 +99,58 @@ pub struct Daemon {
     dataflow_node_results: BTreeMap<Uuid, BTreeMap<NodeId, Result<(), NodeError>>>,
 
     clock: Arc<uhlc::HLC>,
+    metrics_sampler: MetricsSampler,
+    /// Receives the log output of the nodes when running without a coordinator.
+    local_logs: Option<LocalLogs>,
+}
+
+/// Log output of a dataflow that is run through [`Daemon::run_dataflow_with`].
+#[derive(Debug)]
+pub enum LocalLog {
+    /// A line of stdout or stderr output of a node.
+    Output(NodeOutputLine),
+    /// A message of the daemon, e.g. about the exit of a node.
+    Message(LogMessage),
+    /// The given number of logs was dropped because the receiver didn't keep up.
+    Dropped(usize),
+}
+
+/// Sends [`LocalLog`]s without blocking the daemon.
+///
+/// Logs are dropped while the channel is full. Their number is sent as
+/// [`LocalLog::Dropped`] once the receiver catches up, or when the daemon exits.
+struct LocalLogs {
+    sender: mpsc::Sender<LocalLog>,
+    dropped: usize,
+}
+
+impl LocalLogs {
+    fn new(sender: mpsc::Sender<LocalLog>) -> Self {
+        Self { sender, dropped: 0 }
+    }
+
+    fn send(&mut self, log: LocalLog) {
+        if self.dropped > 0 {
+            match self.sender.try_send(LocalLog::Dropped(self.dropped)) {
+                Ok(()) => self.dropped = 0,
+                Err(_) => {
+                    self.dropped += 1;
+                    return;
+                }
+            }
+        }
+        match self.sender.try_send(log) {
+            Ok(()) => {}
+            Err(mpsc::error::TrySendError::Full(_)) => self.dropped += 1,
+            Err(mpsc::error::TrySendError::Closed(_)) => {}
+        }
+    }
+
+    async fn finish(self) {
+        if self.dropped > 0 {
+            let _ = self.sender.send(LocalLog::Dropped(self.dropped)).await;
+        }
+    }
 }
 
 type DaemonRunResult = BTreeMap<Uuid, BTreeMap<NodeId, Result<(), NodeError>>>;
@@ -95,6 +161,7 @@ impl Daemon {
         machine_id: String,
         inter_daemon_addr: SocketAddr,
         local_listen_port: u16,
+        security: TransportSecurity,
     ) -> eyre::Result<()> {
         let clock = Arc::new(HLC::default());
 
@@ -102,28 +169,37 @@ impl Daemon {
 
         // spawn inter daemon listen loop
         let (events_tx, events_rx) = flume::bounded(10);
-        let listen_port =
-            inter_daemon::spawn_listener_loop(inter_daemon_addr, machine_id.clone(), events_tx)
-                .await?;
+        let listen_port = inter_daemon::spawn_listener_loop(
+            inter_daemon_addr,
+            machine_id.clone(),
+            security.clone(),
+            events_tx,
+        )
+        .await?;
         let daemon_events = events_rx.into_stream().map(|e| Timestamped {
             inner: Event::Daemon(e.inner),
             timestamp: e.timestamp,
         });
 
         // connect to the coordinator
-        let coordinator_events =
-            coordinator::register(coordinator_addr, machine_id.clone(), listen_port, &clock)
-                .await
-                .wrap_err("failed to connect to dora-coordinator")?
-                .map(
-                    |Timestamped {
-                         inner: event,
-                         timestamp,
-                     }| Timestamped {
-                        inner: Event::Coordinator(event),
-                        timestamp,
-                    },
-                );
+        let coordinator_events = coordinator::register(
+            coordinator_addr,
+            machine_id.clone(),
+            listen_port,
+            &security,
+            &clock,
+        )
+        .await
+        .wrap_err("failed to connect to dora-coordinator")?
+        .map(
+            |Timestamped {
+                 inner: event,
+                 timestamp,
+             }| Timestamped {
+                inner: Event::Coordinator(event),
+                timestamp,
+            },
+        );
 
         // Spawn local listener loop
         let (events_tx, events_rx) = flume::bounded(10);
@@ -146,15 +222,32 @@ impl Daemon {
             )
                 .merge(),
             Some(coordinator_addr),
+            listen_port,
+            security,
             machine_id,
             None,
             clock,
+            None,
         )
         .await
         .map(|_| ())
     }
 
     pub async fn run_dataflow(dataflow_path: &Path) -> eyre::Result<DataflowResult> {
+        Self::run_dataflow_with(dataflow_path, stream::empty(), None).await
+    }
+
+    /// Runs the given dataflow without a coordinator.
+    ///
+    /// Each received stop request stops all nodes of the dataflow and kills them if
+    /// they don't exit within the given grace duration. The log output of the nodes is
+    /// sent to `logs`, if given. Logs are dropped instead of blocking the nodes while
+    /// the channel is full, see [`LocalLog::Dropped`].
+    pub async fn run_dataflow_with(
+        dataflow_path: &Path,
+        stop_requests: impl Stream<Item = Option<Duration>> + Unpin,
+        logs: Option<mpsc::Sender<LocalLog>>,
+    ) -> eyre::Result<DataflowResult> {
         let working_dir = dataflow_path
             .canonicalize()
             .context("failed to canoncialize dataflow path")?
@@ -169,6 +262,7 @@ impl Daemon {
         let dataflow_id = Uuid::new_v7(Timestamp::now(NoContext));
         let spawn_command = SpawnDataflowNodes {
             dataflow_id,
+            name: None,
             working_dir,
             nodes,
             machine_listen_ports: BTreeMap::new(),
@@ -193,12 +287,20 @@ impl Daemon {
                 timestamp,
             }
         });
+        let stop_clock = clock.clone();
+        let stop_events = stop_requests.map(move |grace_duration| Timestamped {
+            inner: Event::Stop { grace_duration },
+            timestamp: stop_clock.new_timestamp(),
+        });
         let run_result = Self::run_general(
-            Box::pin(coordinator_events),
+            (Box::pin(coordinator_events), stop_events).merge(),
             None,
+            0,
+            TransportSecurity::default(),
             "".to_string(),
             Some(exit_when_done),
             clock.clone(),
+            logs,
         );
 
         let spawn_result = reply_rx
@@ -223,40 +325,49 @@ impl Daemon {
         })
     }
 
+    #[allow(clippy::too_many_arguments)]
     async fn run_general(
         external_events: impl Stream<Item = Timestamped<Event>> + Unpin,
         coordinator_addr: Option<SocketAddr>,
+        inter_daemon_listen_port: u16,
+        security: TransportSecurity,
         machine_id: String,
         exit_when_done: Option<BTreeSet<(Uuid, NodeId)>>,
         clock: Arc<HLC>,
+        local_logs: Option<mpsc::Sender<LocalLog>>,
     ) -> eyre::Result<DaemonRunResult> {
         let coordinator_connection = match coordinator_addr {
-            Some(addr) => {
-                let stream = TcpStream::connect(addr)
-                    .await
-                    .wrap_err("failed to connect to dora-coordinator")?;
-                stream
-                    .set_nodelay(true)
-                    .wrap_err("failed to set TCP_NODELAY")?;
-                Some(stream)
-            }
+            Some(addr) => Some(coordinator::connect(addr, &security).await?),
             None => None,
         };
 
         let (dora_events_tx, dora_events_rx) = mpsc::channel(5);
-        let daemon = Self {
+        let mut daemon = Self {
             running: HashMap::new(),
             working_dir: HashMap::new(),
             events_tx: dora_events_tx,
-            coordinator_connection,
-            last_coordinator_heartbeat: Instant::now(),
+            coordinator: CoordinatorLink::new(coordinator_connection),
+            coordinator_addr,
+            inter_daemon_listen_port,
+            security,
+            reconnecting_to_coordinator: false,
             inter_daemon_connections: BTreeMap::new(),
             machine_id,
             exit_when_done,
             dataflow_node_results: BTreeMap::new(),
             clock,
+            metrics_sampler: MetricsSampler::new(),
+            local_logs: local_logs.map(LocalLogs::new),
         };
 
+        if daemon.coordinator.is_connected() {
+            // a restarted coordinator might still consider some dataflows as running
+            // on this machine
+            daemon
+                .send_coordinator_event(DaemonEvent::RunningDataflows(Vec::new()))
+                .await?;
+        }
+
         let dora_events = ReceiverStream::new(dora_events_rx);
         let watchdog_clock = daemon.clock.clone();
         let watchdog_interval = tokio_stream::wrappers::IntervalStream::new(tokio::time::interval(
@@ -266,7 +377,21 @@ impl Daemon {
             inner: Event::HeartbeatInterval,
             timestamp: watchdog_clock.new_timestamp(),
         });
-        let events = (external_events, dora_events, watchdog_interval).merge();
+        let metrics_clock = daemon.clock.clone();
+        let metrics_interval = tokio_stream::wrappers::IntervalStream::new(tokio::time::interval(
+            metrics::METRICS_INTERVAL,
+        ))
+        .map(move |_| Timestamped {
+            inner: Event::MetricsInterval,
+            timestamp: metrics_clock.new_timestamp(),
+        });
+        let events = (
+            external_events,
+            dora_events,
+            watchdog_interval,
+            metrics_interval,
+        )
+            .merge();
         daemon.run_inner(events).await
     }
 
@@ -306,51 +431,167 @@ impl Daemon {
                 },
                 Event::DynamicNode(event) => self.handle_dynamic_node_event(event).await?,
                 Event::HeartbeatInterval => {
-                    if let Some(connection) = &mut self.coordinator_connection {
-                        let msg = serde_json::to_vec(&Timestamped {
-                            inner: CoordinatorRequest::Event {
-                                machine_id: self.machine_id.clone(),
-                                event: DaemonEvent::Heartbeat,
-                            },
-                            timestamp: self.clock.new_timestamp(),
-                        })?;
-                        tcp_send(connection, &msg)
-                            .await
-                            .wrap_err("failed to send watchdog message to dora-coordinator")?;
-
-                        if self.last_coordinator_heartbeat.elapsed() > Duration::from_secs(20) {
-                            bail!("lost connection to coordinator")
+                    for dataflow_id in self.running.keys() {
+                        if let Some(working_dir) = self.working_dir.get(dataflow_id) {
+                            if let Err(err) = log::mark_running(working_dir, dataflow_id) {
+                                tracing::debug!("failed to touch running marker: {err}");
+                            }
                         }
                     }
+                    if self.coordinator.is_connected() {
+                        self.send_coordinator_event(DaemonEvent::Heartbeat).await?;
+                    } else if self.coordinator_addr.is_some() {
+                        self.coordinator_disconnected();
+                    }
+                }
+                Event::MetricsInterval => {
+                    if self.coordinator.is_connected() && !self.running.is_empty() {
+                        let metrics = self.metrics_sampler.sample(&self.running);
+                        self.try_send_coordinator_event(DaemonEvent::NodeMetrics(metrics))
+                            .await?;
+                    }
+                }
+                Event::CoordinatorReconnected(connection) => {
+                    self.handle_coordinator_reconnect(connection).await?;
                 }
                 Event::CtrlC => {
                     for dataflow in self.running.values_mut() {
                         dataflow.stop_all(&self.clock, None).await;
                     }
                 }
+                Event::Stop { grace_duration } => {
+                    for dataflow in self.running.values_mut() {
+                        dataflow.stop_all(&self.clock, grace_duration).await;
+                    }
+                }
             }
         }
 
+        if let Err(err) = self.try_send_coordinator_event(DaemonEvent::Exit).await {
+            tracing::debug!("failed to report exit to coordinator: {err:?}");
+        }
+        if let Some(local_logs) = self.local_logs.take() {
+            local_logs.finish().await;
+        }
+
         Ok(self.dataflow_node_results)
     }
 
     async fn send_log_message(&mut self, message: LogMessage) -> eyre::Result<()> {
-        if let Some(connection) = &mut self.coordinator_connection {
-            let msg = serde_json::to_vec(&Timestamped {
-                inner: CoordinatorRequest::Event {
-                    machine_id: self.machine_id.clone(),
-                    event: DaemonEvent::Log(message),
-                },
-                timestamp: self.clock.new_timestamp(),
-            })?;
-            tcp_send(connection, &msg)
-                .await
-                .wrap_err("failed to send watchdog message to dora-coordinator")?;
+        if let Some(local_logs) = &mut self.local_logs {
+            local_logs.send(LocalLog::Message(message.clone()));
+        }
+        self.send_coordinator_event(DaemonEvent::Log(message)).await
+    }
+
+    async fn send_coordinator_event(&mut self, event: DaemonEvent) -> eyre::Result<()> {
+        self.try_send_coordinator_event(event).await.map(|_sent| ())
+    }
+
+    /// Sends the given event to the coordinator.
+    ///
+    /// Returns `false` if the event could not be sent because the connection to the
+    /// coordinator was lost.
+    async fn try_send_coordinator_event(&mut self, event: DaemonEvent) -> eyre::Result<bool> {
+        if !self.coordinator.is_connected() {
+            return Ok(false);
+        }
+        let sent = self
+            .coordinator
+            .try_send(event, &self.machine_id, &self.clock)
+            .await?;
+        if !self.coordinator.is_connected() {
+            self.coordinator_disconnected();
+        }
+        Ok(sent)
+    }
+
+    /// Sends the given event to the coordinator, or queues it until the connection to
+    /// the coordinator is restored.
+    async fn send_or_queue_coordinator_event(&mut self, event: DaemonEvent) -> eyre::Result<()> {
+        if !self.try_send_coordinator_event(event.clone()).await? && self.coordinator_addr.is_some()
+        {
+            self.coordinator.queue(event);
+        }
+        Ok(())
+    }
+
+    /// Drops the coordinator connection and starts reconnecting in the background.
+    ///
+    /// Local dataflows keep running while the coordinator is not reachable.
+    fn coordinator_disconnected(&mut self) {
+        self.coordinator.connection = None;
+        let Some(coordinator_addr) = self.coordinator_addr else {
+            return;
+        };
+        if self.reconnecting_to_coordinator {
+            return;
+        }
+        tracing::warn!(
+            "lost connection to coordinator, keeping local dataflows running and trying to reconnect"
+        );
+        self.reconnecting_to_coordinator = true;
 
-            if self.last_coordinator_heartbeat.elapsed() > Duration::from_secs(20) {
-                bail!("lost connection to coordinator")
+        let machine_id = self.machine_id.clone();
+        let listen_port = self.inter_daemon_listen_port;
+        let security = self.security.clone();
+        let clock = self.clock.clone();
+        let events_tx = self.events_tx.clone();
+        tokio::spawn(async move {
+            let (coordinator_events, connection) = coordinator::reconnect(
+                coordinator_addr,
+                machine_id,
+                listen_port,
+                &security,
+                &clock,
+            )
+            .await;
+            let event = Timestamped {
+                inner: Event::CoordinatorReconnected(connection),
+                timestamp: clock.new_timestamp(),
+            };
+            if events_tx.send(event).await.is_err() {
+                return;
             }
+            let mut coordinator_events = std::pin::pin!(coordinator_events);
+            while let Some(Timestamped { inner, timestamp }) = coordinator_events.next().await {
+                let event = Timestamped {
+                    inner: Event::Coordinator(inner),
+                    timestamp,
+                };
+                if events_tx.send(event).await.is_err() {
+                    break;
+                }
+            }
+        });
+    }
+
+    async fn handle_coordinator_reconnect(&mut self, connection: AsyncStream) -> eyre::Result<()> {
+        tracing::info!("reconnected to coordinator");
+        self.reconnecting_to_coordinator = false;
+
+        // report the results of dataflows that finished while the coordinator was
+        // unreachable first, so that they are not reconciled as lost
+        self.coordinator
+            .reconnected(connection, &self.machine_id, &self.clock)
+            .await?;
+        if !self.coordinator.is_connected() {
+            self.coordinator_disconnected();
+            return Ok(());
         }
+
+        // report running dataflows so that a restarted coordinator knows about them
+        let running = self
+            .running
+            .values()
+            .map(|dataflow| RunningDataflowInfo {
+                dataflow_id: dataflow.id,
+                name: dataflow.name.clone(),
+                nodes: dataflow.nodes.clone(),
+            })
+            .collect();
+        self.send_coordinator_event(DaemonEvent::RunningDataflows(running))
+            .await?;
         Ok(())
     }
 
@@ -362,6 +603,7 @@ impl Daemon {
         let status = match event {
             DaemonCoordinatorEvent::Spawn(SpawnDataflowNodes {
                 dataflow_id,
+                name,
                 working_dir,
                 nodes,
                 machine_listen_ports,
@@ -373,18 +615,21 @@ impl Daemon {
                 for (machine_id, socket) in machine_listen_ports {
                     match self.inter_daemon_connections.entry(machine_id) {
                         std::collections::btree_map::Entry::Vacant(entry) => {
-                            entry.insert(InterDaemonConnection::new(socket));
+                            entry.insert(InterDaemonConnection::new(socket, self.security.clone()));
                         }
                         std::collections::btree_map::Entry::Occupied(mut entry) => {
                             if entry.get().socket() != socket {
-                                entry.insert(InterDaemonConnection::new(socket));
+                                entry.insert(InterDaemonConnection::new(
+                                    socket,
+                                    self.security.clone(),
+                                ));
                             }
                         }
                     }
                 }
 
                 let result = self
-                    .spawn_dataflow(dataflow_id, working_dir, nodes, dataflow_descriptor)
+                    .spawn_dataflow(dataflow_id, name, working_dir, nodes, dataflow_descriptor)
                     .await;
                 if let Err(err) = &result {
                     tracing::error!("{err:?}");
@@ -426,31 +671,102 @@ impl Daemon {
                 });
                 RunStatus::Continue
             }
+            DaemonCoordinatorEvent::FollowLogs {
+                dataflow_id,
+                node_ids,
+                tail,
+                since,
+            } => {
+                let working_dir = self.working_dir.get(&dataflow_id).cloned().filter(|dir| {
+                    node_ids.iter().all(|node_id| {
+                        log::log_format(dir, &dataflow_id, node_id) == LogFormat::Jsonl
+                    })
+                });
+                let result = match self.running.get_mut(&dataflow_id) {
+                    Some(dataflow) => {
+                        dataflow.followed_outputs.extend(node_ids.iter().cloned());
+                        match (since, working_dir) {
+                            // the in-memory history might not reach back far enough,
+                            // so read the lines from the timestamped log files instead
+                            (Some(since), Some(working_dir)) => {
+                                let until = SystemTime::now();
+                                let clock_id = *self.clock.get_id();
+                                tokio::spawn(async move {
+                                    let result = log::read_output_history(
+                                        &working_dir,
+                                        dataflow_id,
+                                        &node_ids,
+                                        tail,
+                                        since,
+                                        until,
+                                        clock_id,
+                                    )
+                                    .await
+                                    .map_err(|err| format!("{err:?}"));
+                                    let _ = reply_tx
+                                        .send(Some(DaemonCoordinatorReply::FollowLogsResult(
+                                            result,
+                                        )))
+                                        .map_err(|_| {
+                                            error!(
+                                                "could not send follow logs reply to coordinator"
+                                            )
+                                        });
+                                });
+                                return Ok(RunStatus::Continue);
+                            }
+                            _ => Ok(dataflow.output_history(&node_ids, tail, since)),
+                        }
+                    }
+                    None => Err(format!("no running dataflow with ID `{dataflow_id}`")),
+                };
+                let _ = reply_tx
+                    .send(Some(DaemonCoordinatorReply::FollowLogsResult(result)))
+                    .map_err(|_| error!("could not send follow logs reply to coordinator"));
+                RunStatus::Continue
+            }
+            DaemonCoordinatorEvent::UnfollowLogs {
+                dataflow_id,
+                node_ids,
+            } => {
+                if let Some(dataflow) = self.running.get_mut(&dataflow_id) {
+                    dataflow
+                        .followed_outputs
+                        .retain(|node_id| !node_ids.contains(node_id));
+                }
+                let _ = reply_tx.send(None);
+                RunStatus::Continue
+            }
             DaemonCoordinatorEvent::Logs {
                 dataflow_id,
                 node_id,
+                crash,
+                tail,
             } => {
                 match self.working_dir.get(&dataflow_id) {
                     Some(working_dir) => {
                         let working_dir = working_dir.clone();
                         tokio::spawn(async move {
-                            let logs = async {
-                                let mut file =
-                                    File::open(log::log_path(&working_dir, &dataflow_id, &node_id))
-                                        .await
-                                        .wrap_err(format!(
-                                            "Could not open log file: {:#?}",
-                                            log::log_path(&working_dir, &dataflow_id, &node_id)
-                                        ))?;
-
-                                let mut contents = vec![];
-                                file.read_to_end(&mut contents)
+                            let logs = if crash {
+                                crash::read_bundle(&working_dir, &dataflow_id, &node_id)
                                     .await
-                                    .wrap_err("Could not read content of log file")?;
-                                Result::<Vec<u8>, eyre::Report>::Ok(contents)
-                            }
-                            .await
-                            .map_err(|err| format!("{err:?}"));
+                                    .map(|bundle| match tail {
+                                        Some(tail) => log::last_lines(&bundle, tail).to_vec(),
+                                        None => bundle,
+                                    })
+                            } else {
+                                let log_format =
+                                    log::log_format(&working_dir, &dataflow_id, &node_id);
+                                log::read_logs(
+                                    &working_dir,
+                                    &dataflow_id,
+                                    &node_id,
+                                    log_format,
+                                    tail,
+                                )
+                                .await
+                            };
+                            let logs = logs.map_err(|err| format!("{err:?}"));
                             let _ = reply_tx
                                 .send(Some(DaemonCoordinatorReply::Logs(logs)))
                                 .map_err(|_| {
@@ -482,6 +798,45 @@ impl Daemon {
                     .map_err(|_| error!("could not send reload reply from daemon to coordinator"));
                 RunStatus::Continue
             }
+            DaemonCoordinatorEvent::UpdateDataflow {
+                dataflow_id,
+                working_dir,
+                nodes,
+                dataflow_descriptor,
+                dry_run,
+            } => {
+                let result = self
+                    .update_dataflow(
+                        dataflow_id,
+                        working_dir,
+                        nodes,
+                        dataflow_descriptor,
+                        dry_run,
+                    )
+                    .await;
+                if let Err(err) = &result {
+                    tracing::error!("{err:?}");
+                }
+                let reply =
+                    DaemonCoordinatorReply::UpdateResult(result.map_err(|err| format!("{err:?}")));
+                let _ = reply_tx
+                    .send(Some(reply))
+                    .map_err(|_| error!("could not send update reply from daemon to coordinator"));
+                RunStatus::Continue
+            }
+            DaemonCoordinatorEvent::RestartNode {
+                dataflow_id,
+                node_id,
+                grace_duration,
+            } => {
+                let result = self.restart_node(dataflow_id, node_id, grace_duration);
+                let reply =
+                    DaemonCoordinatorReply::RestartResult(result.map_err(|err| format!("{err:?}")));
+                let _ = reply_tx
+                    .send(Some(reply))
+                    .map_err(|_| error!("could not send restart reply from daemon to coordinator"));
+                RunStatus::Continue
+            }
             DaemonCoordinatorEvent::StopDataflow {
                 dataflow_id,
                 grace_duration,
@@ -515,8 +870,53 @@ impl Daemon {
                 }
                 RunStatus::Exit
             }
+            DaemonCoordinatorEvent::TapOutputs {
+                dataflow_id,
+                outputs,
+            } => {
+                if let Some(dataflow) = self.running.get_mut(&dataflow_id) {
+                    dataflow.tapped_outputs = outputs
+                        .into_iter()
+                        .map(|(node_id, output_id)| OutputId(node_id, output_id))
+                        .collect();
+                }
+                let _ = reply_tx.send(None);
+                RunStatus::Continue
+            }
+            DaemonCoordinatorEvent::MachineLost {
+                dataflow_id,
+                machine_id,
+            } => {
+                if let Some(dataflow) = self.running.get_mut(&dataflow_id) {
+                    tracing::warn!(
+                        "machine `{machine_id}` of dataflow `{dataflow_id}` was lost, \
+                        closing all inputs from nodes on that machine"
+                    );
+                    let lost_nodes: BTreeSet<_> = dataflow
+                        .nodes
+                        .iter()
+                        .filter(|n| n.deploy.machine == machine_id)
+                        .map(|n| n.id.clone())
+                        .collect();
+                    let closed_inputs: Vec<_> = dataflow
+                        .mappings
+                        .iter()
+                        .filter(|(OutputId(source, _), _)| lost_nodes.contains(source))
+                        .flat_map(|(_, receivers)| receivers.iter().cloned())
+                        .collect();
+                    for (receiver_id, input_id) in &closed_inputs {
+                        close_input(dataflow, receiver_id, input_id, &self.clock);
+                    }
+                    // stop sending outputs to the lost machine
+                    for receivers in dataflow.open_external_mappings.values_mut() {
+                        receivers.remove(&machine_id);
+                    }
+                }
+                let _ = reply_tx.send(None);
+                RunStatus::Continue
+            }
             DaemonCoordinatorEvent::Heartbeat => {
-                self.last_coordinator_heartbeat = Instant::now();
+                self.coordinator.last_heartbeat = Instant::now();
                 let _ = reply_tx.send(None);
                 RunStatus::Continue
             }
@@ -584,14 +984,32 @@ impl Daemon {
     async fn spawn_dataflow(
         &mut self,
         dataflow_id: uuid::Uuid,
+        name: Option<String>,
         working_dir: PathBuf,
         nodes: Vec<ResolvedNode>,
         dataflow_descriptor: Descriptor,
     ) -> eyre::Result<()> {
-        let dataflow = RunningDataflow::new(dataflow_id, self.machine_id.clone());
+        if let Some(retention) = &dataflow_descriptor.logging.retention {
+            let keep = self.running.keys().copied().chain([dataflow_id]).collect();
+            if let Err(err) = log::apply_retention(&working_dir, retention, &keep) {
+                tracing::warn!("failed to apply log retention policy: {err:?}");
+            }
+        }
+
+        let mut dataflow = RunningDataflow::new(dataflow_id, self.machine_id.clone());
+        dataflow.name = name;
+        dataflow.nodes = nodes.clone();
+        dataflow.shutdown = dataflow_descriptor.shutdown;
+        #[cfg(unix)]
+        {
+            dataflow.shared_memory_dir = Some(shared_memory::create_dataflow_dir(&dataflow_id)?);
+        }
         let dataflow = match self.running.entry(dataflow_id) {
             std::collections::hash_map::Entry::Vacant(entry) => {
                 self.working_dir.insert(dataflow_id, working_dir.clone());
+                if let Err(err) = log::mark_running(&working_dir, &dataflow_id) {
+                    tracing::warn!("failed to mark log directory of dataflow as running: {err}");
+                }
                 entry.insert(dataflow)
             }
             std::collections::hash_map::Entry::Occupied(_) => {
@@ -611,31 +1029,13 @@ impl Daemon {
                         .entry(node.id.clone())
                         .or_default()
                         .insert(input_id.clone());
-                    match input.mapping {
-                        InputMapping::User(mapping) => {
-                            dataflow
-                                .mappings
-                                .entry(OutputId(mapping.source, mapping.output))
-                                .or_default()
-                                .insert((node.id.clone(), input_id));
-                        }
-                        InputMapping::Timer { interval } => {
-                            dataflow
-                                .timers
-                                .entry(interval)
-                                .or_default()
-                                .insert((node.id.clone(), input_id));
-                        }
-                    }
-                } else if let InputMapping::User(mapping) = input.mapping {
-                    dataflow
-                        .open_external_mappings
-                        .entry(OutputId(mapping.source, mapping.output))
-                        .or_default()
-                        .entry(node.deploy.machine.clone())
-                        .or_default()
-                        .insert((node.id.clone(), input_id));
                 }
+                dataflow.add_input_mapping(
+                    (node.id.clone(), input_id),
+                    input.mapping,
+                    &node.deploy.machine,
+                    local,
+                );
             }
             if local {
                 dataflow.pending_nodes.insert(node.id.clone());
@@ -654,6 +1054,8 @@ impl Daemon {
                     dataflow_descriptor.clone(),
                     self.clock.clone(),
                     node_stderr_most_recent,
+                    dataflow.shared_memory_dir.clone(),
+                    false,
                 )
                 .await
                 .wrap_err_with(|| format!("failed to spawn node `{node_id}`"))
@@ -676,7 +1078,7 @@ impl Daemon {
                             .pending_nodes
                             .handle_node_stop(
                                 &node_id,
-                                &mut self.coordinator_connection,
+                                &mut self.coordinator.connection,
                                 &self.clock,
                                 &mut dataflow.cascading_error_causes,
                             )
@@ -696,6 +1098,307 @@ impl Daemon {
         Ok(())
     }
 
+    /// Replaces the nodes of a running dataflow.
+    ///
+    /// Local nodes that were removed are stopped and new local nodes are spawned.
+    /// Inputs of the remaining nodes are rerouted to their new source. Removed inputs
+    /// are closed, while rerouted inputs stay open.
+    ///
+    /// New nodes that fail to spawn are treated like nodes that exited with an error,
+    /// so that their downstream inputs are closed.
+    async fn update_dataflow(
+        &mut self,
+        dataflow_id: DataflowId,
+        working_dir: PathBuf,
+        nodes: Vec<ResolvedNode>,
+        dataflow_descriptor: Descriptor,
+        dry_run: bool,
+    ) -> eyre::Result<()> {
+        let dataflow = self
+            .running
+            .get_mut(&dataflow_id)
+            .wrap_err_with(|| format!("no running dataflow with ID `{dataflow_id}`"))?;
+        if !dataflow.started {
+            bail!("dataflow `{dataflow_id}` is still starting");
+        }
+        if dataflow.stop_sent {
+            bail!("dataflow `{dataflow_id}` is stopping");
+        }
+        if self.working_dir.get(&dataflow_id) != Some(&working_dir) {
+            bail!("dataflow `{dataflow_id}` was started from a different working directory");
+        }
+        if dry_run {
+            let remote_machines: BTreeSet<_> = nodes
+                .iter()
+                .map(|n| n.deploy.machine.as_str())
+                .filter(|machine| *machine != self.machine_id)
+                .collect();
+            let remote_machines: Vec<_> = remote_machines.into_iter().collect();
+            return dataflow_descriptor.check_in_daemon(&working_dir, &remote_machines, false);
+        }
+
+        let running_ids: BTreeSet<_> = dataflow.nodes.iter().map(|n| n.id.clone()).collect();
+        let ids: BTreeSet<_> = nodes.iter().map(|n| n.id.clone()).collect();
+        let removed: Vec<_> = running_ids.difference(&ids).cloned().collect();
+        let added: Vec<_> = nodes
+            .iter()
+            .filter(|n| !running_ids.contains(&n.id))
+            .cloned()
+            .collect();
+
+        // stop removed nodes before the node list is replaced, which is needed for
+        // looking up their stop timeout
+        for node_id in &removed {
+            if let Some(channel) = dataflow.subscribe_channels.remove(node_id) {
+                let _ =
+                    send_with_timestamp(&channel, daemon_messages::NodeEvent::Stop, &self.clock);
+            }
+            dataflow.open_inputs.remove(node_id);
+            dataflow.schedule_kill(node_id);
+        }
+
+        let running_inputs = input_mappings(&dataflow.nodes);
+        let inputs = input_mappings(&nodes);
+        for (input, (machine, mapping)) in &running_inputs {
+            if inputs.get(input) == running_inputs.get(input) {
+                continue;
+            }
+            let local = machine == &self.machine_id;
+            dataflow.remove_input_mapping(input, mapping, machine, local);
+            if local && !inputs.contains_key(input) && ids.contains(&input.0) {
+                close_input(dataflow, &input.0, &input.1, &self.clock);
+            }
+        }
+        let running_timers: BTreeSet<_> = dataflow.timers.keys().copied().collect();
+        for (input, (machine, mapping)) in inputs {
+            if running_inputs.get(&input) == Some(&(machine.clone(), mapping.clone())) {
+                continue;
+            }
+            let local = machine == self.machine_id;
+            if local && !running_ids.contains(&input.0) {
+                dataflow
+                    .open_inputs
+                    .entry(input.0.clone())
+                    .or_default()
+                    .insert(input.1.clone());
+            }
+            dataflow.add_input_mapping(input, mapping, &machine, local);
+        }
+        let new_timers: Vec<_> = dataflow
+            .timers
+            .keys()
+            .filter(|timer| !running_timers.contains(timer))
+            .copied()
+            .collect();
+        for timer in new_timers {
+            dataflow.start_timer(timer, &self.events_tx, &self.clock);
+        }
+
+        dataflow.nodes = nodes;
+
+        let mut log_messages = Vec::new();
+        for node in added {
+            if node.deploy.machine != self.machine_id {
+                continue;
+            }
+            let node_id = node.id.clone();
+            let node_stderr_most_recent = dataflow
+                .node_stderr_most_recent
+                .entry(node.id.clone())
+                .or_insert_with(|| Arc::new(ArrayQueue::new(STDERR_LOG_LINES)))
+                .clone();
+            match spawn::spawn_node(
+                dataflow_id,
+                &working_dir,
+                node,
+                self.events_tx.clone(),
+                dataflow_descriptor.clone(),
+                self.clock.clone(),
+                node_stderr_most_recent,
+                dataflow.shared_memory_dir.clone(),
+                false,
+            )
+            .await
+            .wrap_err_with(|| format!("failed to spawn node `{node_id}`"))
+            {
+                Ok(running_node) => {
+                    dataflow.running_nodes.insert(node_id, running_node);
+                }
+                Err(err) => {
+                    log_messages.push(LogMessage {
+                        dataflow_id,
+                        node_id: Some(node_id.clone()),
+                        level: Level::Error,
+                        target: None,
+                        module_path: None,
+                        file: None,
+                        line: None,
+                        message: format!("{err:?}"),
+                    });
+                    dataflow.open_inputs.remove(&node_id);
+                    // handled through the event loop, like the exit of a running node
+                    let event = DoraEvent::SpawnedNodeResult {
+                        dataflow_id,
+                        node_id,
+                        exit_status: NodeExitStatus::IoError(format!("{err:#}")),
+                        crash_bundle: None,
+                    };
+                    let event = Timestamped {
+                        inner: event.into(),
+                        timestamp: self.clock.new_timestamp(),
+                    };
+                    let events_tx = self.events_tx.clone();
+                    tokio::spawn(async move {
+                        let _ = events_tx.send(event).await;
+                    });
+                }
+            }
+        }
+
+        for log_message in log_messages {
+            self.send_log_message(log_message).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Stops the given node so that it is spawned again once it exited.
+    ///
+    /// The outputs of the node stay open and inputs for the node are buffered until
+    /// the new instance subscribes.
+    fn restart_node(
+        &mut self,
+        dataflow_id: DataflowId,
+        node_id: NodeId,
+        grace_duration: Option<Duration>,
+    ) -> eyre::Result<()> {
+        let dataflow = self
+            .running
+            .get_mut(&dataflow_id)
+            .wrap_err_with(|| format!("no running dataflow with ID `{dataflow_id}`"))?;
+        if !dataflow.started {
+            bail!("dataflow `{dataflow_id}` is still starting");
+        }
+        if dataflow.stop_sent {
+            bail!("dataflow `{dataflow_id}` is stopping");
+        }
+        let Some(running_node) = dataflow.running_nodes.get(&node_id) else {
+            bail!("node `{node_id}` is not running");
+        };
+        if running_node.node_config.dynamic {
+            bail!("dynamic node `{node_id}` cannot be restarted");
+        }
+        if dataflow.restarting_nodes.contains_key(&node_id) {
+            bail!("node `{node_id}` is already restarting");
+        }
+        let node = dataflow.nodes.iter().find(|n| n.id == node_id);
+        dataflow
+            .restarting_nodes
+            .insert(node_id.clone(), RestartingNode::new(node));
+        if let Some(channel) = dataflow.subscribe_channels.remove(&node_id) {
+            let _ = send_with_timestamp(&channel, daemon_messages::NodeEvent::Stop, &self.clock);
+        }
+        dataflow.schedule_kill_with_grace(&node_id, grace_duration);
+
+        Ok(())
+    }
+
+    /// Spawns a node again after its previous instance was stopped by
+    /// [`Self::restart_node`].
+    ///
+    /// Returns `false` if the node is not restarting, e.g. because the dataflow is
+    /// stopping or because the new instance exited before subscribing.
+    async fn respawn_node(
+        &mut self,
+        dataflow_id: DataflowId,
+        node_id: &NodeId,
+    ) -> eyre::Result<bool> {
+        let Some(dataflow) = self.running.get_mut(&dataflow_id) else {
+            return Ok(false);
+        };
+        match dataflow.restarting_nodes.get(node_id) {
+            Some(restarting) if !restarting.respawned && !dataflow.stop_sent => {}
+            Some(_) => {
+                dataflow.restarting_nodes.remove(node_id);
+                return Ok(false);
+            }
+            None => return Ok(false),
+        }
+
+        // the stopped instance will never report the drop tokens it still holds
+        let tokens: Vec<_> = dataflow
+            .pending_drop_tokens
+            .iter_mut()
+            .filter_map(|(token, info)| info.pending_nodes.remove(node_id).then_some(*token))
+            .collect();
+        for token in tokens {
+            dataflow.check_drop_token(token, &self.clock).await?;
+        }
+        dataflow.drop_channels.remove(node_id);
+        dataflow.scheduled_kills.remove(node_id);
+        dataflow.grace_duration_kills.remove(node_id);
+
+        let node = dataflow
+            .nodes
+            .iter()
+            .find(|n| &n.id == node_id)
+            .cloned()
+            .wrap_err_with(|| format!("no node `{node_id}` in dataflow `{dataflow_id}`"))?;
+        let dataflow_descriptor = dataflow
+            .running_nodes
+            .get(node_id)
+            .map(|n| n.node_config.dataflow_descriptor.clone())
+            .wrap_err_with(|| format!("node `{node_id}` is not running"))?;
+        let working_dir = self
+            .working_dir
+            .get(&dataflow_id)
+            .wrap_err_with(|| format!("no working dir for dataflow `{dataflow_id}`"))?;
+        let node_stderr_most_recent = dataflow
+            .node_stderr_most_recent
+            .entry(node_id.clone())
+            .or_insert_with(|| Arc::new(ArrayQueue::new(STDERR_LOG_LINES)))
+            .clone();
+        let result = spawn::spawn_node(
+            dataflow_id,
+            working_dir,
+            node,
+            self.events_tx.clone(),
+            dataflow_descriptor,
+            self.clock.clone(),
+            node_stderr_most_recent,
+            dataflow.shared_memory_dir.clone(),
+            true,
+        )
+        .await
+        .wrap_err_with(|| format!("failed to respawn node `{node_id}`"));
+        let (respawned, level, message) = match result {
+            Ok(running_node) => {
+                dataflow.running_nodes.insert(node_id.clone(), running_node);
+                if let Some(restarting) = dataflow.restarting_nodes.get_mut(node_id) {
+                    restarting.respawned = true;
+                }
+                (true, Level::Info, "node restarted".to_owned())
+            }
+            Err(err) => {
+                dataflow.restarting_nodes.remove(node_id);
+                (false, Level::Error, format!("{err:?}"))
+            }
+        };
+        self.send_log_message(LogMessage {
+            dataflow_id,
+            node_id: Some(node_id.clone()),
+            level,
+            target: None,
+            module_path: None,
+            file: None,
+            line: None,
+            message,
+        })
+        .await?;
+
+        Ok(respawned)
+    }
+
     async fn handle_dynamic_node_event(
         &mut self,
         event: DynamicNodeEventWrapper,
@@ -783,12 +1486,17 @@ impl Daemon {
                         tracing::debug!("node `{node_id}` is ready");
                         Self::subscribe(dataflow, node_id.clone(), event_sender, &self.clock).await;
 
+                        if dataflow.started {
+                            // node was added to the running dataflow, so there is nothing to wait for
+                            let _ = reply_sender.send(DaemonReply::Result(Ok(())));
+                            return Ok(());
+                        }
                         let status = dataflow
                             .pending_nodes
                             .handle_node_subscription(
                                 node_id.clone(),
                                 reply_sender,
-                                &mut self.coordinator_connection,
+                                &mut self.coordinator.connection,
                                 &self.clock,
                                 &mut dataflow.cascading_error_causes,
                             )
@@ -825,6 +1533,10 @@ impl Daemon {
                         .running
                         .get_mut(&dataflow_id)
                         .wrap_err_with(|| format!("failed to get downstream nodes: no running dataflow with ID `{dataflow_id}`"))?;
+                    if dataflow.restarting_nodes.contains_key(&node_id) {
+                        // the outputs stay open for the restarted node
+                        return Ok(());
+                    }
                     send_input_closed_events(
                         dataflow,
                         &mut self.inter_daemon_connections,
@@ -841,6 +1553,11 @@ impl Daemon {
             }
             DaemonNodeEvent::OutputsDone { reply_sender } => {
                 let result = match self.running.get_mut(&dataflow_id) {
+                    Some(dataflow) if dataflow.restarting_nodes.contains_key(&node_id) => {
+                        // the outputs stay open for the restarted node
+                        dataflow.drop_channels.remove(&node_id);
+                        Ok(())
+                    }
                     Some(dataflow) => {
                         Self::handle_outputs_done(dataflow, &mut self.inter_daemon_connections, &node_id, &self.clock)
                     .await
@@ -883,6 +1600,18 @@ impl Daemon {
                     }
                 }
             }
+            DaemonNodeEvent::InputsDropped { count } => {
+                let dataflow = self.running.get_mut(&dataflow_id);
+                // reports of exited nodes must not recreate their counters
+                if let Some(dataflow) = dataflow.filter(|d| d.running_nodes.contains_key(&node_id))
+                {
+                    dataflow
+                        .node_counters
+                        .entry(node_id)
+                        .or_default()
+                        .dropped_inputs += count;
+                }
+            }
             DaemonNodeEvent::EventStreamDropped { reply_sender } => {
                 let inner = async {
                     let dataflow = self
@@ -935,6 +1664,11 @@ impl Daemon {
         let dataflow = self.running.get_mut(&dataflow_id).wrap_err_with(|| {
             format!("send out failed: no running dataflow with ID `{dataflow_id}`")
         })?;
+        dataflow
+            .node_counters
+            .entry(node_id.clone())
+            .or_default()
+            .outputs += 1;
         let data_bytes = send_output_to_local_receivers(
             node_id.clone(),
             output_id.clone(),
@@ -946,6 +1680,19 @@ impl Daemon {
         .await?;
 
         let output_id = OutputId(node_id, output_id);
+        let tapped = dataflow.tapped_outputs.contains(&output_id).then(|| {
+            let output = TappedOutput {
+                dataflow_id,
+                node_id: output_id.0.clone(),
+                output_id: output_id.1.clone(),
+                metadata: metadata.clone(),
+            };
+            let data = data_bytes
+                .as_ref()
+                .map(|data| data.to_vec())
+                .unwrap_or_default();
+            (output, data)
+        });
         let remote_receivers: Vec<_> = dataflow
             .open_external_mappings
             .get(&output_id)
@@ -970,6 +1717,17 @@ impl Daemon {
             .await
             .wrap_err("failed to forward output to remote receivers")?;
         }
+        if let Some((output, data)) = tapped {
+            if self.coordinator.is_connected() {
+                self.coordinator
+                    .try_send_tapped_output(output, &data, &self.machine_id, &self.clock)
+                    .await
+                    .wrap_err("failed to forward tapped output to coordinator")?;
+                if !self.coordinator.is_connected() {
+                    self.coordinator_disconnected();
+                }
+            }
+        }
 
         Ok(())
     }
@@ -1013,10 +1771,21 @@ impl Daemon {
 
         // if a stop event was already sent for the dataflow, send it to
         // the newly connected node too
-        if dataflow.stop_sent {
+        let stopped_by_event = match &dataflow.ordered_stop_sources {
+            Some(sources) => sources.contains(&node_id),
+            None => true,
+        };
+        if dataflow.stop_sent && stopped_by_event {
             let _ = send_with_timestamp(&event_sender, daemon_messages::NodeEvent::Stop, clock);
         }
 
+        // deliver the inputs that arrived while the node was restarting
+        if let Some(restarting) = dataflow.restarting_nodes.remove(&node_id) {
+            for event in restarting.into_buffered_inputs() {
+                let _ = event_sender.send(event);
+            }
+        }
+
         dataflow.subscribe_channels.insert(node_id, event_sender);
     }
 
@@ -1047,7 +1816,7 @@ impl Daemon {
             .pending_nodes
             .handle_node_stop(
                 node_id,
-                &mut self.coordinator_connection,
+                &mut self.coordinator.connection,
                 &self.clock,
                 &mut dataflow.cascading_error_causes,
             )
@@ -1062,6 +1831,7 @@ impl Daemon {
         .await?;
 
         dataflow.running_nodes.remove(node_id);
+        dataflow.node_counters.remove(node_id);
         if dataflow
             .running_nodes
             .iter()
@@ -1080,22 +1850,20 @@ impl Daemon {
                 "Dataflow `{dataflow_id}` finished on machine `{}`",
                 self.machine_id
             );
-            if let Some(connection) = &mut self.coordinator_connection {
-                let msg = serde_json::to_vec(&Timestamped {
-                    inner: CoordinatorRequest::Event {
-                        machine_id: self.machine_id.clone(),
-                        event: DaemonEvent::AllNodesFinished {
-                            dataflow_id,
-                            result,
-                        },
-                    },
-                    timestamp: self.clock.new_timestamp(),
-                })?;
-                tcp_send(connection, &msg)
-                    .await
-                    .wrap_err("failed to report dataflow finish to dora-coordinator")?;
-            }
             self.running.remove(&dataflow_id);
+            if let Some(working_dir) = self.working_dir.get(&dataflow_id) {
+                log::unmark_running(working_dir, &dataflow_id);
+            }
+            self.send_or_queue_coordinator_event(DaemonEvent::AllNodesFinished {
+                dataflow_id,
+                result,
+            })
+            .await?;
+            #[cfg(unix)]
+            {
+                node_communication::unix_domain::remove_socket_dir(&dataflow_id);
+                shared_memory::remove_dataflow_dir(&dataflow_id);
+            }
         }
 
         for log_message in log_messages {
@@ -1109,7 +1877,7 @@ impl Daemon {
         match event {
             DoraEvent::Timer {
                 dataflow_id,
-                interval,
+                timer,
                 metadata,
             } => {
                 let Some(dataflow) = self.running.get_mut(&dataflow_id) else {
@@ -1117,7 +1885,7 @@ impl Daemon {
                     return Ok(RunStatus::Continue);
                 };
 
-                let Some(subscribers) = dataflow.timers.get(&interval) else {
+                let Some(subscribers) = dataflow.timers.get(&timer) else {
                     return Ok(RunStatus::Continue);
                 };
 
@@ -1137,7 +1905,13 @@ impl Daemon {
                         &self.clock,
                     );
                     match send_result {
-                        Ok(()) => {}
+                        Ok(()) => {
+                            dataflow
+                                .node_counters
+                                .entry(receiver_id.clone())
+                                .or_default()
+                                .inputs += 1;
+                        }
                         Err(_) => {
                             closed.push(receiver_id);
                         }
@@ -1184,7 +1958,13 @@ impl Daemon {
                         &self.clock,
                     );
                     match send_result {
-                        Ok(()) => {}
+                        Ok(()) => {
+                            dataflow
+                                .node_counters
+                                .entry(receiver_id.clone())
+                                .or_default()
+                                .inputs += 1;
+                        }
                         Err(_) => {
                             closed.push(receiver_id);
                         }
@@ -1194,11 +1974,54 @@ impl Daemon {
                     dataflow.subscribe_channels.remove(id);
                 }
             }
+            DoraEvent::NodeOutput {
+                dataflow_id,
+                node_id,
+                output,
+            } => {
+                let Some(dataflow) = self.running.get_mut(&dataflow_id) else {
+                    return Ok(RunStatus::Continue);
+                };
+                let timestamp = self.clock.new_timestamp();
+                let followed = dataflow.followed_outputs.contains(&node_id);
+                let history = dataflow.output_history.entry(node_id.clone()).or_default();
+                let mut forward = Vec::new();
+                for line in output.lines() {
+                    let line = NodeOutputLine {
+                        dataflow_id,
+                        node_id: node_id.clone(),
+                        timestamp,
+                        line: line.to_owned(),
+                    };
+                    if history.len() >= OUTPUT_HISTORY_LINES {
+                        history.pop_front();
+                    }
+                    history.push_back(line.clone());
+                    if let Some(local_logs) = &mut self.local_logs {
+                        local_logs.send(LocalLog::Output(line.clone()));
+                    }
+                    if followed {
+                        forward.push(line);
+                    }
+                }
+                if !forward.is_empty() {
+                    if let Err(err) = self
+                        .send_coordinator_event(DaemonEvent::NodeOutput(forward))
+                        .await
+                    {
+                        tracing::warn!("{:?}", err.wrap_err("failed to forward node output"));
+                    }
+                }
+            }
             DoraEvent::SpawnedNodeResult {
                 dataflow_id,
                 node_id,
                 exit_status,
+                crash_bundle,
             } => {
+                if self.respawn_node(dataflow_id, &node_id).await? {
+                    return Ok(RunStatus::Continue);
+                }
                 let node_result = match exit_status {
                     NodeExitStatus::Success => {
                         tracing::info!("node {dataflow_id}/{node_id} finished successfully");
@@ -1242,6 +2065,7 @@ impl Daemon {
                             timestamp: self.clock.new_timestamp(),
                             cause,
                             exit_status,
+                            crash_bundle,
                         })
                     }
                 };
@@ -1264,6 +2088,12 @@ impl Daemon {
                     },
                 })
                 .await?;
+                self.send_or_queue_coordinator_event(DaemonEvent::NodeExited {
+                    dataflow_id,
+                    node_id: node_id.clone(),
+                    result: node_result.clone(),
+                })
+                .await?;
 
                 self.dataflow_node_results
                     .entry(dataflow_id)
@@ -1302,6 +2132,28 @@ async fn send_output_to_local_receivers(
     let OutputId(node_id, _) = output_id;
     let mut closed = Vec::new();
     for (receiver_id, input_id) in local_receivers {
+        if let Some(restarting) = dataflow.restarting_nodes.get_mut(receiver_id) {
+            // buffer a copy, so that the shared memory can be released
+            let dropped = restarting.buffer_input(
+                input_id,
+                Timestamped {
+                    inner: daemon_messages::NodeEvent::Input {
+                        id: input_id.clone(),
+                        metadata: metadata.clone(),
+                        data: inline_data(&data)?,
+                    },
+                    timestamp,
+                },
+            );
+            if dropped {
+                dataflow
+                    .node_counters
+                    .entry(receiver_id.clone())
+                    .or_default()
+                    .dropped_inputs += 1;
+            }
+            continue;
+        }
         if let Some(channel) = dataflow.subscribe_channels.get(receiver_id) {
             let item = daemon_messages::NodeEvent::Input {
                 id: input_id.clone(),
@@ -1313,6 +2165,11 @@ async fn send_output_to_local_receivers(
                 timestamp,
             }) {
                 Ok(()) => {
+                    dataflow
+                        .node_counters
+                        .entry(receiver_id.clone())
+                        .or_default()
+                        .inputs += 1;
                     if let Some(token) = data.as_ref().and_then(|d| d.drop_token()) {
                         dataflow
                             .pending_drop_tokens
@@ -1340,14 +2197,10 @@ async fn send_output_to_local_receivers(
             shared_memory_id,
             len,
             drop_token,
-        }) => {
-            let memory = ShmemConf::new()
-                .os_id(shared_memory_id)
-                .open()
-                .wrap_err("failed to map shared memory output")?;
-            let data = Some(AVec::from_slice(1, &unsafe { memory.as_slice() }[..len]));
-            (data, Some(drop_token))
-        }
+        }) => (
+            Some(read_shared_memory(&shared_memory_id, len)?),
+            Some(drop_token),
+        ),
         Some(DataMessage::Vec(v)) => (Some(v), None),
     };
     if let Some(token) = drop_token {
@@ -1365,6 +2218,30 @@ async fn send_output_to_local_receivers(
     Ok(data_bytes)
 }
 
+/// Copies shared memory data into a `Vec`, e.g. for buffering it.
+fn inline_data(data: &Option<DataMessage>) -> eyre::Result<Option<DataMessage>> {
+    match data {
+        Some(DataMessage::SharedMemory {
+            shared_memory_id,
+            len,
+            ..
+        }) => Ok(Some(DataMessage::Vec(read_shared_memory(
+            shared_memory_id,
+            *len,
+        )?))),
+        other => Ok(other.clone()),
+    }
+}
+
+fn read_shared_memory(
+    shared_memory_id: &str,
+    len: usize,
+) -> eyre::Result<AVec<u8, ConstAlign<128>>> {
+    let memory =
+        DataRegion::open(shared_memory_id).wrap_err("failed to map shared memory output")?;
+    Ok(AVec::from_slice(1, &unsafe { memory.as_slice() }[..len]))
+}
+
 fn node_inputs(node: &ResolvedNode) -> BTreeMap<DataId, Input> {
     match &node.kind {
         CoreNodeKind::Custom(n) => n.run_config.inputs.clone(),
@@ -1372,6 +2249,21 @@ fn node_inputs(node: &ResolvedNode) -> BTreeMap<DataId, Input> {
     }
 }
 
+/// Returns the source and the machine of every input of the given nodes.
+fn input_mappings(nodes: &[ResolvedNode]) -> BTreeMap<InputId, (String, InputMapping)> {
+    nodes
+        .iter()
+        .flat_map(|node| {
+            node_inputs(node).into_iter().map(|(input_id, input)| {
+                (
+                    (node.id.clone(), input_id),
+                    (node.deploy.machine.clone(), input.mapping),
+                )
+            })
+        })
+        .collect()
+}
+
 async fn send_input_closed_events<F>(
     dataflow: &mut RunningDataflow,
     inter_daemon_connections: &mut BTreeMap<String, InterDaemonConnection>,
@@ -1444,25 +2336,214 @@ fn close_input(
                 send_with_timestamp(channel, daemon_messages::NodeEvent::AllInputsClosed, clock);
         }
     }
+
+    // during an ordered shutdown, the node should exit now that all its inputs are closed
+    if dataflow.stop_sent
+        && dataflow.ordered_stop_sources.is_some()
+        && dataflow.open_inputs(receiver_id).is_empty()
+    {
+        dataflow.schedule_kill(receiver_id);
+    }
+}
+
+/// Returns the nodes that receive a stop event in the `ordered` shutdown mode.
+///
+/// These are the nodes without any non-timer inputs and the nodes that are part
+/// of a feedback loop, since the inputs of the latter would never be closed. All
+/// other nodes exit when their upstream nodes have exited.
+fn shutdown_sources(nodes: &[ResolvedNode]) -> BTreeSet<NodeId> {
+    let upstream = upstream_nodes(nodes);
+
+    // remove nodes without remaining upstream nodes, then nodes without remaining
+    // downstream nodes; what is left are the feedback loops
+    let mut remaining: BTreeSet<&NodeId> = upstream.keys().copied().collect();
+    loop {
+        let done: Vec<_> = remaining
+            .iter()
+            .copied()
+            .filter(|node| upstream[node].iter().all(|u| !remaining.contains(u)))
+            .collect();
+        if done.is_empty() {
+            break;
+        }
+        for node in done {
+            remaining.remove(node);
+        }
+    }
+    loop {
+        let done: Vec<_> = remaining
+            .iter()
+            .copied()
+            .filter(|node| !remaining.iter().any(|other| upstream[other].contains(node)))
+            .collect();
+        if done.is_empty() {
+            break;
+        }
+        for node in done {
+            remaining.remove(node);
+        }
+    }
+
+    upstream
+        .iter()
+        .filter(|(node, sources)| sources.is_empty() || remaining.contains(*node))
+        .map(|(node, _)| (*node).clone())
+        .collect()
+}
+
+/// Returns the nodes that each node receives non-timer inputs from.
+fn upstream_nodes(nodes: &[ResolvedNode]) -> BTreeMap<&NodeId, BTreeSet<NodeId>> {
+    nodes
+        .iter()
+        .map(|node| {
+            let sources = node_inputs(node)
+                .into_values()
+                .filter_map(|input| match input.mapping {
+                    InputMapping::User(mapping) => Some(mapping.source),
+                    InputMapping::Timer(_) => None,
+                });
+            (&node.id, sources.collect())
+        })
+        .collect()
+}
+
+/// Returns the time after which each node is killed in the `ordered` shutdown mode.
+///
+/// The given shutdown sources get their own stop timeout. Every other node gets its
+/// stop timeout in addition to the latest deadline of its upstream nodes, so that it
+/// is killed even if its inputs are never closed.
+fn ordered_stop_deadlines(
+    nodes: &[ResolvedNode],
+    sources: &BTreeSet<NodeId>,
+    stop_timeout: impl Fn(&NodeId) -> Duration,
+) -> BTreeMap<NodeId, Duration> {
+    fn deadline(
+        node: &NodeId,
+        upstream: &BTreeMap<&NodeId, BTreeSet<NodeId>>,
+        sources: &BTreeSet<NodeId>,
+        stop_timeout: &dyn Fn(&NodeId) -> Duration,
+        deadlines: &mut BTreeMap<NodeId, Duration>,
+    ) -> Duration {
+        if let Some(deadline) = deadlines.get(node) {
+            return *deadline;
+        }
+        // breaks cycles, which only exist between shutdown sources
+        deadlines.insert(node.clone(), Duration::ZERO);
+        let mut result = stop_timeout(node);
+        if !sources.contains(node) {
+            let upstream_deadline = upstream
+                .get(node)
+                .into_iter()
+                .flatten()
+                .filter(|u| upstream.contains_key(u))
+                .map(|u| deadline(u, upstream, sources, stop_timeout, deadlines))
+                .max()
+                .unwrap_or_default();
+            result += upstream_deadline;
+        }
+        deadlines.insert(node.clone(), result);
+        result
+    }
+
+    let upstream = upstream_nodes(nodes);
+    let mut deadlines = BTreeMap::new();
+    for node in upstream.keys() {
+        deadline(node, &upstream, sources, &stop_timeout, &mut deadlines);
+    }
+    deadlines
 }
 
 #[derive(Debug, Clone)]
 struct RunningNode {
+    /// PID of the spawned process.
     pid: Option<u32>,
+    /// Whether the node runs in the built-in sandbox, in a child of the spawned process.
+    sandboxed: bool,
     node_config: NodeConfig,
 }
 
+impl RunningNode {
+    /// PID of the node process, for inspecting its resource usage.
+    fn node_pid(&self) -> Option<u32> {
+        match self.pid {
+            Some(pid) if self.sandboxed => sandbox::sandboxed_pid(pid),
+            pid => pid,
+        }
+    }
+}
+
+/// A node that is restarted through `dora restart`.
+struct RestartingNode {
+    /// Set once the stopped instance exited and the node was spawned again.
+    respawned: bool,
+    queue_sizes: BTreeMap<DataId, usize>,
+    /// Inputs that arrived while the node was not subscribed, up to the queue size of
+    /// each input.
+    buffered_inputs: BTreeMap<DataId, VecDeque<Timestamped<daemon_messages::NodeEvent>>>,
+}
+
+impl RestartingNode {
+    fn new(node: Option<&ResolvedNode>) -> Self {
+        let queue_sizes = node
+            .map(node_inputs)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(id, input)| (id, input.queue_size.unwrap_or(spawn::DEFAULT_QUEUE_SIZE)))
+            .collect();
+        Self {
+            respawned: false,
+            queue_sizes,
+            buffered_inputs: BTreeMap::new(),
+        }
+    }
+
+    /// Buffers the given input event, dropping the oldest buffered event of the input
+    /// if its queue is full.
+    ///
+    /// Returns whether an event was dropped.
+    fn buffer_input(
+        &mut self,
+        input_id: &DataId,
+        event: Timestamped<daemon_messages::NodeEvent>,
+    ) -> bool {
+        let queue_size = self
+            .queue_sizes
+            .get(input_id)
+            .copied()
+            .unwrap_or(spawn::DEFAULT_QUEUE_SIZE);
+        let queue = self.buffered_inputs.entry(input_id.clone()).or_default();
+        queue.push_back(event);
+        if queue.len() > queue_size {
+            queue.pop_front();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns the buffered events of all inputs, ordered by their timestamp.
+    fn into_buffered_inputs(self) -> Vec<Timestamped<daemon_messages::NodeEvent>> {
+        let mut inputs: Vec<_> = self.buffered_inputs.into_values().flatten().collect();
+        inputs.sort_by_key(|event| event.timestamp);
+        inputs
+    }
+}
+
 pub struct RunningDataflow {
     id: Uuid,
+    name: Option<String>,
+    /// All nodes of the dataflow, including nodes on other machines.
+    nodes: Vec<ResolvedNode>,
     /// Local nodes that are not started yet
     pending_nodes: PendingNodes,
 
     subscribe_channels: HashMap<NodeId, UnboundedSender<Timestamped<daemon_messages::NodeEvent>>>,
     drop_channels: HashMap<NodeId, UnboundedSender<Timestamped<daemon_messages::NodeDropEvent>>>,
     mappings: HashMap<OutputId, BTreeSet<InputId>>,
-    timers: BTreeMap<Duration, BTreeSet<InputId>>,
+    timers: BTreeMap<TimerConfig, BTreeSet<InputId>>,
     open_inputs: BTreeMap<NodeId, BTreeSet<DataId>>,
     running_nodes: BTreeMap<NodeId, RunningNode>,
+    restarting_nodes: BTreeMap<NodeId, RestartingNode>,
 
     open_external_mappings: HashMap<OutputId, BTreeMap<String, BTreeSet<InputId>>>,
 
@@ -1470,7 +2551,16 @@ pub struct RunningDataflow {
 
     /// Keep handles to all timer tasks of this dataflow to cancel them on drop.
     _timer_handles: Vec<futures::future::RemoteHandle<()>>,
+    /// Set once all nodes are ready and the timers are started.
+    started: bool,
     stop_sent: bool,
+    shutdown: ShutdownMode,
+    /// The nodes that were sent a stop event, if an ordered shutdown is in progress.
+    ordered_stop_sources: Option<BTreeSet<NodeId>>,
+    /// The grace duration of the stop request, used for nodes without a `stop_timeout`.
+    grace_duration: Option<Duration>,
+    /// Nodes that will be killed if they don't exit within their stop timeout.
+    scheduled_kills: BTreeSet<NodeId>,
 
     /// Used in `open_inputs`.
     ///
@@ -1482,12 +2572,27 @@ pub struct RunningDataflow {
     grace_duration_kills: Arc<crossbeam_skiplist::SkipSet<NodeId>>,
 
     node_stderr_most_recent: BTreeMap<NodeId, Arc<ArrayQueue<String>>>,
+
+    /// The most recent output lines of each node, used for `dora logs --follow`.
+    output_history: BTreeMap<NodeId, VecDeque<NodeOutputLine>>,
+    /// Nodes whose output is currently forwarded to the coordinator.
+    followed_outputs: BTreeSet<NodeId>,
+    /// Message counters of the running local nodes, used for reporting node metrics.
+    ///
+    /// Counts the inputs of all sources, including timers and remote nodes.
+    node_counters: BTreeMap<NodeId, NodeCounters>,
+    /// Outputs that are mirrored to the coordinator for `dora record`.
+    tapped_outputs: HashSet<OutputId>,
+    /// Directory for the shared memory regions of the local nodes.
+    shared_memory_dir: Option<PathBuf>,
 }
 
 impl RunningDataflow {
     fn new(dataflow_id: Uuid, machine_id: String) -> RunningDataflow {
         Self {
             id: dataflow_id,
+            name: None,
+            nodes: Vec::new(),
             pending_nodes: PendingNodes::new(dataflow_id, machine_id),
             subscribe_channels: HashMap::new(),
             drop_channels: HashMap::new(),
@@ -1495,15 +2600,54 @@ impl RunningDataflow {
             timers: BTreeMap::new(),
             open_inputs: BTreeMap::new(),
             running_nodes: BTreeMap::new(),
+            restarting_nodes: BTreeMap::new(),
             open_external_mappings: HashMap::new(),
             pending_drop_tokens: HashMap::new(),
             _timer_handles: Vec::new(),
+            started: false,
             stop_sent: false,
+            shutdown: ShutdownMode::default(),
+            ordered_stop_sources: None,
+            grace_duration: None,
+            scheduled_kills: BTreeSet::new(),
             empty_set: BTreeSet::new(),
             cascading_error_causes: Default::default(),
             grace_duration_kills: Default::default(),
             node_stderr_most_recent: BTreeMap::new(),
+            output_history: BTreeMap::new(),
+            followed_outputs: BTreeSet::new(),
+            node_counters: BTreeMap::new(),
+            tapped_outputs: HashSet::new(),
+            shared_memory_dir: None,
+        }
+    }
+
+    /// Returns the recent output lines of the given nodes, ordered by time.
+    ///
+    /// If `tail` is set, only the last `tail` lines of each node are returned.
+    fn output_history(
+        &self,
+        node_ids: &BTreeSet<NodeId>,
+        tail: Option<usize>,
+        since: Option<SystemTime>,
+    ) -> Vec<NodeOutputLine> {
+        let mut lines = Vec::new();
+        for node_id in node_ids {
+            let history: Vec<_> = self
+                .output_history
+                .get(node_id)
+                .into_iter()
+                .flatten()
+                .filter(|line| match since {
+                    Some(since) => line.timestamp.get_time().to_system_time() >= since,
+                    None => true,
+                })
+                .collect();
+            let skip = tail.map_or(0, |tail| history.len().saturating_sub(tail));
+            lines.extend(history.into_iter().skip(skip).cloned());
         }
+        lines.sort_by_key(|line| line.timestamp);
+        lines
     }
 
     async fn start(
@@ -1511,87 +2655,251 @@ impl RunningDataflow {
         events_tx: &mpsc::Sender<Timestamped<Event>>,
         clock: &Arc<HLC>,
     ) -> eyre::Result<()> {
-        for interval in self.timers.keys().copied() {
-            let events_tx = events_tx.clone();
-            let dataflow_id = self.id;
-            let clock = clock.clone();
-            let task = async move {
-                let mut interval_stream = tokio::time::interval(interval);
-                let hlc = HLC::default();
-                loop {
-                    interval_stream.tick().await;
-
-                    let span = tracing::span!(tracing::Level::TRACE, "tick");
-                    let _ = span.enter();
-
-                    let metadata = dora_core::message::Metadata::from_parameters(
-                        hlc.new_timestamp(),
-                        ArrowTypeInfo::empty(),
-                        MetadataParameters {
-                            watermark: 0,
-                            deadline: 0,
-                            #[cfg(feature = "telemetry")]
-                            open_telemetry_context: serialize_context(&span.context()),
-                            #[cfg(not(feature = "telemetry"))]
-                            open_telemetry_context: "".into(),
-                        },
-                    );
-
-                    let event = Timestamped {
-                        inner: DoraEvent::Timer {
-                            dataflow_id,
-                            interval,
-                            metadata,
-                        }
-                        .into(),
-                        timestamp: clock.new_timestamp(),
-                    };
-                    if events_tx.send(event).await.is_err() {
-                        break;
-                    }
-                }
-            };
-            let (task, handle) = task.remote_handle();
-            tokio::spawn(task);
-            self._timer_handles.push(handle);
+        let timers: Vec<_> = self.timers.keys().copied().collect();
+        for timer in timers {
+            self.start_timer(timer, events_tx, clock);
         }
+        self.started = true;
 
         Ok(())
     }
 
+    fn start_timer(
+        &mut self,
+        timer: TimerConfig,
+        events_tx: &mpsc::Sender<Timestamped<Event>>,
+        clock: &Arc<HLC>,
+    ) {
+        let events_tx = events_tx.clone();
+        let dataflow_id = self.id;
+        let clock = clock.clone();
+        let task = async move {
+            // ticks are scheduled relative to the start instant, so they don't drift
+            let mut interval_stream =
+                tokio::time::interval_at(tokio::time::Instant::now() + timer.phase, timer.interval);
+            interval_stream.set_missed_tick_behavior(match timer.missed_ticks {
+                MissedTickBehavior::Burst => tokio::time::MissedTickBehavior::Burst,
+                MissedTickBehavior::Skip => tokio::time::MissedTickBehavior::Skip,
+                MissedTickBehavior::Delay => tokio::time::MissedTickBehavior::Delay,
+            });
+            let hlc = HLC::default();
+            loop {
+                let scheduled = interval_stream.tick().await;
+                let actual_time = SystemTime::now();
+                let scheduled_time = actual_time
+                    .checked_sub(scheduled.elapsed())
+                    .unwrap_or(actual_time);
+
+                let span = tracing::span!(tracing::Level::TRACE, "tick");
+                let _ = span.enter();
+
+                let metadata = dora_core::message::Metadata::from_parameters(
+                    hlc.new_timestamp(),
+                    ArrowTypeInfo::empty(),
+                    MetadataParameters {
+                        watermark: 0,
+                        deadline: 0,
+                        #[cfg(feature = "telemetry")]
+                        open_telemetry_context: serialize_context(&span.context()),
+                        #[cfg(not(feature = "telemetry"))]
+                        open_telemetry_context: "".into(),
+                        user: Default::default(),
+                    }
+                    .with_user_parameter(TIMER_SCHEDULED_TIME, unix_nanos(scheduled_time))
+                    .with_user_parameter(TIMER_ACTUAL_TIME, unix_nanos(actual_time)),
+                );
+
+                let event = Timestamped {
+                    inner: DoraEvent::Timer {
+                        dataflow_id,
+                        timer,
+                        metadata,
+                    }
+                    .into(),
+                    timestamp: clock.new_timestamp(),
+                };
+                if events_tx.send(event).await.is_err() {
+                    break;
+                }
+            }
+        };
+        let (task, handle) = task.remote_handle();
+        tokio::spawn(task);
+        self._timer_handles.push(handle);
+    }
+
     async fn stop_all(&mut self, clock: &HLC, grace_duration: Option<Duration>) {
+        if grace_duration.is_some() {
+            self.grace_duration = grace_duration;
+        }
+        if self.shutdown == ShutdownMode::Ordered && !self.stop_sent {
+            self.stop_ordered(clock);
+            return;
+        }
+
+        // a repeated stop request during an ordered shutdown stops all remaining nodes
+        self.ordered_stop_sources = None;
         for (_node_id, channel) in self.subscribe_channels.drain() {
             let _ = send_with_timestamp(&channel, daemon_messages::NodeEvent::Stop, clock);
         }
+        let running_nodes: Vec<_> = self.running_nodes.keys().cloned().collect();
+        for node_id in &running_nodes {
+            self.schedule_kill(node_id);
+        }
+        self.stop_sent = true;
+    }
+
+    /// Stops the source nodes and lets the closed inputs propagate downstream.
+    fn stop_ordered(&mut self, clock: &HLC) {
+        let sources = shutdown_sources(&self.nodes);
+        for node_id in &sources {
+            if let Some(channel) = self.subscribe_channels.remove(node_id) {
+                let _ = send_with_timestamp(&channel, daemon_messages::NodeEvent::Stop, clock);
+            }
+        }
 
-        let running_nodes = self.running_nodes.clone();
+        // timers don't keep downstream nodes alive
+        self._timer_handles.clear();
+        for (receiver_id, input_id) in std::mem::take(&mut self.timers).into_values().flatten() {
+            close_input(self, &receiver_id, &input_id, clock);
+        }
+        // nodes whose inputs stay open, e.g. because of a stuck upstream node, are
+        // killed after the stop timeouts of their upstream nodes
+        let deadlines = ordered_stop_deadlines(&self.nodes, &sources, |node_id| {
+            self.stop_timeout(node_id, self.grace_duration)
+        });
+        let running_nodes: Vec<_> = self.running_nodes.keys().cloned().collect();
+        for node_id in &running_nodes {
+            let exiting = sources.contains(node_id) || self.open_inputs(node_id).is_empty();
+            match deadlines.get(node_id) {
+                Some(deadline) if !exiting => self.schedule_kill_after(node_id, *deadline),
+                _ => self.schedule_kill(node_id),
+            }
+        }
+        self.ordered_stop_sources = Some(sources);
+        self.stop_sent = true;
+    }
+
+    /// Kills the given node if it doesn't exit within its stop timeout.
+    fn schedule_kill(&mut self, node_id: &NodeId) {
+        self.schedule_kill_with_grace(node_id, self.grace_duration);
+    }
+
+    /// Kills the given node if it doesn't exit within its stop timeout, falling back
+    /// to the given grace duration.
+    fn schedule_kill_with_grace(&mut self, node_id: &NodeId, grace_duration: Option<Duration>) {
+        let duration = self.stop_timeout(node_id, grace_duration);
+        self.schedule_kill_after(node_id, duration);
+    }
+
+    /// The time that the given node has for exiting after it was asked to stop.
+    fn stop_timeout(&self, node_id: &NodeId, grace_duration: Option<Duration>) -> Duration {
+        self.nodes
+            .iter()
+            .find(|n| &n.id == node_id)
+            .and_then(|n| n.stop_timeout)
+            .or(grace_duration)
+            .unwrap_or(Duration::from_millis(2000))
+    }
+
+    /// Kills the given node if it doesn't exit within the given duration.
+    fn schedule_kill_after(&mut self, node_id: &NodeId, duration: Duration) {
+        let Some(pid) = self.running_nodes.get(node_id).and_then(|n| n.pid) else {
+            return;
+        };
+        if !self.scheduled_kills.insert(node_id.clone()) {
+            return;
+        }
+
+        let node_id = node_id.clone();
         let grace_duration_kills = self.grace_duration_kills.clone();
         tokio::spawn(async move {
-            let duration = grace_duration.unwrap_or(Duration::from_millis(2000));
             tokio::time::sleep(duration).await;
             let mut system = sysinfo::System::new();
-            system.refresh_processes();
-
-            for (node, node_details) in running_nodes.iter() {
-                if let Some(pid) = node_details.pid {
-                    if let Some(process) = system.process(Pid::from(pid as usize)) {
-                        grace_duration_kills.insert(node.clone());
-                        process.kill();
-                        warn!(
-                            "{node} was killed due to not stopping within the {:#?} grace period",
-                            duration
-                        )
-                    }
-                }
+            let pid = Pid::from(pid as usize);
+            system.refresh_process(pid);
+
+            if let Some(process) = system.process(pid) {
+                grace_duration_kills.insert(node_id.clone());
+                process.kill();
+                warn!(
+                    "{node_id} was killed due to not stopping within the {:#?} grace period",
+                    duration
+                )
             }
         });
-        self.stop_sent = true;
     }
 
     fn open_inputs(&self, node_id: &NodeId) -> &BTreeSet<DataId> {
         self.open_inputs.get(node_id).unwrap_or(&self.empty_set)
     }
 
+    /// Routes the given output or timer to an input of a node on the given machine.
+    ///
+    /// Timers of remote nodes are handled by the daemon of their machine.
+    fn add_input_mapping(
+        &mut self,
+        input: InputId,
+        mapping: InputMapping,
+        machine: &str,
+        local: bool,
+    ) {
+        match mapping {
+            InputMapping::User(mapping) if local => {
+                self.mappings
+                    .entry(OutputId(mapping.source, mapping.output))
+                    .or_default()
+                    .insert(input);
+            }
+            InputMapping::Timer(timer) if local => {
+                self.timers.entry(timer).or_default().insert(input);
+            }
+            InputMapping::User(mapping) => {
+                self.open_external_mappings
+                    .entry(OutputId(mapping.source, mapping.output))
+                    .or_default()
+                    .entry(machine.to_owned())
+                    .or_default()
+                    .insert(input);
+            }
+            InputMapping::Timer(_) => {}
+        }
+    }
+
+    /// Reverts [`Self::add_input_mapping`].
+    fn remove_input_mapping(
+        &mut self,
+        input: &InputId,
+        mapping: &InputMapping,
+        machine: &str,
+        local: bool,
+    ) {
+        match mapping {
+            InputMapping::User(mapping) if local => {
+                let output_id = OutputId(mapping.source.clone(), mapping.output.clone());
+                if let Some(receivers) = self.mappings.get_mut(&output_id) {
+                    receivers.remove(input);
+                }
+            }
+            InputMapping::Timer(timer) if local => {
+                if let Some(receivers) = self.timers.get_mut(timer) {
+                    receivers.remove(input);
+                }
+            }
+            InputMapping::User(mapping) => {
+                let output_id = OutputId(mapping.source.clone(), mapping.output.clone());
+                if let Some(receivers) = self
+                    .open_external_mappings
+                    .get_mut(&output_id)
+                    .and_then(|m| m.get_mut(machine))
+                {
+                    receivers.remove(input);
+                }
+            }
+            InputMapping::Timer(_) => {}
+        }
+    }
+
     async fn check_drop_token(&mut self, token: DropToken, clock: &HLC) -> eyre::Result<()> {
         match self.pending_drop_tokens.entry(token) {
             std::collections::hash_map::Entry::Occupied(entry) => {
@@ -1649,7 +2957,13 @@ pub enum Event {
     Dora(DoraEvent),
     DynamicNode(DynamicNodeEventWrapper),
     HeartbeatInterval,
+    MetricsInterval,
+    CoordinatorReconnected(AsyncStream),
     CtrlC,
+    /// Stops all dataflows, used when running without a coordinator.
+    Stop {
+        grace_duration: Option<Duration>,
+    },
 }
 
 impl From<DoraEvent> for Event {
@@ -1683,6 +2997,10 @@ pub enum DaemonNodeEvent {
     ReportDrop {
         tokens: Vec<DropToken>,
     },
+    /// Inputs were dropped because the input queue of the node was full.
+    InputsDropped {
+        count: u64,
+    },
     EventStreamDropped {
         reply_sender: oneshot::Sender<DaemonReply>,
     },
@@ -1692,7 +3010,7 @@ pub enum DaemonNodeEvent {
 pub enum DoraEvent {
     Timer {
         dataflow_id: DataflowId,
-        interval: Duration,
+        timer: TimerConfig,
         metadata: dora_core::message::Metadata,
     },
     Logs {
@@ -1705,6 +3023,14 @@ pub enum DoraEvent {
         dataflow_id: DataflowId,
         node_id: NodeId,
         exit_status: NodeExitStatus,
+        /// Directory of the crash bundle, if one was collected.
+        crash_bundle: Option<PathBuf>,
+    },
+    /// Output that a spawned node wrote to stdout or stderr.
+    NodeOutput {
+        dataflow_id: DataflowId,
+        node_id: NodeId,
+        output: String,
     },
 }
 
@@ -1725,6 +3051,13 @@ fn send_with_timestamp<T>(
     })
 }
 
+/// Nanoseconds since the UNIX epoch, as used by the timer tick metadata.
+fn unix_nanos(time: SystemTime) -> i64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_nanos() as i64)
+        .unwrap_or_default()
+}
+
 fn set_up_ctrlc_handler(
     clock: Arc<HLC>,
 ) -> Result<impl Stream<Item = Timestamped<Event>>, eyre::ErrReport> {
@@ -1774,3 +3107,226 @@ impl CascadingErrorCauses {
         self.caused_by.entry(affected_node).or_insert(causing_node);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve(yaml: &str) -> Vec<ResolvedNode> {
+        let descriptor: Descriptor = serde_yaml::from_str(yaml).unwrap();
+        descriptor.resolve_aliases_and_set_defaults().unwrap()
+    }
+
+    fn ids(ids: &[&str]) -> BTreeSet<NodeId> {
+        ids.iter().map(|id| NodeId::from(id.to_string())).collect()
+    }
+
+    const PIPELINE: &str = r#"
+        nodes:
+          - id: camera
+            path: camera
+            inputs:
+              tick: dora/timer/millis/100
+            outputs: [image]
+          - id: detector
+            path: detector
+            inputs:
+              image: camera/image
+              feedback: tracker/feedback
+            outputs: [boxes]
+          - id: tracker
+            path: tracker
+            inputs:
+              boxes: detector/boxes
+            outputs: [feedback, tracks]
+          - id: plot
+            path: plot
+            stop_timeout: 5
+            inputs:
+              image: camera/image
+              tracks: tracker/tracks
+    "#;
+
+    #[tokio::test]
+    async fn inputs_from_remote_nodes_are_counted() {
+        let mut dataflow = RunningDataflow::new(Uuid::new_v4(), "local".into());
+        let camera = NodeId::from("camera".to_owned());
+        let plot = NodeId::from("plot".to_owned());
+        let image = DataId::from("image".to_owned());
+        // `camera` runs on a remote machine, so it only has a mapping to `plot`
+        dataflow
+            .mappings
+            .entry(OutputId(camera.clone(), image.clone()))
+            .or_default()
+            .insert((plot.clone(), image.clone()));
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        dataflow.subscribe_channels.insert(plot.clone(), tx);
+
+        let clock = HLC::default();
+        let metadata = Metadata::new(clock.new_timestamp(), ArrowTypeInfo::empty());
+        for _ in 0..2 {
+            send_output_to_local_receivers(
+                camera.clone(),
+                image.clone(),
+                &mut dataflow,
+                &metadata,
+                Some(DataMessage::Vec(AVec::from_slice(1, b"data"))),
+                &clock,
+            )
+            .await
+            .unwrap();
+        }
+        assert!(rx.try_recv().is_ok());
+        assert_eq!(dataflow.node_counters[&plot].inputs, 2);
+        assert!(!dataflow.node_counters.contains_key(&camera));
+    }
+
+    #[test]
+    fn restarting_nodes_buffer_inputs_up_to_queue_size() {
+        let nodes = resolve(
+            r#"
+            nodes:
+              - id: plot
+                path: plot
+                inputs:
+                  image:
+                    source: camera/image
+                    queue_size: 2
+                  tracks: tracker/tracks
+            "#,
+        );
+        let mut restarting = RestartingNode::new(nodes.first());
+        let image = DataId::from("image".to_owned());
+        let tracks = DataId::from("tracks".to_owned());
+        assert_eq!(restarting.queue_sizes[&image], 2);
+        assert_eq!(restarting.queue_sizes[&tracks], spawn::DEFAULT_QUEUE_SIZE);
+
+        let clock = HLC::default();
+        let event = |id: &DataId| {
+            let timestamp = clock.new_timestamp();
+            let event = Timestamped {
+                inner: daemon_messages::NodeEvent::Input {
+                    id: id.clone(),
+                    metadata: Metadata::new(timestamp, ArrowTypeInfo::empty()),
+                    data: None,
+                },
+                timestamp,
+            };
+            (id.clone(), timestamp, event)
+        };
+        let events = [
+            event(&image),
+            event(&tracks),
+            event(&image),
+            event(&tracks),
+            event(&image),
+        ];
+        let dropped: Vec<_> = events
+            .iter()
+            .map(|(id, _, event)| restarting.buffer_input(id, event.clone()))
+            .collect();
+        assert_eq!(dropped, [false, false, false, false, true]);
+
+        // the oldest image was dropped and the rest is delivered in order
+        let delivered: Vec<_> = restarting
+            .into_buffered_inputs()
+            .into_iter()
+            .map(|event| event.timestamp)
+            .collect();
+        let expected: Vec<_> = events[1..].iter().map(|(_, t, _)| *t).collect();
+        assert_eq!(delivered, expected);
+    }
+
+    #[test]
+    fn shutdown_sources_include_feedback_loops() {
+        let nodes = resolve(PIPELINE);
+        assert_eq!(
+            shutdown_sources(&nodes),
+            ids(&["camera", "detector", "tracker"])
+        );
+
+        let nodes = resolve(
+            r#"
+            nodes:
+              - id: source
+                path: source
+                outputs: [data]
+              - id: sink
+                path: sink
+                inputs:
+                  data: source/data
+            "#,
+        );
+        assert_eq!(shutdown_sources(&nodes), ids(&["source"]));
+    }
+
+    #[test]
+    fn every_node_gets_an_ordered_stop_deadline() {
+        let nodes = resolve(PIPELINE);
+        let sources = shutdown_sources(&nodes);
+        let timeouts: BTreeMap<_, _> = nodes
+            .iter()
+            .map(|node| {
+                let timeout = node.stop_timeout.unwrap_or(Duration::from_secs(2));
+                (node.id.clone(), timeout)
+            })
+            .collect();
+        let deadlines = ordered_stop_deadlines(&nodes, &sources, |node_id| timeouts[node_id]);
+
+        let secs = Duration::from_secs;
+        assert_eq!(
+            deadlines,
+            BTreeMap::from([
+                (NodeId::from("camera".to_owned()), secs(2)),
+                (NodeId::from("detector".to_owned()), secs(2)),
+                (NodeId::from("tracker".to_owned()), secs(2)),
+                // waits for its upstream nodes, then for its own stop timeout
+                (NodeId::from("plot".to_owned()), secs(7)),
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn local_logs_are_dropped_instead_of_blocking() {
+        let message = |i: usize| {
+            LocalLog::Message(LogMessage {
+                dataflow_id: Uuid::nil(),
+                node_id: None,
+                level: Level::Info,
+                target: None,
+                module_path: None,
+                file: None,
+                line: None,
+                message: i.to_string(),
+            })
+        };
+        let received = |log: Option<LocalLog>| match log {
+            Some(LocalLog::Message(message)) => format!("message {}", message.message),
+            Some(LocalLog::Dropped(count)) => format!("dropped {count}"),
+            other => panic!("unexpected log {other:?}"),
+        };
+
+        let (tx, mut rx) = mpsc::channel(2);
+        let mut logs = LocalLogs::new(tx);
+        for i in 0..5 {
+            logs.send(message(i));
+        }
+        assert_eq!(received(rx.recv().await), "message 0");
+        assert_eq!(received(rx.recv().await), "message 1");
+
+        // the number of dropped logs is reported before the next log
+        logs.send(message(5));
+        assert_eq!(received(rx.recv().await), "dropped 3");
+        assert_eq!(received(rx.recv().await), "message 5");
+
+        logs.send(message(6));
+        logs.send(message(7));
+        logs.send(message(8));
+        assert_eq!(received(rx.recv().await), "message 6");
+        assert_eq!(received(rx.recv().await), "message 7");
+        // remaining drops are reported when the daemon exits
+        logs.finish().await;
+        assert_eq!(received(rx.recv().await), "dropped 1");
+        assert!(rx.recv().await.is_none());
+    }
+}
@@ -1,9 +1,825 @@
-use std::path::{Path, PathBuf};
+use std::{
+    collections::BTreeSet,
+    io::SeekFrom,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
 
-use dora_core::config::NodeId;
+use dora_core::{
+    config::{LogFormat, LogParser, LogRetention, LogRotation, LoggingConfig, NodeId},
+    coordinator_messages::NodeOutputLine,
+    message::uhlc,
+};
+use eyre::Context;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    fs::{File, OpenOptions},
+    io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt},
+};
 use uuid::Uuid;
 
-pub fn log_path(working_dir: &Path, dataflow_id: &Uuid, node_id: &NodeId) -> PathBuf {
-    let dataflow_dir = working_dir.join("out").join(dataflow_id.to_string());
-    dataflow_dir.join(format!("log_{node_id}.txt"))
+pub fn dataflow_log_dir(working_dir: &Path, dataflow_id: &Uuid) -> PathBuf {
+    working_dir.join("out").join(dataflow_id.to_string())
+}
+
+/// Marker file that exists in the log directory of running dataflows.
+///
+/// The marker is touched periodically by the daemon that runs the dataflow, so
+/// that log directories of dataflows that are run by other daemons or `dora run`
+/// processes sharing the same working directory are not removed by
+/// [`apply_retention`].
+const RUNNING_MARKER: &str = ".running";
+
+/// Running markers that were not touched within this duration are considered stale,
+/// e.g. because the daemon was killed.
+const RUNNING_MARKER_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Creates or touches the [`RUNNING_MARKER`] of the given dataflow.
+pub fn mark_running(working_dir: &Path, dataflow_id: &Uuid) -> std::io::Result<()> {
+    let dir = dataflow_log_dir(working_dir, dataflow_id);
+    std::fs::create_dir_all(&dir)?;
+    std::fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(dir.join(RUNNING_MARKER))?
+        .set_modified(SystemTime::now())
+}
+
+/// Removes the [`RUNNING_MARKER`] of the given dataflow.
+pub fn unmark_running(working_dir: &Path, dataflow_id: &Uuid) {
+    let _ = std::fs::remove_file(dataflow_log_dir(working_dir, dataflow_id).join(RUNNING_MARKER));
+}
+
+fn is_marked_running(dataflow_dir: &Path) -> bool {
+    std::fs::metadata(dataflow_dir.join(RUNNING_MARKER))
+        .and_then(|m| m.modified())
+        .is_ok_and(|modified| modified.elapsed().unwrap_or_default() < RUNNING_MARKER_TIMEOUT)
+}
+
+/// Detects the format of the given node's log file based on its file extension.
+pub fn log_format(working_dir: &Path, dataflow_id: &Uuid, node_id: &NodeId) -> LogFormat {
+    if log_path(working_dir, dataflow_id, node_id, LogFormat::Jsonl).exists() {
+        LogFormat::Jsonl
+    } else {
+        LogFormat::Text
+    }
+}
+
+pub fn log_path(
+    working_dir: &Path,
+    dataflow_id: &Uuid,
+    node_id: &NodeId,
+    format: LogFormat,
+) -> PathBuf {
+    rotated_log_path(working_dir, dataflow_id, node_id, format, 0)
+}
+
+/// Path of the log file that was rotated `index` times (`0` is the current file).
+fn rotated_log_path(
+    working_dir: &Path,
+    dataflow_id: &Uuid,
+    node_id: &NodeId,
+    format: LogFormat,
+    index: usize,
+) -> PathBuf {
+    let extension = match format {
+        LogFormat::Text => "txt",
+        LogFormat::Jsonl => "jsonl",
+    };
+    let file_name = match index {
+        0 => format!("log_{node_id}.{extension}"),
+        index => format!("log_{node_id}.{index}.{extension}"),
+    };
+    dataflow_log_dir(working_dir, dataflow_id).join(file_name)
+}
+
+/// Paths of the log files of the given node, including all rotated files, oldest first.
+fn log_paths(
+    working_dir: &Path,
+    dataflow_id: &Uuid,
+    node_id: &NodeId,
+    format: LogFormat,
+) -> Vec<PathBuf> {
+    let mut paths: Vec<_> = (1..)
+        .map(|index| rotated_log_path(working_dir, dataflow_id, node_id, format, index))
+        .take_while(|path| path.exists())
+        .collect();
+    paths.reverse();
+    paths.push(log_path(working_dir, dataflow_id, node_id, format));
+    paths
+}
+
+/// Reads the log files of the given node, including all rotated files, oldest first.
+///
+/// If `tail` is set, only the last `tail` lines are read, starting from the end of
+/// the newest file.
+pub async fn read_logs(
+    working_dir: &Path,
+    dataflow_id: &Uuid,
+    node_id: &NodeId,
+    format: LogFormat,
+    tail: Option<usize>,
+) -> eyre::Result<Vec<u8>> {
+    let paths = log_paths(working_dir, dataflow_id, node_id, format);
+    if let Some(mut remaining) = tail {
+        let mut chunks = Vec::new();
+        for path in paths.iter().rev() {
+            if remaining == 0 {
+                break;
+            }
+            let chunk = read_last_lines(path, remaining).await?;
+            remaining = remaining.saturating_sub(line_count(&chunk));
+            chunks.push(chunk);
+        }
+        return Ok(chunks.into_iter().rev().flatten().collect());
+    }
+
+    let mut logs = vec![];
+    for path in paths {
+        File::open(&path)
+            .await
+            .wrap_err_with(|| format!("Could not open log file: {path:#?}"))?
+            .read_to_end(&mut logs)
+            .await
+            .wrap_err("Could not read content of log file")?;
+    }
+    Ok(logs)
+}
+
+/// Number of bytes that are read at once when searching a log file backwards.
+const TAIL_CHUNK_SIZE: u64 = 64 * 1024;
+
+/// Reads the last `count` lines of the given file, without reading the whole file.
+async fn read_last_lines(path: &Path, count: usize) -> eyre::Result<Vec<u8>> {
+    let mut file = File::open(path)
+        .await
+        .wrap_err_with(|| format!("Could not open log file: {path:#?}"))?;
+    let mut start = file
+        .metadata()
+        .await
+        .wrap_err_with(|| format!("failed to read metadata of log file {path:#?}"))?
+        .len();
+    let mut data = Vec::new();
+    // the first line of `data` might be incomplete, so we need one more line
+    while start > 0 && line_count(&data) <= count {
+        let chunk_start = start.saturating_sub(TAIL_CHUNK_SIZE);
+        let mut chunk = vec![0; (start - chunk_start) as usize];
+        file.seek(SeekFrom::Start(chunk_start))
+            .await
+            .wrap_err("Could not seek in log file")?;
+        file.read_exact(&mut chunk)
+            .await
+            .wrap_err("Could not read content of log file")?;
+        chunk.extend_from_slice(&data);
+        data = chunk;
+        start = chunk_start;
+    }
+    Ok(last_lines(&data, count).to_vec())
+}
+
+/// Returns the last `count` lines of the given data.
+pub fn last_lines(data: &[u8], count: usize) -> &[u8] {
+    if count == 0 {
+        return &[];
+    }
+    let trimmed = data.strip_suffix(b"\n").unwrap_or(data);
+    let start = trimmed
+        .iter()
+        .enumerate()
+        .rev()
+        .filter(|(_, &b)| b == b'\n')
+        .nth(count - 1)
+        .map(|(i, _)| i + 1)
+        .unwrap_or(0);
+    &data[start..]
+}
+
+fn line_count(data: &[u8]) -> usize {
+    if data.is_empty() {
+        return 0;
+    }
+    let trimmed = data.strip_suffix(b"\n").unwrap_or(data);
+    trimmed.iter().filter(|&&b| b == b'\n').count() + 1
+}
+
+/// Reads the output lines of the given nodes that were logged between `since` and
+/// `until` from their `jsonl` log files, keeping only the last `tail` lines per node.
+///
+/// Text log files contain no timestamps, so this only works for [`LogFormat::Jsonl`].
+pub async fn read_output_history(
+    working_dir: &Path,
+    dataflow_id: Uuid,
+    node_ids: &BTreeSet<NodeId>,
+    tail: Option<usize>,
+    since: SystemTime,
+    until: SystemTime,
+    clock_id: uhlc::ID,
+) -> eyre::Result<Vec<NodeOutputLine>> {
+    let mut lines = Vec::new();
+    for node_id in node_ids {
+        let mut node_lines = Vec::new();
+        for path in log_paths(working_dir, &dataflow_id, node_id, LogFormat::Jsonl) {
+            // all lines of files that were last written before `since` are too old
+            let modified = tokio::fs::metadata(&path)
+                .await
+                .and_then(|m| m.modified())
+                .unwrap_or(SystemTime::now());
+            if modified < since {
+                continue;
+            }
+            let content = tokio::fs::read_to_string(&path)
+                .await
+                .wrap_err_with(|| format!("Could not read log file {path:#?}"))?;
+            for line in content.lines() {
+                let Ok(line) = serde_json::from_str::<StoredLogLine>(line) else {
+                    continue;
+                };
+                let Ok(time) = chrono::DateTime::parse_from_rfc3339(&line.timestamp) else {
+                    continue;
+                };
+                let time = SystemTime::from(time);
+                if time < since || time >= until {
+                    continue;
+                }
+                let since_epoch = time
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .unwrap_or_default();
+                node_lines.push(NodeOutputLine {
+                    dataflow_id,
+                    node_id: node_id.clone(),
+                    timestamp: uhlc::Timestamp::new(since_epoch.into(), clock_id),
+                    line: line.message,
+                });
+            }
+        }
+        let skip = tail.map_or(0, |tail| node_lines.len().saturating_sub(tail));
+        lines.extend(node_lines.into_iter().skip(skip));
+    }
+    lines.sort_by_key(|line| line.timestamp);
+    Ok(lines)
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogStream {
+    Stdout,
+    Stderr,
+}
+
+#[derive(Serialize)]
+struct JsonLogLine<'a> {
+    timestamp: String,
+    stream: LogStream,
+    node: &'a NodeId,
+    level: Option<&'static str>,
+    message: &'a str,
+}
+
+/// The fields of a [`JsonLogLine`] that are needed for reading it back.
+#[derive(Deserialize)]
+struct StoredLogLine {
+    timestamp: String,
+    message: String,
+}
+
+/// Writes the output of a node to its log file, rotating the file if configured.
+pub struct NodeLogWriter {
+    working_dir: PathBuf,
+    dataflow_id: Uuid,
+    node_id: NodeId,
+    format: LogFormat,
+    rotation: Option<LogRotation>,
+    file: File,
+    written: u64,
+    opened: Instant,
+}
+
+impl NodeLogWriter {
+    /// Creates the log file of the node.
+    ///
+    /// Restarted nodes append to the log file of their previous run, otherwise an
+    /// existing file is truncated.
+    pub async fn create(
+        working_dir: &Path,
+        dataflow_id: Uuid,
+        node_id: NodeId,
+        config: &LoggingConfig,
+        restart: bool,
+    ) -> eyre::Result<Self> {
+        let path = log_path(working_dir, &dataflow_id, &node_id, config.format);
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(restart)
+            .truncate(!restart)
+            .open(&path)
+            .await
+            .wrap_err_with(|| format!("failed to create log file {path:#?}"))?;
+        let written = file
+            .metadata()
+            .await
+            .wrap_err_with(|| format!("failed to read metadata of log file {path:#?}"))?
+            .len();
+        Ok(Self {
+            working_dir: working_dir.to_owned(),
+            dataflow_id,
+            node_id,
+            format: config.format,
+            rotation: config.rotation.clone(),
+            file,
+            written,
+            opened: Instant::now(),
+        })
+    }
+
+    /// Writes the given output chunk, which might consist of multiple lines.
+    pub async fn write(
+        &mut self,
+        stream: LogStream,
+        timestamp: uhlc::Timestamp,
+        output: &str,
+    ) -> eyre::Result<()> {
+        let data = match self.format {
+            LogFormat::Text => output.to_owned(),
+            LogFormat::Jsonl => {
+                let timestamp: chrono::DateTime<chrono::Utc> =
+                    timestamp.get_time().to_system_time().into();
+                let timestamp = timestamp.to_rfc3339_opts(chrono::SecondsFormat::Micros, true);
+                let mut data = String::new();
+                for line in output.lines() {
+                    let line = JsonLogLine {
+                        timestamp: timestamp.clone(),
+                        stream,
+                        node: &self.node_id,
+                        level: detect_level(line),
+                        message: line,
+                    };
+                    data.push_str(
+                        &serde_json::to_string(&line).wrap_err("failed to serialize log line")?,
+                    );
+                    data.push('\n');
+                }
+                data
+            }
+        };
+
+        self.file
+            .write_all(data.as_bytes())
+            .await
+            .wrap_err("failed to write to log file")?;
+        // Make sure that all data has been synced to disk.
+        self.file
+            .sync_all()
+            .await
+            .wrap_err("failed to sync log file")?;
+        self.written += data.len() as u64;
+
+        if self.needs_rotation() {
+            self.rotate().await?;
+        }
+        Ok(())
+    }
+
+    fn needs_rotation(&self) -> bool {
+        let Some(rotation) = &self.rotation else {
+            return false;
+        };
+        let too_large = rotation.max_bytes.is_some_and(|max| self.written >= max);
+        let too_old = rotation
+            .max_age_secs
+            .is_some_and(|max| self.opened.elapsed() >= Duration::from_secs(max));
+        too_large || too_old
+    }
+
+    async fn rotate(&mut self) -> eyre::Result<()> {
+        // `max_files: 0` is rejected when the dataflow is validated, keep at least one
+        // rotated file anyway to never truncate the current file
+        let max_files = self.rotation.as_ref().map_or(1, |r| r.max_files.max(1));
+        let path = |index| {
+            rotated_log_path(
+                &self.working_dir,
+                &self.dataflow_id,
+                &self.node_id,
+                self.format,
+                index,
+            )
+        };
+
+        // shift rotated files by one, dropping the oldest file
+        let _ = tokio::fs::remove_file(path(max_files)).await;
+        for index in (1..max_files).rev() {
+            let from = path(index);
+            if from.exists() {
+                tokio::fs::rename(&from, path(index + 1))
+                    .await
+                    .wrap_err_with(|| format!("failed to rotate log file {from:#?}"))?;
+            }
+        }
+        tokio::fs::rename(path(0), path(1))
+            .await
+            .wrap_err("failed to rotate log file")?;
+
+        let current = path(0);
+        self.file = File::create(&current)
+            .await
+            .wrap_err_with(|| format!("failed to create log file {current:#?}"))?;
+        self.written = 0;
+        self.opened = Instant::now();
+        Ok(())
+    }
+}
+
+/// Tries to detect the log level of the given output line.
+///
+/// Only looks at the level token of common log formats, i.e. the first word of the
+/// line or the first level word in a leading `[...]` header. Level words later in
+/// the message are ignored.
+fn detect_level(line: &str) -> Option<&'static str> {
+    if let Some(parsed) = parse_env_logger_line(line) {
+        return parsed.level;
+    }
+    let line = line.trim_start();
+    let end = line
+        .find(|c: char| !c.is_ascii_alphabetic())
+        .unwrap_or(line.len());
+    level_from_word(&line[..end])
+}
+
+fn level_from_word(word: &str) -> Option<&'static str> {
+    match word {
+        "TRACE" => Some("TRACE"),
+        "DEBUG" => Some("DEBUG"),
+        "INFO" => Some("INFO"),
+        "WARN" | "WARNING" => Some("WARN"),
+        "ERROR" | "CRITICAL" | "FATAL" => Some("ERROR"),
+        _ => None,
+    }
+}
+
+/// An output line that was parsed according to a [`LogParser`].
+pub struct ParsedLogLine<'a> {
+    pub level: Option<&'static str>,
+    pub target: Option<&'a str>,
+    pub message: &'a str,
+}
+
+/// Parses the given output line, keeping the whole line as message if it doesn't
+/// match the format.
+pub fn parse_log_line(parser: LogParser, line: &str) -> ParsedLogLine<'_> {
+    let parsed = match parser {
+        LogParser::EnvLogger => parse_env_logger_line(line),
+        LogParser::Python => parse_python_line(line),
+    };
+    parsed.unwrap_or(ParsedLogLine {
+        level: None,
+        target: None,
+        message: line,
+    })
+}
+
+/// Parses `[<timestamp> <LEVEL> <target>] <message>`, where the timestamp and
+/// target are optional.
+fn parse_env_logger_line(line: &str) -> Option<ParsedLogLine<'_>> {
+    let (header, message) = line.strip_prefix('[')?.split_once(']')?;
+    let mut words = header.split_whitespace();
+    let level = words.by_ref().find_map(level_from_word)?;
+    Some(ParsedLogLine {
+        level: Some(level),
+        target: words.next(),
+        message: message.strip_prefix(' ').unwrap_or(message),
+    })
+}
+
+/// Parses `<LEVEL>:<logger name>:<message>`.
+fn parse_python_line(line: &str) -> Option<ParsedLogLine<'_>> {
+    let (level, rest) = line.split_once(':')?;
+    let level = level_from_word(level)?;
+    let (target, message) = rest.split_once(':')?;
+    Some(ParsedLogLine {
+        level: Some(level),
+        target: Some(target),
+        message,
+    })
+}
+
+/// Removes old `out/<dataflow-id>` directories according to the given retention policy.
+///
+/// The directories of the dataflows in `keep` and of dataflows that are marked as
+/// running by any daemon are never removed. The log directory of the newly spawned
+/// dataflow is counted towards `max_dataflows`.
+pub fn apply_retention(
+    working_dir: &Path,
+    retention: &LogRetention,
+    keep: &BTreeSet<Uuid>,
+) -> eyre::Result<()> {
+    let out_dir = working_dir.join("out");
+    if !out_dir.exists() {
+        return Ok(());
+    }
+
+    let mut dataflow_dirs = Vec::new();
+    for entry in std::fs::read_dir(&out_dir).wrap_err("failed to read out dir")? {
+        let entry = entry.wrap_err("failed to read out dir entry")?;
+        let Some(uuid) = entry
+            .file_name()
+            .to_str()
+            .and_then(|name| Uuid::parse_str(name).ok())
+        else {
+            continue;
+        };
+        if keep.contains(&uuid) || !entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+            continue;
+        }
+        if is_marked_running(&entry.path()) {
+            continue;
+        }
+        let modified = entry
+            .metadata()
+            .and_then(|m| m.modified())
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        dataflow_dirs.push((modified, entry.path()));
+    }
+    // newest first
+    dataflow_dirs.sort_by(|a, b| b.0.cmp(&a.0));
+
+    let now = SystemTime::now();
+    for (index, (modified, path)) in dataflow_dirs.into_iter().enumerate() {
+        let too_many = retention.max_dataflows.is_some_and(|max| index + 1 >= max);
+        let too_old = retention.max_age_secs.is_some_and(|max| {
+            now.duration_since(modified).unwrap_or_default() > Duration::from_secs(max)
+        });
+        if too_many || too_old {
+            tracing::debug!("removing old dataflow log directory {path:#?}");
+            if let Err(err) = std::fs::remove_dir_all(&path) {
+                tracing::warn!("failed to remove old dataflow log directory {path:#?}: {err}");
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn last_lines_of_output() {
+        let logs = b"a\nb\nc\n";
+        assert_eq!(last_lines(logs, 0), b"");
+        assert_eq!(last_lines(logs, 1), b"c\n");
+        assert_eq!(last_lines(logs, 2), b"b\nc\n");
+        assert_eq!(last_lines(logs, 3), logs);
+        assert_eq!(last_lines(logs, 10), logs);
+        assert_eq!(last_lines(b"a\nb", 1), b"b");
+        assert_eq!(last_lines(b"", 1), b"");
+    }
+
+    #[tokio::test]
+    async fn read_tail_across_rotated_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let dataflow_id = Uuid::new_v4();
+        let node_id: NodeId = "node".to_string().into();
+        let path =
+            |index| rotated_log_path(dir.path(), &dataflow_id, &node_id, LogFormat::Text, index);
+        std::fs::create_dir_all(dataflow_log_dir(dir.path(), &dataflow_id)).unwrap();
+        std::fs::write(path(1), "1\n2\n3\n").unwrap();
+        // longer than one chunk, so that the file is read in multiple steps
+        let long_line = "x".repeat(TAIL_CHUNK_SIZE as usize);
+        std::fs::write(path(0), format!("{long_line}\n4\n5\n")).unwrap();
+
+        let read = |tail| read_logs(dir.path(), &dataflow_id, &node_id, LogFormat::Text, tail);
+        assert_eq!(read(Some(0)).await.unwrap(), b"");
+        assert_eq!(read(Some(2)).await.unwrap(), b"4\n5\n");
+        assert_eq!(
+            read(Some(4)).await.unwrap(),
+            format!("3\n{long_line}\n4\n5\n").as_bytes()
+        );
+        assert_eq!(read(Some(10)).await.unwrap(), read(None).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn read_history_since() {
+        let dir = tempfile::tempdir().unwrap();
+        let dataflow_id = Uuid::new_v4();
+        let node_id: NodeId = "node".to_string().into();
+        let config = LoggingConfig {
+            format: LogFormat::Jsonl,
+            ..Default::default()
+        };
+        std::fs::create_dir_all(dataflow_log_dir(dir.path(), &dataflow_id)).unwrap();
+        let mut writer =
+            NodeLogWriter::create(dir.path(), dataflow_id, node_id.clone(), &config, false)
+                .await
+                .unwrap();
+        let clock = uhlc::HLC::default();
+        let timestamp_at = |time: SystemTime| {
+            uhlc::Timestamp::new(
+                time.duration_since(SystemTime::UNIX_EPOCH).unwrap().into(),
+                *clock.get_id(),
+            )
+        };
+        let now = SystemTime::now();
+        let old = now - Duration::from_secs(60);
+        writer
+            .write(LogStream::Stdout, timestamp_at(old), "old\n")
+            .await
+            .unwrap();
+        writer
+            .write(LogStream::Stdout, timestamp_at(now), "new 1\nnew 2\n")
+            .await
+            .unwrap();
+
+        let nodes = BTreeSet::from([node_id]);
+        let read = |tail, since| {
+            read_output_history(
+                dir.path(),
+                dataflow_id,
+                &nodes,
+                tail,
+                since,
+                now + Duration::from_secs(1),
+                *clock.get_id(),
+            )
+        };
+        let lines = |lines: Vec<NodeOutputLine>| -> Vec<String> {
+            lines.into_iter().map(|l| l.line).collect()
+        };
+        assert_eq!(
+            lines(read(None, old - Duration::from_secs(1)).await.unwrap()),
+            ["old", "new 1", "new 2"]
+        );
+        assert_eq!(
+            lines(read(None, now - Duration::from_secs(1)).await.unwrap()),
+            ["new 1", "new 2"]
+        );
+        assert_eq!(
+            lines(read(Some(1), old - Duration::from_secs(1)).await.unwrap()),
+            ["new 2"]
+        );
+    }
+
+    #[tokio::test]
+    async fn only_restarted_nodes_append_to_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let dataflow_id = Uuid::new_v4();
+        let node_id: NodeId = "node".to_string().into();
+        let config = LoggingConfig::default();
+        std::fs::create_dir_all(dataflow_log_dir(dir.path(), &dataflow_id)).unwrap();
+        let clock = uhlc::HLC::default();
+        for (restart, line) in [(false, "1\n"), (true, "2\n"), (false, "3\n")] {
+            let mut writer =
+                NodeLogWriter::create(dir.path(), dataflow_id, node_id.clone(), &config, restart)
+                    .await
+                    .unwrap();
+            writer
+                .write(LogStream::Stdout, clock.new_timestamp(), line)
+                .await
+                .unwrap();
+            if restart {
+                assert_eq!(
+                    read_logs(dir.path(), &dataflow_id, &node_id, LogFormat::Text, None)
+                        .await
+                        .unwrap(),
+                    b"1\n2\n"
+                );
+            }
+        }
+        assert_eq!(
+            read_logs(dir.path(), &dataflow_id, &node_id, LogFormat::Text, None)
+                .await
+                .unwrap(),
+            b"3\n"
+        );
+    }
+
+    #[test]
+    fn detect_level_token() {
+        assert_eq!(detect_level("ERROR: out of memory"), Some("ERROR"));
+        assert_eq!(detect_level("WARNING:root:disk almost full"), Some("WARN"));
+        assert_eq!(
+            detect_level("[2024-01-01T12:00:00Z INFO  my_crate] started"),
+            Some("INFO")
+        );
+        assert_eq!(detect_level("  DEBUG value = 3"), Some("DEBUG"));
+        // level words in the message are not the level of the line
+        assert_eq!(detect_level("received ERROR code from device"), None);
+        assert_eq!(detect_level("[my_crate] INFO started"), None);
+        assert_eq!(detect_level("INFORMATION"), None);
+        assert_eq!(detect_level(""), None);
+    }
+
+    #[tokio::test]
+    async fn rotate_keeps_max_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let dataflow_id = Uuid::new_v4();
+        let node_id: NodeId = "node".to_string().into();
+        std::fs::create_dir_all(dataflow_log_dir(dir.path(), &dataflow_id)).unwrap();
+        let config = LoggingConfig {
+            rotation: Some(LogRotation {
+                max_bytes: Some(4),
+                max_age_secs: None,
+                max_files: 2,
+            }),
+            ..Default::default()
+        };
+        let mut writer =
+            NodeLogWriter::create(dir.path(), dataflow_id, node_id.clone(), &config, false)
+                .await
+                .unwrap();
+        let clock = uhlc::HLC::default();
+        for line in ["1111\n", "2222\n", "3333\n", "44\n"] {
+            writer
+                .write(LogStream::Stdout, clock.new_timestamp(), line)
+                .await
+                .unwrap();
+        }
+
+        let path =
+            |index| rotated_log_path(dir.path(), &dataflow_id, &node_id, LogFormat::Text, index);
+        let read = |index| std::fs::read_to_string(path(index)).unwrap();
+        assert_eq!(read(0), "44\n");
+        assert_eq!(read(1), "3333\n");
+        assert_eq!(read(2), "2222\n");
+        assert!(!path(3).exists());
+        assert_eq!(
+            read_logs(dir.path(), &dataflow_id, &node_id, LogFormat::Text, None)
+                .await
+                .unwrap(),
+            b"2222\n3333\n44\n"
+        );
+    }
+
+    #[test]
+    fn retention_skips_kept_and_running_dataflows() {
+        let dir = tempfile::tempdir().unwrap();
+        let old = SystemTime::now() - Duration::from_secs(3600);
+        let set_modified = |path: &Path, modified| {
+            std::fs::File::options()
+                .read(true)
+                .open(path)
+                .unwrap()
+                .set_modified(modified)
+                .unwrap();
+        };
+        let [kept, running, stale, too_old, recent] = [(); 5].map(|()| Uuid::new_v4());
+        for id in [kept, running, stale, too_old, recent] {
+            std::fs::create_dir_all(dataflow_log_dir(dir.path(), &id)).unwrap();
+        }
+        mark_running(dir.path(), &running).unwrap();
+        mark_running(dir.path(), &stale).unwrap();
+        set_modified(
+            &dataflow_log_dir(dir.path(), &stale).join(RUNNING_MARKER),
+            old,
+        );
+        for id in [kept, running, stale, too_old] {
+            set_modified(&dataflow_log_dir(dir.path(), &id), old);
+        }
+
+        let retention = LogRetention {
+            max_dataflows: None,
+            max_age_secs: Some(60),
+        };
+        apply_retention(dir.path(), &retention, &BTreeSet::from([kept])).unwrap();
+
+        let exists = |id| dataflow_log_dir(dir.path(), &id).exists();
+        assert!(exists(kept));
+        assert!(exists(running));
+        assert!(!exists(stale));
+        assert!(!exists(too_old));
+        assert!(exists(recent));
+
+        unmark_running(dir.path(), &running);
+        set_modified(&dataflow_log_dir(dir.path(), &running), old);
+        apply_retention(dir.path(), &retention, &BTreeSet::new()).unwrap();
+        assert!(!exists(running));
+    }
+
+    fn parse(parser: LogParser, line: &str) -> (Option<&'static str>, Option<&str>, &str) {
+        let parsed = parse_log_line(parser, line);
+        (parsed.level, parsed.target, parsed.message)
+    }
+
+    #[test]
+    fn parse_log_lines() {
+        assert_eq!(
+            parse(
+                LogParser::EnvLogger,
+                "[2024-01-01T12:00:00Z WARN  my_crate::module] disk almost full"
+            ),
+            (Some("WARN"), Some("my_crate::module"), "disk almost full")
+        );
+        assert_eq!(
+            parse(LogParser::EnvLogger, "[INFO ] started"),
+            (Some("INFO"), None, "started")
+        );
+        assert_eq!(
+            parse(LogParser::Python, "CRITICAL:root:out of memory: exiting"),
+            (Some("ERROR"), Some("root"), "out of memory: exiting")
+        );
+        assert_eq!(
+            parse(LogParser::Python, "[INFO my_crate] not python"),
+            (None, None, "[INFO my_crate] not python")
+        );
+    }
 }
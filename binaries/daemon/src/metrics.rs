@@ -0,0 +1,178 @@
+//! Sampling of the resource usage and message statistics of running nodes.
+
+use crate::RunningDataflow;
+use dora_core::{config::NodeId, coordinator_messages::NodeMetrics, daemon_messages::DataflowId};
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::{Duration, Instant},
+};
+use sysinfo::{Pid, ProcessRefreshKind};
+
+/// Interval in which node metrics are reported to the coordinator.
+pub const METRICS_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Message counters of a node, used to calculate message rates.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NodeCounters {
+    pub outputs: u64,
+    pub inputs: u64,
+    pub dropped_inputs: u64,
+}
+
+/// Counter values of a node at the time of a sample.
+#[derive(Debug, Default, Clone, Copy)]
+struct NodeSample {
+    counters: NodeCounters,
+    /// Total number of bytes that the node process read from disk.
+    disk_read_bytes: u64,
+    /// Total number of bytes that the node process wrote to disk.
+    disk_written_bytes: u64,
+}
+
+impl NodeSample {
+    /// Sets the rates of the given metrics to the change since the `previous` sample,
+    /// which was taken `elapsed` ago.
+    fn set_rates(&self, previous: &NodeSample, elapsed: Duration, metrics: &mut NodeMetrics) {
+        let rate = |current, previous| rate(current, previous, elapsed);
+        metrics.outputs_per_sec = rate(self.counters.outputs, previous.counters.outputs);
+        metrics.inputs_per_sec = rate(self.counters.inputs, previous.counters.inputs);
+        metrics.disk_read_bytes_per_sec = rate(self.disk_read_bytes, previous.disk_read_bytes);
+        metrics.disk_written_bytes_per_sec =
+            rate(self.disk_written_bytes, previous.disk_written_bytes);
+    }
+}
+
+/// Calculates the rate per second of a counter that changed from `previous` to
+/// `current` within `elapsed`.
+///
+/// A counter that is lower than before was reset in between, e.g. because the node
+/// was restarted, so its whole value counts as change.
+fn rate(current: u64, previous: u64, elapsed: Duration) -> f64 {
+    let change = current.checked_sub(previous).unwrap_or(current);
+    change as f64 / elapsed.as_secs_f64().max(f64::EPSILON)
+}
+
+pub struct MetricsSampler {
+    system: sysinfo::System,
+    last_sample: Instant,
+    last_samples: HashMap<(DataflowId, NodeId), NodeSample>,
+}
+
+impl MetricsSampler {
+    pub fn new() -> Self {
+        Self {
+            system: sysinfo::System::new(),
+            last_sample: Instant::now(),
+            last_samples: HashMap::new(),
+        }
+    }
+
+    /// Samples the metrics of all local nodes of the given dataflows.
+    ///
+    /// Rates are calculated over the time since the previous sample.
+    pub fn sample(
+        &mut self,
+        running: &HashMap<DataflowId, RunningDataflow>,
+    ) -> BTreeMap<DataflowId, BTreeMap<NodeId, NodeMetrics>> {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_sample);
+        self.last_sample = now;
+
+        let pids: Vec<_> = running
+            .values()
+            .flat_map(|dataflow| dataflow.running_nodes.values())
+            .filter_map(|node| node.node_pid())
+            .map(|pid| Pid::from(pid as usize))
+            .collect();
+        self.system.refresh_pids_specifics(
+            &pids,
+            ProcessRefreshKind::new()
+                .with_cpu()
+                .with_memory()
+                .with_disk_usage(),
+        );
+
+        let mut last_samples = HashMap::new();
+        let mut metrics = BTreeMap::new();
+        for (&dataflow_id, dataflow) in running {
+            let mut nodes = BTreeMap::new();
+            for (node_id, node) in &dataflow.running_nodes {
+                let key = (dataflow_id, node_id.clone());
+                let counters = dataflow
+                    .node_counters
+                    .get(node_id)
+                    .copied()
+                    .unwrap_or_default();
+                let mut sample = NodeSample {
+                    counters,
+                    ..Default::default()
+                };
+                let mut node_metrics = NodeMetrics {
+                    dropped_inputs: counters.dropped_inputs,
+                    ..Default::default()
+                };
+                let process = node
+                    .node_pid()
+                    .and_then(|pid| self.system.process(Pid::from(pid as usize)));
+                if let Some(process) = process {
+                    let disk_usage = process.disk_usage();
+                    node_metrics.cpu_usage = process.cpu_usage();
+                    node_metrics.memory_bytes = process.memory();
+                    node_metrics.threads = process.tasks().map(|tasks| tasks.len());
+                    sample.disk_read_bytes = disk_usage.total_read_bytes;
+                    sample.disk_written_bytes = disk_usage.total_written_bytes;
+                }
+                // nodes that started since the last sample count from zero
+                let previous = self.last_samples.get(&key).copied().unwrap_or_default();
+                sample.set_rates(&previous, elapsed, &mut node_metrics);
+
+                nodes.insert(node_id.clone(), node_metrics);
+                last_samples.insert(key, sample);
+            }
+            metrics.insert(dataflow_id, nodes);
+        }
+        self.last_samples = last_samples;
+        metrics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rates_are_calculated_over_elapsed_time() {
+        let elapsed = Duration::from_millis(2500);
+        assert_eq!(rate(15, 5, elapsed), 4.0);
+        assert_eq!(rate(5, 5, elapsed), 0.0);
+        // reset counters, e.g. of a restarted node
+        assert_eq!(rate(10, 50, elapsed), 4.0);
+        // no division by zero
+        assert!(rate(10, 0, Duration::ZERO).is_finite());
+
+        let previous = NodeSample {
+            counters: NodeCounters {
+                outputs: 10,
+                inputs: 100,
+                dropped_inputs: 0,
+            },
+            disk_read_bytes: 1024,
+            disk_written_bytes: 0,
+        };
+        let current = NodeSample {
+            counters: NodeCounters {
+                outputs: 30,
+                inputs: 100,
+                dropped_inputs: 3,
+            },
+            disk_read_bytes: 1024 + 4096,
+            disk_written_bytes: 2048,
+        };
+        let mut metrics = NodeMetrics::default();
+        current.set_rates(&previous, Duration::from_secs(2), &mut metrics);
+        assert_eq!(metrics.outputs_per_sec, 10.0);
+        assert_eq!(metrics.inputs_per_sec, 0.0);
+        assert_eq!(metrics.disk_read_bytes_per_sec, 2048.0);
+        assert_eq!(metrics.disk_written_bytes_per_sec, 1024.0);
+    }
+}
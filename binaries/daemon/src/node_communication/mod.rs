@@ -6,6 +6,7 @@ use dora_core::{
         Timestamped,
     },
     message::uhlc,
+    protocol::NODE_DAEMON_PROTOCOL,
     topics::LOCALHOST,
 };
 use eyre::{eyre, Context};
@@ -28,6 +29,8 @@ use tokio::{
 // TODO unify and avoid duplication;
 pub mod shmem;
 pub mod tcp;
+#[cfg(unix)]
+pub mod unix_domain;
 
 pub async fn spawn_listener_loop(
     dataflow_id: &DataflowId,
@@ -138,6 +141,23 @@ pub async fn spawn_listener_loop(
                 daemon_events_close_region_id,
             })
         }
+        #[cfg(unix)]
+        LocalCommunicationConfig::UnixDomain => {
+            let (socket, socket_file) = unix_domain::bind(dataflow_id, node_id)?;
+
+            let event_loop_node_id = format!("{dataflow_id}/{node_id}");
+            let daemon_tx = daemon_tx.clone();
+            tokio::spawn(async move {
+                unix_domain::listener_loop(socket, daemon_tx, queue_sizes, clock).await;
+                tracing::debug!("event listener loop finished for `{event_loop_node_id}`");
+            });
+
+            Ok(DaemonCommunication::UnixDomain { socket_file })
+        }
+        #[cfg(not(unix))]
+        LocalCommunicationConfig::UnixDomain => {
+            eyre::bail!("communication via unix domain sockets is only supported on unix systems")
+        }
     }
 }
 
@@ -149,6 +169,9 @@ struct Listener {
     subscribed_drop_events: Option<UnboundedReceiver<Timestamped<NodeDropEvent>>>,
     queue: VecDeque<Box<Option<Timestamped<NodeEvent>>>>,
     queue_sizes: BTreeMap<DataId, usize>,
+    /// Dropped inputs that could not be reported to the daemon yet because its
+    /// event channel was full.
+    unreported_dropped_inputs: u64,
     clock: Arc<uhlc::HLC>,
 }
 
@@ -171,7 +194,18 @@ impl Listener {
                 return;
             } // disconnected
             Err(err) => {
-                tracing::info!("{err:?}");
+                // the register message layout is kept stable across versions, so a
+                // decoding failure most likely means that the node uses an
+                // incompatible protocol version
+                let err = err.wrap_err(format!(
+                    "failed to decode register message (the node probably uses a dora \
+                    node API that is incompatible with daemon v{})",
+                    env!("CARGO_PKG_VERSION")
+                ));
+                tracing::warn!("{err:?}");
+                let _ = connection
+                    .send_reply(DaemonReply::Result(Err(format!("{err:?}"))))
+                    .await;
                 return;
             }
         };
@@ -185,16 +219,21 @@ impl Listener {
                 dataflow_id,
                 node_id,
                 dora_version: node_api_version,
+                protocol_version,
             } => {
-                let daemon_version = env!("CARGO_PKG_VERSION");
-                let result = if node_api_version == daemon_version {
-                    Ok(())
-                } else {
-                    Err(format!(
-                        "version mismatch: node API v{node_api_version} is not compatible \
-                        with daemon v{daemon_version}"
-                    ))
-                };
+                let result = NODE_DAEMON_PROTOCOL
+                    .negotiate(
+                        &protocol_version,
+                        "daemon",
+                        env!("CARGO_PKG_VERSION"),
+                        "node API",
+                        &node_api_version,
+                    )
+                    .map(|version| {
+                        tracing::debug!(
+                            "node {dataflow_id}/{node_id} uses protocol version {version}"
+                        );
+                    });
                 let send_result = connection
                     .send_reply(DaemonReply::Result(result.clone()))
                     .await
@@ -209,6 +248,7 @@ impl Listener {
                             subscribed_drop_events: None,
                             queue_sizes,
                             queue: VecDeque::new(),
+                            unreported_dropped_inputs: 0,
                             clock: hlc.clone(),
                         };
                         match listener
@@ -328,8 +368,39 @@ impl Listener {
                 "dropped {dropped} inputs of node `{}` because event queue was too full",
                 self.node_id
             );
+            self.unreported_dropped_inputs += dropped;
+        }
+        self.report_dropped_inputs()
+    }
+
+    /// Reports the dropped inputs to the daemon without waiting.
+    ///
+    /// Waiting for the daemon would delay the delivery of the remaining inputs, so the
+    /// count is kept for the next attempt if the daemon's event channel is full.
+    fn report_dropped_inputs(&mut self) -> eyre::Result<()> {
+        if self.unreported_dropped_inputs == 0 {
+            return Ok(());
+        }
+        let event = Timestamped {
+            inner: Event::Node {
+                dataflow_id: self.dataflow_id,
+                node_id: self.node_id.clone(),
+                event: DaemonNodeEvent::InputsDropped {
+                    count: self.unreported_dropped_inputs,
+                },
+            },
+            timestamp: self.clock.new_timestamp(),
+        };
+        match self.daemon_tx.try_send(event) {
+            Ok(()) => {
+                self.unreported_dropped_inputs = 0;
+                Ok(())
+            }
+            Err(mpsc::error::TrySendError::Full(_)) => Ok(()),
+            Err(mpsc::error::TrySendError::Closed(_)) => {
+                Err(eyre!("failed to report dropped inputs to daemon"))
+            }
         }
-        Ok(())
     }
 
     #[tracing::instrument(skip(self, connection), fields(%self.dataflow_id, %self.node_id), level = "trace")]
@@ -574,3 +645,72 @@ trait Connection {
     async fn receive_message(&mut self) -> eyre::Result<Option<Timestamped<DaemonRequest>>>;
     async fn send_reply(&mut self, message: DaemonReply) -> eyre::Result<()>;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dora_core::message::{ArrowTypeInfo, Metadata};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn dropped_inputs_are_reported_without_waiting() {
+        let (daemon_tx, mut daemon_rx) = mpsc::channel(1);
+        let image = DataId::from("image".to_owned());
+        let clock = Arc::new(uhlc::HLC::default());
+        let mut listener = Listener {
+            dataflow_id: DataflowId::new_v4(),
+            node_id: NodeId::from("plot".to_owned()),
+            daemon_tx: daemon_tx.clone(),
+            subscribed_events: None,
+            subscribed_drop_events: None,
+            queue: VecDeque::new(),
+            queue_sizes: BTreeMap::from([(image.clone(), 1)]),
+            unreported_dropped_inputs: 0,
+            clock: clock.clone(),
+        };
+        let push_inputs = |listener: &mut Listener, count| {
+            for _ in 0..count {
+                let timestamp = clock.new_timestamp();
+                listener.queue.push_back(Box::new(Some(Timestamped {
+                    inner: NodeEvent::Input {
+                        id: image.clone(),
+                        metadata: Metadata::new(timestamp, ArrowTypeInfo::empty()),
+                        data: None,
+                    },
+                    timestamp,
+                })));
+            }
+        };
+        let dropped_count = |event: Timestamped<Event>| match event.inner {
+            Event::Node {
+                event: DaemonNodeEvent::InputsDropped { count },
+                ..
+            } => count,
+            _ => panic!("unexpected event"),
+        };
+
+        // fill the daemon channel, so that the report can't be sent
+        daemon_tx
+            .try_send(Timestamped {
+                inner: Event::CtrlC,
+                timestamp: clock.new_timestamp(),
+            })
+            .unwrap();
+        push_inputs(&mut listener, 3);
+        tokio::time::timeout(Duration::from_secs(1), listener.drop_oldest_inputs())
+            .await
+            .expect("dropping inputs waited for the daemon")
+            .unwrap();
+        assert_eq!(listener.unreported_dropped_inputs, 2);
+
+        // the count is reported with the next dropped inputs
+        assert!(matches!(
+            daemon_rx.recv().await.unwrap().inner,
+            Event::CtrlC
+        ));
+        push_inputs(&mut listener, 1);
+        listener.drop_oldest_inputs().await.unwrap();
+        assert_eq!(listener.unreported_dropped_inputs, 0);
+        assert_eq!(dropped_count(daemon_rx.recv().await.unwrap()), 3);
+    }
+}
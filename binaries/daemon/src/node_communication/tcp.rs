@@ -12,9 +12,11 @@ use dora_core::{
 };
 use eyre::Context;
 use tokio::{
-    net::{TcpListener, TcpStream},
+    io::{AsyncRead, AsyncWrite},
+    net::TcpListener,
     sync::mpsc,
 };
+use tokio_stream::{wrappers::TcpListenerStream, Stream, StreamExt};
 
 #[tracing::instrument(skip(listener, daemon_tx, clock), level = "trace")]
 pub async fn listener_loop(
@@ -23,16 +25,33 @@ pub async fn listener_loop(
     queue_sizes: BTreeMap<DataId, usize>,
     clock: Arc<HLC>,
 ) {
-    loop {
-        match listener
-            .accept()
-            .await
-            .wrap_err("failed to accept new connection")
-        {
+    let incoming = TcpListenerStream::new(listener).map(|connection| {
+        let connection = connection?;
+        if let Err(err) = connection.set_nodelay(true) {
+            tracing::warn!("failed to set nodelay for connection: {err}");
+        }
+        Ok(connection)
+    });
+    accept_loop(incoming, daemon_tx, queue_sizes, clock).await
+}
+
+/// Handles each incoming connection in a separate task.
+///
+/// Used for all stream-based transports, i.e. TCP and unix domain sockets.
+pub(super) async fn accept_loop<S>(
+    mut incoming: impl Stream<Item = std::io::Result<S>> + Unpin,
+    daemon_tx: mpsc::Sender<Timestamped<Event>>,
+    queue_sizes: BTreeMap<DataId, usize>,
+    clock: Arc<HLC>,
+) where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    while let Some(connection) = incoming.next().await {
+        match connection.wrap_err("failed to accept new connection") {
             Err(err) => {
                 tracing::info!("{err}");
             }
-            Ok((connection, _)) => {
+            Ok(connection) => {
                 tokio::spawn(handle_connection_loop(
                     connection,
                     daemon_tx.clone(),
@@ -45,23 +64,24 @@ pub async fn listener_loop(
 }
 
 #[tracing::instrument(skip(connection, daemon_tx, clock), level = "trace")]
-async fn handle_connection_loop(
-    connection: TcpStream,
+async fn handle_connection_loop<S>(
+    connection: S,
     daemon_tx: mpsc::Sender<Timestamped<Event>>,
     queue_sizes: BTreeMap<DataId, usize>,
     clock: Arc<HLC>,
-) {
-    if let Err(err) = connection.set_nodelay(true) {
-        tracing::warn!("failed to set nodelay for connection: {err}");
-    }
-
-    Listener::run(TcpConnection(connection), daemon_tx, queue_sizes, clock).await
+) where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    Listener::run(StreamConnection(connection), daemon_tx, queue_sizes, clock).await
 }
 
-struct TcpConnection(TcpStream);
+struct StreamConnection<S>(S);
 
 #[async_trait::async_trait]
-impl Connection for TcpConnection {
+impl<S> Connection for StreamConnection<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send,
+{
     async fn receive_message(&mut self) -> eyre::Result<Option<Timestamped<DaemonRequest>>> {
         let raw = match tcp_receive(&mut self.0).await {
             Ok(raw) => raw,
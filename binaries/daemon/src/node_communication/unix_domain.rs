@@ -0,0 +1,219 @@
+use std::{
+    collections::BTreeMap,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use super::tcp;
+use crate::Event;
+use dora_core::{
+    config::{DataId, NodeId},
+    daemon_messages::{DataflowId, Timestamped},
+    message::uhlc::HLC,
+};
+use eyre::Context;
+use tokio::{net::UnixListener, sync::mpsc};
+use tokio_stream::wrappers::UnixListenerStream;
+
+/// Returns the private directory that contains the node sockets of the given dataflow.
+pub fn socket_dir(dataflow_id: &DataflowId) -> PathBuf {
+    socket_root().join(dataflow_id.to_string())
+}
+
+/// Returns the per-user directory that contains the socket directories of all dataflows.
+///
+/// Uses `$XDG_RUNTIME_DIR/dora` if set, and `<tmp>/dora-<uid>` otherwise, so that the
+/// sockets of different users never share a parent directory.
+fn socket_root() -> PathBuf {
+    match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir).join("dora"),
+        _ => {
+            let uid = unsafe { libc::getuid() };
+            std::env::temp_dir().join(format!("dora-{uid}"))
+        }
+    }
+}
+
+/// Creates a new unix domain socket for the given node.
+///
+/// The socket is placed in a per-dataflow directory that is only accessible by
+/// the user running the daemon (and thus its spawned nodes).
+pub fn bind(dataflow_id: &DataflowId, node_id: &NodeId) -> eyre::Result<(UnixListener, PathBuf)> {
+    create_private_dir(&socket_root())?;
+    let dir = socket_dir(dataflow_id);
+    create_private_dir(&dir)?;
+
+    let socket_file = dir.join(format!("{node_id}.sock"));
+    if socket_file.exists() {
+        std::fs::remove_file(&socket_file).wrap_err_with(|| {
+            format!("failed to remove stale socket `{}`", socket_file.display())
+        })?;
+    }
+    let socket = UnixListener::bind(&socket_file).wrap_err_with(|| {
+        format!(
+            "failed to create unix domain socket at `{}`",
+            socket_file.display()
+        )
+    })?;
+    set_permissions(&socket_file, 0o600)?;
+
+    Ok((socket, socket_file))
+}
+
+/// Removes the socket directory of the given dataflow, if it exists.
+pub fn remove_socket_dir(dataflow_id: &DataflowId) {
+    let dir = socket_dir(dataflow_id);
+    if dir.exists() {
+        if let Err(err) = std::fs::remove_dir_all(&dir) {
+            tracing::warn!(
+                "failed to remove unix domain socket dir `{}`: {err}",
+                dir.display()
+            );
+        }
+    }
+}
+
+/// Creates the given directory with mode `0700`.
+///
+/// An existing directory is only accepted if it is a real directory (not a symlink)
+/// that is owned by the current user and not accessible by anyone else. Otherwise
+/// another local user could have prepared it to intercept the node sockets.
+pub(crate) fn create_private_dir(dir: &Path) -> eyre::Result<()> {
+    use std::os::unix::fs::DirBuilderExt;
+
+    match std::fs::DirBuilder::new().mode(0o700).create(dir) {
+        Ok(()) => Ok(()),
+        Err(err) if err.kind() == ErrorKind::AlreadyExists => check_private_dir(dir),
+        Err(err) => Err(err).wrap_err_with(|| format!("failed to create `{}`", dir.display())),
+    }
+}
+
+fn check_private_dir(dir: &Path) -> eyre::Result<()> {
+    use std::os::unix::fs::MetadataExt;
+
+    let metadata = std::fs::symlink_metadata(dir)
+        .wrap_err_with(|| format!("failed to read metadata of `{}`", dir.display()))?;
+    if !metadata.file_type().is_dir() {
+        eyre::bail!("`{}` exists but is not a directory", dir.display());
+    }
+    let uid = unsafe { libc::getuid() };
+    if metadata.uid() != uid {
+        eyre::bail!(
+            "`{}` is owned by uid {} instead of the current user (uid {uid})",
+            dir.display(),
+            metadata.uid()
+        );
+    }
+    let mode = metadata.mode() & 0o777;
+    if mode != 0o700 {
+        eyre::bail!(
+            "`{}` has mode {mode:o}, but must only be accessible by its owner (700)",
+            dir.display()
+        );
+    }
+    Ok(())
+}
+
+fn set_permissions(path: &Path, mode: u32) -> eyre::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+        .wrap_err_with(|| format!("failed to set permissions of `{}`", path.display()))
+}
+
+pub async fn listener_loop(
+    listener: UnixListener,
+    daemon_tx: mpsc::Sender<Timestamped<Event>>,
+    queue_sizes: BTreeMap<DataId, usize>,
+    clock: Arc<HLC>,
+) {
+    let incoming = UnixListenerStream::new(listener);
+    tcp::accept_loop(incoming, daemon_tx, queue_sizes, clock).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tcp_utils::{tcp_receive, tcp_send};
+    use dora_core::{
+        daemon_messages::{DaemonReply, DaemonRequest},
+        protocol::NODE_DAEMON_PROTOCOL,
+    };
+    use std::os::unix::fs::PermissionsExt;
+    use tokio::net::UnixStream;
+    use uuid::{NoContext, Timestamp, Uuid};
+
+    fn mode(path: &Path) -> u32 {
+        std::fs::metadata(path).unwrap().permissions().mode() & 0o777
+    }
+
+    #[tokio::test]
+    async fn register_over_unix_socket() {
+        let dataflow_id = Uuid::new_v7(Timestamp::now(NoContext));
+        let node_id = NodeId::from("node".to_owned());
+        let (listener, socket_file) = bind(&dataflow_id, &node_id).unwrap();
+        assert_eq!(mode(&socket_dir(&dataflow_id)), 0o700);
+        assert_eq!(mode(&socket_file), 0o600);
+
+        let (daemon_tx, _daemon_rx) = mpsc::channel(1);
+        let clock = Arc::new(HLC::default());
+        tokio::spawn(listener_loop(
+            listener,
+            daemon_tx,
+            BTreeMap::new(),
+            clock.clone(),
+        ));
+
+        let mut stream = UnixStream::connect(&socket_file).await.unwrap();
+        let register = Timestamped {
+            inner: DaemonRequest::Register {
+                dataflow_id,
+                node_id,
+                dora_version: env!("CARGO_PKG_VERSION").to_owned(),
+                protocol_version: NODE_DAEMON_PROTOCOL.version(),
+            },
+            timestamp: clock.new_timestamp(),
+        };
+        tcp_send(&mut stream, &bincode::serialize(&register).unwrap())
+            .await
+            .unwrap();
+        let reply: DaemonReply =
+            bincode::deserialize(&tcp_receive(&mut stream).await.unwrap()).unwrap();
+        assert!(matches!(reply, DaemonReply::Result(Ok(()))));
+
+        remove_socket_dir(&dataflow_id);
+        assert!(!socket_dir(&dataflow_id).exists());
+    }
+
+    #[test]
+    fn existing_dirs_must_be_private() {
+        use std::os::unix::fs::DirBuilderExt;
+
+        let temp = tempfile::tempdir().unwrap();
+
+        let private = temp.path().join("private");
+        create_private_dir(&private).unwrap();
+        assert_eq!(mode(&private), 0o700);
+        // an existing private directory is reused
+        create_private_dir(&private).unwrap();
+
+        let shared = temp.path().join("shared");
+        std::fs::DirBuilder::new()
+            .mode(0o755)
+            .create(&shared)
+            .unwrap();
+        std::fs::set_permissions(&shared, std::fs::Permissions::from_mode(0o777)).unwrap();
+        assert!(create_private_dir(&shared).is_err());
+        // the permissions of the foreign directory are left alone
+        assert_eq!(mode(&shared), 0o777);
+
+        let link = temp.path().join("link");
+        std::os::unix::fs::symlink(&private, &link).unwrap();
+        assert!(create_private_dir(&link).is_err());
+
+        let file = temp.path().join("file");
+        std::fs::write(&file, "").unwrap();
+        assert!(create_private_dir(&file).is_err());
+    }
+}
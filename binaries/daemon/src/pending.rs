@@ -6,8 +6,9 @@ use dora_core::{
     daemon_messages::{DaemonReply, DataflowId, Timestamped},
     message::uhlc::{Timestamp, HLC},
 };
-use eyre::{bail, Context};
-use tokio::{net::TcpStream, sync::oneshot};
+use dora_transport_security::AsyncStream;
+use eyre::bail;
+use tokio::sync::oneshot;
 
 use crate::{tcp_utils::tcp_send, CascadingErrorCauses};
 
@@ -59,7 +60,7 @@ impl PendingNodes {
         &mut self,
         node_id: NodeId,
         reply_sender: oneshot::Sender<DaemonReply>,
-        coordinator_connection: &mut Option<TcpStream>,
+        coordinator_connection: &mut Option<AsyncStream>,
         clock: &HLC,
         cascading_errors: &mut CascadingErrorCauses,
     ) -> eyre::Result<DataflowStatus> {
@@ -74,7 +75,7 @@ impl PendingNodes {
     pub async fn handle_node_stop(
         &mut self,
         node_id: &NodeId,
-        coordinator_connection: &mut Option<TcpStream>,
+        coordinator_connection: &mut Option<AsyncStream>,
         clock: &HLC,
         cascading_errors: &mut CascadingErrorCauses,
     ) -> eyre::Result<Vec<LogMessage>> {
@@ -114,7 +115,7 @@ impl PendingNodes {
 
     async fn update_dataflow_status(
         &mut self,
-        coordinator_connection: &mut Option<TcpStream>,
+        coordinator_connection: &mut Option<AsyncStream>,
         clock: &HLC,
         cascading_errors: &mut CascadingErrorCauses,
     ) -> eyre::Result<DataflowStatus> {
@@ -129,6 +130,8 @@ impl PendingNodes {
             } else {
                 self.answer_subscribe_requests(Vec::new(), cascading_errors)
                     .await;
+                self.report_dataflow_ready(coordinator_connection, clock.new_timestamp())
+                    .await?;
                 Ok(DataflowStatus::AllNodesReady)
             }
         } else {
@@ -170,11 +173,12 @@ impl PendingNodes {
 
     async fn report_nodes_ready(
         &self,
-        coordinator_connection: &mut Option<TcpStream>,
+        coordinator_connection: &mut Option<AsyncStream>,
         timestamp: Timestamp,
     ) -> eyre::Result<()> {
         let Some(connection) = coordinator_connection else {
-            bail!("no coordinator connection to send AllNodesReady");
+            tracing::warn!("no coordinator connection to send AllNodesReady");
+            return Ok(());
         };
 
         tracing::info!(
@@ -192,9 +196,37 @@ impl PendingNodes {
             },
             timestamp,
         })?;
-        tcp_send(connection, &msg)
-            .await
-            .wrap_err("failed to send AllNodesReady message to dora-coordinator")?;
+        if let Err(err) = tcp_send(connection, &msg).await {
+            tracing::warn!("failed to send AllNodesReady message to dora-coordinator: {err}");
+            // the daemon reconnects on the next heartbeat interval
+            *coordinator_connection = None;
+        }
+        Ok(())
+    }
+
+    /// Informs the coordinator that all nodes of a dataflow without remote nodes are ready.
+    async fn report_dataflow_ready(
+        &self,
+        coordinator_connection: &mut Option<AsyncStream>,
+        timestamp: Timestamp,
+    ) -> eyre::Result<()> {
+        let Some(connection) = coordinator_connection else {
+            return Ok(());
+        };
+        let msg = serde_json::to_vec(&Timestamped {
+            inner: CoordinatorRequest::Event {
+                machine_id: self.machine_id.clone(),
+                event: DaemonEvent::DataflowReady {
+                    dataflow_id: self.dataflow_id,
+                    exited_before_subscribe: self.exited_before_subscribe.clone(),
+                },
+            },
+            timestamp,
+        })?;
+        if let Err(err) = tcp_send(connection, &msg).await {
+            tracing::warn!("failed to send DataflowReady message to dora-coordinator: {err}");
+            *coordinator_connection = None;
+        }
         Ok(())
     }
 }
@@ -0,0 +1,417 @@
+//! Isolation of nodes that set the `sandbox` option.
+
+use dora_core::{
+    config::LocalCommunicationConfig,
+    daemon_messages::{DaemonCommunication, NodeConfig},
+    descriptor::SandboxConfig,
+};
+use eyre::{bail, Context, ContextCompat};
+use std::{
+    ffi::OsStr,
+    path::{Path, PathBuf},
+};
+use tokio::process::Command;
+
+#[cfg(target_os = "linux")]
+mod namespaces;
+
+/// Directory of the POSIX shared memory regions.
+///
+/// Sandboxed nodes get a private directory, which only contains the regions for
+/// communicating with the daemon and the shared memory directory of the dataflow
+/// (see `NodeConfig::shared_memory_dir`).
+const SHARED_MEMORY_DIR: &str = "/dev/shm";
+
+/// Returns the local communication that a sandboxed node uses to reach the daemon.
+///
+/// Nodes in the built-in sandbox without network access can't reach the daemon over
+/// TCP, so they use a Unix domain socket instead.
+pub fn local_communication(
+    config: &SandboxConfig,
+    local: LocalCommunicationConfig,
+) -> LocalCommunicationConfig {
+    let isolated_network = config.wrapper.is_none() && !config.network;
+    if isolated_network && local == LocalCommunicationConfig::Tcp && cfg!(unix) {
+        LocalCommunicationConfig::UnixDomain
+    } else {
+        local
+    }
+}
+
+/// Returns the PID of the sandboxed node process.
+///
+/// The built-in sandbox starts an init process in a child of the spawned process,
+/// which stays outside of the sandbox to supervise it. The node is a child of the init
+/// process. Returns `None` if the node process was not started yet or already exited.
+#[cfg(target_os = "linux")]
+pub fn sandboxed_pid(supervisor_pid: u32) -> Option<u32> {
+    let first_child = |pid: u32| -> Option<u32> {
+        let children = std::fs::read_to_string(format!("/proc/{pid}/task/{pid}/children")).ok()?;
+        children.split_whitespace().next()?.parse().ok()
+    };
+    first_child(first_child(supervisor_pid)?)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn sandboxed_pid(_supervisor_pid: u32) -> Option<u32> {
+    None
+}
+
+/// Prepares the given node command to run in a sandbox.
+///
+/// Returns a new command if the sandbox is implemented by a `wrapper` command.
+pub fn sandbox_command(
+    command: Command,
+    config: &SandboxConfig,
+    working_dir: &Path,
+    node_config: &NodeConfig,
+) -> eyre::Result<Command> {
+    let paths = SandboxPaths::collect(
+        command.as_std(),
+        config,
+        working_dir,
+        &node_config.daemon_communication,
+        node_config.shared_memory_dir.as_deref(),
+    )?;
+    match &config.wrapper {
+        Some(wrapper) => wrap_command(command, wrapper, &paths),
+        None => isolate(command, config, working_dir, &paths),
+    }
+}
+
+/// The paths that a sandboxed node needs to access, in addition to system directories.
+struct SandboxPaths {
+    read_only: Vec<PathBuf>,
+    writable: Vec<PathBuf>,
+}
+
+impl SandboxPaths {
+    fn collect(
+        command: &std::process::Command,
+        config: &SandboxConfig,
+        working_dir: &Path,
+        communication: &DaemonCommunication,
+        shared_memory_dir: Option<&Path>,
+    ) -> eyre::Result<Self> {
+        let mut writable = vec![working_dir.to_owned()];
+        match communication {
+            DaemonCommunication::Shmem {
+                daemon_control_region_id,
+                daemon_drop_region_id,
+                daemon_events_region_id,
+                daemon_events_close_region_id,
+            } => writable.extend(
+                [
+                    daemon_control_region_id,
+                    daemon_drop_region_id,
+                    daemon_events_region_id,
+                    daemon_events_close_region_id,
+                ]
+                .map(|id| Path::new(SHARED_MEMORY_DIR).join(id.trim_start_matches('/'))),
+            ),
+            DaemonCommunication::UnixDomain { socket_file } => writable.push(socket_file.clone()),
+            DaemonCommunication::Tcp { .. } => {}
+        }
+        writable.extend(shared_memory_dir.map(Path::to_owned));
+        writable.extend(config.writable.iter().cloned());
+
+        // the node executable and files passed as arguments (e.g. Python scripts)
+        let mut read_only = Vec::new();
+        let program = Path::new(command.get_program());
+        let program = if program.components().count() > 1 {
+            Some(working_dir.join(program))
+        } else {
+            which::which(program).ok()
+        };
+        let args = command
+            .get_args()
+            .map(Path::new)
+            .filter(|arg| arg.components().count() > 1)
+            .map(|arg| working_dir.join(arg));
+        read_only.extend(
+            program
+                .into_iter()
+                .chain(args)
+                .filter(|path| path.exists() && !path.starts_with(working_dir)),
+        );
+        read_only.extend(config.read_only.iter().cloned());
+
+        for path in read_only.iter().chain(&writable) {
+            if !path.exists() {
+                bail!("sandbox path `{}` does not exist", path.display());
+            }
+        }
+
+        Ok(Self {
+            read_only,
+            writable,
+        })
+    }
+}
+
+/// Starts the node through the user-configured wrapper command.
+fn wrap_command(command: Command, wrapper: &str, paths: &SandboxPaths) -> eyre::Result<Command> {
+    let command = command.as_std();
+    let wrapper_args = shlex::split(wrapper).context("sandbox wrapper has unbalanced quotes")?;
+    let Some((wrapper_program, wrapper_args)) = wrapper_args.split_first() else {
+        bail!("sandbox wrapper must not be empty");
+    };
+
+    let mut wrapped = Command::new(wrapper_program);
+    wrapped.args(wrapper_args);
+    wrapped.arg(command.get_program());
+    wrapped.args(command.get_args());
+    if let Some(dir) = command.get_current_dir() {
+        wrapped.current_dir(dir);
+    }
+    for (key, value) in command.get_envs() {
+        match value {
+            Some(value) => wrapped.env(key, value),
+            None => wrapped.env_remove(key),
+        };
+    }
+    wrapped.env("DORA_SANDBOX_READ_ONLY", join_paths(&paths.read_only)?);
+    wrapped.env("DORA_SANDBOX_WRITABLE", join_paths(&paths.writable)?);
+    Ok(wrapped)
+}
+
+fn join_paths(paths: &[PathBuf]) -> eyre::Result<impl AsRef<OsStr>> {
+    std::env::join_paths(paths).context("sandbox paths must not contain `:`")
+}
+
+#[cfg(target_os = "linux")]
+fn isolate(
+    mut command: Command,
+    config: &SandboxConfig,
+    working_dir: &Path,
+    paths: &SandboxPaths,
+) -> eyre::Result<Command> {
+    let program = command.as_std().get_program().to_owned();
+    let namespaces = namespaces::Namespaces::new(config.network, working_dir, &program, paths)?;
+    // SAFETY: `enter` only performs system calls that are safe to use after `fork`
+    unsafe {
+        command.pre_exec(move || namespaces.enter());
+    }
+    Ok(command)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn isolate(
+    _command: Command,
+    _config: &SandboxConfig,
+    _working_dir: &Path,
+    _paths: &SandboxPaths,
+) -> eyre::Result<Command> {
+    bail!("the built-in node sandbox is only supported on Linux, set a sandbox `wrapper` instead")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn isolated_nodes_use_unix_domain_sockets() {
+        let isolated = SandboxConfig::default();
+        let with_network = SandboxConfig {
+            network: true,
+            ..Default::default()
+        };
+        let wrapped = SandboxConfig {
+            wrapper: Some("bwrap".into()),
+            ..Default::default()
+        };
+
+        let expected = if cfg!(unix) {
+            LocalCommunicationConfig::UnixDomain
+        } else {
+            LocalCommunicationConfig::Tcp
+        };
+        assert_eq!(
+            local_communication(&isolated, LocalCommunicationConfig::Tcp),
+            expected
+        );
+        assert_eq!(
+            local_communication(&isolated, LocalCommunicationConfig::Shmem),
+            LocalCommunicationConfig::Shmem
+        );
+        for config in [with_network, wrapped] {
+            assert_eq!(
+                local_communication(&config, LocalCommunicationConfig::Tcp),
+                LocalCommunicationConfig::Tcp
+            );
+        }
+    }
+
+    #[test]
+    fn wrapper_arguments_are_split_like_a_shell() {
+        let mut command = Command::new("node");
+        command
+            .arg("--flag")
+            .current_dir("/work")
+            .env("KEY", "value");
+        let paths = SandboxPaths {
+            read_only: vec![PathBuf::from("/a"), PathBuf::from("/b")],
+            writable: vec![PathBuf::from("/work")],
+        };
+
+        let wrapped = wrap_command(
+            command,
+            r#"bwrap --bind "/my dir" '/my dir' --die-with-parent"#,
+            &paths,
+        )
+        .unwrap();
+        let wrapped = wrapped.as_std();
+        assert_eq!(wrapped.get_program(), "bwrap");
+        assert_eq!(
+            wrapped.get_args().collect::<Vec<_>>(),
+            [
+                "--bind",
+                "/my dir",
+                "/my dir",
+                "--die-with-parent",
+                "node",
+                "--flag"
+            ]
+        );
+        assert_eq!(wrapped.get_current_dir(), Some(Path::new("/work")));
+        let envs: Vec<_> = wrapped.get_envs().collect();
+        assert!(envs.contains(&(OsStr::new("KEY"), Some(OsStr::new("value")))));
+        assert!(envs.contains(&(
+            OsStr::new("DORA_SANDBOX_READ_ONLY"),
+            Some(OsStr::new("/a:/b"))
+        )));
+
+        let command = || Command::new("node");
+        assert!(wrap_command(command(), "bwrap \"unbalanced", &paths).is_err());
+        assert!(wrap_command(command(), "  ", &paths).is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn only_daemon_regions_and_dataflow_dir_are_shared() {
+        let working_dir = tempfile::tempdir().unwrap();
+        let shared_memory_dir = tempfile::tempdir().unwrap();
+        let regions: Vec<_> = (0..4)
+            .map(|_| tempfile::NamedTempFile::new_in(SHARED_MEMORY_DIR).unwrap())
+            .collect();
+        let ids: Vec<_> = regions
+            .iter()
+            .map(|region| {
+                let name = region.path().file_name().unwrap().to_str().unwrap();
+                format!("/{name}")
+            })
+            .collect();
+        let communication = DaemonCommunication::Shmem {
+            daemon_control_region_id: ids[0].clone(),
+            daemon_drop_region_id: ids[1].clone(),
+            daemon_events_region_id: ids[2].clone(),
+            daemon_events_close_region_id: ids[3].clone(),
+        };
+
+        let paths = SandboxPaths::collect(
+            Command::new("sh").as_std(),
+            &SandboxConfig::default(),
+            working_dir.path(),
+            &communication,
+            Some(shared_memory_dir.path()),
+        )
+        .unwrap();
+        let mut expected = vec![working_dir.path().to_owned()];
+        expected.extend(regions.iter().map(|region| region.path().to_owned()));
+        expected.push(shared_memory_dir.path().to_owned());
+        assert_eq!(paths.writable, expected);
+        assert!(!paths
+            .read_only
+            .iter()
+            .chain(&paths.writable)
+            .any(|path| path == Path::new(SHARED_MEMORY_DIR)));
+    }
+
+    /// Starts the given shell script in the built-in sandbox.
+    ///
+    /// Returns `None` if namespaces are not available, e.g. in containers.
+    #[cfg(target_os = "linux")]
+    fn spawn_isolated(
+        script: &str,
+        working_dir: &Path,
+        shared_memory_dir: Option<&Path>,
+    ) -> Option<tokio::process::Child> {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(script).current_dir(working_dir);
+        let communication = DaemonCommunication::Tcp {
+            socket_addr: ([127, 0, 0, 1], 0).into(),
+        };
+        let config = SandboxConfig::default();
+        let paths = SandboxPaths::collect(
+            command.as_std(),
+            &config,
+            working_dir,
+            &communication,
+            shared_memory_dir,
+        )
+        .unwrap();
+        let mut command = isolate(command, &config, working_dir, &paths).unwrap();
+        match command.spawn() {
+            Ok(child) => Some(child),
+            Err(err) => {
+                eprintln!("skipping sandbox test, failed to create namespaces: {err}");
+                None
+            }
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn nodes_without_signal_handlers_exit_on_sigterm() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let working_dir = tempfile::tempdir().unwrap();
+        let Some(mut child) = spawn_isolated("exec sleep 60", working_dir.path(), None) else {
+            return;
+        };
+        let pid = child.id().unwrap();
+        // wait until the node runs, as signals to the shell would be lost otherwise
+        let started = std::time::Instant::now();
+        loop {
+            let comm = sandboxed_pid(pid)
+                .and_then(|node| std::fs::read_to_string(format!("/proc/{node}/comm")).ok());
+            if comm.as_deref() == Some("sleep\n") {
+                break;
+            }
+            assert!(started.elapsed() < std::time::Duration::from_secs(5));
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+
+        unsafe { libc::kill(pid as libc::pid_t, libc::SIGTERM) };
+        let status = tokio::time::timeout(std::time::Duration::from_secs(5), child.wait())
+            .await
+            .expect("sandboxed node did not exit on SIGTERM")
+            .unwrap();
+        assert_eq!(status.signal(), Some(libc::SIGTERM));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn dataflow_shared_memory_dir_is_shared() {
+        let working_dir = tempfile::tempdir().unwrap();
+        let shared_memory_dir = tempfile::tempdir().unwrap();
+        let region = shared_memory_dir.path().join("region");
+        std::fs::write(&region, "input").unwrap();
+
+        let script = format!(
+            "cat {region} > {dir}/copy && echo output > {dir}/created && exit 3",
+            region = region.display(),
+            dir = shared_memory_dir.path().display()
+        );
+        let Some(mut child) =
+            spawn_isolated(&script, working_dir.path(), Some(shared_memory_dir.path()))
+        else {
+            return;
+        };
+        let status = child.wait().await.unwrap();
+        assert_eq!(status.code(), Some(3));
+        let read = |name| std::fs::read_to_string(shared_memory_dir.path().join(name)).unwrap();
+        assert_eq!(read("copy"), "input");
+        assert_eq!(read("created"), "output\n");
+    }
+}
@@ -0,0 +1,687 @@
+//! Built-in sandbox based on Linux namespaces.
+//!
+//! The node process is started in new user, mount, PID, and (optionally) network
+//! namespaces. Its root filesystem is a fresh `tmpfs` that only contains bind mounts of
+//! the allowed paths, similar to what `bubblewrap` does. `/dev/shm` is a private
+//! `tmpfs` that only contains the shared memory regions of the daemon connection and
+//! the shared memory directory of the dataflow.
+//!
+//! The mount setup happens in the forked child process before `exec`, so all paths are
+//! prepared upfront and [`Namespaces::enter`] only performs plain system calls.
+//!
+//! The first process of the new PID namespace runs a minimal init (see [`init`]), and
+//! the node runs as its child. Otherwise the node would be PID 1, which ignores all
+//! signals that it doesn't handle explicitly, e.g. `SIGTERM` and `SIGINT`.
+
+use super::{SandboxPaths, SHARED_MEMORY_DIR};
+use eyre::Context;
+use std::{
+    collections::BTreeSet,
+    ffi::{CString, OsStr},
+    io,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicI32, Ordering},
+};
+
+/// System directories that are available read-only. Missing directories are skipped.
+const SYSTEM_DIRS: &[&str] = &[
+    "/usr", "/bin", "/sbin", "/lib", "/lib32", "/lib64", "/libx32", "/etc",
+];
+/// Device files that are available in the sandboxed `/dev`.
+const DEVICES: &[&str] = &[
+    "/dev/null",
+    "/dev/zero",
+    "/dev/full",
+    "/dev/random",
+    "/dev/urandom",
+    "/dev/tty",
+];
+const DEV_LINKS: &[(&str, &str)] = &[
+    ("/proc/self/fd", "/dev/fd"),
+    ("/proc/self/fd/0", "/dev/stdin"),
+    ("/proc/self/fd/1", "/dev/stdout"),
+    ("/proc/self/fd/2", "/dev/stderr"),
+];
+
+/// Mount point of the new root filesystem while it is set up.
+const NEW_ROOT: &str = "/newroot";
+/// Mount point of the host root filesystem while the new root is set up.
+const OLD_ROOT: &str = "/oldroot";
+
+pub struct Namespaces {
+    network: bool,
+    /// Process name of the supervisor, see [`supervise`].
+    name: CString,
+    uid_map: Vec<u8>,
+    gid_map: Vec<u8>,
+    steps: Vec<Step>,
+    working_dir: CString,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Step {
+    Mkdir(CString),
+    CreateFile(CString),
+    Symlink {
+        target: CString,
+        link: CString,
+    },
+    Bind {
+        source: CString,
+        target: CString,
+        read_only: bool,
+    },
+    Tmpfs(CString),
+    Proc(CString),
+}
+
+impl Namespaces {
+    pub fn new(
+        network: bool,
+        working_dir: &Path,
+        program: &OsStr,
+        paths: &SandboxPaths,
+    ) -> eyre::Result<Self> {
+        // SAFETY: these functions are always successful
+        let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+
+        // like the kernel, use the first 15 bytes of the executable name
+        let name = Path::new(program)
+            .file_name()
+            .unwrap_or(program)
+            .as_bytes()
+            .iter()
+            .copied()
+            .take(15)
+            .collect::<Vec<_>>();
+
+        Ok(Self {
+            network,
+            name: CString::new(name).context("node executable contains a null byte")?,
+            uid_map: format!("{uid} {uid} 1\n").into_bytes(),
+            gid_map: format!("{gid} {gid} 1\n").into_bytes(),
+            steps: Self::root_steps(paths)?,
+            working_dir: c_path(working_dir)?,
+        })
+    }
+
+    /// Steps that populate the new root filesystem.
+    fn root_steps(paths: &SandboxPaths) -> eyre::Result<Vec<Step>> {
+        let mut builder = StepsBuilder::default();
+        for dir in SYSTEM_DIRS.iter().map(Path::new) {
+            match dir.symlink_metadata() {
+                Ok(metadata) if metadata.is_symlink() => {
+                    let target = dir.read_link().context("failed to read symlink")?;
+                    builder.symlink(&target, dir)?;
+                }
+                Ok(_) => builder.bind(dir, false)?,
+                Err(_) => {}
+            }
+        }
+
+        builder.tmpfs(Path::new("/dev"))?;
+        for device in DEVICES.iter().map(Path::new) {
+            if device.exists() {
+                builder.bind(device, true)?;
+            }
+        }
+        for (target, link) in DEV_LINKS {
+            builder.symlink(Path::new(target), Path::new(link))?;
+        }
+        // the shared memory regions of the daemon connection are bound below
+        builder.tmpfs(Path::new(SHARED_MEMORY_DIR))?;
+        builder.proc(Path::new("/proc"))?;
+        builder.tmpfs(Path::new("/tmp"))?;
+
+        // bind parent directories first, so that they don't hide nested paths
+        let mut binds: Vec<_> = paths
+            .read_only
+            .iter()
+            .map(|path| (path, false))
+            .chain(paths.writable.iter().map(|path| (path, true)))
+            .collect();
+        binds.sort();
+        for (path, writable) in binds {
+            builder.bind(path, writable)?;
+        }
+
+        Ok(builder.steps)
+    }
+
+    /// Moves the current process into the sandbox.
+    ///
+    /// Runs in the forked child process, right before the node executable is started.
+    pub fn enter(&self) -> io::Result<()> {
+        let mut flags = libc::CLONE_NEWUSER | libc::CLONE_NEWNS | libc::CLONE_NEWPID;
+        if !self.network {
+            flags |= libc::CLONE_NEWNET;
+        }
+        check(unsafe { libc::unshare(flags) })?;
+        write_file(b"/proc/self/uid_map\0", &self.uid_map)?;
+        write_file(b"/proc/self/setgroups\0", b"deny")?;
+        write_file(b"/proc/self/gid_map\0", &self.gid_map)?;
+
+        // the init process reports the exit status of the node through this pipe
+        let mut status_pipe = [0; 2];
+        check(unsafe { libc::pipe2(status_pipe.as_mut_ptr(), libc::O_CLOEXEC) })?;
+        let [status_read, status_write] = status_pipe;
+
+        // block the forwarded signals until the handlers are set up, so that signals
+        // sent right after starting are not lost
+        let mut previous_mask: libc::sigset_t = unsafe { std::mem::zeroed() };
+        let forwarded = forwarded_signals();
+        check(unsafe { libc::pthread_sigmask(libc::SIG_BLOCK, &forwarded, &mut previous_mask) })?;
+
+        // only processes forked after `unshare` are part of the new PID namespace
+        let child = check(unsafe { libc::fork() })?;
+        if child != 0 {
+            supervise(child, status_read, &self.name);
+        }
+        unsafe { libc::close(status_read) };
+        check(unsafe { libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL) })?;
+
+        self.set_up_root()?;
+        if !self.network {
+            set_loopback_up()?;
+        }
+
+        let node = check(unsafe { libc::fork() })?;
+        if node != 0 {
+            init(node, status_write);
+        }
+        unsafe { libc::close(status_write) };
+        check(unsafe {
+            libc::pthread_sigmask(libc::SIG_SETMASK, &previous_mask, std::ptr::null_mut())
+        })?;
+        Ok(())
+    }
+
+    fn set_up_root(&self) -> io::Result<()> {
+        check(unsafe {
+            libc::mount(
+                std::ptr::null(),
+                b"/\0".as_ptr().cast(),
+                std::ptr::null(),
+                libc::MS_REC | libc::MS_PRIVATE,
+                std::ptr::null(),
+            )
+        })?;
+
+        // use a tmpfs as the temporary root, with the host root mounted in it
+        mount_tmpfs(b"/tmp\0".as_ptr().cast())?;
+        check(unsafe { libc::chdir(b"/tmp\0".as_ptr().cast()) })?;
+        check(unsafe { libc::mkdir(b"newroot\0".as_ptr().cast(), 0o755) })?;
+        check(unsafe { libc::mkdir(b"oldroot\0".as_ptr().cast(), 0o755) })?;
+        pivot_root(b".\0".as_ptr().cast(), b"oldroot\0".as_ptr().cast())?;
+        check(unsafe { libc::chdir(b"/\0".as_ptr().cast()) })?;
+        mount_tmpfs(b"/newroot\0".as_ptr().cast())?;
+
+        for step in &self.steps {
+            step.run()?;
+        }
+
+        check(unsafe { libc::umount2(b"/oldroot\0".as_ptr().cast(), libc::MNT_DETACH) })?;
+        check(unsafe { libc::chdir(b"/newroot\0".as_ptr().cast()) })?;
+        pivot_root(b".\0".as_ptr().cast(), b".\0".as_ptr().cast())?;
+        check(unsafe { libc::umount2(b".\0".as_ptr().cast(), libc::MNT_DETACH) })?;
+        check(unsafe { libc::chdir(self.working_dir.as_ptr()) })?;
+        Ok(())
+    }
+}
+
+impl Step {
+    fn run(&self) -> io::Result<()> {
+        match self {
+            Step::Mkdir(path) => check(unsafe { libc::mkdir(path.as_ptr(), 0o755) }).map(drop),
+            Step::CreateFile(path) => {
+                let fd = check(unsafe {
+                    libc::open(path.as_ptr(), libc::O_CREAT | libc::O_WRONLY, 0o644)
+                })?;
+                check(unsafe { libc::close(fd) }).map(drop)
+            }
+            Step::Symlink { target, link } => {
+                check(unsafe { libc::symlink(target.as_ptr(), link.as_ptr()) }).map(drop)
+            }
+            Step::Bind {
+                source,
+                target,
+                read_only,
+            } => {
+                check(unsafe {
+                    libc::mount(
+                        source.as_ptr(),
+                        target.as_ptr(),
+                        std::ptr::null(),
+                        libc::MS_BIND | libc::MS_REC,
+                        std::ptr::null(),
+                    )
+                })?;
+                if *read_only {
+                    remount_read_only(target)?;
+                }
+                Ok(())
+            }
+            Step::Tmpfs(path) => mount_tmpfs(path.as_ptr()),
+            Step::Proc(path) => check(unsafe {
+                libc::mount(
+                    b"proc\0".as_ptr().cast(),
+                    path.as_ptr(),
+                    b"proc\0".as_ptr().cast(),
+                    libc::MS_NOSUID | libc::MS_NODEV | libc::MS_NOEXEC,
+                    std::ptr::null(),
+                )
+            })
+            .map(drop),
+        }
+    }
+}
+
+/// Collects the steps to set up the new root filesystem.
+#[derive(Default)]
+struct StepsBuilder {
+    steps: Vec<Step>,
+    /// Directories that already exist in the new root.
+    created: BTreeSet<PathBuf>,
+    /// Bind-mounted paths and whether they are writable.
+    binds: Vec<(PathBuf, bool)>,
+}
+
+impl StepsBuilder {
+    fn bind(&mut self, path: &Path, writable: bool) -> eyre::Result<()> {
+        let covered = self
+            .binds
+            .iter()
+            .any(|(bound, bound_writable)| path.starts_with(bound) && *bound_writable >= writable);
+        if covered {
+            return Ok(());
+        }
+        // resolve symlinks on the host, as absolute symlinks don't work below `OLD_ROOT`
+        let source = path
+            .canonicalize()
+            .with_context(|| format!("failed to resolve `{}`", path.display()))?;
+        if source.is_dir() {
+            self.create_dir(path)?;
+        } else {
+            self.create_file(path)?;
+        }
+        self.steps.push(Step::Bind {
+            source: c_path(&Path::new(OLD_ROOT).join(source.strip_prefix("/")?))?,
+            target: self.new_root_path(path)?,
+            read_only: !writable,
+        });
+        self.binds.push((path.to_owned(), writable));
+        Ok(())
+    }
+
+    fn symlink(&mut self, target: &Path, link: &Path) -> eyre::Result<()> {
+        if let Some(parent) = link.parent() {
+            self.create_dir(parent)?;
+        }
+        self.steps.push(Step::Symlink {
+            target: c_path(target)?,
+            link: self.new_root_path(link)?,
+        });
+        Ok(())
+    }
+
+    fn tmpfs(&mut self, path: &Path) -> eyre::Result<()> {
+        self.create_dir(path)?;
+        self.steps.push(Step::Tmpfs(self.new_root_path(path)?));
+        Ok(())
+    }
+
+    fn proc(&mut self, path: &Path) -> eyre::Result<()> {
+        self.create_dir(path)?;
+        self.steps.push(Step::Proc(self.new_root_path(path)?));
+        Ok(())
+    }
+
+    fn create_dir(&mut self, path: &Path) -> eyre::Result<()> {
+        let mut ancestors: Vec<_> = path.ancestors().collect();
+        ancestors.reverse();
+        for dir in ancestors {
+            // paths below bind mounts exist already
+            let exists = dir.parent().is_none()
+                || self.binds.iter().any(|(bound, _)| dir.starts_with(bound));
+            if !exists && self.created.insert(dir.to_owned()) {
+                self.steps.push(Step::Mkdir(self.new_root_path(dir)?));
+            }
+        }
+        Ok(())
+    }
+
+    fn create_file(&mut self, path: &Path) -> eyre::Result<()> {
+        if let Some(parent) = path.parent() {
+            self.create_dir(parent)?;
+        }
+        if !self.binds.iter().any(|(bound, _)| path.starts_with(bound)) {
+            self.steps.push(Step::CreateFile(self.new_root_path(path)?));
+        }
+        Ok(())
+    }
+
+    fn new_root_path(&self, path: &Path) -> eyre::Result<CString> {
+        let relative = path
+            .strip_prefix("/")
+            .with_context(|| format!("sandbox path `{}` is not absolute", path.display()))?;
+        c_path(&Path::new(NEW_ROOT).join(relative))
+    }
+}
+
+fn c_path(path: &Path) -> eyre::Result<CString> {
+    CString::new(path.as_os_str().as_bytes())
+        .with_context(|| format!("path `{}` contains a null byte", path.display()))
+}
+
+/// Signals that are forwarded to the node.
+const FORWARDED_SIGNALS: &[libc::c_int] = &[
+    libc::SIGHUP,
+    libc::SIGINT,
+    libc::SIGQUIT,
+    libc::SIGTERM,
+    libc::SIGUSR1,
+    libc::SIGUSR2,
+];
+
+fn forwarded_signals() -> libc::sigset_t {
+    let mut set: libc::sigset_t = unsafe { std::mem::zeroed() };
+    unsafe { libc::sigemptyset(&mut set) };
+    for &signal in FORWARDED_SIGNALS {
+        unsafe { libc::sigaddset(&mut set, signal) };
+    }
+    set
+}
+
+/// PID of the child process that signals are forwarded to.
+static CHILD_PID: AtomicI32 = AtomicI32::new(0);
+
+/// Forwards the signals in [`FORWARDED_SIGNALS`] to the given child and unblocks them.
+unsafe fn forward_signals(child: libc::pid_t) {
+    CHILD_PID.store(child, Ordering::SeqCst);
+    extern "C" fn forward_signal(signal: libc::c_int) {
+        unsafe { libc::kill(CHILD_PID.load(Ordering::SeqCst), signal) };
+    }
+
+    for &signal in FORWARDED_SIGNALS {
+        libc::signal(signal, forward_signal as libc::sighandler_t);
+    }
+    let forwarded = forwarded_signals();
+    libc::pthread_sigmask(libc::SIG_UNBLOCK, &forwarded, std::ptr::null_mut());
+}
+
+/// Closes all file descriptors except for stdio and `keep`.
+///
+/// Otherwise the daemon would wait for this process to `exec` before continuing, as
+/// it holds the inherited end of the pipe that reports `exec` errors.
+unsafe fn close_inherited_fds(keep: libc::c_int) {
+    // move `keep` to the lowest descriptor that is closed otherwise
+    if keep != 3 {
+        libc::dup2(keep, 3);
+        libc::close(keep);
+    }
+    if libc::syscall(libc::SYS_close_range, 4u32, u32::MAX, 0u32) != 0 {
+        for fd in 4..1024 {
+            libc::close(fd);
+        }
+    }
+}
+
+/// Runs as PID 1 of the sandbox until the node exits.
+///
+/// Forwards signals to the node, reaps orphaned processes, and writes the wait status
+/// of the node to `status_pipe`. All other processes of the sandbox are killed when
+/// this process exits.
+fn init(node: libc::pid_t, status_pipe: libc::c_int) -> ! {
+    unsafe {
+        close_inherited_fds(status_pipe);
+        forward_signals(node);
+
+        let mut status = 0;
+        loop {
+            let pid = libc::waitpid(-1, &mut status, 0);
+            if pid == node {
+                break;
+            }
+            if pid < 0 && io::Error::last_os_error().raw_os_error() != Some(libc::EINTR) {
+                libc::_exit(1);
+            }
+        }
+        let status = status.to_ne_bytes();
+        libc::write(3, status.as_ptr().cast(), status.len());
+        libc::_exit(0)
+    }
+}
+
+/// Waits for the sandbox and exits with the exit status of the node.
+///
+/// This process stays in the parent PID namespace, so that the daemon can observe
+/// the exit status of the node and kill it. The sandbox is killed when this process
+/// exits because of `PR_SET_PDEATHSIG`.
+///
+/// The process takes the name of the node executable and never writes a core dump
+/// itself, so that crash bundles find the core dump of the node. The resource usage
+/// that the daemon observes for this process includes the reaped node process.
+fn supervise(child: libc::pid_t, status_pipe: libc::c_int, name: &CString) -> ! {
+    unsafe {
+        libc::prctl(libc::PR_SET_NAME, name.as_ptr());
+        let no_core = libc::rlimit {
+            rlim_cur: 0,
+            rlim_max: 0,
+        };
+        libc::setrlimit(libc::RLIMIT_CORE, &no_core);
+
+        close_inherited_fds(status_pipe);
+        forward_signals(child);
+
+        let mut status = 0;
+        loop {
+            if libc::waitpid(child, &mut status, 0) == child {
+                break;
+            }
+            if io::Error::last_os_error().raw_os_error() != Some(libc::EINTR) {
+                libc::_exit(1);
+            }
+        }
+        // use the status of the node if the init process reported it
+        let mut node_status = [0u8; 4];
+        if libc::read(3, node_status.as_mut_ptr().cast(), node_status.len()) == 4 {
+            status = libc::c_int::from_ne_bytes(node_status);
+        }
+        if libc::WIFSIGNALED(status) {
+            let signal = libc::WTERMSIG(status);
+            libc::signal(signal, libc::SIG_DFL);
+            libc::kill(libc::getpid(), signal);
+            libc::_exit(128 + signal);
+        }
+        libc::_exit(libc::WEXITSTATUS(status))
+    }
+}
+
+fn mount_tmpfs(path: *const libc::c_char) -> io::Result<()> {
+    check(unsafe {
+        libc::mount(
+            b"tmpfs\0".as_ptr().cast(),
+            path,
+            b"tmpfs\0".as_ptr().cast(),
+            libc::MS_NOSUID | libc::MS_NODEV,
+            b"mode=0755\0".as_ptr().cast(),
+        )
+    })
+    .map(drop)
+}
+
+/// Makes a bind mount read-only, keeping the flags that can't be changed in a user
+/// namespace.
+fn remount_read_only(path: &CString) -> io::Result<()> {
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    check(unsafe { libc::statvfs(path.as_ptr(), &mut stat) })?;
+    let mut flags = libc::MS_BIND | libc::MS_REMOUNT | libc::MS_RDONLY;
+    for (st_flag, ms_flag) in [
+        (libc::ST_NOSUID, libc::MS_NOSUID),
+        (libc::ST_NODEV, libc::MS_NODEV),
+        (libc::ST_NOEXEC, libc::MS_NOEXEC),
+        (libc::ST_NOATIME, libc::MS_NOATIME),
+        (libc::ST_NODIRATIME, libc::MS_NODIRATIME),
+        (libc::ST_RELATIME, libc::MS_RELATIME),
+    ] {
+        if stat.f_flag & st_flag != 0 {
+            flags |= ms_flag;
+        }
+    }
+    check(unsafe {
+        libc::mount(
+            std::ptr::null(),
+            path.as_ptr(),
+            std::ptr::null(),
+            flags,
+            std::ptr::null(),
+        )
+    })
+    .map(drop)
+}
+
+fn pivot_root(new_root: *const libc::c_char, put_old: *const libc::c_char) -> io::Result<()> {
+    check(unsafe { libc::syscall(libc::SYS_pivot_root, new_root, put_old) } as libc::c_int)
+        .map(drop)
+}
+
+/// Enables the loopback interface of the new network namespace.
+fn set_loopback_up() -> io::Result<()> {
+    let socket = check(unsafe { libc::socket(libc::AF_INET, libc::SOCK_DGRAM, 0) })?;
+    let mut request: libc::ifreq = unsafe { std::mem::zeroed() };
+    request.ifr_name[0] = b'l' as libc::c_char;
+    request.ifr_name[1] = b'o' as libc::c_char;
+    let result = check(unsafe { libc::ioctl(socket, libc::SIOCGIFFLAGS as _, &mut request) })
+        .and_then(|_| {
+            unsafe { request.ifr_ifru.ifru_flags |= libc::IFF_UP as libc::c_short };
+            check(unsafe { libc::ioctl(socket, libc::SIOCSIFFLAGS as _, &request) })
+        });
+    unsafe { libc::close(socket) };
+    result.map(|_| ())
+}
+
+fn write_file(path: &[u8], content: &[u8]) -> io::Result<()> {
+    let fd = check(unsafe { libc::open(path.as_ptr().cast(), libc::O_WRONLY) })?;
+    let written = unsafe { libc::write(fd, content.as_ptr().cast(), content.len()) };
+    unsafe { libc::close(fd) };
+    if written < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn check(result: libc::c_int) -> io::Result<libc::c_int> {
+    if result < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_root(path: &str) -> CString {
+        c_path(&Path::new(NEW_ROOT).join(path)).unwrap()
+    }
+
+    #[test]
+    fn shared_memory_is_private() {
+        let mut builder = StepsBuilder::default();
+        builder.tmpfs(Path::new("/dev")).unwrap();
+        builder.tmpfs(Path::new(SHARED_MEMORY_DIR)).unwrap();
+        let region = tempfile::NamedTempFile::new().unwrap();
+        builder.bind(region.path(), true).unwrap();
+
+        assert_eq!(
+            &builder.steps[..4],
+            &[
+                Step::Mkdir(new_root("dev")),
+                Step::Tmpfs(new_root("dev")),
+                Step::Mkdir(new_root("dev/shm")),
+                Step::Tmpfs(new_root("dev/shm")),
+            ]
+        );
+        assert!(!builder.steps.iter().any(|step| matches!(
+            step,
+            Step::Bind { target, .. } if *target == new_root("dev/shm")
+        )));
+    }
+
+    #[test]
+    fn nested_binds() {
+        let dir = tempfile::tempdir().unwrap();
+        let nested = dir.path().join("nested");
+        std::fs::create_dir(&nested).unwrap();
+        let file = nested.join("file");
+        std::fs::write(&file, "").unwrap();
+
+        let mut builder = StepsBuilder::default();
+        builder.bind(dir.path(), false).unwrap();
+        // covered by the read-only parent
+        builder.bind(&nested, false).unwrap();
+        // writable paths below read-only binds are bound again
+        builder.bind(&file, true).unwrap();
+
+        let binds: Vec<_> = builder
+            .steps
+            .iter()
+            .filter_map(|step| match step {
+                Step::Bind {
+                    target, read_only, ..
+                } => Some((target.clone(), *read_only)),
+                _ => None,
+            })
+            .collect();
+        let target =
+            |path: &Path| c_path(&Path::new(NEW_ROOT).join(path.strip_prefix("/").unwrap()));
+        assert_eq!(
+            binds,
+            [
+                (target(dir.path()).unwrap(), true),
+                (target(&file).unwrap(), false)
+            ]
+        );
+        // no directories or files are created below bind mounts
+        let created = builder
+            .steps
+            .iter()
+            .filter(|step| matches!(step, Step::Mkdir(_) | Step::CreateFile(_)))
+            .count();
+        assert_eq!(created, dir.path().ancestors().count() - 1);
+    }
+
+    #[test]
+    fn parent_dirs_are_created_once() {
+        let mut builder = StepsBuilder::default();
+        builder.tmpfs(Path::new("/a/b")).unwrap();
+        builder
+            .symlink(Path::new("/target"), Path::new("/a/b/link"))
+            .unwrap();
+        builder.proc(Path::new("/a/c")).unwrap();
+
+        assert_eq!(
+            builder.steps,
+            [
+                Step::Mkdir(new_root("a")),
+                Step::Mkdir(new_root("a/b")),
+                Step::Tmpfs(new_root("a/b")),
+                Step::Symlink {
+                    target: c_path(Path::new("/target")).unwrap(),
+                    link: new_root("a/b/link"),
+                },
+                Step::Mkdir(new_root("a/c")),
+                Step::Proc(new_root("a/c")),
+            ]
+        );
+    }
+
+    #[test]
+    fn relative_paths_are_rejected() {
+        assert!(StepsBuilder::default()
+            .tmpfs(Path::new("relative"))
+            .is_err());
+    }
+}
@@ -0,0 +1,49 @@
+//! Per-dataflow directories for the shared memory regions of node outputs.
+//!
+//! Nodes create their output regions as files in the directory of their dataflow
+//! (see `NodeConfig::shared_memory_dir`). Sandboxed nodes get access to this
+//! directory only, instead of all shared memory regions of the system.
+
+use crate::node_communication::unix_domain::create_private_dir;
+use dora_core::daemon_messages::DataflowId;
+use std::path::{Path, PathBuf};
+
+/// Returns the shared memory directory of the given dataflow.
+pub fn dataflow_dir(dataflow_id: &DataflowId) -> PathBuf {
+    root().join(dataflow_id.to_string())
+}
+
+/// Uses `/dev/shm` if available, so that the regions are kept in memory like POSIX
+/// shared memory objects.
+fn root() -> PathBuf {
+    let uid = unsafe { libc::getuid() };
+    let shm = Path::new("/dev/shm");
+    let parent = if shm.is_dir() {
+        shm.to_owned()
+    } else {
+        std::env::temp_dir()
+    };
+    parent.join(format!("dora-{uid}"))
+}
+
+/// Creates the private shared memory directory of the given dataflow.
+pub fn create_dataflow_dir(dataflow_id: &DataflowId) -> eyre::Result<PathBuf> {
+    create_private_dir(&root())?;
+    let dir = dataflow_dir(dataflow_id);
+    create_private_dir(&dir)?;
+    Ok(dir)
+}
+
+/// Removes the shared memory directory of the given dataflow, including regions
+/// that were not cleaned up by crashed nodes.
+pub fn remove_dataflow_dir(dataflow_id: &DataflowId) {
+    let dir = dataflow_dir(dataflow_id);
+    if dir.exists() {
+        if let Err(err) = std::fs::remove_dir_all(&dir) {
+            tracing::warn!(
+                "failed to remove shared memory dir `{}`: {err}",
+                dir.display()
+            );
+        }
+    }
+}
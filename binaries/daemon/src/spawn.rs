@@ -1,23 +1,29 @@
 use crate::{
-    log, node_communication::spawn_listener_loop, node_inputs, DoraEvent, Event, NodeExitStatus,
-    OutputId, RunningNode,
+    crash::{self, CrashContext},
+    log::{self, LogStream, NodeLogWriter},
+    node_communication::spawn_listener_loop,
+    node_inputs,
+    sandbox::{self, sandbox_command},
+    DoraEvent, Event, NodeExitStatus, OutputId, RunningNode,
 };
 use aligned_vec::{AVec, ConstAlign};
 use crossbeam::queue::ArrayQueue;
 use dora_arrow_convert::IntoArrow;
 use dora_core::{
-    config::DataId,
+    config::LogParser,
     daemon_messages::{DataMessage, DataflowId, NodeConfig, RuntimeConfig, Timestamped},
     descriptor::{
         resolve_path, source_is_url, Descriptor, OperatorDefinition, OperatorSource, PythonSource,
         ResolvedNode, DYNAMIC_SOURCE, SHELL_SOURCE,
     },
-    get_python_path,
     message::uhlc::HLC,
 };
 use dora_download::download_file;
 use dora_node_api::{
-    arrow::array::ArrayData,
+    arrow::{
+        array::{ArrayData, ArrayRef, StringArray, StructArray},
+        datatypes::{DataType, Field},
+    },
     arrow_utils::{copy_array_into_sample, required_data_size},
     Metadata,
 };
@@ -27,15 +33,19 @@ use std::{
     path::{Path, PathBuf},
     process::Stdio,
     sync::Arc,
+    time::{Instant, SystemTime},
 };
 use tokio::{
-    fs::File,
-    io::{AsyncBufReadExt, AsyncWriteExt},
+    io::AsyncBufReadExt,
     sync::{mpsc, oneshot},
 };
 use tracing::error;
 
+/// Queue size of inputs that don't specify a `queue_size`.
+pub const DEFAULT_QUEUE_SIZE: usize = 10;
+
 /// clock is required for generating timestamps when dropping messages early because queue is full
+#[allow(clippy::too_many_arguments)]
 pub async fn spawn_node(
     dataflow_id: DataflowId,
     working_dir: &Path,
@@ -44,25 +54,35 @@ pub async fn spawn_node(
     dataflow_descriptor: Descriptor,
     clock: Arc<HLC>,
     node_stderr_most_recent: Arc<ArrayQueue<String>>,
+    shared_memory_dir: Option<PathBuf>,
+    restart: bool,
 ) -> eyre::Result<RunningNode> {
     let node_id = node.id.clone();
     tracing::debug!("Spawning node `{dataflow_id}/{node_id}`");
 
     let queue_sizes = node_inputs(&node)
         .into_iter()
-        .map(|(k, v)| (k, v.queue_size.unwrap_or(10)))
+        .map(|(k, v)| (k, v.queue_size.unwrap_or(DEFAULT_QUEUE_SIZE)))
         .collect();
+    let sandbox = node.sandbox.clone();
+    let local_communication = match &sandbox {
+        Some(config) => {
+            sandbox::local_communication(config, dataflow_descriptor.communication.local)
+        }
+        None => dataflow_descriptor.communication.local,
+    };
     let daemon_communication = spawn_listener_loop(
         &dataflow_id,
         &node_id,
         &daemon_tx,
-        dataflow_descriptor.communication.local,
+        local_communication,
         queue_sizes,
         clock.clone(),
     )
     .await?;
-    let send_stdout_to = node
-        .send_stdout_as()
+    let logging_config = dataflow_descriptor.logging.clone();
+    let log_outputs = node
+        .log_outputs()
         .context("Could not resolve `send_stdout_as` configuration")?;
 
     let node_config = NodeConfig {
@@ -72,14 +92,17 @@ pub async fn spawn_node(
         daemon_communication,
         dataflow_descriptor,
         dynamic: node.kind.dynamic(),
+        shared_memory_dir,
     };
 
-    let mut child = match node.kind {
+    let mut crash_environment = None;
+    let mut child = match node.kind.clone() {
         dora_core::descriptor::CoreNodeKind::Custom(n) => {
             let mut command = match n.source.as_str() {
                 DYNAMIC_SOURCE => {
                     return Ok(RunningNode {
                         pid: None,
+                        sandboxed: false,
                         node_config,
                     });
                 }
@@ -113,7 +136,9 @@ pub async fn spawn_node(
                     // If extension is .py, use python to run the script
                     let mut cmd = match resolved_path.extension().map(|ext| ext.to_str()) {
                         Some(Some("py")) => {
-                            let python = get_python_path().context("Could not get python path")?;
+                            let python = node
+                                .python_path(working_dir)
+                                .context("Could not get python path")?;
                             tracing::info!("spawning: {:?} {}", &python, resolved_path.display());
                             let mut cmd = tokio::process::Command::new(&python);
                             cmd.arg(&resolved_path);
@@ -153,6 +178,13 @@ pub async fn spawn_node(
                     command.env(key, value.to_string());
                 }
             }
+            if let Some(sandbox) = &sandbox {
+                command = sandbox_command(command, sandbox, working_dir, &node_config)
+                    .wrap_err_with(|| format!("failed to set up sandbox for node `{node_id}`"))?;
+            }
+            if logging_config.crash_bundles {
+                crash_environment = Some(crash::prepare_command(&mut command));
+            }
             command
                 .stdin(Stdio::null())
                 .stdout(Stdio::piped())
@@ -213,7 +245,8 @@ pub async fn spawn_node(
                     ]);
                     command
                 } else {
-                    let python = get_python_path()
+                    let python = node
+                        .python_path(working_dir)
                         .context("Could not find python path when spawning runtime node")?;
                     let mut command = tokio::process::Command::new(python);
                     command.args([
@@ -249,6 +282,13 @@ pub async fn spawn_node(
                     command.env(key, value.to_string());
                 }
             }
+            if let Some(sandbox) = &sandbox {
+                command = sandbox_command(command, sandbox, working_dir, &node_config)
+                    .wrap_err_with(|| format!("failed to set up sandbox for node `{node_id}`"))?;
+            }
+            if logging_config.crash_bundles {
+                crash_environment = Some(crash::prepare_command(&mut command));
+            }
 
             command
                 .stdin(Stdio::null())
@@ -262,24 +302,51 @@ pub async fn spawn_node(
         }
     };
 
-    let dataflow_dir: PathBuf = working_dir.join("out").join(dataflow_id.to_string());
+    let dataflow_dir: PathBuf = log::dataflow_log_dir(working_dir, &dataflow_id);
     if !dataflow_dir.exists() {
         std::fs::create_dir_all(&dataflow_dir).context("could not create dataflow_dir")?;
     }
     let (tx, mut rx) = mpsc::channel(10);
-    let mut file = File::create(log::log_path(working_dir, &dataflow_id, &node_id))
-        .await
-        .expect("Failed to create log file");
+    let mut log_writer = NodeLogWriter::create(
+        working_dir,
+        dataflow_id,
+        node_id.clone(),
+        &logging_config,
+        restart,
+    )
+    .await?;
     let mut child_stdout =
         tokio::io::BufReader::new(child.stdout.take().expect("failed to take stdout"));
     let pid = child.id().context(
         "Could not get the pid for the just spawned node and indicate that there is an error",
     )?;
+    // the built-in sandbox runs the node in a child of the spawned process
+    let sandboxed = sandbox
+        .as_ref()
+        .is_some_and(|sandbox| sandbox.wrapper.is_none());
+    let crash_context = crash_environment.map(|environment| CrashContext {
+        working_dir: working_dir.to_owned(),
+        log_format: logging_config.format,
+        pid,
+        sandboxed,
+        node_pid: if sandboxed {
+            sandbox::sandboxed_pid(pid)
+        } else {
+            Some(pid)
+        },
+        environment,
+        node_config: node_config.clone(),
+        spawned_at: SystemTime::now(),
+        spawned: Instant::now(),
+    });
     let running_node = RunningNode {
         pid: Some(pid),
+        sandboxed,
         node_config,
     };
     let stdout_tx = tx.clone();
+    // parsed log lines are sent line by line
+    let group_lines = log_outputs.parser.is_none();
 
     // Stdout listener stream
     tokio::spawn(async move {
@@ -312,11 +379,12 @@ pub async fn spawn_node(
                 }
             };
 
-            if buffer.contains("TRACE")
-                || buffer.contains("INFO")
-                || buffer.contains("DEBUG")
-                || buffer.contains("WARN")
-                || buffer.contains("ERROR")
+            if group_lines
+                && (buffer.contains("TRACE")
+                    || buffer.contains("INFO")
+                    || buffer.contains("DEBUG")
+                    || buffer.contains("WARN")
+                    || buffer.contains("ERROR"))
             {
                 // tracing output, potentially multi-line -> keep reading following lines
                 // until double-newline
@@ -327,7 +395,7 @@ pub async fn spawn_node(
 
             // send the buffered lines
             let lines = std::mem::take(&mut buffer);
-            let sent = stdout_tx.send(lines.clone()).await;
+            let sent = stdout_tx.send((LogStream::Stdout, lines.clone())).await;
             if sent.is_err() {
                 println!("Could not log: {lines}");
             }
@@ -378,7 +446,7 @@ pub async fn spawn_node(
 
             // send the buffered lines
             let lines = std::mem::take(&mut buffer);
-            let sent = stderr_tx.send(lines.clone()).await;
+            let sent = stderr_tx.send((LogStream::Stderr, lines.clone())).await;
             if sent.is_err() {
                 println!("Could not log: {lines}");
             }
@@ -388,12 +456,30 @@ pub async fn spawn_node(
     let node_id = node.id.clone();
     let (log_finish_tx, log_finish_rx) = oneshot::channel();
     tokio::spawn(async move {
+        // collect the resource usage before the process is reaped
+        let exit_info = match &crash_context {
+            Some(context) => Some(crash::wait_for_exit(context.pid).await),
+            None => None,
+        };
         let exit_status = NodeExitStatus::from(child.wait().await);
         let _ = log_finish_rx.await;
+        let crash_bundle = match (crash_context, exit_info) {
+            (Some(context), Some(exit_info)) if !matches!(exit_status, NodeExitStatus::Success) => {
+                match crash::write_bundle(context, exit_info, &exit_status).await {
+                    Ok(dir) => Some(dir),
+                    Err(err) => {
+                        tracing::warn!("failed to write crash bundle of node `{node_id}`: {err:?}");
+                        None
+                    }
+                }
+            }
+            _ => None,
+        };
         let event = DoraEvent::SpawnedNodeResult {
             dataflow_id,
             node_id,
             exit_status,
+            crash_bundle,
         }
         .into();
         let event = Timestamped {
@@ -406,23 +492,22 @@ pub async fn spawn_node(
     let node_id = node.id.clone();
     // Log to file stream.
     tokio::spawn(async move {
-        while let Some(message) = rx.recv().await {
+        while let Some((stream, message)) = rx.recv().await {
             // If log is an output, we're sending the logs to the dataflow
-            if let Some(stdout_output_name) = &send_stdout_to {
+            let log_output = match stream {
+                LogStream::Stdout => log_outputs.stdout.as_ref(),
+                LogStream::Stderr => log_outputs.stderr.as_ref().or(log_outputs.stdout.as_ref()),
+            };
+            if let Some(log_output) = log_output {
                 // Convert logs to DataMessage
-                let array = message.into_arrow();
-
-                let array: ArrayData = array.into();
+                let array = log_output_array(&message, log_outputs.parser);
                 let total_len = required_data_size(&array);
                 let mut sample: AVec<u8, ConstAlign<128>> = AVec::__from_elem(128, 0, total_len);
 
                 let type_info = copy_array_into_sample(&mut sample, &array);
 
                 let metadata = Metadata::new(uhlc.new_timestamp(), type_info);
-                let output_id = OutputId(
-                    node_id.clone(),
-                    DataId::from(stdout_output_name.to_string()),
-                );
+                let output_id = OutputId(node_id.clone(), log_output.clone());
                 let event = DoraEvent::Logs {
                     dataflow_id,
                     output_id,
@@ -437,10 +522,22 @@ pub async fn spawn_node(
                 let _ = daemon_tx_log.send(event).await;
             }
 
-            let _ = file
-                .write_all(message.as_bytes())
+            if let Err(err) = log_writer
+                .write(stream, uhlc.new_timestamp(), &message)
                 .await
-                .map_err(|err| error!("Could not log {message} to file due to {err}"));
+            {
+                error!("Could not log {message} to file due to {err:?}");
+            }
+            let event = Timestamped {
+                inner: DoraEvent::NodeOutput {
+                    dataflow_id,
+                    node_id: node_id.clone(),
+                    output: message.clone(),
+                }
+                .into(),
+                timestamp: uhlc.new_timestamp(),
+            };
+            let _ = daemon_tx_log.send(event).await;
             let formatted = message.lines().fold(String::default(), |mut output, line| {
                 output.push_str("      ");
                 output.push_str(line);
@@ -448,11 +545,6 @@ pub async fn spawn_node(
                 output
             });
             tracing::trace!("{dataflow_id}/{} logged:\n{formatted}", node.id.clone());
-            // Make sure that all data has been synced to disk.
-            let _ = file
-                .sync_all()
-                .await
-                .map_err(|err| error!("Could not sync logs to file due to {err}"));
         }
         let _ = log_finish_tx
             .send(())
@@ -460,3 +552,27 @@ pub async fn spawn_node(
     });
     Ok(running_node)
 }
+
+/// Converts the given output lines into an Arrow array.
+///
+/// If a log parser is set, the array is a struct array with one `level`, `target`,
+/// and `message` entry per line. Otherwise, it is a single string.
+fn log_output_array(message: &str, parser: Option<LogParser>) -> ArrayData {
+    let Some(parser) = parser else {
+        return message.to_owned().into_arrow().into();
+    };
+    let lines: Vec<_> = message
+        .lines()
+        .map(|line| log::parse_log_line(parser, line))
+        .collect();
+    let field = |name, nullable| Arc::new(Field::new(name, DataType::Utf8, nullable));
+    let level = StringArray::from_iter(lines.iter().map(|line| line.level));
+    let target = StringArray::from_iter(lines.iter().map(|line| line.target));
+    let message = StringArray::from_iter_values(lines.iter().map(|line| line.message));
+    StructArray::from(vec![
+        (field("level", true), Arc::new(level) as ArrayRef),
+        (field("target", true), Arc::new(target) as ArrayRef),
+        (field("message", false), Arc::new(message) as ArrayRef),
+    ])
+    .into()
+}
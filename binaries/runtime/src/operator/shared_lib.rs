@@ -8,15 +8,17 @@ use dora_core::{
 use dora_download::download_file;
 use dora_node_api::{
     arrow_utils::{copy_array_into_sample, required_data_size},
-    Event, MetadataParameters,
+    Event, MetadataParameters, Parameter,
 };
 use dora_operator_api_types::{
-    safer_ffi::closure::ArcDynFn1, DoraDropOperator, DoraInitOperator, DoraInitResult, DoraOnEvent,
-    DoraResult, DoraStatus, Metadata, OnEventResult, Output, SendOutput,
+    safer_ffi::{self, closure::ArcDynFn1},
+    DoraDropOperator, DoraInitOperator, DoraInitResult, DoraOnEvent, DoraResult, DoraStatus,
+    Metadata, OnEventResult, Output, ParameterKind, SendOutput,
 };
 use eyre::{bail, eyre, Context, Result};
 use libloading::Symbol;
 use std::{
+    collections::BTreeMap,
     ffi::c_void,
     panic::{catch_unwind, AssertUnwindSafe},
     path::Path,
@@ -115,12 +117,19 @@ impl<'lib> SharedLibraryOperator<'lib> {
                 id: output_id,
                 data_array,
                 schema,
-                metadata: Metadata {
-                    open_telemetry_context,
-                },
+                metadata:
+                    Metadata {
+                        open_telemetry_context,
+                        parameters,
+                    },
             } = output;
+            let user = match parameters_from_ffi(parameters) {
+                Ok(user) => user,
+                Err(err) => return DoraResult::from_error(format!("{err:?}")),
+            };
             let parameters = MetadataParameters {
                 open_telemetry_context: open_telemetry_context.into(),
+                user,
                 ..Default::default()
             };
 
@@ -199,6 +208,7 @@ impl<'lib> SharedLibraryOperator<'lib> {
                         data_array: Some(data_array),
                         schema,
                         metadata: Metadata {
+                            parameters: parameters_to_ffi(&metadata.parameters.user),
                             open_telemetry_context: metadata
                                 .parameters
                                 .open_telemetry_context
@@ -295,3 +305,77 @@ impl<'lib> Bindings<'lib> {
         Ok(bindings)
     }
 }
+
+fn parameters_to_ffi(
+    parameters: &BTreeMap<String, Parameter>,
+) -> safer_ffi::Vec<dora_operator_api_types::Parameter> {
+    use dora_operator_api_types::Parameter as FfiParameter;
+
+    parameters
+        .iter()
+        .map(|(key, value)| {
+            let key = key.clone();
+            match value {
+                Parameter::Bool(v) => FfiParameter::bool(key, *v),
+                Parameter::Integer(v) => FfiParameter::integer(key, *v),
+                Parameter::Float(v) => FfiParameter::float(key, *v),
+                Parameter::String(v) => FfiParameter::string(key, v.clone()),
+                Parameter::Bytes(v) => FfiParameter::bytes(key, v.clone()),
+            }
+        })
+        .collect::<Vec<_>>()
+        .into()
+}
+
+fn parameters_from_ffi(
+    parameters: safer_ffi::Vec<dora_operator_api_types::Parameter>,
+) -> eyre::Result<BTreeMap<String, Parameter>> {
+    Vec::from(parameters)
+        .into_iter()
+        .map(|p| {
+            let key = String::from(p.key);
+            let value = match p.kind {
+                ParameterKind::Bool => Parameter::Bool(p.bool_value),
+                ParameterKind::Integer => Parameter::Integer(p.int_value),
+                ParameterKind::Float => Parameter::Float(p.float_value),
+                ParameterKind::String => Parameter::String(
+                    String::from_utf8(p.bytes_value.into())
+                        .wrap_err_with(|| format!("parameter `{key}` is not valid UTF-8"))?,
+                ),
+                ParameterKind::Bytes => Parameter::Bytes(p.bytes_value.into()),
+            };
+            Ok((key, value))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parameters_ffi_roundtrip() {
+        let parameters: BTreeMap<String, Parameter> = [
+            ("bool", Parameter::Bool(true)),
+            ("integer", Parameter::Integer(-42)),
+            ("float", Parameter::Float(1.5)),
+            ("string", Parameter::String("rgb8".into())),
+            ("bytes", Parameter::Bytes(vec![0, 1, 255])),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key.to_owned(), value))
+        .collect();
+
+        let ffi = parameters_to_ffi(&parameters);
+        assert_eq!(ffi.len(), parameters.len());
+        assert_eq!(parameters_from_ffi(ffi).unwrap(), parameters);
+    }
+
+    #[test]
+    fn invalid_utf8_string_parameter() {
+        let mut parameter = dora_operator_api_types::Parameter::bytes("key".into(), vec![0xff]);
+        parameter.kind = ParameterKind::String;
+        let ffi: safer_ffi::Vec<_> = vec![parameter].into();
+        assert!(parameters_from_ffi(ffi).is_err());
+    }
+}
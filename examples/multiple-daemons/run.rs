@@ -48,6 +48,9 @@ async fn main() -> eyre::Result<()> {
     let (coordinator_port, coordinator) = dora_coordinator::start(
         coordinator_bind,
         coordinator_control_bind,
+        None,
+        None,
+        Default::default(),
         ReceiverStream::new(coordinator_events_rx),
     )
     .await?;
@@ -134,6 +137,7 @@ async fn start_dataflow(
                 dataflow: dataflow_descriptor,
                 local_working_dir: working_dir,
                 name: None,
+                dataflow_path: Some(dataflow.to_owned()),
             },
             reply_sender,
         }))
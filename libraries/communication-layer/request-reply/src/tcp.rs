@@ -3,16 +3,25 @@ use std::{
     net::{SocketAddr, TcpListener, TcpStream},
 };
 
+use dora_transport_security::{SyncStream, TransportSecurity};
+
 use crate::{ListenConnection, RequestReplyConnection, RequestReplyLayer};
 
 pub type TcpRequestReplyConnection =
     dyn RequestReplyConnection<RequestData = Vec<u8>, ReplyData = Vec<u8>, Error = std::io::Error>;
 
-pub struct TcpLayer {}
+pub struct TcpLayer {
+    security: TransportSecurity,
+}
 
 impl TcpLayer {
     pub fn new() -> Self {
-        Self {}
+        Self::with_security(TransportSecurity::default())
+    }
+
+    /// Secures all connections of this layer using the given TLS and token settings.
+    pub fn with_security(security: TransportSecurity) -> Self {
+        Self { security }
     }
 }
 
@@ -48,21 +57,23 @@ impl RequestReplyLayer for TcpLayer {
         >,
         Self::Error,
     > {
+        let security = self.security.clone();
         let incoming: Box<dyn Iterator<Item = Result<_, _>>> = Box::new(
             IntoIncoming {
                 listener: TcpListener::bind(addr)?,
             }
-            .map(|r| {
-                r.map(|stream| {
-                    let connection: Box<
-                        dyn ListenConnection<
-                            RequestData = Self::RequestData,
-                            ReplyData = Self::ReplyData,
-                            Error = Self::Error,
-                        >,
-                    > = Box::new(TcpConnection { stream });
-                    connection
-                })
+            .map(move |r| {
+                r.and_then(|stream| security.accept_blocking(stream))
+                    .map(|stream| {
+                        let connection: Box<
+                            dyn ListenConnection<
+                                RequestData = Self::RequestData,
+                                ReplyData = Self::ReplyData,
+                                Error = Self::Error,
+                            >,
+                        > = Box::new(TcpConnection { stream });
+                        connection
+                    })
             }),
         );
         Ok(incoming)
@@ -81,7 +92,8 @@ impl RequestReplyLayer for TcpLayer {
         >,
         Self::Error,
     > {
-        TcpStream::connect(addr).map(|s| {
+        let stream = TcpStream::connect(addr)?;
+        self.security.connect_blocking(stream).map(|s| {
             let connection: Box<
                 dyn RequestReplyConnection<
                     RequestData = Self::RequestData,
@@ -95,7 +107,7 @@ impl RequestReplyLayer for TcpLayer {
 }
 
 pub struct TcpConnection {
-    pub stream: TcpStream,
+    pub stream: SyncStream,
 }
 
 impl ListenConnection for TcpConnection {
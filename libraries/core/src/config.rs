@@ -129,17 +129,73 @@ impl Borrow<str> for DataId {
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, JsonSchema)]
 pub enum InputMapping {
-    Timer { interval: Duration },
+    Timer(TimerConfig),
     User(UserInputMapping),
 }
 
+/// A `dora/timer/...` input.
+///
+/// Timer ticks are scheduled at fixed points in time (`start + phase + n * interval`),
+/// so they don't drift if a tick is delivered late.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, JsonSchema)]
+pub struct TimerConfig {
+    pub interval: Duration,
+    /// What to do if ticks were missed, e.g. because the daemon was stalled.
+    pub missed_ticks: MissedTickBehavior,
+    /// Offset of the first tick relative to the dataflow start.
+    pub phase: Duration,
+}
+
+impl TimerConfig {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            missed_ticks: MissedTickBehavior::default(),
+            phase: Duration::ZERO,
+        }
+    }
+}
+
+/// Key of the timer tick metadata parameter that contains the time at which the tick
+/// was scheduled, in nanoseconds since the UNIX epoch.
+pub const TIMER_SCHEDULED_TIME: &str = "timer_scheduled_time_ns";
+/// Key of the timer tick metadata parameter that contains the time at which the tick
+/// was actually sent, in nanoseconds since the UNIX epoch.
+pub const TIMER_ACTUAL_TIME: &str = "timer_actual_time_ns";
+
+/// How a timer catches up on ticks that were missed.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    Hash,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    JsonSchema,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum MissedTickBehavior {
+    /// Send all missed ticks at once, then continue on the original schedule.
+    Burst,
+    /// Drop the missed ticks and continue on the original schedule.
+    #[default]
+    Skip,
+    /// Send one tick immediately and schedule the following ticks relative to it.
+    Delay,
+}
+
 impl InputMapping {
     pub fn source(&self) -> &NodeId {
         static DORA_NODE_ID: OnceCell<NodeId> = OnceCell::new();
 
         match self {
             InputMapping::User(mapping) => &mapping.source,
-            InputMapping::Timer { .. } => DORA_NODE_ID.get_or_init(|| NodeId("dora".to_string())),
+            InputMapping::Timer(_) => DORA_NODE_ID.get_or_init(|| NodeId("dora".to_string())),
         }
     }
 }
@@ -147,8 +203,8 @@ impl InputMapping {
 impl fmt::Display for InputMapping {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            InputMapping::Timer { interval } => {
-                let duration = format_duration(*interval);
+            InputMapping::Timer(timer) => {
+                let duration = format_duration(timer.interval);
                 write!(f, "dora/timer/{duration}")
             }
             InputMapping::User(mapping) => {
@@ -180,35 +236,12 @@ impl<'de> Deserialize<'de> for InputMapping {
         let deserialized = match source {
             "dora" => match output.split_once('/') {
                 Some(("timer", output)) => {
-                    let (unit, value) = output.split_once('/').ok_or_else(|| {
-                        serde::de::Error::custom(
-                            "timer input must specify unit and value (e.g. `secs/5` or `millis/100`)",
-                        )
-                    })?;
-                    let interval = match unit {
-                        "secs" => {
-                            let value = value.parse().map_err(|_| {
-                                serde::de::Error::custom(format!(
-                                    "secs must be an integer (got `{value}`)"
-                                ))
-                            })?;
-                            Duration::from_secs(value)
-                        }
-                        "millis" => {
-                            let value = value.parse().map_err(|_| {
-                                serde::de::Error::custom(format!(
-                                    "millis must be an integer (got `{value}`)"
-                                ))
-                            })?;
-                            Duration::from_millis(value)
-                        }
-                        other => {
-                            return Err(serde::de::Error::custom(format!(
-                                "timer unit must be either secs or millis (got `{other}`"
-                            )))
-                        }
-                    };
-                    Self::Timer { interval }
+                    let interval =
+                        parse_duration(output, true).map_err(serde::de::Error::custom)?;
+                    if interval.is_zero() {
+                        return Err(serde::de::Error::custom("timer interval must not be zero"));
+                    }
+                    Self::Timer(TimerConfig::new(interval))
                 }
                 Some((other, _)) => {
                     return Err(serde::de::Error::custom(format!(
@@ -233,14 +266,61 @@ pub struct UserInputMapping {
     pub output: DataId,
 }
 
+/// Parses a duration in the `<unit>/<value>` format of timer inputs.
+///
+/// Supported units are `secs`, `millis`, `micros`, and `nanos`. The `hz` unit, which
+/// specifies a frequency instead, is only allowed if `allow_hz` is set.
+fn parse_duration(s: &str, allow_hz: bool) -> Result<Duration, String> {
+    let (unit, value) = s.split_once('/').ok_or_else(|| {
+        format!("duration must specify unit and value (e.g. `secs/5` or `millis/100`, got `{s}`)")
+    })?;
+    let integer = || {
+        value
+            .parse::<u64>()
+            .map_err(|_| format!("{unit} must be an integer (got `{value}`)"))
+    };
+    match unit {
+        "secs" => integer().map(Duration::from_secs),
+        "millis" => integer().map(Duration::from_millis),
+        "micros" => integer().map(Duration::from_micros),
+        "nanos" => integer().map(Duration::from_nanos),
+        "hz" if allow_hz => {
+            let frequency: f64 = value
+                .parse()
+                .ok()
+                .filter(|f: &f64| f.is_finite() && *f > 0.0)
+                .ok_or_else(|| format!("hz must be a positive number (got `{value}`)"))?;
+            Ok(hz_interval(frequency))
+        }
+        other if allow_hz => Err(format!(
+            "timer unit must be one of secs, millis, micros, nanos, or hz (got `{other}`)"
+        )),
+        other => Err(format!(
+            "duration unit must be one of secs, millis, micros, or nanos (got `{other}`)"
+        )),
+    }
+}
+
+fn hz_interval(frequency: f64) -> Duration {
+    Duration::from_secs_f64(1.0 / frequency)
+}
+
 pub struct FormattedDuration(pub Duration);
 
 impl fmt::Display for FormattedDuration {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        if self.0.subsec_millis() == 0 {
+        let nanos = self.0.as_nanos();
+        let frequency = (1e9 / nanos as f64).round();
+        if nanos % 1_000_000_000 == 0 {
             write!(f, "secs/{}", self.0.as_secs())
-        } else {
+        } else if nanos % 1_000_000 == 0 {
             write!(f, "millis/{}", self.0.as_millis())
+        } else if nanos % 1_000 == 0 {
+            write!(f, "micros/{}", self.0.as_micros())
+        } else if frequency >= 1.0 && hz_interval(frequency) == self.0 {
+            write!(f, "hz/{frequency}")
+        } else {
+            write!(f, "nanos/{nanos}")
         }
     }
 }
@@ -275,7 +355,7 @@ pub struct NodeRunConfig {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
-#[serde(deny_unknown_fields, from = "InputDef", into = "InputDef")]
+#[serde(deny_unknown_fields, try_from = "InputDef", into = "InputDef")]
 pub struct Input {
     pub mapping: InputMapping,
     pub queue_size: Option<usize>,
@@ -288,38 +368,87 @@ pub enum InputDef {
     WithOptions {
         source: InputMapping,
         queue_size: Option<usize>,
+        /// Missed tick behavior of `dora/timer` inputs (`burst`, `skip`, or `delay`).
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        missed_ticks: Option<MissedTickBehavior>,
+        /// Phase offset of `dora/timer` inputs, e.g. `millis/5`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        phase: Option<String>,
     },
 }
 
 impl From<Input> for InputDef {
     fn from(input: Input) -> Self {
+        let (missed_ticks, phase) = match &input.mapping {
+            InputMapping::Timer(timer) => (
+                Some(timer.missed_ticks).filter(|m| *m != MissedTickBehavior::default()),
+                Some(timer.phase).filter(|p| !p.is_zero()).map(|p| {
+                    match format_duration(p).to_string() {
+                        // phases don't support the `hz` unit
+                        f if f.starts_with("hz/") => format!("nanos/{}", p.as_nanos()),
+                        f => f,
+                    }
+                }),
+            ),
+            InputMapping::User(_) => (None, None),
+        };
         match input {
             Input {
                 mapping,
                 queue_size: None,
-            } => Self::MappingOnly(mapping),
+            } if missed_ticks.is_none() && phase.is_none() => Self::MappingOnly(mapping),
             Input {
                 mapping,
                 queue_size,
             } => Self::WithOptions {
                 source: mapping,
                 queue_size,
+                missed_ticks,
+                phase,
             },
         }
     }
 }
 
-impl From<InputDef> for Input {
-    fn from(value: InputDef) -> Self {
+impl TryFrom<InputDef> for Input {
+    type Error = String;
+
+    fn try_from(value: InputDef) -> Result<Self, Self::Error> {
         match value {
-            InputDef::MappingOnly(mapping) => Self {
+            InputDef::MappingOnly(mapping) => Ok(Self {
                 mapping,
                 queue_size: None,
-            },
-            InputDef::WithOptions { source, queue_size } => Self {
-                mapping: source,
+            }),
+            InputDef::WithOptions {
+                mut source,
                 queue_size,
-            },
+                missed_ticks,
+                phase,
+            } => {
+                match &mut source {
+                    InputMapping::Timer(timer) => {
+                        if let Some(missed_ticks) = missed_ticks {
+                            timer.missed_ticks = missed_ticks;
+                        }
+                        if let Some(phase) = phase {
+                            timer.phase = parse_duration(&phase, false)
+                                .map_err(|err| format!("invalid timer phase: {err}"))?;
+                        }
+                    }
+                    InputMapping::User(_) => {
+                        if missed_ticks.is_some() || phase.is_some() {
+                            return Err(
+                                "`missed_ticks` and `phase` are only supported for `dora/timer` inputs"
+                                    .into(),
+                            );
+                        }
+                    }
+                }
+                Ok(Self {
+                    mapping: source,
+                    queue_size,
+                })
+            }
         }
     }
 }
@@ -344,10 +473,110 @@ pub struct CommunicationConfig {
     pub remote: RemoteCommunicationConfig,
 }
 
+/// Configuration of the per-node log files that are written to `out/<dataflow-id>`.
+#[derive(Debug, Default, Serialize, Deserialize, JsonSchema, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct LoggingConfig {
+    /// Format of the log files.
+    #[serde(default)]
+    pub format: LogFormat,
+    /// Rotate the log file of a node when it becomes too large or too old.
+    ///
+    /// Log files grow without bounds if not set.
+    #[serde(default)]
+    pub rotation: Option<LogRotation>,
+    /// Remove the `out/<dataflow-id>` directories of old dataflows when a new
+    /// dataflow is spawned.
+    ///
+    /// Old log directories are kept forever if not set.
+    #[serde(default)]
+    pub retention: Option<LogRetention>,
+    /// Collect a crash bundle in `out/<dataflow-id>/crash_<node-id>` for every node
+    /// that fails.
+    ///
+    /// The bundle contains the tail of the node's log, its exit status, its resource
+    /// usage, its environment, its node config, and its core dump (if the system
+    /// writes core dumps to files). Show it with `dora logs --crash`.
+    #[serde(default)]
+    pub crash_bundles: bool,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Write the raw stdout and stderr output of the node.
+    #[default]
+    Text,
+    /// Write one JSON object per output line, containing the timestamp, the
+    /// stream (`stdout` or `stderr`), the node ID, and the detected log level.
+    Jsonl,
+}
+
+/// Log line format that is parsed for outputs set through `send_stdout_as` and
+/// `send_stderr_as`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LogParser {
+    /// The default format of the Rust `env_logger` crate, e.g.
+    /// `[2024-01-01T12:00:00Z INFO  my_crate::module] message`.
+    EnvLogger,
+    /// The default format of the Python `logging` module, e.g.
+    /// `WARNING:my_logger:message`.
+    Python,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct LogRotation {
+    /// Rotate the log file once it exceeds the given size in bytes.
+    #[serde(default)]
+    pub max_bytes: Option<u64>,
+    /// Rotate the log file once it was written for the given number of seconds.
+    #[serde(default)]
+    pub max_age_secs: Option<u64>,
+    /// Number of rotated log files to keep per node, the oldest files are
+    /// removed first. Must be at least 1.
+    #[serde(default = "LogRotation::default_max_files")]
+    pub max_files: usize,
+}
+
+impl LogRotation {
+    fn default_max_files() -> usize {
+        5
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct LogRetention {
+    /// Maximum number of dataflow log directories to keep, including the
+    /// directory of the new dataflow.
+    #[serde(default)]
+    pub max_dataflows: Option<usize>,
+    /// Remove dataflow log directories that were last modified more than the
+    /// given number of seconds ago.
+    #[serde(default)]
+    pub max_age_secs: Option<u64>,
+}
+
+/// Order in which the nodes of a dataflow are stopped on `dora stop` or ctrl-c.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ShutdownMode {
+    /// Send a stop event to all nodes at the same time.
+    #[default]
+    All,
+    /// Send a stop event only to source nodes. Downstream nodes are stopped
+    /// through `InputClosed` events once all their upstream nodes have exited,
+    /// so that the last outputs still reach them.
+    Ordered,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub enum LocalCommunicationConfig {
     Tcp,
     Shmem,
+    UnixDomain,
 }
 
 impl Default for LocalCommunicationConfig {
@@ -372,3 +601,161 @@ impl Default for RemoteCommunicationConfig {
         Self::Tcp
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_duration_units() {
+        assert_eq!(parse_duration("secs/2", false), Ok(Duration::from_secs(2)));
+        assert_eq!(
+            parse_duration("millis/5", false),
+            Ok(Duration::from_millis(5))
+        );
+        assert_eq!(
+            parse_duration("micros/250", false),
+            Ok(Duration::from_micros(250))
+        );
+        assert_eq!(
+            parse_duration("nanos/7", false),
+            Ok(Duration::from_nanos(7))
+        );
+        assert_eq!(parse_duration("hz/4", true), Ok(Duration::from_millis(250)));
+        assert_eq!(
+            parse_duration("hz/2.5", true),
+            Ok(Duration::from_millis(400))
+        );
+
+        assert!(parse_duration("hz/4", false).is_err());
+        for invalid in [
+            "hz/0",
+            "hz/-1",
+            "hz/inf",
+            "hz/NaN",
+            "hz/x",
+            "millis/1.5",
+            "5",
+            "days/1",
+        ] {
+            assert!(parse_duration(invalid, true).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn format_duration_units() {
+        let formatted = |d: Duration| format_duration(d).to_string();
+        assert_eq!(formatted(Duration::from_secs(3)), "secs/3");
+        assert_eq!(formatted(Duration::from_millis(1500)), "millis/1500");
+        assert_eq!(formatted(Duration::from_micros(10)), "micros/10");
+        assert_eq!(formatted(hz_interval(3.0)), "hz/3");
+        assert_eq!(formatted(hz_interval(60.0)), "hz/60");
+        assert_eq!(formatted(Duration::from_nanos(1234567)), "nanos/1234567");
+    }
+
+    #[test]
+    fn duration_format_roundtrip() {
+        let durations = [
+            Duration::from_secs(1),
+            Duration::from_millis(10),
+            Duration::from_micros(333),
+            Duration::from_nanos(1),
+            Duration::from_nanos(3_333_333_333),
+            hz_interval(3.0),
+            hz_interval(7.0),
+            hz_interval(30.0),
+            hz_interval(0.3),
+        ];
+        for duration in durations {
+            let formatted = format_duration(duration).to_string();
+            assert_eq!(
+                parse_duration(&formatted, true),
+                Ok(duration),
+                "{formatted}"
+            );
+        }
+    }
+
+    fn parse_input(yaml: &str) -> Result<Input, serde_yaml::Error> {
+        serde_yaml::from_str(yaml)
+    }
+
+    fn timer(input: &Input) -> TimerConfig {
+        match &input.mapping {
+            InputMapping::Timer(timer) => *timer,
+            other => panic!("expected timer input, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn timer_input_options() {
+        let input = parse_input("dora/timer/hz/4").unwrap();
+        assert_eq!(timer(&input), TimerConfig::new(Duration::from_millis(250)));
+
+        let input = parse_input(
+            "
+source: dora/timer/millis/10
+missed_ticks: burst
+phase: micros/500
+queue_size: 2
+",
+        )
+        .unwrap();
+        let timer_config = timer(&input);
+        assert_eq!(timer_config.interval, Duration::from_millis(10));
+        assert_eq!(timer_config.missed_ticks, MissedTickBehavior::Burst);
+        assert_eq!(timer_config.phase, Duration::from_micros(500));
+        assert_eq!(input.queue_size, Some(2));
+
+        for delay in ["delay", "skip"] {
+            let input = parse_input(&format!(
+                "{{source: dora/timer/secs/1, missed_ticks: {delay}}}"
+            ))
+            .unwrap();
+            assert_eq!(
+                serde_yaml::to_string(&timer(&input).missed_ticks)
+                    .unwrap()
+                    .trim(),
+                delay
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_timer_input_options() {
+        let invalid = [
+            // the phase can't be given as frequency
+            "{source: dora/timer/millis/10, phase: hz/5}",
+            "{source: dora/timer/millis/10, phase: soon}",
+            "{source: dora/timer/millis/10, missed_ticks: catch_up}",
+            "{source: dora/timer/millis/0}",
+            // options that only apply to timers
+            "{source: node/output, missed_ticks: burst}",
+            "{source: node/output, phase: millis/5}",
+        ];
+        for yaml in invalid {
+            assert!(parse_input(yaml).is_err(), "{yaml}");
+        }
+    }
+
+    #[test]
+    fn timer_input_roundtrip() {
+        let mut timer_config = TimerConfig::new(hz_interval(30.0));
+        timer_config.missed_ticks = MissedTickBehavior::Delay;
+        timer_config.phase = hz_interval(3.0);
+        let inputs = [
+            Input {
+                mapping: InputMapping::Timer(TimerConfig::new(Duration::from_millis(100))),
+                queue_size: None,
+            },
+            Input {
+                mapping: InputMapping::Timer(timer_config),
+                queue_size: Some(1),
+            },
+        ];
+        for input in inputs {
+            let yaml = serde_yaml::to_string(&input).unwrap();
+            assert_eq!(parse_input(&yaml).unwrap(), input, "{yaml}");
+        }
+    }
+}
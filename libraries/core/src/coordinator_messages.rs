@@ -1,6 +1,21 @@
-use crate::{config::NodeId, daemon_messages::DataflowId, topics::DataflowDaemonResult};
+use crate::{
+    config::{DataId, NodeId},
+    daemon_messages::DataflowId,
+    descriptor::ResolvedNode,
+    message::{uhlc, Metadata},
+    protocol::ProtocolVersion,
+    topics::{DataflowDaemonResult, NodeError},
+};
 use eyre::eyre;
 pub use log::Level;
+use std::{collections::BTreeMap, time::Duration};
+
+/// Delay before the first attempt of a daemon to reconnect to the coordinator.
+pub const DAEMON_RECONNECT_BACKOFF_MIN: Duration = Duration::from_secs(1);
+/// Maximum delay between two attempts of a daemon to reconnect to the coordinator.
+///
+/// The delay is doubled after every failed attempt, up to this limit.
+pub const DAEMON_RECONNECT_BACKOFF_MAX: Duration = Duration::from_secs(30);
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub enum CoordinatorRequest {
@@ -8,6 +23,9 @@ pub enum CoordinatorRequest {
         dora_version: String,
         machine_id: String,
         listen_port: u16,
+        /// Not set by daemons older than the introduction of protocol versions.
+        #[serde(default)]
+        protocol_version: Option<ProtocolVersion>,
     },
     Event {
         machine_id: String,
@@ -15,7 +33,7 @@ pub enum CoordinatorRequest {
     },
 }
 
-#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 #[must_use]
 pub struct LogMessage {
     pub dataflow_id: DataflowId,
@@ -28,18 +46,95 @@ pub struct LogMessage {
     pub message: String,
 }
 
-#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub enum DaemonEvent {
     AllNodesReady {
         dataflow_id: DataflowId,
         exited_before_subscribe: Vec<NodeId>,
     },
+    /// All nodes of a dataflow that runs only on the sending machine are ready.
+    ///
+    /// Only informational, the daemon starts the dataflow without waiting for the
+    /// coordinator.
+    DataflowReady {
+        dataflow_id: DataflowId,
+        exited_before_subscribe: Vec<NodeId>,
+    },
     AllNodesFinished {
         dataflow_id: DataflowId,
         result: DataflowDaemonResult,
     },
+    /// A node of the dataflow exited and will not be restarted.
+    NodeExited {
+        dataflow_id: DataflowId,
+        node_id: NodeId,
+        result: Result<(), NodeError>,
+    },
     Heartbeat,
     Log(LogMessage),
+    /// Output lines of a followed node, batched per output chunk.
+    NodeOutput(Vec<NodeOutputLine>),
+    /// Sent after a daemon reconnected to the coordinator, so that the coordinator
+    /// can restore its state after a restart.
+    RunningDataflows(Vec<RunningDataflowInfo>),
+    /// Periodic resource usage and message statistics of the nodes running on the daemon.
+    NodeMetrics(BTreeMap<DataflowId, BTreeMap<NodeId, NodeMetrics>>),
+    /// A message sent on an output that is tapped by `dora record`.
+    TappedOutput(TappedOutput),
+    /// The daemon exits, e.g. because it received a Ctrl-C signal.
+    ///
+    /// This is the last event sent by the daemon.
+    Exit,
+}
+
+/// A dataflow that is still running on a daemon.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct RunningDataflowInfo {
+    pub dataflow_id: DataflowId,
+    pub name: Option<String>,
+    pub nodes: Vec<ResolvedNode>,
+}
+
+/// Resource usage and message statistics of a running node.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct NodeMetrics {
+    /// CPU usage in percent, where 100% corresponds to one fully used core.
+    pub cpu_usage: f32,
+    /// Resident set size in bytes.
+    pub memory_bytes: u64,
+    /// Number of threads, if supported on the platform.
+    pub threads: Option<usize>,
+    pub disk_read_bytes_per_sec: f64,
+    pub disk_written_bytes_per_sec: f64,
+    /// Number of outputs sent by the node per second.
+    pub outputs_per_sec: f64,
+    /// Number of inputs delivered to the node per second.
+    pub inputs_per_sec: f64,
+    /// Total number of inputs that were dropped because the input queue of the node
+    /// was full.
+    pub dropped_inputs: u64,
+}
+
+/// A copy of a message that a node sent on a tapped output.
+///
+/// The message data is not part of this struct. It is sent as a separate binary
+/// frame directly after the serialized `TappedOutput`, which is empty if the
+/// message has no data.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct TappedOutput {
+    pub dataflow_id: DataflowId,
+    pub node_id: NodeId,
+    pub output_id: DataId,
+    pub metadata: Metadata,
+}
+
+/// A single line of stdout/stderr output of a node.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct NodeOutputLine {
+    pub dataflow_id: DataflowId,
+    pub node_id: NodeId,
+    pub timestamp: uhlc::Timestamp,
+    pub line: String,
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
@@ -3,12 +3,14 @@ use std::{
     fmt,
     net::SocketAddr,
     path::PathBuf,
-    time::Duration,
+    time::{Duration, SystemTime},
 };
 
 use crate::{
     config::{DataId, NodeId, NodeRunConfig, OperatorId},
+    coordinator_messages::NodeOutputLine,
     descriptor::{Descriptor, OperatorDefinition, ResolvedNode},
+    protocol::ProtocolVersion,
 };
 use aligned_vec::{AVec, ConstAlign};
 use dora_message::{uhlc, Metadata};
@@ -22,6 +24,12 @@ pub struct NodeConfig {
     pub daemon_communication: DaemonCommunication,
     pub dataflow_descriptor: Descriptor,
     pub dynamic: bool,
+    /// Directory for the shared memory regions of outputs.
+    ///
+    /// All nodes of a dataflow use the same directory, so that sandboxed nodes can
+    /// access the regions. Regions are POSIX shared memory objects if not set.
+    #[serde(default)]
+    pub shared_memory_dir: Option<PathBuf>,
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -35,6 +43,9 @@ pub enum DaemonCommunication {
     Tcp {
         socket_addr: SocketAddr,
     },
+    UnixDomain {
+        socket_file: PathBuf,
+    },
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
@@ -45,10 +56,13 @@ pub struct RuntimeConfig {
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub enum DaemonRequest {
+    /// Must always be the first variant with unchanged field order, to allow
+    /// reporting version mismatches in a readable way.
     Register {
         dataflow_id: DataflowId,
         node_id: NodeId,
         dora_version: String,
+        protocol_version: ProtocolVersion,
     },
     Subscribe,
     SendMessage {
@@ -245,9 +259,61 @@ pub enum DaemonCoordinatorEvent {
         node_id: NodeId,
         operator_id: Option<OperatorId>,
     },
+    /// Replaces the nodes of a running dataflow.
+    ///
+    /// The coordinator already verified that the nodes differ from the running ones
+    /// only in added and removed nodes and in the input mappings of existing nodes.
+    UpdateDataflow {
+        dataflow_id: DataflowId,
+        working_dir: PathBuf,
+        nodes: Vec<ResolvedNode>,
+        dataflow_descriptor: Descriptor,
+        /// Only check that the update can be applied on the machine, without applying it.
+        ///
+        /// The coordinator checks the update on all machines before applying it.
+        #[serde(default)]
+        dry_run: bool,
+    },
+    /// Stops the given node and spawns it again once it exited.
+    RestartNode {
+        dataflow_id: DataflowId,
+        node_id: NodeId,
+        grace_duration: Option<Duration>,
+    },
     Logs {
         dataflow_id: DataflowId,
         node_id: NodeId,
+        /// Return the crash bundle of the node instead of its log file.
+        crash: bool,
+        /// Only return the last `tail` lines.
+        #[serde(default)]
+        tail: Option<usize>,
+    },
+    /// Start forwarding the output of the given nodes to the coordinator.
+    ///
+    /// The reply contains the recent output history of the nodes, filtered
+    /// according to `tail` and `since`.
+    FollowLogs {
+        dataflow_id: DataflowId,
+        node_ids: BTreeSet<NodeId>,
+        tail: Option<usize>,
+        since: Option<SystemTime>,
+    },
+    /// Stop forwarding the output of the given nodes.
+    UnfollowLogs {
+        dataflow_id: DataflowId,
+        node_ids: BTreeSet<NodeId>,
+    },
+    /// Replaces the set of outputs whose messages are mirrored to the coordinator.
+    TapOutputs {
+        dataflow_id: DataflowId,
+        outputs: BTreeSet<(NodeId, DataId)>,
+    },
+    /// The daemon of the given machine disconnected, so the outputs of all nodes
+    /// on that machine should be treated as closed.
+    MachineLost {
+        dataflow_id: DataflowId,
+        machine_id: String,
     },
     Destroy,
     Heartbeat,
@@ -277,6 +343,8 @@ pub enum InterDaemonEvent {
 pub enum DaemonCoordinatorReply {
     SpawnResult(Result<(), String>),
     ReloadResult(Result<(), String>),
+    UpdateResult(Result<(), String>),
+    RestartResult(Result<(), String>),
     StopResult(Result<(), String>),
     DestroyResult {
         result: Result<(), String>,
@@ -284,6 +352,7 @@ pub enum DaemonCoordinatorReply {
         notify: Option<tokio::sync::oneshot::Sender<()>>,
     },
     Logs(Result<Vec<u8>, String>),
+    FollowLogsResult(Result<Vec<NodeOutputLine>, String>),
 }
 
 pub type DataflowId = Uuid;
@@ -291,6 +360,8 @@ pub type DataflowId = Uuid;
 #[derive(Debug, serde::Deserialize, serde::Serialize)]
 pub struct SpawnDataflowNodes {
     pub dataflow_id: DataflowId,
+    #[serde(default)]
+    pub name: Option<String>,
     pub working_dir: PathBuf,
     pub nodes: Vec<ResolvedNode>,
     pub machine_listen_ports: BTreeMap<String, SocketAddr>,
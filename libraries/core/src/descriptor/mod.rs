@@ -1,7 +1,11 @@
-use crate::config::{
-    CommunicationConfig, DataId, Input, InputMapping, NodeId, NodeRunConfig, OperatorId,
+use crate::{
+    config::{
+        CommunicationConfig, DataId, Input, InputMapping, LogParser, LoggingConfig, NodeId,
+        NodeRunConfig, OperatorId, ShutdownMode,
+    },
+    get_python_path,
 };
-use eyre::{bail, eyre, Context, OptionExt, Result};
+use eyre::{bail, Context, OptionExt, Result};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_with_expand_env::with_expand_envs;
@@ -10,6 +14,7 @@ use std::{
     env::consts::EXE_EXTENSION,
     fmt,
     path::{Path, PathBuf},
+    time::Duration,
 };
 use tracing::warn;
 pub use visualize::collect_dora_timers;
@@ -29,6 +34,11 @@ pub struct Descriptor {
     #[schemars(skip)]
     #[serde(default, rename = "_unstable_deploy")]
     pub deploy: Deploy,
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    /// Order in which the nodes are stopped.
+    #[serde(default)]
+    pub shutdown: ShutdownMode,
     pub nodes: Vec<Node>,
 }
 
@@ -65,7 +75,7 @@ impl Descriptor {
             for mapping in input_mappings
                 .into_iter()
                 .filter_map(|i| match &mut i.mapping {
-                    InputMapping::Timer { .. } => None,
+                    InputMapping::Timer(_) => None,
                     InputMapping::User(m) => Some(m),
                 })
             {
@@ -81,6 +91,8 @@ impl Descriptor {
                     args: node.args,
                     build: node.build,
                     send_stdout_as: node.send_stdout_as,
+                    send_stderr_as: node.send_stderr_as,
+                    parse_logs: node.parse_logs,
                     run_config: NodeRunConfig {
                         inputs: node.inputs,
                         outputs: node.outputs,
@@ -97,12 +109,21 @@ impl Descriptor {
                 }),
             };
 
+            let stop_timeout = node
+                .stop_timeout
+                .map(Duration::try_from_secs_f64)
+                .transpose()
+                .wrap_err_with(|| format!("invalid `stop_timeout` of node `{}`", node.id))?;
+
             resolved.push(ResolvedNode {
                 id: node.id,
                 name: node.name,
                 description: node.description,
                 env: node.env,
                 deploy: ResolvedDeploy::new(node.deploy, self),
+                sandbox: node.sandbox.and_then(Sandbox::into_config),
+                python: node.python,
+                stop_timeout,
                 kind,
             });
         }
@@ -193,10 +214,27 @@ pub struct Node {
     pub build: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub send_stdout_as: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub send_stderr_as: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parse_logs: Option<LogParser>,
     #[serde(default)]
     pub inputs: BTreeMap<DataId, Input>,
     #[serde(default)]
     pub outputs: BTreeSet<DataId>,
+
+    /// Run the node in an isolated sandbox (`true` or a sandbox configuration).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sandbox: Option<Sandbox>,
+
+    /// Python interpreter or virtual environment for `.py` nodes and Python operators.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub python: Option<PythonConfig>,
+
+    /// Seconds to wait for the node to exit after it was asked to stop, before it
+    /// is killed. Overrides the grace duration of `dora stop`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop_timeout: Option<f64>,
 }
 
 impl Node {
@@ -248,6 +286,24 @@ impl Node {
                 .ok_or_eyre("no operator"),
         }
     }
+
+    /// Turns the node into a standard node that runs the given executable.
+    ///
+    /// The node keeps its ID, name, environment variables, and deploy config. Its
+    /// inputs are removed and its outputs are replaced by the given ones.
+    pub fn replace_executable(&mut self, path: String, outputs: BTreeSet<DataId>) {
+        self.operators = None;
+        self.custom = None;
+        self.operator = None;
+        self.path = Some(path);
+        self.args = None;
+        self.build = None;
+        self.send_stdout_as = None;
+        self.send_stderr_as = None;
+        self.parse_logs = None;
+        self.inputs = BTreeMap::new();
+        self.outputs = outputs;
+    }
 }
 
 #[derive(Debug)]
@@ -281,37 +337,87 @@ pub struct ResolvedNode {
     #[serde(default)]
     pub deploy: ResolvedDeploy,
 
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sandbox: Option<SandboxConfig>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub python: Option<PythonConfig>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop_timeout: Option<Duration>,
+
     #[serde(flatten)]
     pub kind: CoreNodeKind,
 }
 
 impl ResolvedNode {
-    pub fn send_stdout_as(&self) -> Result<Option<String>> {
+    /// Returns the outputs that the stdout and stderr of the node are sent to.
+    ///
+    /// For runtime nodes, the settings of the operator are used and the output IDs
+    /// are prefixed with the operator ID. Routing the output of each operator
+    /// separately is not supported: the operators of a runtime node share its stdout
+    /// and stderr, so at most one of them may set `send_stdout_as` or `send_stderr_as`.
+    pub fn log_outputs(&self) -> Result<LogOutputs> {
         match &self.kind {
-            // TODO: Split stdout between operators
             CoreNodeKind::Runtime(n) => {
-                let count = n
-                    .operators
-                    .iter()
-                    .filter(|op| op.config.send_stdout_as.is_some())
-                    .count();
-                if count == 1 && n.operators.len() > 1 {
-                    warn!("All stdout from all operators of a runtime are going to be sent in the selected `send_stdout_as` operator.")
-                } else if count > 1 {
-                    return Err(eyre!("More than one `send_stdout_as` entries for a runtime node. Please only use one `send_stdout_as` per runtime."));
+                let mut routed = n.operators.iter().filter(|op| {
+                    op.config.send_stdout_as.is_some() || op.config.send_stderr_as.is_some()
+                });
+                let Some(op) = routed.next() else {
+                    return Ok(LogOutputs::default());
+                };
+                if routed.next().is_some() {
+                    bail!(
+                        "more than one operator of runtime node `{}` sets `send_stdout_as` or \
+                        `send_stderr_as`, but the operators of a runtime node share its output",
+                        self.id
+                    );
+                }
+                if n.operators.len() > 1 {
+                    warn!(
+                        "all output of runtime node `{}` is sent as outputs of operator `{}`",
+                        self.id, op.id
+                    );
                 }
-                Ok(n.operators.iter().find_map(|op| {
-                    op.config
-                        .send_stdout_as
-                        .clone()
-                        .map(|stdout| format!("{}/{}", op.id, stdout))
-                }))
+                let output = |name: &Option<String>| {
+                    name.as_ref()
+                        .map(|name| DataId::from(format!("{}/{name}", op.id)))
+                };
+                Ok(LogOutputs {
+                    stdout: output(&op.config.send_stdout_as),
+                    stderr: output(&op.config.send_stderr_as),
+                    parser: op.config.parse_logs,
+                })
             }
-            CoreNodeKind::Custom(n) => Ok(n.send_stdout_as.clone()),
+            CoreNodeKind::Custom(n) => Ok(LogOutputs {
+                stdout: n.send_stdout_as.clone().map(DataId::from),
+                stderr: n.send_stderr_as.clone().map(DataId::from),
+                parser: n.parse_logs,
+            }),
+        }
+    }
+
+    /// Returns the Python interpreter that runs the node if it is a Python node.
+    ///
+    /// Uses the `python` setting of the node if set, otherwise the `python3` in `PATH`.
+    pub fn python_path(&self, working_dir: &Path) -> Result<PathBuf> {
+        match &self.python {
+            Some(python) => python.interpreter_path(working_dir),
+            None => get_python_path(),
         }
     }
 }
 
+/// Outputs that the stdout and stderr lines of a node are sent to.
+#[derive(Debug, Clone, Default)]
+pub struct LogOutputs {
+    /// Receives stdout, and also stderr if `stderr` is not set.
+    pub stdout: Option<DataId>,
+    pub stderr: Option<DataId>,
+    /// Send parsed log lines instead of raw strings.
+    pub parser: Option<LogParser>,
+}
+
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ResolvedDeploy {
     pub machine: String,
@@ -327,6 +433,55 @@ impl ResolvedDeploy {
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum Sandbox {
+    /// Enables the sandbox with the default configuration.
+    Enabled(bool),
+    Config(SandboxConfig),
+}
+
+impl Sandbox {
+    pub fn into_config(self) -> Option<SandboxConfig> {
+        match self {
+            Sandbox::Enabled(true) => Some(SandboxConfig::default()),
+            Sandbox::Enabled(false) => None,
+            Sandbox::Config(config) => Some(config),
+        }
+    }
+}
+
+/// Isolates a node from the host system.
+///
+/// By default, the node is started in new Linux mount, PID, and network namespaces.
+/// It can only access its working directory, its connection to the daemon, the shared
+/// memory regions of its dataflow, and read-only system directories such as `/usr` and
+/// `/etc`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct SandboxConfig {
+    /// Keep access to the network of the host.
+    ///
+    /// Without network access, the node reaches the daemon through a Unix domain
+    /// socket if the dataflow uses TCP for the local communication.
+    #[serde(default)]
+    pub network: bool,
+    /// Additional paths that the node can read.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub read_only: Vec<PathBuf>,
+    /// Additional paths that the node can read and write.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub writable: Vec<PathBuf>,
+    /// Start the node through the given command instead of using the built-in
+    /// namespace sandbox, e.g. `bwrap --unshare-all --die-with-parent ...`.
+    ///
+    /// The node executable and its arguments are appended to the command. The paths
+    /// that the node needs to access are passed in the colon-separated
+    /// `DORA_SANDBOX_READ_ONLY` and `DORA_SANDBOX_WRITABLE` environment variables.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wrapper: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum CoreNodeKind {
@@ -418,8 +573,18 @@ pub struct OperatorConfig {
 
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub build: Option<String>,
+    /// Send the stdout of the runtime node as an output of this operator, together
+    /// with stderr if `send_stderr_as` is not set.
+    ///
+    /// The operators of a runtime node share its stdout and stderr, so only one
+    /// operator of the node may set `send_stdout_as` or `send_stderr_as`.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub send_stdout_as: Option<String>,
+    /// Send the stderr of the runtime node as an output of this operator.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub send_stderr_as: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parse_logs: Option<LogParser>,
 }
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
@@ -475,6 +640,146 @@ impl From<PythonSourceDef> for PythonSource {
     }
 }
 
+/// Python interpreter of a node.
+///
+/// Can be given as the path of an interpreter (`python: /usr/bin/python3.11`) or with
+/// options. If a `venv` is set, the node is run by the Python interpreter of that
+/// virtual environment.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(
+    deny_unknown_fields,
+    from = "PythonConfigDef",
+    into = "PythonConfigDef"
+)]
+pub struct PythonConfig {
+    /// Path or name of the Python interpreter.
+    ///
+    /// If a `venv` is set, this interpreter is used to create it.
+    pub interpreter: Option<PathBuf>,
+    /// Directory of a virtual environment, relative to the dataflow.
+    ///
+    /// `dora build` creates the virtual environment if it doesn't exist.
+    pub venv: Option<PathBuf>,
+    /// A `requirements.txt` or `pyproject.toml` file that `dora build` installs into
+    /// a newly created `venv`.
+    ///
+    /// Defaults to the `requirements.txt` or `pyproject.toml` next to the dataflow,
+    /// if any.
+    pub requirements: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged, deny_unknown_fields)]
+pub enum PythonConfigDef {
+    Interpreter(PathBuf),
+    WithOptions {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        interpreter: Option<PathBuf>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        venv: Option<PathBuf>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        requirements: Option<PathBuf>,
+    },
+}
+
+impl From<PythonConfig> for PythonConfigDef {
+    fn from(config: PythonConfig) -> Self {
+        match config {
+            PythonConfig {
+                interpreter: Some(interpreter),
+                venv: None,
+                requirements: None,
+            } => Self::Interpreter(interpreter),
+            PythonConfig {
+                interpreter,
+                venv,
+                requirements,
+            } => Self::WithOptions {
+                interpreter,
+                venv,
+                requirements,
+            },
+        }
+    }
+}
+
+impl From<PythonConfigDef> for PythonConfig {
+    fn from(value: PythonConfigDef) -> Self {
+        match value {
+            PythonConfigDef::Interpreter(interpreter) => Self {
+                interpreter: Some(interpreter),
+                venv: None,
+                requirements: None,
+            },
+            PythonConfigDef::WithOptions {
+                interpreter,
+                venv,
+                requirements,
+            } => Self {
+                interpreter,
+                venv,
+                requirements,
+            },
+        }
+    }
+}
+
+impl PythonConfig {
+    /// Returns the Python interpreter that runs the node.
+    pub fn interpreter_path(&self, working_dir: &Path) -> Result<PathBuf> {
+        match &self.venv {
+            Some(venv) => {
+                let venv = working_dir.join(venv);
+                let python = if cfg!(windows) {
+                    venv.join("Scripts").join("python.exe")
+                } else {
+                    venv.join("bin").join("python")
+                };
+                if !python.exists() {
+                    bail!(
+                        "no Python interpreter in virtual environment `{}` \
+                        (run `dora build` to create it)",
+                        venv.display()
+                    );
+                }
+                Ok(python)
+            }
+            None => self.base_interpreter_path(working_dir),
+        }
+    }
+
+    /// Returns the interpreter that is used to create the `venv`.
+    pub fn base_interpreter_path(&self, working_dir: &Path) -> Result<PathBuf> {
+        match &self.interpreter {
+            Some(interpreter) if interpreter.components().count() > 1 => {
+                let path = working_dir.join(interpreter);
+                if !path.exists() {
+                    bail!("Python interpreter `{}` does not exist", path.display());
+                }
+                Ok(path)
+            }
+            Some(interpreter) => which::which(interpreter).wrap_err_with(|| {
+                format!(
+                    "failed to find Python interpreter `{}`",
+                    interpreter.display()
+                )
+            }),
+            None => get_python_path(),
+        }
+    }
+
+    /// Returns the requirements that should be installed into a new `venv`.
+    pub fn requirements_path(&self, working_dir: &Path) -> Option<PathBuf> {
+        match &self.requirements {
+            Some(requirements) => Some(working_dir.join(requirements)),
+            None => ["requirements.txt", "pyproject.toml"]
+                .into_iter()
+                .map(|file| working_dir.join(file))
+                .find(|path| path.exists()),
+        }
+    }
+}
+
 pub fn source_is_url(source: &str) -> bool {
     source.contains("://")
 }
@@ -530,9 +835,16 @@ pub struct CustomNode {
     pub envs: Option<BTreeMap<String, EnvValue>>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub build: Option<String>,
-    /// Send stdout and stderr to another node
+    /// Send stdout to another node, together with stderr if `send_stderr_as` is not set
     #[serde(skip_serializing_if = "Option::is_none")]
     pub send_stdout_as: Option<String>,
+    /// Send stderr to another node
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub send_stderr_as: Option<String>,
+    /// Parse the lines sent through `send_stdout_as` and `send_stderr_as` as log
+    /// messages and send them as structs with `level`, `target` and `message` fields
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parse_logs: Option<LogParser>,
 
     #[serde(flatten)]
     pub run_config: NodeRunConfig,
@@ -558,3 +870,148 @@ impl fmt::Display for EnvValue {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve(yaml: &str) -> Vec<ResolvedNode> {
+        let descriptor: Descriptor = serde_yaml::from_str(yaml).unwrap();
+        descriptor.resolve_aliases_and_set_defaults().unwrap()
+    }
+
+    #[test]
+    fn operator_log_outputs() {
+        let nodes = resolve(
+            r#"
+            nodes:
+              - id: runtime
+                operators:
+                  - id: op
+                    python: op.py
+                    send_stdout_as: logs
+                    outputs: [logs]
+                  - id: other
+                    python: other.py
+            "#,
+        );
+        let outputs = nodes[0].log_outputs().unwrap();
+        assert_eq!(outputs.stdout, Some(DataId::from("op/logs".to_owned())));
+        assert_eq!(outputs.stderr, None);
+
+        let nodes = resolve(
+            r#"
+            nodes:
+              - id: runtime
+                operators:
+                  - id: op
+                    python: op.py
+                    send_stdout_as: logs
+                    outputs: [logs]
+                  - id: other
+                    python: other.py
+                    send_stderr_as: errors
+                    outputs: [errors]
+            "#,
+        );
+        let err = nodes[0].log_outputs().unwrap_err().to_string();
+        assert!(
+            err.contains("`send_stdout_as` or `send_stderr_as`, but"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn python_config_forms() {
+        let config: PythonConfig = serde_yaml::from_str("/usr/bin/python3.11").unwrap();
+        assert_eq!(
+            config,
+            PythonConfig {
+                interpreter: Some("/usr/bin/python3.11".into()),
+                venv: None,
+                requirements: None,
+            }
+        );
+        assert_eq!(
+            serde_yaml::to_string(&config).unwrap().trim(),
+            "/usr/bin/python3.11"
+        );
+
+        let config: PythonConfig =
+            serde_yaml::from_str("{interpreter: python3.11, venv: .venv}").unwrap();
+        assert_eq!(config.interpreter, Some("python3.11".into()));
+        assert_eq!(config.venv, Some(".venv".into()));
+        let roundtrip: PythonConfig =
+            serde_yaml::from_str(&serde_yaml::to_string(&config).unwrap()).unwrap();
+        assert_eq!(roundtrip, config);
+
+        assert!(serde_yaml::from_str::<PythonConfig>("{venv: .venv, unknown: 1}").is_err());
+    }
+
+    #[test]
+    fn python_interpreter_resolution() {
+        let dir = tempfile::tempdir().unwrap();
+        let venv = PythonConfig {
+            interpreter: Some("./bin/python".into()),
+            venv: Some(".venv".into()),
+            requirements: None,
+        };
+        // the venv is created by `dora build`
+        let err = venv.interpreter_path(dir.path()).unwrap_err();
+        assert!(err.to_string().contains("dora build"), "{err}");
+
+        let venv_python = if cfg!(windows) {
+            dir.path().join(".venv/Scripts/python.exe")
+        } else {
+            dir.path().join(".venv/bin/python")
+        };
+        std::fs::create_dir_all(venv_python.parent().unwrap()).unwrap();
+        std::fs::write(&venv_python, "").unwrap();
+        assert_eq!(venv.interpreter_path(dir.path()).unwrap(), venv_python);
+
+        // relative interpreter paths are resolved against the working directory
+        assert!(venv.base_interpreter_path(dir.path()).is_err());
+        let interpreter = dir.path().join("bin/python");
+        std::fs::create_dir_all(interpreter.parent().unwrap()).unwrap();
+        std::fs::write(&interpreter, "").unwrap();
+        assert_eq!(venv.base_interpreter_path(dir.path()).unwrap(), interpreter);
+
+        let missing = PythonConfig {
+            interpreter: Some("dora-missing-python".into()),
+            venv: None,
+            requirements: None,
+        };
+        assert!(missing.interpreter_path(dir.path()).is_err());
+    }
+
+    #[test]
+    fn python_requirements_default() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = PythonConfig {
+            interpreter: None,
+            venv: Some(".venv".into()),
+            requirements: None,
+        };
+        assert_eq!(config.requirements_path(dir.path()), None);
+
+        std::fs::write(dir.path().join("pyproject.toml"), "").unwrap();
+        assert_eq!(
+            config.requirements_path(dir.path()),
+            Some(dir.path().join("pyproject.toml"))
+        );
+        std::fs::write(dir.path().join("requirements.txt"), "").unwrap();
+        assert_eq!(
+            config.requirements_path(dir.path()),
+            Some(dir.path().join("requirements.txt"))
+        );
+
+        let explicit = PythonConfig {
+            requirements: Some("deps/requirements.txt".into()),
+            ..config
+        };
+        assert_eq!(
+            explicit.requirements_path(dir.path()),
+            Some(dir.path().join("deps/requirements.txt"))
+        );
+    }
+}
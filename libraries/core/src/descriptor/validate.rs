@@ -2,11 +2,10 @@ use crate::{
     adjust_shared_library_path,
     config::{DataId, Input, InputMapping, OperatorId, UserInputMapping},
     descriptor::{self, source_is_url, CoreNodeKind, OperatorSource, EXE_EXTENSION},
-    get_python_path,
 };
 
 use eyre::{bail, eyre, Context};
-use std::{path::Path, process::Command};
+use std::{collections::BTreeSet, path::Path, process::Command};
 use tracing::info;
 
 use super::{resolve_path, Descriptor, DYNAMIC_SOURCE, SHELL_SOURCE};
@@ -19,7 +18,20 @@ pub fn check_dataflow(
     coordinator_is_remote: bool,
 ) -> eyre::Result<()> {
     let nodes = dataflow.resolve_aliases_and_set_defaults()?;
-    let mut has_python_operator = false;
+
+    if let Some(rotation) = &dataflow.logging.rotation {
+        if rotation.max_files == 0 {
+            bail!("`logging.rotation.max_files` must be at least 1");
+        }
+    }
+    // nodes on remote machines use the Python interpreters of their machine
+    let is_local = |node: &super::ResolvedNode| match remote_daemon_id {
+        Some(remote_daemon_id) => {
+            !(remote_daemon_id.contains(&node.deploy.machine.as_str()) || coordinator_is_remote)
+        }
+        None => true,
+    };
+    let mut python_interpreters = BTreeSet::new();
 
     // check that nodes and operators exist
     for node in &nodes {
@@ -28,6 +40,9 @@ pub fn check_dataflow(
                 SHELL_SOURCE => (),
                 DYNAMIC_SOURCE => (),
                 source => {
+                    if source.ends_with(".py") && node.python.is_some() && is_local(node) {
+                        python_interpreters.insert(node.python_path(working_dir)?);
+                    }
                     if source_is_url(source) {
                         info!("{source} is a URL."); // TODO: Implement url check.
                     } else if let Some(remote_daemon_id) = remote_daemon_id {
@@ -58,8 +73,8 @@ pub fn check_dataflow(
                     };
                 }
             },
-            descriptor::CoreNodeKind::Runtime(node) => {
-                for operator_definition in &node.operators {
+            descriptor::CoreNodeKind::Runtime(runtime_node) => {
+                for operator_definition in &runtime_node.operators {
                     match &operator_definition.config.source {
                         OperatorSource::SharedLibrary(path) => {
                             if source_is_url(path) {
@@ -72,7 +87,15 @@ pub fn check_dataflow(
                             }
                         }
                         OperatorSource::Python(python_source) => {
-                            has_python_operator = true;
+                            if python_source.conda_env.is_some() && node.python.is_some() {
+                                bail!(
+                                    "node `{}` must not set both `python` and a `conda_env`",
+                                    node.id
+                                );
+                            }
+                            if is_local(node) {
+                                python_interpreters.insert(node.python_path(working_dir)?);
+                            }
                             let path = &python_source.source;
                             if source_is_url(path) {
                                 info!("{path} is a URL."); // TODO: Implement url check.
@@ -115,14 +138,21 @@ pub fn check_dataflow(
         };
     }
 
-    // Check that nodes can resolve `send_stdout_as`
+    // dynamic nodes are started by the user, outside of the sandbox
+    for node in &nodes {
+        if node.sandbox.is_some() && node.kind.dynamic() {
+            bail!("dynamic node `{}` cannot be sandboxed", node.id);
+        }
+    }
+
+    // Check that nodes can resolve `send_stdout_as` and `send_stderr_as`
     for node in &nodes {
-        node.send_stdout_as()
+        node.log_outputs()
             .context("Could not resolve `send_stdout_as` configuration")?;
     }
 
-    if has_python_operator {
-        check_python_runtime()?;
+    for python in python_interpreters {
+        check_python_runtime(&python)?;
     }
 
     Ok(())
@@ -134,7 +164,7 @@ fn check_input(
     input_id_str: &str,
 ) -> Result<(), eyre::ErrReport> {
     match &input.mapping {
-        InputMapping::Timer { interval: _ } => {}
+        InputMapping::Timer(_) => {}
         InputMapping::User(UserInputMapping { source, output }) => {
             let source_node = nodes.iter().find(|n| &n.id == source).ok_or_else(|| {
                 eyre!("source node `{source}` mapped to input `{input_id_str}` does not exist",)
@@ -177,11 +207,13 @@ fn check_input(
     Ok(())
 }
 
-fn check_python_runtime() -> eyre::Result<()> {
+fn check_python_runtime(python: &Path) -> eyre::Result<()> {
     // Check if python dora-rs is installed and match cli version
-    let reinstall_command =
-        format!("Please reinstall it with: `pip install dora-rs=={VERSION} --force`");
-    let mut command = Command::new(get_python_path().context("Could not get python binary")?);
+    let reinstall_command = format!(
+        "Please reinstall it with: `{} -m pip install dora-rs=={VERSION} --force`",
+        python.display()
+    );
+    let mut command = Command::new(python);
     command.args([
         "-c",
         &format!(
@@ -204,3 +236,34 @@ assert dora.__version__=='{VERSION}',  'Python dora-rs should be {VERSION}, but
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn python_of_remote_nodes_is_not_checked() {
+        let dataflow: Descriptor = serde_yaml::from_str(
+            r#"
+            nodes:
+              - id: remote
+                path: /opt/nodes/remote.py
+                python:
+                  venv: .venv
+                _unstable_deploy:
+                  machine: b
+            "#,
+        )
+        .unwrap();
+        let working_dir = tempfile::tempdir().unwrap();
+
+        check_dataflow(&dataflow, working_dir.path(), Some(&["b"]), false).unwrap();
+
+        // the venv doesn't exist on this machine
+        let err = check_dataflow(&dataflow, working_dir.path(), None, false).unwrap_err();
+        assert!(
+            format!("{err:?}").contains("virtual environment"),
+            "{err:?}"
+        );
+    }
+}
@@ -58,8 +58,8 @@ fn collect_dora_nodes(
     for input in values {
         match &input.mapping {
             InputMapping::User(_) => {}
-            InputMapping::Timer { interval } => {
-                dora_timers.insert(*interval);
+            InputMapping::Timer(timer) => {
+                dora_timers.insert(timer.interval);
             }
         }
     }
@@ -132,12 +132,9 @@ fn visualize_node_inputs(
 ) {
     let node_id = &node.id;
     match &node.kind {
-        CoreNodeKind::Custom(node) => visualize_inputs(
-            &node_id.to_string(),
-            &node.run_config.inputs,
-            flowchart,
-            nodes,
-        ),
+        CoreNodeKind::Custom(node) => {
+            visualize_inputs(node_id.as_ref(), &node.run_config.inputs, flowchart, nodes)
+        }
         CoreNodeKind::Runtime(RuntimeNode { operators, .. }) => {
             for operator in operators {
                 visualize_inputs(
@@ -159,7 +156,7 @@ fn visualize_inputs(
 ) {
     for (input_id, input) in inputs {
         match &input.mapping {
-            mapping @ InputMapping::Timer { .. } => {
+            mapping @ InputMapping::Timer(_) => {
                 writeln!(flowchart, "  {} -- {input_id} --> {target}", mapping).unwrap();
             }
             InputMapping::User(mapping) => {
@@ -0,0 +1,169 @@
+//! Versions of the communication protocols between dora components.
+//!
+//! Each connection between dora components (node and daemon, daemon and daemon,
+//! daemon and coordinator) starts with a handshake that exchanges the protocol
+//! version of both sides, together with the range of remote versions that each
+//! side supports. The connection is only accepted if each version lies in the
+//! supported range of the other side.
+//!
+//! The handshake happens before any other message is decoded, so it also covers
+//! the layout of the message types, including the message [`Metadata`]. Messages
+//! are encoded with `bincode`, which can't skip unknown fields, so even adding a
+//! field makes the messages unreadable for the other side. For this reason, the
+//! supported ranges only accept the same major and minor version, and any layout
+//! change requires a new minor (or major) version. The patch version can be
+//! increased for changes that keep the message layout intact.
+//!
+//! [`Metadata`]: crate::message::Metadata
+
+use semver::{Version, VersionReq};
+
+/// Protocol version spoken between nodes and their local daemon.
+pub const NODE_DAEMON_PROTOCOL: Protocol = Protocol::new(1, 0, "~1.0");
+/// Protocol version spoken between daemons of different machines.
+pub const INTER_DAEMON_PROTOCOL: Protocol = Protocol::new(1, 0, "~1.0");
+/// Protocol version spoken between daemons and the coordinator.
+pub const DAEMON_COORDINATOR_PROTOCOL: Protocol = Protocol::new(1, 0, "~1.0");
+
+/// A protocol version of this build and the range of remote versions it supports.
+#[derive(Debug, Clone)]
+pub struct Protocol {
+    version: Version,
+    supported: &'static str,
+}
+
+impl Protocol {
+    const fn new(major: u64, minor: u64, supported: &'static str) -> Self {
+        Self {
+            version: Version::new(major, minor, 0),
+            supported,
+        }
+    }
+
+    /// The version information that is sent to the remote side on connect.
+    pub fn version(&self) -> ProtocolVersion {
+        ProtocolVersion {
+            version: self.version.clone(),
+            supported: VersionReq::parse(self.supported).expect("invalid protocol version range"),
+        }
+    }
+
+    /// Checks that this protocol and the `remote` version are compatible.
+    ///
+    /// Returns the lower of the two versions, which only differ in the patch version
+    /// if the supported ranges follow the rules of this module. The given names and
+    /// dora versions are only used for the error message.
+    pub fn negotiate(
+        &self,
+        remote: &ProtocolVersion,
+        local_name: &str,
+        local_dora_version: &str,
+        remote_name: &str,
+        remote_dora_version: &str,
+    ) -> Result<Version, String> {
+        let local = self.version();
+        if !local.supported.matches(&remote.version) {
+            Err(format!(
+                "incompatible protocol versions: {remote_name} v{remote_dora_version} uses \
+                protocol version {}, but {local_name} v{local_dora_version} only supports \
+                protocol versions `{}` (please use matching dora versions)",
+                remote.version, local.supported
+            ))
+        } else if !remote.supported.matches(&local.version) {
+            Err(format!(
+                "incompatible protocol versions: {local_name} v{local_dora_version} uses \
+                protocol version {}, but {remote_name} v{remote_dora_version} only supports \
+                protocol versions `{}` (please use matching dora versions)",
+                local.version, remote.supported
+            ))
+        } else {
+            Ok(local.version.min(remote.version.clone()))
+        }
+    }
+}
+
+/// Protocol version information that is exchanged on connect.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ProtocolVersion {
+    pub version: Version,
+    /// The protocol versions of the remote side that are supported.
+    pub supported: VersionReq,
+}
+
+/// First message sent on every new inter-daemon connection.
+///
+/// The layout of this struct must never change to ensure that version
+/// mismatches can be reported in a readable way.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct InterDaemonHandshake {
+    pub protocol_version: ProtocolVersion,
+    pub dora_version: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn remote(version: &str, supported: &str) -> ProtocolVersion {
+        ProtocolVersion {
+            version: Version::parse(version).unwrap(),
+            supported: VersionReq::parse(supported).unwrap(),
+        }
+    }
+
+    fn negotiate(local: &Protocol, remote: &ProtocolVersion) -> Result<Version, String> {
+        local.negotiate(remote, "daemon", "0.3.5", "node", "0.3.6")
+    }
+
+    #[test]
+    fn compatibility_matrix() {
+        let local = Protocol::new(1, 2, "~1.2");
+
+        // same version
+        assert_eq!(
+            negotiate(&local, &remote("1.2.0", "~1.2")),
+            Ok(Version::new(1, 2, 0))
+        );
+        // patch versions keep the message layout
+        assert_eq!(
+            negotiate(&local, &remote("1.2.3", "~1.2")),
+            Ok(Version::new(1, 2, 0))
+        );
+        // other minor versions have a different message layout
+        assert!(negotiate(&local, &remote("1.1.0", "~1.1")).is_err());
+        assert!(negotiate(&local, &remote("1.3.0", "~1.3")).is_err());
+        // remote version outside of the local range
+        assert!(negotiate(&local, &remote("2.0.0", ">=1.0, <3")).is_err());
+        assert!(negotiate(&local, &remote("0.9.0", ">=0.9, <2")).is_err());
+        // local version outside of the remote range
+        assert!(negotiate(&local, &remote("1.2.0", ">=1.3, <2")).is_err());
+        assert!(negotiate(&local, &remote("3.0.0", ">=3.0, <4")).is_err());
+    }
+
+    #[test]
+    fn error_names_the_unsupported_side() {
+        let local = Protocol::new(1, 2, "~1.2");
+        let err = negotiate(&local, &remote("2.0.0", ">=1.0, <3")).unwrap_err();
+        assert!(
+            err.contains("node v0.3.6 uses protocol version 2.0.0"),
+            "{err}"
+        );
+        let err = negotiate(&local, &remote("1.2.0", ">=1.3, <2")).unwrap_err();
+        assert!(
+            err.contains("daemon v0.3.5 uses protocol version 1.2.0"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn protocol_ranges_are_valid() {
+        for protocol in [
+            NODE_DAEMON_PROTOCOL,
+            INTER_DAEMON_PROTOCOL,
+            DAEMON_COORDINATOR_PROTOCOL,
+        ] {
+            let version = protocol.version();
+            assert!(version.supported.matches(&version.version));
+        }
+    }
+}
@@ -5,12 +5,13 @@ use std::{
     fmt::Display,
     net::{IpAddr, Ipv4Addr},
     path::PathBuf,
-    time::Duration,
+    time::{Duration, SystemTime},
 };
 use uuid::Uuid;
 
 use crate::{
-    config::{NodeId, OperatorId},
+    config::{DataId, NodeId, OperatorId},
+    coordinator_messages::NodeMetrics,
     descriptor::Descriptor,
 };
 
@@ -29,12 +30,37 @@ pub enum ControlRequest {
         // TODO: remove this once we figure out deploying of node/operator
         // binaries from CLI to coordinator/daemon
         local_working_dir: PathBuf,
+        /// Path of the descriptor file, recorded in the dataflow history.
+        #[serde(default)]
+        dataflow_path: Option<PathBuf>,
     },
     Reload {
         dataflow_id: Uuid,
         node_id: NodeId,
         operator_id: Option<OperatorId>,
     },
+    /// Applies a changed descriptor to a running dataflow.
+    ///
+    /// Nodes that are no longer part of the descriptor are stopped, new nodes are
+    /// spawned, and the input mappings of the remaining nodes are updated. All
+    /// other changes are rejected.
+    Apply {
+        dataflow_uuid: Uuid,
+        dataflow: Descriptor,
+        local_working_dir: PathBuf,
+    },
+    /// Stops the given node and spawns it again.
+    ///
+    /// Downstream nodes don't see their inputs closed. Inputs that arrive for the
+    /// node while it is restarting are buffered up to the queue size of the input.
+    Restart {
+        uuid: Option<Uuid>,
+        name: Option<String>,
+        node: NodeId,
+        /// Kill the node if it doesn't stop within this duration, unless the node
+        /// has a `stop_timeout`.
+        grace_duration: Option<Duration>,
+    },
     Check {
         dataflow_uuid: Uuid,
     },
@@ -50,15 +76,65 @@ pub enum ControlRequest {
         uuid: Option<Uuid>,
         name: Option<String>,
         node: String,
+        /// Return the crash bundle of the node instead of its log file.
+        #[serde(default)]
+        crash: bool,
+        /// Only return the last `tail` lines.
+        #[serde(default)]
+        tail: Option<usize>,
     },
     Destroy,
     List,
+    /// Returns the records of all running and finished dataflows known to the
+    /// coordinator, ordered by start time.
+    History,
+    /// Returns the record of a single dataflow.
+    ///
+    /// The name can be suffixed with `@<index>` to select a previous run with that
+    /// name, where `@0` is the latest one.
+    Inspect {
+        uuid: Option<Uuid>,
+        name: Option<String>,
+    },
     DaemonConnected,
     ConnectedMachines,
     LogSubscribe {
         dataflow_id: Uuid,
         level: log::LevelFilter,
     },
+    /// Streams the lifecycle events of the coordinator.
+    ///
+    /// The coordinator replies with [`ControlRequestReply::EventSubscribed`], followed
+    /// by a stream of [`LifecycleEvent`] messages. The connection is closed when the
+    /// coordinator stops.
+    EventSubscribe,
+    /// Streams the output of the given nodes.
+    ///
+    /// The coordinator replies with [`ControlRequestReply::LogFollowStarted`],
+    /// followed by a stream of [`NodeOutputLine`](crate::coordinator_messages::NodeOutputLine)
+    /// messages. The connection is closed when the dataflow finishes.
+    LogFollow {
+        uuid: Option<Uuid>,
+        name: Option<String>,
+        nodes: Vec<NodeId>,
+        tail: Option<usize>,
+        since: Option<SystemTime>,
+    },
+    /// Returns the latest resource usage and message statistics of the nodes of all
+    /// running dataflows.
+    NodeMetrics,
+    /// Mirrors the given outputs of a running dataflow to this connection.
+    ///
+    /// All outputs of the dataflow are recorded if `outputs` is empty. The coordinator
+    /// replies with [`ControlRequestReply::RecordStarted`], followed by a stream of
+    /// [`TappedOutput`](crate::coordinator_messages::TappedOutput) messages, each
+    /// followed by a binary frame with the message data. The connection is closed
+    /// when the dataflow finishes or when the recorder doesn't keep up.
+    Record {
+        uuid: Option<Uuid>,
+        name: Option<String>,
+        outputs: Vec<(NodeId, DataId)>,
+    },
 }
 
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
@@ -87,18 +163,131 @@ pub enum DataflowStatus {
     Failed,
 }
 
+/// Summary of a running or finished dataflow, as stored in the coordinator history.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct DataflowRecord {
+    pub id: DataflowId,
+    /// Position among the runs with the same name, `0` for the latest one.
+    pub index: usize,
+    pub status: DataflowStatus,
+    /// Not known for dataflows that were not started through `dora start`.
+    pub dataflow_path: Option<PathBuf>,
+    pub machines: BTreeSet<String>,
+    pub nodes: Vec<NodeId>,
+    pub started: Option<SystemTime>,
+    /// Time of the latest result reported by a daemon, `None` while running.
+    pub finished: Option<SystemTime>,
+    /// Exit results of the nodes that finished.
+    pub node_results: BTreeMap<NodeId, Result<(), NodeError>>,
+}
+
+/// An event that is sent to [`ControlRequest::EventSubscribe`] connections.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct LifecycleEvent {
+    pub time: SystemTime,
+    pub kind: LifecycleEventKind,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub enum LifecycleEventKind {
+    DataflowSpawned {
+        dataflow: DataflowId,
+        machines: BTreeSet<String>,
+    },
+    /// All nodes of the dataflow are ready on all machines.
+    NodesReady {
+        dataflow: DataflowId,
+        exited_before_subscribe: Vec<NodeId>,
+    },
+    NodeExited {
+        dataflow: DataflowId,
+        node_id: NodeId,
+        machine_id: String,
+        result: Result<(), NodeError>,
+    },
+    DataflowFinished {
+        dataflow: DataflowId,
+        result: DataflowResult,
+    },
+    DaemonConnected {
+        machine_id: String,
+    },
+    /// The daemon exited, was destroyed, or was replaced by a new connection of the
+    /// same machine. Also sent if it stopped sending heartbeats or could not be
+    /// reached anymore.
+    DaemonDisconnected {
+        machine_id: String,
+    },
+}
+
+/// Changes made to a running dataflow by [`ControlRequest::Apply`].
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize)]
+pub struct DataflowChanges {
+    pub added_nodes: BTreeSet<NodeId>,
+    pub removed_nodes: BTreeSet<NodeId>,
+    /// Inputs of existing nodes that were rewired or removed.
+    pub changed_inputs: BTreeSet<(NodeId, DataId)>,
+}
+
+impl DataflowChanges {
+    pub fn is_empty(&self) -> bool {
+        self.added_nodes.is_empty()
+            && self.removed_nodes.is_empty()
+            && self.changed_inputs.is_empty()
+    }
+}
+
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
 pub enum ControlRequestReply {
     Error(String),
     CoordinatorStopped,
-    DataflowStarted { uuid: Uuid },
-    DataflowReloaded { uuid: Uuid },
-    DataflowStopped { uuid: Uuid, result: DataflowResult },
+    DataflowStarted {
+        uuid: Uuid,
+    },
+    DataflowReloaded {
+        uuid: Uuid,
+    },
+    DataflowApplied {
+        uuid: Uuid,
+        changes: DataflowChanges,
+    },
+    NodeRestarted {
+        uuid: Uuid,
+        node_id: NodeId,
+    },
+    DataflowStopped {
+        uuid: Uuid,
+        result: DataflowResult,
+    },
     DataflowList(DataflowList),
+    History(Vec<DataflowRecord>),
+    DataflowRecord(DataflowRecord),
     DestroyOk,
     DaemonConnected(bool),
     ConnectedMachines(BTreeSet<String>),
     Logs(Vec<u8>),
+    LogFollowStarted,
+    EventSubscribed,
+    NodeMetrics(Vec<DataflowMetrics>),
+    RecordStarted {
+        dataflow_id: Uuid,
+        outputs: BTreeSet<(NodeId, DataId)>,
+    },
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct DataflowMetrics {
+    pub id: DataflowId,
+    pub nodes: Vec<NodeMetricsEntry>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct NodeMetricsEntry {
+    pub node_id: NodeId,
+    pub machine_id: String,
+    /// Not set if the daemon has not reported any metrics for the node yet, or if
+    /// the node is no longer running.
+    pub metrics: Option<NodeMetrics>,
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -155,10 +344,28 @@ pub struct NodeError {
     pub timestamp: uhlc::Timestamp,
     pub cause: NodeErrorCause,
     pub exit_status: NodeExitStatus,
+    /// Directory with the crash diagnostics of the node, on the machine that ran it.
+    ///
+    /// Only collected if `crash_bundles` is enabled in the `logging` config of the dataflow.
+    #[serde(default)]
+    pub crash_bundle: Option<PathBuf>,
 }
 
 impl std::fmt::Display for NodeError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let NodeErrorCause::MachineLost { machine_id } = &self.cause {
+            return write!(
+                f,
+                "machine lost: the daemon on machine `{machine_id}` running this node disconnected"
+            );
+        }
+        if let NodeErrorCause::NoResult { machine_id } = &self.cause {
+            return write!(
+                f,
+                "no result: the daemon on machine `{machine_id}` no longer runs this node \
+                and reported no result for it (was the daemon restarted?)"
+            );
+        }
         match &self.exit_status {
             NodeExitStatus::Success => write!(f, "<success>"),
             NodeExitStatus::IoError(err) => write!(f, "I/O error while reading exit status: {err}"),
@@ -190,7 +397,9 @@ impl std::fmt::Display for NodeError {
         }?;
 
         match &self.cause {
-            NodeErrorCause::GraceDuration => {}, // handled above
+            NodeErrorCause::GraceDuration
+            | NodeErrorCause::MachineLost { .. }
+            | NodeErrorCause::NoResult { .. } => {}, // handled above
             NodeErrorCause::Cascading { caused_by_node } => write!(
                 f,
                 ". This error occurred because node `{caused_by_node}` exited before connecting to dora."
@@ -202,6 +411,10 @@ impl std::fmt::Display for NodeError {
             },
         }
 
+        if let Some(crash_bundle) = &self.crash_bundle {
+            write!(f, "\ncrash diagnostics: {}", crash_bundle.display())?;
+        }
+
         Ok(())
     }
 }
@@ -214,6 +427,15 @@ pub enum NodeErrorCause {
     Cascading {
         caused_by_node: NodeId,
     },
+    /// The daemon of the machine that the node was running on disconnected.
+    MachineLost {
+        machine_id: String,
+    },
+    /// The daemon of the machine that the node was running on no longer knows the
+    /// node and reported no result for it, e.g. because the daemon was restarted.
+    NoResult {
+        machine_id: String,
+    },
     Other {
         stderr: String,
     },
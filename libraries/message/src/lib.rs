@@ -7,8 +7,18 @@ use arrow_data::ArrayData;
 use arrow_schema::DataType;
 use eyre::Context;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 pub use uhlc;
 
+/// Version of the [`Metadata`] format.
+///
+/// Stored in every [`Metadata`] for debugging purposes. Compatibility of the
+/// layout is not checked here, because [`Metadata`] is only readable once it was
+/// decoded successfully. Instead, layout changes of [`Metadata`] or
+/// [`MetadataParameters`] require a new minor version of the node/daemon
+/// protocol, which is checked when nodes register.
+pub const METADATA_VERSION: u16 = 1;
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Metadata {
     metadata_version: u16,
@@ -110,6 +120,11 @@ pub struct MetadataParameters {
     pub watermark: u64,
     pub deadline: u64,
     pub open_telemetry_context: String,
+    /// Arbitrary user-defined key/value pairs.
+    ///
+    /// These parameters are not interpreted by dora. They are forwarded
+    /// untouched to all receivers of the message.
+    pub user: BTreeMap<String, Parameter>,
 }
 
 impl MetadataParameters {
@@ -119,6 +134,79 @@ impl MetadataParameters {
             ..self
         }
     }
+
+    /// Sets the user parameter with the given key, replacing any previous value.
+    pub fn with_user_parameter(
+        mut self,
+        key: impl Into<String>,
+        value: impl Into<Parameter>,
+    ) -> Self {
+        self.user.insert(key.into(), value.into());
+        self
+    }
+}
+
+/// A typed value of a user-defined metadata parameter.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub enum Parameter {
+    Bool(bool),
+    Integer(i64),
+    Float(f64),
+    String(String),
+    Bytes(Vec<u8>),
+}
+
+/// Floats are compared by their bit pattern, so that `Parameter` (and thus
+/// `Metadata`) can implement `Eq`.
+impl PartialEq for Parameter {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Bool(a), Self::Bool(b)) => a == b,
+            (Self::Integer(a), Self::Integer(b)) => a == b,
+            (Self::Float(a), Self::Float(b)) => a.to_bits() == b.to_bits(),
+            (Self::String(a), Self::String(b)) => a == b,
+            (Self::Bytes(a), Self::Bytes(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Parameter {}
+
+impl From<bool> for Parameter {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+impl From<i64> for Parameter {
+    fn from(value: i64) -> Self {
+        Self::Integer(value)
+    }
+}
+
+impl From<f64> for Parameter {
+    fn from(value: f64) -> Self {
+        Self::Float(value)
+    }
+}
+
+impl From<String> for Parameter {
+    fn from(value: String) -> Self {
+        Self::String(value)
+    }
+}
+
+impl From<&str> for Parameter {
+    fn from(value: &str) -> Self {
+        Self::String(value.to_owned())
+    }
+}
+
+impl From<Vec<u8>> for Parameter {
+    fn from(value: Vec<u8>) -> Self {
+        Self::Bytes(value)
+    }
 }
 
 impl Metadata {
@@ -132,7 +220,7 @@ impl Metadata {
         parameters: MetadataParameters,
     ) -> Self {
         Self {
-            metadata_version: 0,
+            metadata_version: METADATA_VERSION,
             timestamp,
             parameters,
             type_info,
@@ -142,4 +230,8 @@ impl Metadata {
     pub fn timestamp(&self) -> uhlc::Timestamp {
         self.timestamp
     }
+
+    pub fn metadata_version(&self) -> u16 {
+        self.metadata_version
+    }
 }
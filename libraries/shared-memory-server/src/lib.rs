@@ -1,6 +1,7 @@
 #![allow(clippy::missing_safety_doc)]
 
 use self::channel::ShmemChannel;
+pub use self::region::DataRegion;
 use eyre::{eyre, Context};
 use serde::{Deserialize, Serialize};
 pub use shared_memory_extended::{Shmem, ShmemConf};
@@ -8,6 +9,7 @@ use std::marker::PhantomData;
 use std::time::Duration;
 
 mod channel;
+mod region;
 
 pub struct ShmemServer<T, U> {
     channel: ShmemChannel,
@@ -0,0 +1,208 @@
+//! Shared memory regions for message data.
+//!
+//! Regions are either POSIX shared memory objects or memory-mapped files in a
+//! directory, which is used for dataflows that have a dedicated shared memory
+//! directory. Files are identified by their absolute path, so that receivers don't
+//! need to know the directory.
+
+use eyre::Context;
+use memmap2::{MmapOptions, MmapRaw};
+use shared_memory_extended::{Shmem, ShmemConf};
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+pub struct DataRegion {
+    inner: Inner,
+}
+
+enum Inner {
+    Shmem(Shmem),
+    File {
+        map: MmapRaw,
+        id: String,
+        /// Set if this region created the file, which removes it on drop.
+        owner: bool,
+    },
+}
+
+impl DataRegion {
+    /// Creates a new writable region of the given size.
+    ///
+    /// The region is created as a file in `dir` if set, and as a POSIX shared memory
+    /// object otherwise.
+    pub fn create(size: usize, dir: Option<&Path>) -> eyre::Result<Self> {
+        let inner = match dir {
+            Some(dir) => {
+                let (file, path) = create_file(dir)?;
+                file.set_len(size as u64)
+                    .wrap_err("failed to resize shared memory file")?;
+                let map = MmapOptions::new()
+                    .len(size)
+                    .map_raw(&file)
+                    .wrap_err("failed to map shared memory file")?;
+                let id = path
+                    .into_os_string()
+                    .into_string()
+                    .map_err(|path| eyre::eyre!("shared memory path {path:?} is not UTF-8"))?;
+                Inner::File {
+                    map,
+                    id,
+                    owner: true,
+                }
+            }
+            None => Inner::Shmem(
+                ShmemConf::new()
+                    .size(size)
+                    .writable(true)
+                    .create()
+                    .wrap_err("failed to create shared memory")?,
+            ),
+        };
+        Ok(Self { inner })
+    }
+
+    /// Opens the existing region with the given ID for reading.
+    pub fn open(id: &str) -> eyre::Result<Self> {
+        let inner = if is_file_id(id) {
+            let file = File::open(id)
+                .wrap_err_with(|| format!("failed to open shared memory file `{id}`"))?;
+            let map = MmapOptions::new()
+                .map_raw_read_only(&file)
+                .wrap_err_with(|| format!("failed to map shared memory file `{id}`"))?;
+            Inner::File {
+                map,
+                id: id.to_owned(),
+                owner: false,
+            }
+        } else {
+            Inner::Shmem(
+                ShmemConf::new()
+                    .os_id(id)
+                    .writable(false)
+                    .open()
+                    .wrap_err("failed to open shared memory")?,
+            )
+        };
+        Ok(Self { inner })
+    }
+
+    /// The ID to open the region with, see [`DataRegion::open`].
+    pub fn id(&self) -> &str {
+        match &self.inner {
+            Inner::Shmem(shmem) => shmem.get_os_id(),
+            Inner::File { id, .. } => id,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match &self.inner {
+            Inner::Shmem(shmem) => shmem.len(),
+            Inner::File { map, .. } => map.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// # Safety
+    ///
+    /// Other processes might write to the region at the same time.
+    pub unsafe fn as_slice(&self) -> &[u8] {
+        match &self.inner {
+            Inner::Shmem(shmem) => shmem.as_slice(),
+            Inner::File { map, .. } => std::slice::from_raw_parts(map.as_ptr(), map.len()),
+        }
+    }
+
+    /// # Safety
+    ///
+    /// Other processes might access the region at the same time. The region must
+    /// have been created by [`DataRegion::create`].
+    pub unsafe fn as_slice_mut(&mut self) -> &mut [u8] {
+        match &mut self.inner {
+            Inner::Shmem(shmem) => shmem.as_slice_mut(),
+            Inner::File { map, .. } => std::slice::from_raw_parts_mut(map.as_mut_ptr(), map.len()),
+        }
+    }
+}
+
+impl Drop for DataRegion {
+    fn drop(&mut self) {
+        if let Inner::File {
+            id, owner: true, ..
+        } = &self.inner
+        {
+            // existing mappings of other processes stay valid
+            if let Err(err) = std::fs::remove_file(id) {
+                tracing::warn!("failed to remove shared memory file `{id}`: {err}");
+            }
+        }
+    }
+}
+
+/// POSIX shared memory IDs never contain a `/` after the leading one.
+fn is_file_id(id: &str) -> bool {
+    id.trim_start_matches('/').contains('/')
+}
+
+fn create_file(dir: &Path) -> eyre::Result<(File, PathBuf)> {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    loop {
+        let name = format!(
+            "{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        );
+        let path = dir.join(name);
+        let mut options = std::fs::OpenOptions::new();
+        options.read(true).write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        match options.open(&path) {
+            Ok(file) => return Ok((file, path)),
+            // left over by an earlier process with the same PID
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(err) => {
+                return Err(err).wrap_err_with(|| {
+                    format!("failed to create shared memory file in `{}`", dir.display())
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_regions_are_shared() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut region = DataRegion::create(4096, Some(dir.path())).unwrap();
+        assert!(Path::new(region.id()).starts_with(dir.path()));
+        unsafe { region.as_slice_mut()[..3].copy_from_slice(&[1, 2, 3]) };
+
+        let opened = DataRegion::open(region.id()).unwrap();
+        assert_eq!(opened.len(), 4096);
+        assert_eq!(unsafe { &opened.as_slice()[..3] }, &[1, 2, 3]);
+
+        // the creator removes the file, but existing mappings stay valid
+        let path = PathBuf::from(region.id());
+        drop(region);
+        assert!(!path.exists());
+        assert_eq!(unsafe { &opened.as_slice()[..3] }, &[1, 2, 3]);
+        drop(opened);
+    }
+
+    #[test]
+    fn shmem_ids_are_not_paths() {
+        assert!(!is_file_id("/shmem_1234"));
+        assert!(!is_file_id("shmem_1234"));
+        assert!(is_file_id("/dev/shm/dora-1000/1234"));
+    }
+}
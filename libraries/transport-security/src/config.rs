@@ -0,0 +1,39 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Transport security settings, e.g. parsed from a YAML config file.
+///
+/// ```yaml
+/// tls:
+///   ca: certs/ca.pem
+///   cert: certs/machine-a.pem
+///   key: certs/machine-a.key
+/// token: some-secret-token
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SecurityConfig {
+    /// Use TLS with mutual certificate authentication.
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+    /// Require connecting peers to present this pre-shared token.
+    #[serde(default)]
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TlsConfig {
+    /// PEM file with the CA certificate(s) that peer certificates must be signed by.
+    pub ca: PathBuf,
+    /// PEM file with the certificate chain of this peer.
+    pub cert: PathBuf,
+    /// PEM file with the private key of this peer.
+    pub key: PathBuf,
+    /// Name that the certificates of accepting peers are verified against.
+    ///
+    /// Defaults to the IP address of the peer, so certificates need a matching IP
+    /// subject alternative name in that case.
+    #[serde(default)]
+    pub server_name: Option<String>,
+}
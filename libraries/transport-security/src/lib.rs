@@ -0,0 +1,267 @@
+//! Optional transport security for the TCP connections between the dora CLI,
+//! coordinator, and daemons.
+//!
+//! Two mechanisms are supported, which can also be combined:
+//!
+//! - **TLS** with mutual certificate authentication: both peers present a certificate
+//!   that is signed by the configured CA and verify the certificate of the other side.
+//! - **Pre-shared token**: after the (optional) TLS handshake, the connecting peer sends
+//!   a token, which the accepting peer compares against its own token. Connections with
+//!   a wrong token are closed. Without TLS, the token is sent in plaintext, so it only
+//!   protects against accidental or unauthenticated access, not against eavesdroppers.
+//!
+//! All peers of a dora deployment need to use the same configuration.
+
+use rustls::pki_types::ServerName;
+use std::{io, net::TcpStream, sync::Arc, time::Duration};
+
+pub use config::{SecurityConfig, TlsConfig};
+pub use stream::{AsyncStream, SyncStream};
+
+mod config;
+mod stream;
+mod tls;
+mod token;
+
+/// Maximum duration of the TLS and token handshake on accepted connections.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Loaded transport security configuration, used to secure new connections.
+///
+/// Cheap to clone. The [`Default`] value uses plain TCP without authentication.
+#[derive(Clone, Default)]
+pub struct TransportSecurity {
+    tls: Option<tls::LoadedTls>,
+    token: Option<Arc<str>>,
+}
+
+impl TransportSecurity {
+    /// Loads the certificates and keys referenced by the given config.
+    pub fn new(config: &SecurityConfig) -> io::Result<Self> {
+        Ok(Self {
+            tls: config.tls.as_ref().map(tls::LoadedTls::load).transpose()?,
+            token: config.token.as_deref().map(Arc::from),
+        })
+    }
+
+    /// Returns `true` if neither TLS nor token authentication is enabled.
+    pub fn is_plaintext(&self) -> bool {
+        self.tls.is_none() && self.token.is_none()
+    }
+
+    /// Returns `true` if a pre-shared token is configured.
+    pub fn has_token(&self) -> bool {
+        self.token.is_some()
+    }
+
+    /// Checks a token that was received outside of the handshake, e.g. in an HTTP
+    /// `Authorization` header. Always succeeds if no token is configured.
+    pub fn verify_token(&self, received: Option<&str>) -> bool {
+        match &self.token {
+            Some(token) => {
+                received.is_some_and(|r| token::tokens_equal(r.as_bytes(), token.as_bytes()))
+            }
+            None => true,
+        }
+    }
+
+    /// Secures the given outgoing connection (blocking).
+    pub fn connect_blocking(&self, stream: TcpStream) -> io::Result<SyncStream> {
+        let mut stream = match &self.tls {
+            Some(tls) => {
+                let server_name = tls.server_name(stream.peer_addr()?.ip())?;
+                let mut connection = rustls::ClientConnection::new(tls.client.clone(), server_name)
+                    .map_err(tls::io_error)?;
+                let mut stream = stream;
+                while connection.is_handshaking() {
+                    connection.complete_io(&mut stream)?;
+                }
+                SyncStream::TlsClient(Box::new(rustls::StreamOwned::new(connection, stream)))
+            }
+            None => SyncStream::Plain(stream),
+        };
+        if let Some(token) = &self.token {
+            token::send_blocking(&mut stream, token)?;
+        }
+        Ok(stream)
+    }
+
+    /// Secures the given accepted connection (blocking).
+    pub fn accept_blocking(&self, stream: TcpStream) -> io::Result<SyncStream> {
+        stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+        let mut stream = match &self.tls {
+            Some(tls) => {
+                let mut connection =
+                    rustls::ServerConnection::new(tls.server.clone()).map_err(tls::io_error)?;
+                let mut stream = stream;
+                while connection.is_handshaking() {
+                    connection.complete_io(&mut stream)?;
+                }
+                SyncStream::TlsServer(Box::new(rustls::StreamOwned::new(connection, stream)))
+            }
+            None => SyncStream::Plain(stream),
+        };
+        if let Some(token) = &self.token {
+            token::verify_blocking(&mut stream, token)?;
+        }
+        stream.tcp_stream().set_read_timeout(None)?;
+        Ok(stream)
+    }
+
+    /// Secures the given outgoing connection.
+    pub async fn connect(&self, stream: tokio::net::TcpStream) -> io::Result<AsyncStream> {
+        let mut stream = match &self.tls {
+            Some(tls) => {
+                let server_name: ServerName<'static> = tls.server_name(stream.peer_addr()?.ip())?;
+                let connector = tokio_rustls::TlsConnector::from(tls.client.clone());
+                AsyncStream::TlsClient(Box::new(connector.connect(server_name, stream).await?))
+            }
+            None => AsyncStream::Plain(stream),
+        };
+        if let Some(token) = &self.token {
+            token::send(&mut stream, token).await?;
+        }
+        Ok(stream)
+    }
+
+    /// Secures the given accepted connection.
+    pub async fn accept(&self, stream: tokio::net::TcpStream) -> io::Result<AsyncStream> {
+        let handshake = async {
+            let mut stream = self.tls_accept(stream).await?;
+            if let Some(token) = &self.token {
+                token::verify(&mut stream, token).await?;
+            }
+            Ok(stream)
+        };
+        with_handshake_timeout(handshake).await
+    }
+
+    /// Performs only the TLS handshake of an accepted connection, if TLS is enabled.
+    ///
+    /// For protocols that check the token themselves, e.g. through the HTTP
+    /// `Authorization` header (see [`verify_token`](Self::verify_token)).
+    pub async fn accept_tls(&self, stream: tokio::net::TcpStream) -> io::Result<AsyncStream> {
+        with_handshake_timeout(self.tls_accept(stream)).await
+    }
+
+    async fn tls_accept(&self, stream: tokio::net::TcpStream) -> io::Result<AsyncStream> {
+        match &self.tls {
+            Some(tls) => {
+                let acceptor = tokio_rustls::TlsAcceptor::from(tls.server.clone());
+                Ok(AsyncStream::TlsServer(Box::new(
+                    acceptor.accept(stream).await?,
+                )))
+            }
+            None => Ok(AsyncStream::Plain(stream)),
+        }
+    }
+}
+
+async fn with_handshake_timeout<T>(
+    handshake: impl std::future::Future<Output = io::Result<T>>,
+) -> io::Result<T> {
+    tokio::time::timeout(HANDSHAKE_TIMEOUT, handshake)
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "security handshake timed out"))?
+}
+
+impl std::fmt::Debug for TransportSecurity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TransportSecurity")
+            .field("tls", &self.tls.is_some())
+            .field("token", &self.token.is_some())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    /// Loads a TLS config with the given certificate from `test_certs`, which trusts
+    /// the certificates signed by `test_certs/ca.pem`.
+    fn tls(cert: &str, server_name: Option<&str>) -> TransportSecurity {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("test_certs");
+        TransportSecurity::new(&SecurityConfig {
+            tls: Some(TlsConfig {
+                ca: dir.join("ca.pem"),
+                cert: dir.join(format!("{cert}.pem")),
+                key: dir.join(format!("{cert}.key")),
+                server_name: server_name.map(Into::into),
+            }),
+            token: None,
+        })
+        .unwrap()
+    }
+
+    /// Connects the client to the server over localhost and returns the secured
+    /// streams of both sides.
+    async fn handshake(
+        client: &TransportSecurity,
+        server: &TransportSecurity,
+    ) -> (io::Result<AsyncStream>, io::Result<AsyncStream>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let connect = async {
+            let stream = tokio::net::TcpStream::connect(addr).await?;
+            client.connect(stream).await
+        };
+        let accept = async {
+            let (stream, _) = listener.accept().await?;
+            server.accept(stream).await
+        };
+        tokio::join!(connect, accept)
+    }
+
+    #[tokio::test]
+    async fn mutual_tls_with_trusted_certs() {
+        let mut security = tls("peer", None);
+        security.token = Some("secret".into());
+        let (client, server) = handshake(&security, &security).await;
+        let (mut client, mut server) = (client.unwrap(), server.unwrap());
+        assert!(matches!(client, AsyncStream::TlsClient(_)));
+        assert!(matches!(server, AsyncStream::TlsServer(_)));
+
+        client.write_all(b"ping").await.unwrap();
+        client.flush().await.unwrap();
+        let mut received = [0; 4];
+        server.read_exact(&mut received).await.unwrap();
+        assert_eq!(&received, b"ping");
+    }
+
+    #[tokio::test]
+    async fn untrusted_server_cert_is_rejected() {
+        let (client, _server) = handshake(&tls("peer", None), &tls("untrusted", None)).await;
+        let err = client.unwrap_err();
+        assert!(err.to_string().contains("UnknownIssuer"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn untrusted_client_cert_is_rejected() {
+        let (_client, server) = handshake(&tls("untrusted", None), &tls("peer", None)).await;
+        let err = server.unwrap_err();
+        assert!(err.to_string().contains("UnknownIssuer"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn server_name_overrides_peer_ip() {
+        // `named` is only valid for `coordinator.dora`, not for the peer IP
+        let server = tls("named", None);
+        let (client, _server) = handshake(&tls("peer", None), &server).await;
+        let err = client.unwrap_err();
+        assert!(err.to_string().contains("NotValidForName"), "{err}");
+
+        let client = tls("peer", Some("coordinator.dora"));
+        let (client, accepted) = handshake(&client, &server).await;
+        assert!(client.is_ok() && accepted.is_ok());
+
+        let client = tls("peer", Some("other.dora"));
+        let (client, _server) = handshake(&client, &server).await;
+        assert!(client.is_err());
+    }
+}
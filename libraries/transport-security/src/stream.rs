@@ -0,0 +1,157 @@
+use std::{
+    io::{self, Read, Write},
+    net::{SocketAddr, TcpStream},
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// A blocking TCP stream that is optionally wrapped in TLS.
+pub enum SyncStream {
+    Plain(TcpStream),
+    TlsClient(Box<rustls::StreamOwned<rustls::ClientConnection, TcpStream>>),
+    TlsServer(Box<rustls::StreamOwned<rustls::ServerConnection, TcpStream>>),
+}
+
+impl SyncStream {
+    /// The underlying TCP stream.
+    pub fn tcp_stream(&self) -> &TcpStream {
+        match self {
+            SyncStream::Plain(stream) => stream,
+            SyncStream::TlsClient(stream) => stream.get_ref(),
+            SyncStream::TlsServer(stream) => stream.get_ref(),
+        }
+    }
+
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.tcp_stream().peer_addr()
+    }
+}
+
+impl std::fmt::Debug for SyncStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = match self {
+            SyncStream::Plain(_) => "Plain",
+            SyncStream::TlsClient(_) => "TlsClient",
+            SyncStream::TlsServer(_) => "TlsServer",
+        };
+        f.debug_tuple(kind).field(self.tcp_stream()).finish()
+    }
+}
+
+impl From<TcpStream> for SyncStream {
+    fn from(stream: TcpStream) -> Self {
+        Self::Plain(stream)
+    }
+}
+
+impl Read for SyncStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            SyncStream::Plain(stream) => stream.read(buf),
+            SyncStream::TlsClient(stream) => stream.read(buf),
+            SyncStream::TlsServer(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for SyncStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            SyncStream::Plain(stream) => stream.write(buf),
+            SyncStream::TlsClient(stream) => stream.write(buf),
+            SyncStream::TlsServer(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            SyncStream::Plain(stream) => stream.flush(),
+            SyncStream::TlsClient(stream) => stream.flush(),
+            SyncStream::TlsServer(stream) => stream.flush(),
+        }
+    }
+}
+
+/// An async TCP stream that is optionally wrapped in TLS.
+pub enum AsyncStream {
+    Plain(tokio::net::TcpStream),
+    TlsClient(Box<tokio_rustls::client::TlsStream<tokio::net::TcpStream>>),
+    TlsServer(Box<tokio_rustls::server::TlsStream<tokio::net::TcpStream>>),
+}
+
+impl AsyncStream {
+    /// The underlying TCP stream.
+    pub fn tcp_stream(&self) -> &tokio::net::TcpStream {
+        match self {
+            AsyncStream::Plain(stream) => stream,
+            AsyncStream::TlsClient(stream) => stream.get_ref().0,
+            AsyncStream::TlsServer(stream) => stream.get_ref().0,
+        }
+    }
+
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.tcp_stream().peer_addr()
+    }
+}
+
+impl std::fmt::Debug for AsyncStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = match self {
+            AsyncStream::Plain(_) => "Plain",
+            AsyncStream::TlsClient(_) => "TlsClient",
+            AsyncStream::TlsServer(_) => "TlsServer",
+        };
+        f.debug_tuple(kind).field(self.tcp_stream()).finish()
+    }
+}
+
+impl From<tokio::net::TcpStream> for AsyncStream {
+    fn from(stream: tokio::net::TcpStream) -> Self {
+        Self::Plain(stream)
+    }
+}
+
+impl AsyncRead for AsyncStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            AsyncStream::Plain(stream) => Pin::new(stream).poll_read(cx, buf),
+            AsyncStream::TlsClient(stream) => Pin::new(stream).poll_read(cx, buf),
+            AsyncStream::TlsServer(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for AsyncStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            AsyncStream::Plain(stream) => Pin::new(stream).poll_write(cx, buf),
+            AsyncStream::TlsClient(stream) => Pin::new(stream).poll_write(cx, buf),
+            AsyncStream::TlsServer(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            AsyncStream::Plain(stream) => Pin::new(stream).poll_flush(cx),
+            AsyncStream::TlsClient(stream) => Pin::new(stream).poll_flush(cx),
+            AsyncStream::TlsServer(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            AsyncStream::Plain(stream) => Pin::new(stream).poll_shutdown(cx),
+            AsyncStream::TlsClient(stream) => Pin::new(stream).poll_shutdown(cx),
+            AsyncStream::TlsServer(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
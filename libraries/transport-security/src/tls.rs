@@ -0,0 +1,107 @@
+use crate::TlsConfig;
+use rustls::{
+    pki_types::{CertificateDer, PrivateKeyDer, ServerName},
+    server::WebPkiClientVerifier,
+    ClientConfig, RootCertStore, ServerConfig,
+};
+use std::{fs::File, io, io::BufReader, net::IpAddr, path::Path, sync::Arc};
+
+#[derive(Clone)]
+pub struct LoadedTls {
+    pub client: Arc<ClientConfig>,
+    pub server: Arc<ServerConfig>,
+    server_name: Option<ServerName<'static>>,
+}
+
+impl LoadedTls {
+    pub fn load(config: &TlsConfig) -> io::Result<Self> {
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+
+        let mut roots = RootCertStore::empty();
+        for cert in load_certs(&config.ca)? {
+            roots.add(cert).map_err(io_error)?;
+        }
+        let roots = Arc::new(roots);
+        let certs = load_certs(&config.cert)?;
+        let key = load_key(&config.key)?;
+
+        let client = ClientConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()
+            .map_err(io_error)?
+            .with_root_certificates(roots.clone())
+            .with_client_auth_cert(certs.clone(), key.clone_key())
+            .map_err(io_error)?;
+
+        let client_verifier = WebPkiClientVerifier::builder_with_provider(roots, provider.clone())
+            .build()
+            .map_err(io_error)?;
+        let server = ServerConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .map_err(io_error)?
+            .with_client_cert_verifier(client_verifier)
+            .with_single_cert(certs, key)
+            .map_err(io_error)?;
+
+        let server_name = config
+            .server_name
+            .clone()
+            .map(|name| {
+                ServerName::try_from(name).map_err(|err| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("invalid server name: {err}"),
+                    )
+                })
+            })
+            .transpose()?;
+
+        Ok(Self {
+            client: Arc::new(client),
+            server: Arc::new(server),
+            server_name,
+        })
+    }
+
+    /// The name that the certificate of the given peer is verified against.
+    pub fn server_name(&self, peer: IpAddr) -> io::Result<ServerName<'static>> {
+        Ok(self
+            .server_name
+            .clone()
+            .unwrap_or_else(|| ServerName::IpAddress(peer.into())))
+    }
+}
+
+fn load_certs(path: &Path) -> io::Result<Vec<CertificateDer<'static>>> {
+    let mut reader = BufReader::new(open(path)?);
+    let certs = rustls_pemfile::certs(&mut reader).collect::<Result<Vec<_>, _>>()?;
+    if certs.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("no certificates found in {}", path.display()),
+        ));
+    }
+    Ok(certs)
+}
+
+fn load_key(path: &Path) -> io::Result<PrivateKeyDer<'static>> {
+    let mut reader = BufReader::new(open(path)?);
+    rustls_pemfile::private_key(&mut reader)?.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("no private key found in {}", path.display()),
+        )
+    })
+}
+
+fn open(path: &Path) -> io::Result<File> {
+    File::open(path).map_err(|err| {
+        io::Error::new(
+            err.kind(),
+            format!("failed to open {}: {err}", path.display()),
+        )
+    })
+}
+
+pub fn io_error(err: impl std::error::Error + Send + Sync + 'static) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
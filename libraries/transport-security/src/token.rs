@@ -0,0 +1,119 @@
+//! Pre-shared token handshake.
+//!
+//! The connecting peer sends the token as a length-prefixed frame (`u64`, little
+//! endian). The accepting peer replies with a single byte: [`ACCEPTED`] if the token
+//! matches, [`REJECTED`] otherwise. Rejected connections are closed afterwards.
+
+use std::io::{self, Read, Write};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+const ACCEPTED: u8 = 1;
+const REJECTED: u8 = 0;
+const MAX_TOKEN_LEN: u64 = 4096;
+
+pub fn send_blocking(stream: &mut (impl Read + Write), token: &str) -> io::Result<()> {
+    stream.write_all(&(token.len() as u64).to_le_bytes())?;
+    stream.write_all(token.as_bytes())?;
+    stream.flush()?;
+    let mut reply = [0];
+    stream.read_exact(&mut reply)?;
+    check_reply(reply[0])
+}
+
+pub fn verify_blocking(stream: &mut (impl Read + Write), token: &str) -> io::Result<()> {
+    let mut len = [0; 8];
+    stream.read_exact(&mut len)?;
+    let len = u64::from_le_bytes(len);
+    let received = if len <= MAX_TOKEN_LEN {
+        let mut received = vec![0; len as usize];
+        stream.read_exact(&mut received)?;
+        Some(received)
+    } else {
+        None
+    };
+    let accepted = received.is_some_and(|r| tokens_equal(&r, token.as_bytes()));
+    stream.write_all(&[if accepted { ACCEPTED } else { REJECTED }])?;
+    stream.flush()?;
+    check_received(accepted)
+}
+
+pub async fn send(
+    stream: &mut (impl AsyncRead + AsyncWrite + Unpin),
+    token: &str,
+) -> io::Result<()> {
+    stream
+        .write_all(&(token.len() as u64).to_le_bytes())
+        .await?;
+    stream.write_all(token.as_bytes()).await?;
+    stream.flush().await?;
+    let reply = stream.read_u8().await?;
+    check_reply(reply)
+}
+
+pub async fn verify(
+    stream: &mut (impl AsyncRead + AsyncWrite + Unpin),
+    token: &str,
+) -> io::Result<()> {
+    let len = stream.read_u64_le().await?;
+    let received = if len <= MAX_TOKEN_LEN {
+        let mut received = vec![0; len as usize];
+        stream.read_exact(&mut received).await?;
+        Some(received)
+    } else {
+        None
+    };
+    let accepted = received.is_some_and(|r| tokens_equal(&r, token.as_bytes()));
+    stream
+        .write_all(&[if accepted { ACCEPTED } else { REJECTED }])
+        .await?;
+    stream.flush().await?;
+    check_received(accepted)
+}
+
+fn check_reply(reply: u8) -> io::Result<()> {
+    match reply {
+        ACCEPTED => Ok(()),
+        _ => Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "authentication token was rejected by peer",
+        )),
+    }
+}
+
+fn check_received(accepted: bool) -> io::Result<()> {
+    if accepted {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "peer sent an invalid authentication token",
+        ))
+    }
+}
+
+/// Compares the tokens in constant time (for tokens of equal length).
+pub(crate) fn tokens_equal(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn token_handshake() {
+        let (mut client, mut server) = tokio::io::duplex(64);
+        let (sent, verified) =
+            tokio::join!(send(&mut client, "secret"), verify(&mut server, "secret"));
+        assert!(sent.is_ok() && verified.is_ok());
+
+        let (mut client, mut server) = tokio::io::duplex(64);
+        let (sent, verified) =
+            tokio::join!(send(&mut client, "wrong"), verify(&mut server, "secret"));
+        assert_eq!(sent.unwrap_err().kind(), io::ErrorKind::PermissionDenied);
+        assert_eq!(
+            verified.unwrap_err().kind(),
+            io::ErrorKind::PermissionDenied
+        );
+    }
+}
@@ -0,0 +1,33 @@
+#!/bin/sh
+# Generates the certificates that are used by the transport security tests.
+#
+# - `ca.pem`: CA that the tests trust
+# - `peer.pem`/`peer.key`: signed by the CA, valid for IP `127.0.0.1`
+# - `named.pem`/`named.key`: signed by the CA, only valid for the name `coordinator.dora`
+# - `untrusted.pem`/`untrusted.key`: valid for IP `127.0.0.1`, but signed by another CA
+set -e
+cd "$(dirname "$0")"
+tmp=$(mktemp -d)
+trap 'rm -rf "$tmp"' EXIT
+
+ca() {
+    openssl req -x509 -newkey ec -pkeyopt ec_paramgen_curve:P-256 -nodes -days 36500 \
+        -subj "/CN=$1" -keyout "$tmp/$1.key" -out "$tmp/$1.pem" 2>/dev/null
+}
+
+leaf() {
+    openssl req -newkey ec -pkeyopt ec_paramgen_curve:P-256 -nodes -subj "/CN=$1" \
+        -keyout "$tmp/$1.key" -out "$tmp/$1.csr" 2>/dev/null
+    printf "subjectAltName=%s\nextendedKeyUsage=serverAuth,clientAuth\nbasicConstraints=CA:FALSE\n" "$3" \
+        > "$tmp/$1.ext"
+    openssl x509 -req -in "$tmp/$1.csr" -CA "$tmp/$2.pem" -CAkey "$tmp/$2.key" \
+        -CAcreateserial -days 36500 -extfile "$tmp/$1.ext" -out "$1.pem" 2>/dev/null
+    openssl pkcs8 -topk8 -nocrypt -in "$tmp/$1.key" -out "$1.key"
+}
+
+ca dora-test-ca
+ca other-ca
+cp "$tmp/dora-test-ca.pem" ca.pem
+leaf peer dora-test-ca "IP:127.0.0.1"
+leaf named dora-test-ca "DNS:coordinator.dora"
+leaf untrusted other-ca "IP:127.0.0.1"
@@ -0,0 +1,381 @@
+//! Parquet file layout used by the `dora-record` node and the `dora record` command.
+//!
+//! The recordings of a dataflow run are stored in `<out>/<DATAFLOW_ID>/`, with a
+//! separate file `<INPUT>.parquet` for each recorded input (see [`recording_path`]).
+//! Each file contains one row per message with the columns `trace_id`, `span_id`,
+//! `timestamp_uhlc`, `timestamp_utc`, `parameters` (the metadata parameters as
+//! JSON), and a list column named after the input with the data. The output that
+//! the input is mapped to is stored as [`SOURCE_METADATA_KEY`] in the schema
+//! metadata. [`RecordingReader`] reads such a file back, e.g. for replaying it.
+
+use chrono::{DateTime, Utc};
+use dora_node_api::{
+    arrow::{
+        array::{
+            make_array, Array, ArrayRef, AsArray, ListArray, StringArray,
+            TimestampMillisecondArray, UInt64Array,
+        },
+        buffer::{OffsetBuffer, ScalarBuffer},
+        datatypes::{DataType, Field, Schema, UInt64Type},
+        record_batch::RecordBatch,
+    },
+    Metadata, MetadataParameters,
+};
+use dora_tracing::telemetry::deserialize_to_hashmap;
+use eyre::{Context, ContextCompat};
+use parquet::{
+    arrow::{
+        arrow_reader::{ParquetRecordBatchReader, ParquetRecordBatchReaderBuilder},
+        AsyncArrowWriter,
+    },
+    basic::BrotliLevel,
+    file::properties::WriterProperties,
+};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// Schema metadata key of the recorded output, formatted as `<NODE>/<OUTPUT>`.
+///
+/// Recordings of `dora-record` versions before this key was added don't have it.
+pub const SOURCE_METADATA_KEY: &str = "dora_source";
+
+/// Path of the recording of the given input in the recording directory of a
+/// dataflow run.
+pub fn recording_path(dataflow_dir: &Path, input_id: &str) -> PathBuf {
+    dataflow_dir.join(format!("{input_id}.parquet"))
+}
+
+/// Writes the messages of a single input or output into a Parquet file.
+pub struct ParquetWriter {
+    writer: AsyncArrowWriter<tokio::fs::File>,
+    schema: Arc<Schema>,
+}
+
+impl ParquetWriter {
+    /// Creates the file at `path` for data of the given type.
+    ///
+    /// The data column is named `column`. The recorded output is stored in the schema
+    /// metadata if `source` is set. Parent directories are created if needed.
+    pub async fn create(
+        path: &Path,
+        column: &str,
+        source: Option<(&str, &str)>,
+        data_type: &DataType,
+    ) -> eyre::Result<Self> {
+        let field_uhlc = Field::new("timestamp_uhlc", DataType::UInt64, false);
+        let field_utc_epoch = Field::new(
+            "timestamp_utc",
+            DataType::Timestamp(dora_node_api::arrow::datatypes::TimeUnit::Millisecond, None),
+            false,
+        );
+        let field_trace_id = Field::new("trace_id", DataType::Utf8, true);
+        let field_span_id = Field::new("span_id", DataType::Utf8, true);
+        let field_parameters = Field::new("parameters", DataType::Utf8, true);
+        let field_values = Arc::new(Field::new("item", data_type.clone(), true));
+        let field_data = Field::new(column, DataType::List(field_values), true);
+
+        let schema = Arc::new(Schema::new(vec![
+            field_trace_id,
+            field_span_id,
+            field_uhlc,
+            field_utc_epoch,
+            field_parameters,
+            field_data,
+        ]));
+        let schema = match source {
+            Some((node_id, output_id)) => {
+                Arc::new(schema.as_ref().clone().with_metadata(HashMap::from([(
+                    SOURCE_METADATA_KEY.to_owned(),
+                    format!("{node_id}/{output_id}"),
+                )])))
+            }
+            None => schema,
+        };
+        if let Some(dir) = path.parent() {
+            if !dir.exists() {
+                std::fs::create_dir_all(dir).context("could not create dataflow_dir")?;
+            }
+        }
+        let file = tokio::fs::File::create(path)
+            .await
+            .context("Couldn't create write file")?;
+        let writer = AsyncArrowWriter::try_new(
+            file,
+            schema.clone(),
+            Some(
+                WriterProperties::builder()
+                    .set_compression(parquet::basic::Compression::BROTLI(BrotliLevel::default()))
+                    .build(),
+            ),
+        )
+        .context("Could not create parquet writer")?;
+        Ok(Self { writer, schema })
+    }
+
+    /// Write a row of data into the writer
+    pub async fn write(&mut self, data: Arc<dyn Array>, metadata: &Metadata) -> eyre::Result<()> {
+        let offsets = OffsetBuffer::new(ScalarBuffer::from(vec![0, data.len() as i32]));
+        let field = Arc::new(Field::new("item", data.data_type().clone(), true));
+        let list = ListArray::new(field, offsets, data.clone(), None);
+
+        let timestamp = metadata.timestamp();
+        let timestamp_uhlc = UInt64Array::from(vec![timestamp.get_time().0]);
+        let timestamp_uhlc = make_array(timestamp_uhlc.into());
+        let system_time = timestamp.get_time().to_system_time();
+
+        let dt: DateTime<Utc> = system_time.into();
+        let timestamp_utc = TimestampMillisecondArray::from(vec![dt.timestamp_millis()]);
+        let timestamp_utc = make_array(timestamp_utc.into());
+
+        let string_otel_context = metadata.parameters.open_telemetry_context.to_string();
+        let otel_context = deserialize_to_hashmap(&string_otel_context);
+        let traceparent = otel_context.get("traceparent");
+        let trace_id = match traceparent {
+            None => "",
+            Some(trace) => trace.split('-').nth(1).context("Trace is malformatted")?,
+        };
+        let span_id = match traceparent {
+            None => "",
+            Some(trace) => trace.split('-').nth(2).context("Trace is malformatted")?,
+        };
+        let trace_id_array = StringArray::from(vec![trace_id]);
+        let trace_id_array = make_array(trace_id_array.into());
+        let span_id_array = StringArray::from(vec![span_id]);
+        let span_id_array = make_array(span_id_array.into());
+        let parameters = serde_json::to_string(&metadata.parameters)
+            .context("failed to serialize metadata parameters")?;
+        let parameters_array = make_array(StringArray::from(vec![parameters]).into());
+
+        let record = RecordBatch::try_new(
+            self.schema.clone(),
+            vec![
+                trace_id_array,
+                span_id_array,
+                timestamp_uhlc,
+                timestamp_utc,
+                parameters_array,
+                make_array(list.into()),
+            ],
+        )
+        .context("Could not create record batch with the given data")?;
+        self.writer
+            .write(&record)
+            .await
+            .context("Could not write recordbatch to file")?;
+
+        Ok(())
+    }
+
+    /// Flushes the remaining data and writes the file footer.
+    pub async fn close(self) -> eyre::Result<()> {
+        self.writer
+            .close()
+            .await
+            .context("Could not close the parquet writer")?;
+        Ok(())
+    }
+}
+
+/// A single message that was read back from a recording.
+#[derive(Debug, Clone)]
+pub struct RecordedMessage {
+    /// The ID that the message was recorded under (the name of the data column).
+    pub id: String,
+    pub timestamp_uhlc: u64,
+    /// The metadata parameters of the message.
+    ///
+    /// Recordings without a `parameters` column only contain the OpenTelemetry
+    /// context, which is restored from the `trace_id` and `span_id` columns.
+    pub parameters: MetadataParameters,
+    pub data: ArrayRef,
+}
+
+/// Reads the messages of a Parquet file written by [`ParquetWriter`].
+///
+/// The file is read one record batch at a time, so recordings don't need to fit
+/// into memory.
+pub struct RecordingReader {
+    id: String,
+    source: Option<(String, String)>,
+    batches: ParquetRecordBatchReader,
+    messages: std::vec::IntoIter<RecordedMessage>,
+}
+
+impl RecordingReader {
+    pub fn open(path: &Path) -> eyre::Result<Self> {
+        let file = std::fs::File::open(path)
+            .with_context(|| format!("failed to open `{}`", path.display()))?;
+        let builder = ParquetRecordBatchReaderBuilder::try_new(file)
+            .context("Could not create parquet reader")?;
+        let id = builder
+            .schema()
+            .fields()
+            .last()
+            .context("recording has no data column")?
+            .name()
+            .clone();
+        let source = builder
+            .schema()
+            .metadata()
+            .get(SOURCE_METADATA_KEY)
+            .and_then(|source| source.split_once('/'))
+            .map(|(node_id, output_id)| (node_id.to_owned(), output_id.to_owned()));
+        let batches = builder.build().context("Could not create parquet reader")?;
+        Ok(Self {
+            id,
+            source,
+            batches,
+            messages: Vec::new().into_iter(),
+        })
+    }
+
+    /// The ID that the messages were recorded under (the name of the data column).
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// The node and output that were recorded, if stored in the file.
+    pub fn source(&self) -> Option<(&str, &str)> {
+        self.source
+            .as_ref()
+            .map(|(node_id, output_id)| (node_id.as_str(), output_id.as_str()))
+    }
+
+    fn read_batch(&self, batch: RecordBatch) -> eyre::Result<Vec<RecordedMessage>> {
+        let column = |name: &str| {
+            batch
+                .column_by_name(name)
+                .with_context(|| format!("recording has no `{name}` column"))
+        };
+        let trace_ids = column("trace_id")?
+            .as_string_opt::<i32>()
+            .context("unexpected type of `trace_id` column")?;
+        let span_ids = column("span_id")?
+            .as_string_opt::<i32>()
+            .context("unexpected type of `span_id` column")?;
+        let timestamps = column("timestamp_uhlc")?
+            .as_primitive_opt::<UInt64Type>()
+            .context("unexpected type of `timestamp_uhlc` column")?;
+        let parameters = match batch.column_by_name("parameters") {
+            Some(column) => Some(
+                column
+                    .as_string_opt::<i32>()
+                    .context("unexpected type of `parameters` column")?,
+            ),
+            None => None,
+        };
+        let data = column(&self.id)?
+            .as_list_opt::<i32>()
+            .context("unexpected type of data column")?;
+
+        (0..batch.num_rows())
+            .map(|row| {
+                let parameters = match parameters {
+                    Some(parameters) if parameters.is_valid(row) => {
+                        serde_json::from_str(parameters.value(row))
+                            .context("failed to parse metadata parameters")?
+                    }
+                    _ => {
+                        let trace_id = trace_ids.value(row);
+                        let span_id = span_ids.value(row);
+                        MetadataParameters {
+                            open_telemetry_context: if trace_id.is_empty() {
+                                String::new()
+                            } else {
+                                format!("traceparent:00-{trace_id}-{span_id}-01;")
+                            },
+                            ..Default::default()
+                        }
+                    }
+                };
+                Ok(RecordedMessage {
+                    id: self.id.clone(),
+                    timestamp_uhlc: timestamps.value(row),
+                    parameters,
+                    data: data.value(row),
+                })
+            })
+            .collect()
+    }
+}
+
+impl Iterator for RecordingReader {
+    type Item = eyre::Result<RecordedMessage>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(message) = self.messages.next() {
+                return Some(Ok(message));
+            }
+            let batch = match self.batches.next()? {
+                Ok(batch) => batch,
+                Err(err) => return Some(Err(err).context("Could not read record batch")),
+            };
+            match self.read_batch(batch) {
+                Ok(messages) => self.messages = messages.into_iter(),
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dora_node_api::{
+        arrow::array::UInt8Array,
+        dora_core::message::{uhlc::HLC, ArrowTypeInfo},
+    };
+
+    #[test]
+    fn recording_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = recording_path(dir.path(), "image");
+        assert_eq!(path, dir.path().join("image.parquet"));
+
+        let clock = HLC::default();
+        let metadata: Vec<_> = (0..3)
+            .map(|i| {
+                let parameters = MetadataParameters {
+                    open_telemetry_context: format!(
+                        "traceparent:00-0af7651916cd43dd8448eb211c80319c-b7ad6b716920333{i}-01;"
+                    ),
+                    ..Default::default()
+                }
+                .with_user_parameter("frame", i as i64);
+                Metadata::from_parameters(clock.new_timestamp(), ArrowTypeInfo::empty(), parameters)
+            })
+            .collect();
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let mut writer =
+                ParquetWriter::create(&path, "image", Some(("camera", "frame")), &DataType::UInt8)
+                    .await
+                    .unwrap();
+            for (i, metadata) in metadata.iter().enumerate() {
+                let data: ArrayRef = Arc::new(UInt8Array::from(vec![i as u8; i + 1]));
+                writer.write(data, metadata).await.unwrap();
+            }
+            writer.close().await.unwrap();
+        });
+
+        let reader = RecordingReader::open(&path).unwrap();
+        assert_eq!(reader.id(), "image");
+        assert_eq!(reader.source(), Some(("camera", "frame")));
+        let messages: Vec<_> = reader.collect::<eyre::Result<_>>().unwrap();
+        assert_eq!(messages.len(), 3);
+        for (i, (message, metadata)) in messages.iter().zip(&metadata).enumerate() {
+            assert_eq!(message.id, "image");
+            assert_eq!(message.timestamp_uhlc, metadata.timestamp().get_time().0);
+            assert_eq!(message.parameters, metadata.parameters);
+            let data = message
+                .data
+                .as_primitive::<dora_node_api::arrow::datatypes::UInt8Type>();
+            assert_eq!(data.values().to_vec(), vec![i as u8; i + 1]);
+        }
+    }
+}
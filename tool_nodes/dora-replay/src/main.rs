@@ -0,0 +1,260 @@
+use dora_node_api::{self, dora_core::config::DataId, uhlc::NTP64, DoraNode, Event, EventStream};
+use dora_record::{RecordedMessage, RecordingReader};
+use eyre::{bail, Context};
+use std::{
+    iter::Peekable,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, UNIX_EPOCH},
+};
+
+/// Key of the user metadata parameter that contains the time at which a replayed
+/// message was originally sent, in nanoseconds since the UNIX epoch.
+const RECORDED_TIME: &str = "recorded_time_ns";
+
+fn main() -> eyre::Result<()> {
+    let path = PathBuf::from(std::env::var("REPLAY_PATH").context("REPLAY_PATH is not set")?);
+    let speed = match std::env::var("REPLAY_SPEED") {
+        Ok(speed) => speed
+            .parse::<f64>()
+            .context("REPLAY_SPEED must be a number")?,
+        Err(_) => 1.0,
+    };
+    if speed < 0.0 || !speed.is_finite() {
+        bail!("REPLAY_SPEED must be a positive number or 0");
+    }
+
+    // only replay the recorded outputs of this node if set
+    let replay_node = std::env::var("REPLAY_NODE").ok();
+
+    let (mut node, mut events) = DoraNode::init_from_env()?;
+
+    let mut files = Vec::new();
+    collect_recordings(&path, &mut files)?;
+    let mut readers = Vec::new();
+    for file in files {
+        let reader = RecordingReader::open(&file)?;
+        let Some(output) = replayed_output(&reader, replay_node.as_deref()) else {
+            continue;
+        };
+        if node.node_config().outputs.contains(&output) {
+            readers.push((output, reader));
+        } else {
+            eprintln!(
+                "skipping recorded `{}` because `{output}` is not an output of this node",
+                reader.id()
+            );
+        }
+    }
+
+    let readers = readers.into_iter().map(|(output, reader)| {
+        reader.map(move |message| {
+            message.map(|message| RecordedMessage {
+                id: output.to_string(),
+                ..message
+            })
+        })
+    });
+    let mut messages = MergedMessages::new(readers).peekable();
+    let Some(first) = messages
+        .peek()
+        .and_then(|m| m.as_ref().ok())
+        .map(|m| m.timestamp_uhlc)
+    else {
+        eprintln!("no messages to replay in `{}`", path.display());
+        return Ok(());
+    };
+    let start = Instant::now();
+    for message in messages {
+        let RecordedMessage {
+            id,
+            timestamp_uhlc,
+            parameters,
+            data,
+        } = message?;
+        if speed > 0.0 {
+            let offset = (NTP64(timestamp_uhlc) - NTP64(first)).to_duration();
+            let deadline = start + offset.div_f64(speed);
+            if wait_until(&mut events, deadline) {
+                break;
+            }
+        }
+        let recorded_time = NTP64(timestamp_uhlc)
+            .to_system_time()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let parameters = parameters.with_user_parameter(RECORDED_TIME, recorded_time as i64);
+        node.send_output(DataId::from(id), parameters, data)?;
+    }
+
+    Ok(())
+}
+
+/// The output that the messages of the given recording are sent on.
+///
+/// This is the recorded output if it is stored in the file, and the name of the data
+/// column otherwise. Returns `None` if the recording belongs to a different node.
+fn replayed_output(reader: &RecordingReader, replay_node: Option<&str>) -> Option<DataId> {
+    match (reader.source(), replay_node) {
+        (Some((node_id, _)), Some(replay_node)) if node_id != replay_node => None,
+        (Some((_, output_id)), _) => Some(DataId::from(output_id.to_owned())),
+        (None, Some(_)) => None,
+        (None, None) => Some(DataId::from(reader.id().to_owned())),
+    }
+}
+
+/// Merges the messages of multiple recordings in the order of their timestamps.
+///
+/// The messages of each recording are expected to be sorted already, which is the
+/// case for files written by `dora-record` and `dora record`.
+struct MergedMessages<I: Iterator> {
+    recordings: Vec<Peekable<I>>,
+}
+
+impl<I> MergedMessages<I>
+where
+    I: Iterator<Item = eyre::Result<RecordedMessage>>,
+{
+    fn new(recordings: impl IntoIterator<Item = I>) -> Self {
+        Self {
+            recordings: recordings.into_iter().map(Iterator::peekable).collect(),
+        }
+    }
+}
+
+impl<I> Iterator for MergedMessages<I>
+where
+    I: Iterator<Item = eyre::Result<RecordedMessage>>,
+{
+    type Item = eyre::Result<RecordedMessage>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // report errors first, so that they are not skipped
+        let next = self
+            .recordings
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(i, r)| Some((i, r.peek()?)))
+            .min_by_key(|(_, message)| message.as_ref().map(|m| m.timestamp_uhlc).ok())
+            .map(|(i, _)| i)?;
+        self.recordings[next].next()
+    }
+}
+
+/// Collects all Parquet files at the given path, searching directories recursively.
+fn collect_recordings(path: &Path, files: &mut Vec<PathBuf>) -> eyre::Result<()> {
+    if path.is_dir() {
+        let mut entries = std::fs::read_dir(path)
+            .with_context(|| format!("failed to read `{}`", path.display()))?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        entries.sort();
+        for entry in entries {
+            if entry.is_dir() || entry.extension().is_some_and(|ext| ext == "parquet") {
+                collect_recordings(&entry, files)?;
+            }
+        }
+    } else if path.exists() {
+        files.push(path.to_owned());
+    } else {
+        bail!("recording `{}` does not exist", path.display());
+    }
+    Ok(())
+}
+
+/// Waits until the given deadline. Returns `true` if the node should stop instead.
+fn wait_until(events: &mut EventStream, deadline: Instant) -> bool {
+    loop {
+        let Some(remaining) = deadline.checked_duration_since(Instant::now()) else {
+            return false;
+        };
+        if remaining == Duration::ZERO {
+            return false;
+        }
+        match events.recv_timeout(remaining) {
+            Some(Event::Stop) => return true,
+            Some(_) => {}
+            None => {
+                // the node has no inputs, so the event stream is already closed
+                std::thread::sleep(remaining);
+                return false;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dora_node_api::{arrow::array::UInt8Array, MetadataParameters};
+    use std::sync::Arc;
+
+    fn message(id: &str, timestamp_uhlc: u64) -> eyre::Result<RecordedMessage> {
+        Ok(RecordedMessage {
+            id: id.to_owned(),
+            timestamp_uhlc,
+            parameters: MetadataParameters::default(),
+            data: Arc::new(UInt8Array::from(vec![1])),
+        })
+    }
+
+    #[test]
+    fn recordings_are_merged_by_timestamp() {
+        let a = vec![message("a", 1), message("a", 4), message("a", 5)];
+        let b = vec![message("b", 2), message("b", 3), message("b", 6)];
+        let merged: Vec<_> = MergedMessages::new([a.into_iter(), b.into_iter()])
+            .map(|m| {
+                let m = m.unwrap();
+                (m.id, m.timestamp_uhlc)
+            })
+            .collect();
+        let expected: Vec<_> = [("a", 1), ("b", 2), ("b", 3), ("a", 4), ("a", 5), ("b", 6)]
+            .into_iter()
+            .map(|(id, t)| (id.to_owned(), t))
+            .collect();
+        assert_eq!(merged, expected);
+    }
+
+    #[test]
+    fn read_errors_are_not_skipped() {
+        let a = vec![message("a", 1), Err(eyre::eyre!("broken"))];
+        let b = vec![message("b", 2)];
+        let mut merged = MergedMessages::new([a.into_iter(), b.into_iter()]);
+        assert_eq!(merged.next().unwrap().unwrap().timestamp_uhlc, 1);
+        assert!(merged.next().unwrap().is_err());
+        assert_eq!(merged.next().unwrap().unwrap().timestamp_uhlc, 2);
+        assert!(merged.next().is_none());
+    }
+
+    #[test]
+    fn outputs_are_selected_by_recorded_source() {
+        let dir = tempfile::tempdir().unwrap();
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let open = |input: &str, source| {
+            let path = dora_record::recording_path(dir.path(), input);
+            rt.block_on(async {
+                let writer = dora_record::ParquetWriter::create(
+                    &path,
+                    input,
+                    source,
+                    &dora_node_api::arrow::datatypes::DataType::UInt8,
+                )
+                .await
+                .unwrap();
+                writer.close().await.unwrap();
+            });
+            RecordingReader::open(&path).unwrap()
+        };
+        let image = open("camera-image", Some(("camera", "image")));
+        let legacy = open("image", None);
+        let output = |id: &str| Some(DataId::from(id.to_owned()));
+
+        assert_eq!(replayed_output(&image, None), output("image"));
+        assert_eq!(replayed_output(&image, Some("camera")), output("image"));
+        assert_eq!(replayed_output(&image, Some("lidar")), None);
+        assert_eq!(replayed_output(&legacy, None), output("image"));
+        assert_eq!(replayed_output(&legacy, Some("camera")), None);
+    }
+}